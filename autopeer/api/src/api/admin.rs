@@ -0,0 +1,1026 @@
+//! Operator endpoints, authenticated with `ADMIN_TOKEN`
+
+use super::asn_lock;
+use crate::api::peering::{deployment_info, DeploymentInfo};
+use crate::api::tombstone;
+use crate::bird;
+use crate::config::{AppConfig, SharedConfig};
+use crate::ipalloc::interface_name;
+use crate::middleware::AdminAuth;
+use crate::wireguard::{self, deploy::InterfaceDump, PublicKey, WgConfig, WgKeypair};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::Response,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+/// Most ASNs a single status batch may ask for
+const MAX_STATUS_BATCH: usize = 500;
+
+/// How long a BIRD reload waits for sessions to re-establish, unless the request says otherwise
+const DEFAULT_RELOAD_TIMEOUT_SECS: u64 = 60;
+
+/// How often a BIRD reload checks the sessions while waiting
+const RELOAD_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// An issued, still valid token
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TokenInfo {
+    pub jti: String,
+    /// Expiration time (Unix timestamp)
+    pub exp: i64,
+}
+
+/// Response from token listing
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TokensResponse {
+    pub asn: u32,
+    pub tokens: Vec<TokenInfo>,
+}
+
+/// Response from token revocation
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RevokeResponse {
+    pub asn: u32,
+    /// Number of tokens revoked
+    pub revoked: usize,
+}
+
+/// Request to switch maintenance mode
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MaintenanceRequest {
+    pub enabled: bool,
+}
+
+/// Response from switching maintenance mode
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MaintenanceResponse {
+    pub maintenance_mode: bool,
+}
+
+/// Request for a reload of every BIRD peer config
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct BirdReloadRequest {
+    /// Seconds the sessions get to re-establish (default 60)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    /// Sessions that may stay down before the previous configs are put back
+    #[serde(default)]
+    pub max_dropped: usize,
+}
+
+/// Outcome of a BIRD reload
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BirdReloadResponse {
+    /// Peers whose config was regenerated
+    pub regenerated: Vec<u32>,
+    /// Peers established before the reload and not (yet) after it
+    pub dropped: Vec<u32>,
+    /// Whether the previous configs were put back because too many sessions dropped
+    pub rolled_back: bool,
+}
+
+/// Deployment status of a peering along with its live state
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PeerStatus {
+    #[serde(flatten)]
+    pub deployment: DeploymentInfo,
+    /// Unix time of the latest WireGuard handshake, `None` if there hasn't been one
+    pub latest_handshake: Option<u64>,
+    /// Whether BIRD reports the BGP session as established (`None` if BIRD couldn't be asked)
+    pub bgp_established: Option<bool>,
+}
+
+/// Which registry key authorized a peering
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AuthzResponse {
+    pub asn: u32,
+    /// PGP fingerprint or minisign public key the challenge was verified with
+    /// (`None` for peerings deployed before this was recorded)
+    pub authorized_fingerprint: Option<String>,
+    /// When the challenge was verified (Unix timestamp)
+    pub authorized_at: Option<i64>,
+}
+
+/// A deleted peering kept as a tombstone, with the registry key that had authorized it
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DeletedPeering {
+    pub asn: u32,
+    /// When the peering was deleted (Unix timestamp)
+    pub deleted_at: u64,
+    /// PGP fingerprint or minisign public key the challenge was verified with
+    pub authorized_fingerprint: Option<String>,
+    /// When the challenge was verified (Unix timestamp)
+    pub authorized_at: Option<i64>,
+}
+
+/// GET /admin/tokens/{asn} - Tokens issued to an ASN that are still valid
+pub async fn list_tokens(
+    State(config): State<Arc<AppConfig>>,
+    _admin: AdminAuth,
+    Path(asn): Path<u32>,
+) -> Result<Json<TokensResponse>, (StatusCode, String)> {
+    let tokens = config
+        .runtime
+        .tokens
+        .active_tokens(&config.token_store, asn)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?
+        .into_iter()
+        .map(|(jti, exp)| TokenInfo { jti, exp })
+        .collect();
+
+    Ok(Json(TokensResponse { asn, tokens }))
+}
+
+/// POST /admin/revoke/{asn} - Revoke all tokens issued to an ASN so far
+pub async fn revoke_tokens(
+    State(config): State<Arc<AppConfig>>,
+    _admin: AdminAuth,
+    Path(asn): Path<u32>,
+) -> Result<Json<RevokeResponse>, (StatusCode, String)> {
+    let revoked = config
+        .runtime
+        .tokens
+        .revoke_asn(&config.token_store, asn)
+        .map_err(|e| {
+            error!("Failed to revoke tokens for ASN {}: {}", asn, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e)
+        })?;
+    info!("Revoked {} token(s) for ASN {}", revoked, asn);
+
+    Ok(Json(RevokeResponse { asn, revoked }))
+}
+
+/// POST /admin/maintenance - Refuse or accept new peerings; existing ones are unaffected
+pub async fn set_maintenance(
+    State(shared): State<SharedConfig>,
+    _admin: AdminAuth,
+    Json(req): Json<MaintenanceRequest>,
+) -> Json<MaintenanceResponse> {
+    shared.update(|config| config.maintenance_mode = req.enabled);
+    info!(
+        "Maintenance mode {}",
+        if req.enabled { "enabled" } else { "disabled" }
+    );
+
+    Json(MaintenanceResponse {
+        maintenance_mode: req.enabled,
+    })
+}
+
+/// GET /admin/peering/{asn}/status - Deployment status of any peering
+pub async fn get_peer_status(
+    State(config): State<Arc<AppConfig>>,
+    _admin: AdminAuth,
+    Path(asn): Path<u32>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    info!("Admin status request for ASN {}", asn);
+    crate::api::peering::peering_status(&config, asn, &headers).await
+}
+
+/// GET /admin/peering/{asn}/bird-config - Our rendered BIRD config for any peering
+pub async fn get_peer_bird_config(
+    State(config): State<Arc<AppConfig>>,
+    _admin: AdminAuth,
+    Path(asn): Path<u32>,
+) -> Result<Response, (StatusCode, String)> {
+    info!("Admin BIRD config request for ASN {}", asn);
+    crate::api::peering::bird_config_response(&config, asn)
+}
+
+/// POST /admin/bird/reload - Regenerate every BIRD peer config with one `birdc configure`
+///
+/// Waits for the sessions that were established to come back, and puts the previous configs
+/// back (409) when more than `max_dropped` of them don't within the timeout. Every peer's
+/// lock is held throughout, so activations and deactivations wait instead of being undone.
+pub async fn reload_bird(
+    State(config): State<Arc<AppConfig>>,
+    _admin: AdminAuth,
+    Json(req): Json<BirdReloadRequest>,
+) -> Result<(StatusCode, Json<BirdReloadResponse>), (StatusCode, String)> {
+    let system = &config.system;
+
+    // Locked in ASN order, so two reloads can't deadlock on each other
+    let asns = bird::deploy::deployed_asns(&system.bird_peers_dir);
+    let mut locks = Vec::with_capacity(asns.len());
+    for asn in &asns {
+        locks.push(asn_lock::lock(&config, *asn).await?);
+    }
+    // A peer deactivated while we waited for its lock stays that way
+    let asns: Vec<u32> = asns
+        .into_iter()
+        .filter(|asn| bird::deploy::config_path(&system.bird_peers_dir, *asn).exists())
+        .collect();
+
+    // Render everything up front, so a template error changes nothing
+    let mut configs = Vec::new();
+    for asn in asns {
+        let config_path = format!("{}/{}.conf", config.data_verified_dir, interface_name(asn));
+        let Some(bgp) = WgConfig::from_file(&config_path)
+            .ok()
+            .and_then(|wg_config| wg_config.bgp)
+        else {
+            warn!(
+                "No stored BGP settings for ASN {}, leaving its BIRD config alone",
+                asn
+            );
+            continue;
+        };
+        configs.push((
+            asn,
+            crate::api::peering::render_bird_config(&config, asn, &bgp)?,
+        ));
+    }
+    let regenerated: Vec<u32> = configs.iter().map(|(asn, _)| *asn).collect();
+    info!("BIRD reload of {} peer(s)", regenerated.len());
+
+    let before = bird::deploy::show_all_protocols(system)
+        .await
+        .map(|output| bird::status::parse_sessions(&output))
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to read BGP sessions: {}", e),
+            )
+        })?;
+
+    let snapshot = bird::deploy::write_configs(system, &configs)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let restore = || async {
+        bird::deploy::restore_configs(system, &snapshot)
+            .await
+            .map_err(|e| {
+                error!("Failed to restore the previous BIRD configs: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to restore the previous BIRD configs: {}", e),
+                )
+            })
+    };
+    if let Err(e) = bird::deploy::reload(system).await {
+        error!("BIRD reload rejected: {}", e);
+        restore().await?;
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, e));
+    }
+
+    // Sessions the config change restarted need a moment to come back
+    let deadline = Instant::now()
+        + Duration::from_secs(req.timeout_secs.unwrap_or(DEFAULT_RELOAD_TIMEOUT_SECS));
+    let after = loop {
+        let after = bird::deploy::show_all_protocols(system)
+            .await
+            .map(|output| bird::status::parse_sessions(&output))
+            .unwrap_or_else(|e| {
+                warn!("Failed to read BGP sessions after the reload: {}", e);
+                bird::status::SessionStates::new()
+            });
+        if bird::status::dropped_sessions(&before, &after).is_empty() || Instant::now() >= deadline
+        {
+            break after;
+        }
+        tokio::time::sleep(RELOAD_POLL_INTERVAL).await;
+    };
+
+    let dropped = bird::status::dropped_sessions(&before, &after);
+    let rolled_back = bird::status::too_many_dropped(&before, &after, req.max_dropped);
+    if rolled_back {
+        warn!("{} session(s) didn't come back after the BIRD reload, restoring the previous configs: {:?}", dropped.len(), dropped);
+        restore().await?;
+    }
+    drop(locks);
+    let status = if rolled_back {
+        StatusCode::CONFLICT
+    } else {
+        StatusCode::OK
+    };
+    Ok((
+        status,
+        Json(BirdReloadResponse {
+            regenerated,
+            dropped,
+            rolled_back,
+        }),
+    ))
+}
+
+/// GET /admin/peering/{asn}/authz - The registry key that authorized a peering, for audits
+pub async fn get_peer_authz(
+    State(config): State<Arc<AppConfig>>,
+    _admin: AdminAuth,
+    Path(asn): Path<u32>,
+) -> Result<Json<AuthzResponse>, (StatusCode, String)> {
+    info!("Admin authorization request for ASN {}", asn);
+    let config_path = format!("{}/{}.conf", config.data_verified_dir, interface_name(asn));
+    let wg_config = WgConfig::from_file(&config_path)
+        .map_err(|e| (StatusCode::NOT_FOUND, format!("Config not found: {}", e)))?;
+
+    let registry = wg_config.registry;
+    Ok(Json(AuthzResponse {
+        asn,
+        authorized_fingerprint: registry
+            .as_ref()
+            .map(|registry| registry.fingerprint.clone()),
+        authorized_at: registry.map(|registry| registry.authorized_at),
+    }))
+}
+
+/// GET /admin/peering/deleted - Tombstones of deleted peerings, oldest first
+pub async fn list_deleted_peerings(
+    State(config): State<Arc<AppConfig>>,
+    _admin: AdminAuth,
+) -> Result<Json<Vec<DeletedPeering>>, (StatusCode, String)> {
+    info!("Admin request for deleted peerings");
+    let tombstones = tombstone::list(std::path::Path::new(&config.data_deleted_dir))
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    let deleted = tombstones
+        .into_iter()
+        .map(|tombstone| {
+            // An unreadable tombstone is still listed, just without its authorization
+            let registry = tombstone::authorization(&tombstone.path)
+                .map_err(|e| warn!("Failed to read tombstone {:?}: {}", tombstone.path, e))
+                .ok()
+                .flatten();
+            DeletedPeering {
+                asn: tombstone.asn,
+                deleted_at: tombstone.deleted_at,
+                authorized_fingerprint: registry
+                    .as_ref()
+                    .map(|registry| registry.fingerprint.clone()),
+                authorized_at: registry.map(|registry| registry.authorized_at),
+            }
+        })
+        .collect();
+    Ok(Json(deleted))
+}
+
+/// POST /admin/peering/status-batch - Status of many peerings at once (`null` for unknown ASNs)
+pub async fn get_peer_status_batch(
+    State(config): State<Arc<AppConfig>>,
+    _admin: AdminAuth,
+    Json(asns): Json<Vec<u32>>,
+) -> Result<Json<BTreeMap<u32, Option<PeerStatus>>>, (StatusCode, String)> {
+    if asns.len() > MAX_STATUS_BATCH {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("At most {} ASNs per batch", MAX_STATUS_BATCH),
+        ));
+    }
+    info!("Admin status request for {} ASN(s)", asns.len());
+
+    // One `wg show all dump` and one `birdc show protocols` for the whole batch
+    let interfaces = wireguard::deploy::dump_all(&config.system)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Failed to list WireGuard interfaces: {}", e);
+            HashMap::new()
+        });
+    let protocols = bird::deploy::show_all_protocols(&config.system)
+        .await
+        .map_err(|e| warn!("Failed to list BIRD protocols: {}", e))
+        .ok();
+
+    let mut statuses = BTreeMap::new();
+    for asn in asns {
+        let status = batch_peer_status(&config, asn, &interfaces, protocols.as_deref()).await?;
+        statuses.insert(asn, status);
+    }
+    Ok(Json(statuses))
+}
+
+/// Status of one peering from the batched tool output, `None` if it isn't deployed
+async fn batch_peer_status(
+    config: &AppConfig,
+    asn: u32,
+    interfaces: &HashMap<String, InterfaceDump>,
+    protocols: Option<&str>,
+) -> Result<Option<PeerStatus>, (StatusCode, String)> {
+    let iface_name = interface_name(asn);
+    let config_path = format!("{}/{}.conf", config.data_verified_dir, iface_name);
+    let Ok(wg_config) = WgConfig::from_file(&config_path) else {
+        return Ok(None);
+    };
+
+    // The dump already has the public key of a running interface
+    let interface = interfaces.get(&iface_name);
+    let public_key =
+        match interface.and_then(|interface| interface.public_key.parse::<PublicKey>().ok()) {
+            Some(public_key) => public_key,
+            None => WgKeypair::derive_public_key(&config.system, &wg_config.interface.private_key)
+                .await
+                .map_err(|e| {
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("Failed to derive public key: {}", e),
+                    )
+                })?,
+        };
+
+    Ok(Some(PeerStatus {
+        deployment: deployment_info(config, asn, &wg_config, &public_key, interface.is_some()),
+        latest_handshake: interface.and_then(|interface| interface.peer?.latest_handshake),
+        bgp_established: protocols.map(|output| bird::deploy::session_established(output, asn)),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_helpers::{
+        authed_request, test_config_with_system, write_verified_config,
+    };
+    use crate::jwt::{generate_challenge_token, JwtScope};
+    use axum::{
+        body::Body,
+        http::{header, Request},
+        routing::{get, post},
+        Router,
+    };
+    use tower::ServiceExt;
+    use tower_cookies::CookieManagerLayer;
+
+    fn app(config: Arc<AppConfig>) -> Router {
+        Router::new()
+            .route("/admin/revoke/{asn}", post(revoke_tokens))
+            .route("/admin/tokens/{asn}", get(list_tokens))
+            .route("/admin/peering/{asn}/status", get(get_peer_status))
+            .route("/admin/peering/status-batch", post(get_peer_status_batch))
+            .route(
+                "/admin/peering/{asn}/bird-config",
+                get(get_peer_bird_config),
+            )
+            .route("/admin/peering/{asn}/authz", get(get_peer_authz))
+            .route("/admin/peering/deleted", get(list_deleted_peerings))
+            .route("/admin/bird/reload", post(reload_bird))
+            .route("/peering/state", get(crate::api::get_state))
+            .route("/peering/deploy", post(crate::api::deploy_peering))
+            .route(
+                "/peering",
+                axum::routing::delete(crate::api::delete_peering),
+            )
+            .layer(CookieManagerLayer::new())
+            .with_state(config)
+    }
+
+    fn admin_request(method: &str, uri: &str, token: &str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn state_request(token: &str) -> Request<Body> {
+        Request::builder()
+            .uri("/peering/state")
+            .header(header::COOKIE, format!("autopeer_token={}", token))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_revoked_token_is_rejected() {
+        let (config, _root) = test_config_with_system();
+        let config = Arc::new(AppConfig {
+            admin_token: Some("admin-secret".to_string()),
+            ..(*config).clone()
+        });
+        let asn = 4242422225;
+        let scope = JwtScope::from_config(&config);
+        let issue = || {
+            generate_challenge_token(
+                asn,
+                "AUTOPEER-4242422225-abc",
+                None,
+                None,
+                &config.jwt_secret,
+                &scope,
+            )
+            .unwrap()
+        };
+
+        let old = issue();
+        let response = app(config.clone())
+            .oneshot(state_request(&old))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app(config.clone())
+            .oneshot(admin_request(
+                "POST",
+                &format!("/admin/revoke/{}", asn),
+                "admin-secret",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<RevokeResponse>(&body)
+                .unwrap()
+                .revoked,
+            1
+        );
+
+        let response = app(config.clone())
+            .oneshot(state_request(&old))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // A token from a new verification is accepted
+        let fresh = issue();
+        let response = app(config.clone())
+            .oneshot(state_request(&fresh))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app(config.clone())
+            .oneshot(admin_request(
+                "GET",
+                &format!("/admin/tokens/{}", asn),
+                "admin-secret",
+            ))
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<TokensResponse>(&body)
+                .unwrap()
+                .tokens
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_admin_reads_any_status() {
+        let (config, _root) = test_config_with_system();
+        let config = Arc::new(AppConfig {
+            admin_token: Some("admin-secret".to_string()),
+            ..(*config).clone()
+        });
+        let asn = 4242422225;
+        write_verified_config(&config, asn);
+
+        let response = app(config.clone())
+            .oneshot(admin_request(
+                "GET",
+                &format!("/admin/peering/{}/status", asn),
+                "admin-secret",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let deployment: crate::api::peering::DeploymentInfo =
+            serde_json::from_slice(&body).unwrap();
+        assert_eq!(deployment.bgp_remote_as, asn);
+        assert_eq!(deployment.bgp_local_as, config.my_asn);
+
+        let response = app(config.clone())
+            .oneshot(admin_request(
+                "GET",
+                "/admin/peering/4242421816/status",
+                "admin-secret",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Another peer's token is not enough
+        let response = app(config.clone())
+            .oneshot(authed_request(
+                "GET",
+                &format!("/admin/peering/{}/status", asn),
+                &config,
+                4242421816,
+                None,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app(config.clone())
+            .oneshot(admin_request(
+                "GET",
+                &format!("/admin/peering/{}/bird-config", asn),
+                "admin-secret",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(String::from_utf8_lossy(&body)
+            .contains(&format!("protocol bgp autopeer_as{} from dnpeers", asn)));
+    }
+
+    #[tokio::test]
+    async fn test_authz_records_deploying_fingerprint() {
+        let (config, _root) = test_config_with_system();
+        let config = Arc::new(AppConfig {
+            admin_token: Some("admin-secret".to_string()),
+            ..(*config).clone()
+        });
+        let asn = 4242422225;
+        let fingerprint = "8B7F0384CBE0272761D852EA0684E36E6CF9D4D4";
+        let token = generate_challenge_token(
+            asn,
+            "AUTOPEER-4242422225-abc",
+            None,
+            Some(fingerprint),
+            &config.jwt_secret,
+            &JwtScope::from_config(&config),
+        )
+        .unwrap();
+
+        let body = serde_json::json!({
+            "wg_public_key": crate::api::test_helpers::PEER_PUBLIC_KEY,
+            "endpoint": "192.0.2.1:51820",
+        });
+        let deploy = Request::builder()
+            .method("POST")
+            .uri("/peering/deploy")
+            .header(header::COOKIE, format!("autopeer_token={}", token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app(config.clone()).oneshot(deploy).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app(config.clone())
+            .oneshot(admin_request(
+                "GET",
+                &format!("/admin/peering/{}/authz", asn),
+                "admin-secret",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let authz: AuthzResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(authz.authorized_fingerprint.as_deref(), Some(fingerprint));
+        let authorized_at = authz.authorized_at.unwrap();
+        assert!((chrono::Utc::now().timestamp() - authorized_at).abs() < 60);
+
+        // Peerings deployed before fingerprints were recorded
+        write_verified_config(&config, 4242421816);
+        let response = app(config.clone())
+            .oneshot(admin_request(
+                "GET",
+                "/admin/peering/4242421816/authz",
+                "admin-secret",
+            ))
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<AuthzResponse>(&body)
+                .unwrap()
+                .authorized_fingerprint,
+            None
+        );
+
+        let response = app(config.clone())
+            .oneshot(admin_request(
+                "GET",
+                "/admin/peering/4242421817/authz",
+                "admin-secret",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_deleted_peerings_keep_authz() {
+        let (config, _root) = test_config_with_system();
+        let config = Arc::new(AppConfig {
+            admin_token: Some("admin-secret".to_string()),
+            tombstone_deletes: true,
+            ..(*config).clone()
+        });
+        let asn = 4242422225;
+        let fingerprint = "8B7F0384CBE0272761D852EA0684E36E6CF9D4D4";
+        let token = generate_challenge_token(
+            asn,
+            "AUTOPEER-4242422225-abc",
+            None,
+            Some(fingerprint),
+            &config.jwt_secret,
+            &JwtScope::from_config(&config),
+        )
+        .unwrap();
+        let peer_request = |method: &str, uri: &str, body: Body| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::COOKIE, format!("autopeer_token={}", token))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body)
+                .unwrap()
+        };
+        let list = || async {
+            let response = app(config.clone())
+                .oneshot(admin_request(
+                    "GET",
+                    "/admin/peering/deleted",
+                    "admin-secret",
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<Vec<DeletedPeering>>(&body).unwrap()
+        };
+        assert!(list().await.is_empty());
+
+        let body = serde_json::json!({
+            "wg_public_key": crate::api::test_helpers::PEER_PUBLIC_KEY,
+            "endpoint": "192.0.2.1:51820",
+        });
+        let response = app(config.clone())
+            .oneshot(peer_request(
+                "POST",
+                "/peering/deploy",
+                Body::from(body.to_string()),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app(config.clone())
+            .oneshot(peer_request("DELETE", "/peering", Body::empty()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let deleted = list().await;
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].asn, asn);
+        assert!((chrono::Utc::now().timestamp() - deleted[0].deleted_at as i64).abs() < 60);
+        assert_eq!(
+            deleted[0].authorized_fingerprint.as_deref(),
+            Some(fingerprint)
+        );
+        assert!(deleted[0].authorized_at.is_some());
+
+        let response = app(config.clone())
+            .oneshot(admin_request("GET", "/admin/peering/deleted", "wrong"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_status_batch() {
+        let (config, root) = test_config_with_system();
+        let bin_dir = root.path().join("bin");
+        let mut system = config.system.clone();
+        system.wg_bin = crate::api::test_helpers::write_stub(
+            &bin_dir,
+            "wg-all",
+            &std::fs::read_to_string(&config.system.wg_bin).unwrap().replace(
+                "show) exit 1 ;;",
+                &format!(
+                    "show) printf 'wg-as4242422225\\tcHJpdmF0ZQ==\\t{}\\t32225\\toff\\n\
+                     wg-as4242422225\\tpeer\\t(none)\\t192.0.2.1:51820\\tfe80::/64\\t1700000000\\t1\\t2\\t25\\n' ;;",
+                    crate::api::test_helpers::STUB_PUBLIC_KEY
+                ),
+            ),
+        );
+        system.birdc_bin = crate::api::test_helpers::write_stub(
+            &bin_dir,
+            "birdc-protocols",
+            "#!/bin/sh\necho 'autopeer_as4242422225 BGP --- up 2024-01-01 Established'\n",
+        );
+        let config = Arc::new(AppConfig {
+            admin_token: Some("admin-secret".to_string()),
+            system,
+            ..(*config).clone()
+        });
+        write_verified_config(&config, 4242422225);
+        write_verified_config(&config, 4242421816);
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/admin/peering/status-batch")
+            .header(header::AUTHORIZATION, "Bearer admin-secret")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("[4242422225, 4242421816, 4242420001]"))
+            .unwrap();
+        let response = app(config.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let statuses: BTreeMap<u32, Option<PeerStatus>> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(statuses.len(), 3);
+
+        let up = statuses[&4242422225].as_ref().unwrap();
+        assert!(up.deployment.is_active);
+        assert_eq!(
+            up.deployment.our_public_key,
+            crate::api::test_helpers::STUB_PUBLIC_KEY
+        );
+        assert_eq!(up.latest_handshake, Some(1700000000));
+        assert_eq!(up.bgp_established, Some(true));
+
+        let down = statuses[&4242421816].as_ref().unwrap();
+        assert!(!down.deployment.is_active);
+        assert_eq!(down.latest_handshake, None);
+        assert_eq!(down.bgp_established, Some(false));
+
+        assert!(statuses[&4242420001].is_none());
+    }
+
+    #[tokio::test]
+    async fn test_bird_reload_rolls_back_dropped_sessions() {
+        let (config, root) = test_config_with_system();
+        let peers_dir = config.system.bird_peers_dir.clone();
+        let mut system = config.system.clone();
+        // Sessions only come up with configs inheriting from `dnpeers`
+        system.birdc_bin = crate::api::test_helpers::write_stub(
+            &root.path().join("bin"),
+            "birdc-reload",
+            &format!(
+                "#!/bin/sh\n\
+                 [ \"$1\" = configure ] && {{ echo Reconfigured; exit 0; }}\n\
+                 for asn in 4242421816 4242422225; do\n\
+                 if grep -q 'from dnpeers {{' {dir}/autopeer_as$asn.conf; then state=Established; else state=Active; fi\n\
+                 echo \"autopeer_as$asn BGP --- up 2024-01-01 $state\"\n\
+                 done\n",
+                dir = peers_dir.display()
+            ),
+        );
+        let config = Arc::new(AppConfig {
+            admin_token: Some("admin-secret".to_string()),
+            system,
+            ..(*config).clone()
+        });
+        std::fs::create_dir_all(&peers_dir).unwrap();
+        for asn in [4242421816, 4242422225] {
+            write_verified_config(&config, asn);
+            std::fs::write(
+                bird::deploy::config_path(&peers_dir, asn),
+                "protocol bgp old from dnpeers {}",
+            )
+            .unwrap();
+        }
+        let reload = |config: Arc<AppConfig>| async move {
+            let request = Request::builder()
+                .method("POST")
+                .uri("/admin/bird/reload")
+                .header(header::AUTHORIZATION, "Bearer admin-secret")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"timeout_secs": 0}"#))
+                .unwrap();
+            let response = app(config).oneshot(request).await.unwrap();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (
+                status,
+                serde_json::from_slice::<BirdReloadResponse>(&body).unwrap(),
+            )
+        };
+
+        let (status, outcome) = reload(config.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(outcome.regenerated, vec![4242421816, 4242422225]);
+        assert!(outcome.dropped.is_empty() && !outcome.rolled_back);
+        let rendered =
+            std::fs::read_to_string(bird::deploy::config_path(&peers_dir, 4242422225)).unwrap();
+        assert!(
+            rendered.contains("protocol bgp autopeer_as4242422225 from dnpeers {"),
+            "{}",
+            rendered
+        );
+
+        // A change to one of the peers in progress is waited for
+        let lock = asn_lock::lock(&config, 4242422225).await.unwrap();
+        let pending = tokio::spawn(reload(config.clone()));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!pending.is_finished());
+        drop(lock);
+        assert_eq!(pending.await.unwrap().0, StatusCode::OK);
+
+        // A template the sessions don't survive is undone
+        let config = Arc::new(AppConfig {
+            bird_peer_template: "dnpeers_strict".to_string(),
+            ..(*config).clone()
+        });
+        let (status, outcome) = reload(config).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(outcome.dropped, vec![4242421816, 4242422225]);
+        assert!(outcome.rolled_back);
+        assert_eq!(
+            std::fs::read_to_string(bird::deploy::config_path(&peers_dir, 4242422225)).unwrap(),
+            rendered
+        );
+    }
+
+    #[tokio::test]
+    async fn test_toggle_maintenance() {
+        let (config, _root) = test_config_with_system();
+        let config = AppConfig {
+            admin_token: Some("admin-secret".to_string()),
+            ..(*config).clone()
+        };
+        let shared = SharedConfig::new(Arc::new(config.clone()));
+        let app = Router::new()
+            .route("/admin/maintenance", post(set_maintenance))
+            .route("/peering/init", post(crate::api::init_peering))
+            .with_state(shared.clone());
+        let post_json = |uri: &str, body: &str| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header(header::AUTHORIZATION, "Bearer admin-secret")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(post_json("/admin/maintenance", r#"{"enabled": true}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(shared.load().maintenance_mode);
+
+        let response = app
+            .clone()
+            .oneshot(post_json("/peering/init", r#"{"asn": 4242422225}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // A reload from the environment keeps the runtime setting
+        shared.reload(config);
+        assert!(shared.load().maintenance_mode);
+
+        let response = app
+            .oneshot(post_json("/admin/maintenance", r#"{"enabled": false}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!shared.load().maintenance_mode);
+    }
+
+    #[tokio::test]
+    async fn test_admin_token_required() {
+        let (config, _root) = test_config_with_system();
+        let uri = "/admin/revoke/4242422225";
+
+        // Disabled without ADMIN_TOKEN
+        let response = app(config.clone())
+            .oneshot(admin_request("POST", uri, "anything"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let config = Arc::new(AppConfig {
+            admin_token: Some("admin-secret".to_string()),
+            ..(*config).clone()
+        });
+        let response = app(config.clone())
+            .oneshot(admin_request("POST", uri, "wrong"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // A peer's cookie is no admin credential
+        let response = app(config.clone())
+            .oneshot(authed_request("POST", uri, &config, 4242422225, None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
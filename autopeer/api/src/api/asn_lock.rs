@@ -0,0 +1,34 @@
+//! Per-ASN lock around router changes, so e.g. a double-clicked activate/deactivate
+//! runs one full WireGuard + BIRD sequence after the other instead of interleaving
+
+use crate::config::AppConfig;
+use axum::http::StatusCode;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OwnedMutexGuard;
+
+/// One lock per ASN, kept in [`RuntimeState`](super::state::RuntimeState)
+#[derive(Debug, Default)]
+pub(crate) struct AsnLocks(Mutex<HashMap<u32, Arc<tokio::sync::Mutex<()>>>>);
+
+/// Wait until no other change to this ASN's peering is running; hold the guard for the whole change
+pub async fn lock(
+    config: &AppConfig,
+    asn: u32,
+) -> Result<OwnedMutexGuard<()>, (StatusCode, String)> {
+    let lock = config
+        .runtime
+        .asn_locks
+        .0
+        .lock()
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to acquire lock: {}", e),
+            )
+        })?
+        .entry(asn)
+        .or_default()
+        .clone();
+    Ok(lock.lock_owned().await)
+}
@@ -0,0 +1,98 @@
+//! Failed verification attempts per pending challenge
+
+use super::pending;
+use crate::config::AppConfig;
+use axum::http::StatusCode;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+
+/// Failed verifications after which the pending challenge is discarded
+pub const MAX_VERIFY_ATTEMPTS: u32 = 5;
+
+/// Failure count per pending challenge path, kept in [`RuntimeState`](super::state::RuntimeState)
+#[derive(Debug, Default)]
+pub(crate) struct FailedAttempts(Mutex<HashMap<PathBuf, u32>>);
+
+/// Record a failed verification for a pending challenge and pass the error through.
+/// Once the limit is reached the challenge and its contact are removed, forcing a new /peering/init.
+pub fn fail(
+    config: &AppConfig,
+    challenge_path: &str,
+    err: (StatusCode, String),
+) -> (StatusCode, String) {
+    let Ok(mut attempts) = config.runtime.failed_attempts.0.lock() else {
+        return err;
+    };
+
+    let path = PathBuf::from(challenge_path);
+    let count = attempts.entry(path.clone()).or_insert(0);
+    *count += 1;
+    if *count < MAX_VERIFY_ATTEMPTS {
+        return err;
+    }
+
+    // `pending::remove` clears the count itself, which needs the lock
+    drop(attempts);
+    warn!(
+        "Too many failed verifications, discarding challenge {:?}",
+        path
+    );
+    if let Err(e) = pending::remove(config, &path) {
+        warn!("Failed to remove pending challenge {:?}: {}", path, e);
+    }
+
+    let (status, message) = err;
+    (
+        status,
+        format!(
+            "{} (too many failed attempts, please restart with /peering/init)",
+            message
+        ),
+    )
+}
+
+/// Forget the failures for a pending challenge (new challenge, success or delete)
+pub fn clear<P: AsRef<Path>>(config: &AppConfig, challenge_path: P) {
+    if let Ok(mut attempts) = config.runtime.failed_attempts.0.lock() {
+        attempts.remove(challenge_path.as_ref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_helpers::test_config;
+
+    #[test]
+    fn test_discard_removes_contact() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = AppConfig {
+            data_pending_dir: dir.path().to_string_lossy().to_string(),
+            ..(*test_config()).clone()
+        };
+        let challenge = pending::path(&config.data_pending_dir, 4242422225, "aa");
+        std::fs::write(&challenge, "challenge").unwrap();
+        std::fs::write(pending::contact_path(&challenge), "noc@example.com").unwrap();
+
+        let err = || (StatusCode::UNAUTHORIZED, "bad signature".to_string());
+        for _ in 1..MAX_VERIFY_ATTEMPTS {
+            assert_eq!(fail(&config, &challenge, err()), err());
+        }
+        assert!(std::path::Path::new(&challenge).exists());
+
+        let (status, message) = fail(&config, &challenge, err());
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(message.contains("too many failed attempts"));
+        assert!(!std::path::Path::new(&challenge).exists());
+        assert!(!pending::contact_path(&challenge).exists());
+        assert!(!config
+            .runtime
+            .failed_attempts
+            .0
+            .lock()
+            .unwrap()
+            .contains_key(Path::new(&challenge)));
+    }
+}
@@ -0,0 +1,141 @@
+//! Download bundle for the peer's side of a peering: a `wg-quick` config and a README
+//!
+//! The peer's private key never leaves their machine, so the config loads it from a
+//! key file via `PostUp` instead of carrying a `PrivateKey` line.
+
+use super::peering::{local_asn, stored_session_addrs};
+use crate::config::AppConfig;
+use crate::ipalloc::{interface_name, BgpTransport, Ipv6LinkLocal};
+use crate::util::tar::TarBuilder;
+use crate::wireguard::WgConfig;
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the tunnel on the peer's side (named after our ASN on the peering)
+pub fn peer_interface(config: &AppConfig, ours: &WgConfig) -> String {
+    interface_name(local_asn(config, ours.bgp.as_ref()))
+}
+
+/// `wg-quick` config for the peer, mirroring our verified config for their ASN
+pub fn peer_wg_config(
+    config: &AppConfig,
+    asn: u32,
+    ours: &WgConfig,
+    our_public_key: &str,
+) -> Result<String, String> {
+    let peer = ours.peer().ok_or("Verified config has no [Peer] section")?;
+    let iface = peer_interface(config, ours);
+    // The peer's addresses come from the stored `[BGP]` section, as for our BIRD session;
+    // our interface only carries our own side of the tunnel
+    let link_local = ours
+        .bgp
+        .as_ref()
+        .and_then(|bgp| stored_session_addrs(bgp).0)
+        .unwrap_or_else(|| Ipv6LinkLocal::from_asns(local_asn(config, ours.bgp.as_ref()), asn))
+        .peer;
+
+    // Link-local unless the session runs over a transfer net only; the ULA or IPv4 session address on top
+    let transfer_net_only = ours
+        .bgp
+        .as_ref()
+        .is_some_and(|bgp| bgp.transport == BgpTransport::TransferNet);
+    let mut addresses = Vec::new();
+    if !transfer_net_only {
+        addresses.push(format!("{}/64", link_local));
+    }
+    if let Some(bgp) = ours.bgp.as_ref().filter(|bgp| bgp.neighbor != link_local) {
+        let len = if bgp.neighbor.contains(':') { 64 } else { 32 };
+        addresses.push(format!("{}/{}", bgp.neighbor, len));
+    }
+
+    let family = ours
+        .bgp
+        .as_ref()
+        .map(|bgp| bgp.address_family)
+        .unwrap_or_default();
+    let allowed_ips = [
+        ("0.0.0.0/0", family.has_ipv4()),
+        ("::/0", family.has_ipv6()),
+    ];
+
+    let mut out = String::from("[Interface]\n");
+    for address in &addresses {
+        let _ = writeln!(out, "Address = {}", address);
+    }
+    if let Some(port) = peer
+        .endpoint
+        .as_deref()
+        .and_then(|e| e.rsplit_once(':'))
+        .map(|(_, port)| port)
+    {
+        let _ = writeln!(out, "ListenPort = {}", port);
+    }
+    let _ = writeln!(
+        out,
+        "PostUp = wg set %i private-key /etc/wireguard/{}.key",
+        iface
+    );
+    let _ = writeln!(out, "Table = off");
+    let _ = writeln!(out);
+    let _ = writeln!(out, "[Peer]");
+    let _ = writeln!(out, "PublicKey = {}", our_public_key);
+    if let Some(preshared_key) = &peer.preshared_key {
+        let _ = writeln!(out, "PresharedKey = {}", preshared_key.expose());
+    }
+    let _ = writeln!(
+        out,
+        "Endpoint = {}:{}",
+        config.public_endpoint, ours.interface.listen_port
+    );
+    for (prefix, _) in allowed_ips.iter().filter(|(_, enabled)| *enabled) {
+        let _ = writeln!(out, "AllowedIPs = {}", prefix);
+    }
+    let _ = writeln!(out, "PersistentKeepalive = 25");
+    Ok(out)
+}
+
+/// Setup instructions shipped alongside the config
+pub fn readme(config: &AppConfig, ours: &WgConfig) -> String {
+    let iface = peer_interface(config, ours);
+    format!(
+        "# Peering with AS{my_asn}\n\
+         \n\
+         `{iface}.conf` is a wg-quick config for the tunnel to AS{my_asn}. It reads your\n\
+         WireGuard private key (the one matching the public key you deployed with)\n\
+         from `/etc/wireguard/{iface}.key`.\n\
+         \n\
+         ```sh\n\
+         install -m 600 {iface}.conf /etc/wireguard/{iface}.conf\n\
+         install -m 600 /path/to/your/private.key /etc/wireguard/{iface}.key\n\
+         systemctl enable --now wg-quick@{iface}.service\n\
+         wg show {iface}\n\
+         ```\n\
+         \n\
+         The BGP session config for BIRD is available from `GET /peering/bird-snippet`.\n",
+        my_asn = local_asn(config, ours.bgp.as_ref()),
+        iface = iface,
+    )
+}
+
+/// Tarball with the peer's config and README
+pub fn build(
+    config: &AppConfig,
+    asn: u32,
+    ours: &WgConfig,
+    our_public_key: &str,
+) -> Result<Vec<u8>, String> {
+    let iface = peer_interface(config, ours);
+    let mtime = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let mut tar = TarBuilder::new(mtime);
+    tar.append(
+        &format!("{}.conf", iface),
+        0o600,
+        peer_wg_config(config, asn, ours, our_public_key)?.as_bytes(),
+    )?;
+    tar.append("README.md", 0o644, readme(config, ours).as_bytes())?;
+    Ok(tar.finish())
+}
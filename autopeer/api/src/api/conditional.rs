@@ -0,0 +1,136 @@
+//! Conditional GET support (ETag / Last-Modified) for per-peer config files
+
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// HTTP date format (RFC 7231 IMF-fixdate)
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Cache validators for a response derived from a config file
+#[derive(Debug, Clone)]
+pub struct Validators {
+    /// Quoted strong ETag
+    pub etag: String,
+    /// File modification time, truncated to whole seconds
+    pub last_modified: DateTime<Utc>,
+}
+
+impl Validators {
+    /// Build validators from the file's content and mtime
+    pub fn for_file<P: AsRef<Path>>(path: P, content: &str) -> Result<Self, String> {
+        let mtime = std::fs::metadata(path.as_ref())
+            .and_then(|m| m.modified())
+            .map_err(|e| format!("Failed to read mtime: {}", e))?;
+
+        Ok(Self::new(content, mtime))
+    }
+
+    /// Build validators from arbitrary content and a modification time
+    pub fn new(content: &str, modified: SystemTime) -> Self {
+        let digest = Sha256::digest(content.as_bytes());
+        let etag = format!("\"{}\"", hex::encode(&digest[..16]));
+
+        let modified: DateTime<Utc> = modified.into();
+        let last_modified = DateTime::from_timestamp(modified.timestamp(), 0).unwrap_or(modified);
+
+        Self {
+            etag,
+            last_modified,
+        }
+    }
+
+    /// Whether the request's conditional headers match, i.e. a 304 can be sent.
+    /// `If-None-Match` takes precedence over `If-Modified-Since` (RFC 7232 §6).
+    pub fn is_not_modified(&self, headers: &HeaderMap) -> bool {
+        if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+            let Ok(value) = if_none_match.to_str() else {
+                return false;
+            };
+            return value
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == "*" || tag == self.etag);
+        }
+
+        headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+            .is_some_and(|since| self.last_modified <= since)
+    }
+
+    /// Attach the validators to a response
+    pub fn apply(&self, mut response: Response) -> Response {
+        let headers = response.headers_mut();
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            headers.insert(header::ETAG, etag);
+        }
+        let last_modified = self.last_modified.format(HTTP_DATE_FORMAT).to_string();
+        if let Ok(last_modified) = HeaderValue::from_str(&last_modified) {
+            headers.insert(header::LAST_MODIFIED, last_modified);
+        }
+        response
+    }
+
+    /// Empty 304 response carrying the validators
+    pub fn not_modified(&self) -> Response {
+        self.apply(StatusCode::NOT_MODIFIED.into_response())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn validators() -> Validators {
+        Validators::new(
+            "[Interface]\n",
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        )
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let v = validators();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::IF_NONE_MATCH,
+            HeaderValue::from_str(&v.etag).unwrap(),
+        );
+        assert!(v.is_not_modified(&headers));
+
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("\"other\""));
+        assert!(!v.is_not_modified(&headers));
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let v = validators();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::IF_MODIFIED_SINCE,
+            HeaderValue::from_static("Tue, 14 Nov 2023 22:13:20 GMT"),
+        );
+        assert!(v.is_not_modified(&headers));
+
+        headers.insert(
+            header::IF_MODIFIED_SINCE,
+            HeaderValue::from_static("Tue, 14 Nov 2023 22:13:19 GMT"),
+        );
+        assert!(!v.is_not_modified(&headers));
+    }
+
+    #[test]
+    fn test_etag_changes_with_content() {
+        let a = Validators::new("a", SystemTime::UNIX_EPOCH);
+        let b = Validators::new("b", SystemTime::UNIX_EPOCH);
+        assert_ne!(a.etag, b.etag);
+    }
+}
@@ -0,0 +1,122 @@
+//! Field-by-field differences between two versions of a peering config
+
+use crate::wireguard::WgConfig;
+use serde::{Deserialize, Serialize};
+
+/// One setting that differs between two configs (`None` where it is unset)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ConfigChange {
+    /// Section and setting, e.g. `peer.endpoint`
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// The settings a peer can see, by name; secrets only show whether they are set
+fn fields(config: &WgConfig) -> Vec<(&'static str, Option<String>)> {
+    let join = |values: &[String]| Some(values.join(", ")).filter(|joined| !joined.is_empty());
+    let peer = config.peer();
+    let bgp = config.bgp.as_ref();
+    vec![
+        ("interface.address", join(&config.interface.address)),
+        (
+            "interface.listen_port",
+            Some(config.interface.listen_port.to_string()),
+        ),
+        ("interface.table", config.interface.table.clone()),
+        ("interface.dns", join(&config.interface.dns)),
+        (
+            "peer.public_key",
+            peer.map(|peer| peer.public_key.to_string()),
+        ),
+        (
+            "peer.preshared_key",
+            peer.and_then(|peer| peer.preshared_key.as_ref())
+                .map(|_| "(set)".to_string()),
+        ),
+        ("peer.endpoint", peer.and_then(|peer| peer.endpoint.clone())),
+        (
+            "peer.allowed_ips",
+            peer.and_then(|peer| join(&peer.allowed_ips)),
+        ),
+        (
+            "peer.persistent_keepalive",
+            peer.and_then(|peer| peer.persistent_keepalive)
+                .map(|n| n.to_string()),
+        ),
+        ("bgp.enabled", bgp.map(|bgp| bgp.enabled.to_string())),
+        ("bgp.mpbgp", bgp.map(|bgp| bgp.mpbgp.to_string())),
+        (
+            "bgp.extended_next_hop",
+            bgp.map(|bgp| bgp.extended_next_hop.to_string()),
+        ),
+        ("bgp.local", bgp.map(|bgp| bgp.local.clone())),
+        ("bgp.neighbor", bgp.map(|bgp| bgp.neighbor.clone())),
+        (
+            "bgp.max_prefixes",
+            bgp.and_then(|bgp| bgp.max_prefixes).map(|n| n.to_string()),
+        ),
+        (
+            "bgp.address_family",
+            bgp.map(|bgp| bgp.address_family.to_string()),
+        ),
+        (
+            "bgp.router_id",
+            bgp.and_then(|bgp| bgp.router_id).map(|id| id.to_string()),
+        ),
+        ("bgp.peer_name", bgp.and_then(|bgp| bgp.peer_name.clone())),
+        (
+            "bgp.graceful_restart",
+            bgp.and_then(|bgp| bgp.graceful_restart)
+                .map(|on| on.to_string()),
+        ),
+        ("bgp.transport", bgp.map(|bgp| bgp.transport.to_string())),
+        (
+            "meta.contact",
+            config.meta.as_ref().and_then(|meta| meta.contact.clone()),
+        ),
+    ]
+}
+
+/// What changes when `old` is replaced by `new`, in config file order
+pub fn diff(old: &WgConfig, new: &WgConfig) -> Vec<ConfigChange> {
+    fields(old)
+        .into_iter()
+        .zip(fields(new))
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|((field, old), (_, new))| ConfigChange {
+            field: field.to_string(),
+            old,
+            new,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_helpers::{test_config_with_temp_dirs, write_verified_config};
+
+    #[test]
+    fn test_diff_reports_endpoint_change() {
+        let (config, _pending, _verified) = test_config_with_temp_dirs();
+        let old = write_verified_config(&config, 4242422225);
+        assert!(diff(&old, &old).is_empty());
+
+        let mut new = old.clone();
+        new.peer_mut().unwrap().endpoint = Some("peer.example.net:51820".to_string());
+        new.bgp.as_mut().unwrap().max_prefixes = Some(500);
+        let changes = diff(&old, &new);
+        assert_eq!(
+            changes[0],
+            ConfigChange {
+                field: "peer.endpoint".to_string(),
+                old: old.peer().unwrap().endpoint.clone(),
+                new: Some("peer.example.net:51820".to_string()),
+            }
+        );
+        assert_eq!(changes[1].field, "bgp.max_prefixes");
+        assert_eq!(changes[1].new.as_deref(), Some("500"));
+        assert_eq!(changes.len(), 2);
+    }
+}
@@ -0,0 +1,82 @@
+//! Per-ASN cooldown between configuration changes (deploy/update)
+
+use super::state::RuntimeState;
+use crate::config::AppConfig;
+use axum::http::StatusCode;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Last change per ASN, kept in [`RuntimeState`]
+#[derive(Debug, Default)]
+pub(crate) struct Cooldowns(Mutex<HashMap<u32, Instant>>);
+
+/// A reserved change slot; the reservation is rolled back unless committed
+pub struct CooldownGuard {
+    reserved: Option<(Arc<RuntimeState>, u32)>,
+    previous: Option<Instant>,
+}
+
+impl CooldownGuard {
+    /// Keep the reservation (the change went through)
+    pub fn commit(mut self) {
+        self.reserved = None;
+    }
+}
+
+impl Drop for CooldownGuard {
+    fn drop(&mut self) {
+        let Some((runtime, asn)) = self.reserved.take() else {
+            return;
+        };
+        let Ok(mut last_change) = runtime.cooldowns.0.lock() else {
+            return;
+        };
+        match self.previous {
+            Some(previous) => last_change.insert(asn, previous),
+            None => last_change.remove(&asn),
+        };
+    }
+}
+
+/// Reserve a change for an ASN, or fail with 429 while its cooldown is running.
+/// Check and reservation happen under one lock, so concurrent requests can't both pass.
+pub fn acquire(config: &AppConfig, asn: u32) -> Result<CooldownGuard, (StatusCode, String)> {
+    if config.deploy_cooldown_secs == 0 {
+        return Ok(CooldownGuard {
+            reserved: None,
+            previous: None,
+        });
+    }
+
+    let cooldown = Duration::from_secs(config.deploy_cooldown_secs);
+    let now = Instant::now();
+
+    let mut last_change = config.runtime.cooldowns.0.lock().map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to acquire lock: {}", e),
+        )
+    })?;
+
+    let previous = last_change.get(&asn).copied();
+    let remaining = previous
+        .and_then(|at| cooldown.checked_sub(now.duration_since(at)))
+        .filter(|remaining| !remaining.is_zero());
+    if let Some(remaining) = remaining {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            format!(
+                "Configuration for ASN {} changed recently, retry in {}s",
+                asn,
+                remaining.as_secs().max(1)
+            ),
+        ));
+    }
+
+    last_change.insert(asn, now);
+    Ok(CooldownGuard {
+        reserved: Some((config.runtime.clone(), asn)),
+        previous,
+    })
+}
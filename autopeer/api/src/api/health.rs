@@ -0,0 +1,129 @@
+//! Readiness probe for load balancers and alerting
+
+use crate::config::AppConfig;
+use axum::{extract::State, http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::warn;
+
+/// Response for GET /readyz
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    /// Seconds since the last successful registry sync (`None` if it never synced)
+    pub registry_age_secs: Option<u64>,
+    /// Why the service isn't ready, empty when it is
+    pub problems: Vec<String>,
+}
+
+/// Reasons the service shouldn't take traffic, given the registry's age
+fn readiness_problems(config: &AppConfig, registry_age_secs: Option<u64>) -> Vec<String> {
+    let max_age = config.max_registry_age_secs;
+    if max_age == 0 {
+        return Vec::new();
+    }
+    match registry_age_secs {
+        None => vec!["Registry has never synced".to_string()],
+        Some(age) if age > max_age => vec![format!(
+            "Registry last synced {}s ago (limit {}s)",
+            age, max_age
+        )],
+        Some(_) => Vec::new(),
+    }
+}
+
+/// GET /readyz - 200 when ready, 503 with the problems otherwise
+pub async fn get_readyz(
+    State(config): State<Arc<AppConfig>>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let registry_age_secs = config
+        .runtime
+        .registry_syncs
+        .registry_age_secs(&config.registry.path);
+    let problems = readiness_problems(&config, registry_age_secs);
+
+    let status = if problems.is_empty() {
+        StatusCode::OK
+    } else {
+        warn!("Not ready: {}", problems.join("; "));
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let response = ReadinessResponse {
+        ready: problems.is_empty(),
+        registry_age_secs,
+        problems,
+    };
+    (status, Json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_helpers::test_config;
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use std::time::{Duration, SystemTime};
+    use tower::ServiceExt;
+
+    async fn readyz(config: AppConfig) -> (StatusCode, ReadinessResponse) {
+        let app = Router::new()
+            .route("/readyz", get(get_readyz))
+            .with_state(Arc::new(config));
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/readyz")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_stale_registry_fails_readiness() {
+        let registry = tempfile::tempdir().unwrap();
+        let mut config = (*test_config()).clone();
+        config.registry.path = registry.path().to_path_buf();
+        config.max_registry_age_secs = 3600;
+
+        // The syncer has never succeeded
+        let (status, body) = readyz(config.clone()).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body.registry_age_secs, None);
+
+        config
+            .runtime
+            .registry_syncs
+            .record_success(registry.path(), SystemTime::now() - Duration::from_secs(60));
+        let (status, body) = readyz(config.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.ready);
+        assert!(body.problems.is_empty());
+
+        // Syncs have been failing silently for two hours
+        config.runtime.registry_syncs.record_success(
+            registry.path(),
+            SystemTime::now() - Duration::from_secs(7200),
+        );
+        let (status, body) = readyz(config.clone()).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(!body.ready);
+        assert!(body.registry_age_secs.unwrap() >= 7200);
+        assert!(
+            body.problems[0].contains("limit 3600s"),
+            "{:?}",
+            body.problems
+        );
+
+        // Without a limit the age is reported but never fails readiness
+        config.max_registry_age_secs = 0;
+        let (status, body) = readyz(config).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.registry_age_secs.unwrap() >= 7200);
+    }
+}
@@ -0,0 +1,105 @@
+//! Previous versions of a verified config, kept as `{iface}.conf.bak.{n}` (1 is the newest)
+
+use crate::util::private_file;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Path of the n-th backup of a config file
+pub fn backup_path(config_path: &Path, n: usize) -> PathBuf {
+    let mut path = config_path.as_os_str().to_owned();
+    path.push(format!(".bak.{}", n));
+    PathBuf::from(path)
+}
+
+/// Rename `from` to `to` if it exists
+fn shift(from: &Path, to: &Path) -> Result<(), String> {
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("Failed to move {:?} to {:?}: {}", from, to, e)),
+    }
+}
+
+/// Copy the current config into the ring before it is overwritten, dropping the oldest
+/// beyond `keep` versions. Backups hold the private key, so only the owner may read them.
+/// Does nothing when `keep` is 0.
+pub fn backup(config_path: &Path, keep: usize) -> Result<(), String> {
+    if keep == 0 {
+        return Ok(());
+    }
+
+    match fs::remove_file(backup_path(config_path, keep)) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Failed to drop oldest backup: {}", e)),
+    }
+    for n in (1..keep).rev() {
+        shift(
+            &backup_path(config_path, n),
+            &backup_path(config_path, n + 1),
+        )?;
+    }
+
+    fs::read(config_path)
+        .and_then(|content| private_file::write(backup_path(config_path, 1), content))
+        .map_err(|e| format!("Failed to back up {:?}: {}", config_path, e))
+}
+
+/// Latest backup's content, if there is one
+pub fn latest(config_path: &Path) -> Result<Option<String>, String> {
+    match fs::read_to_string(backup_path(config_path, 1)) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read backup: {}", e)),
+    }
+}
+
+/// Put the latest backup back in place and move the older ones up the ring
+pub fn restore_latest(config_path: &Path, keep: usize) -> Result<(), String> {
+    fs::rename(backup_path(config_path, 1), config_path)
+        .map_err(|e| format!("Failed to restore backup: {}", e))?;
+
+    for n in 2..=keep {
+        shift(
+            &backup_path(config_path, n),
+            &backup_path(config_path, n - 1),
+        )?;
+    }
+    Ok(())
+}
+
+/// Drop all backups of a config (the peering is gone)
+pub fn remove_all(config_path: &Path, keep: usize) {
+    for n in 1..=keep {
+        let _ = fs::remove_file(backup_path(config_path, n));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_ring() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("wg-as4242422225.conf");
+
+        for version in 1..=4 {
+            fs::write(&path, format!("v{}", version)).unwrap();
+            backup(&path, 2).unwrap();
+        }
+        // Only the newest two versions are kept
+        assert_eq!(fs::read_to_string(backup_path(&path, 1)).unwrap(), "v4");
+        assert_eq!(fs::read_to_string(backup_path(&path, 2)).unwrap(), "v3");
+        assert!(!backup_path(&path, 3).exists());
+
+        restore_latest(&path, 2).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "v4");
+        assert_eq!(latest(&path).unwrap().as_deref(), Some("v3"));
+
+        restore_latest(&path, 2).unwrap();
+        assert_eq!(latest(&path).unwrap(), None);
+        assert!(restore_latest(&path, 2).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+//! Prometheus metrics for the peerings on this router
+//!
+//! Collected when `/metrics` is scraped by querying `wg` and `birdc` for every
+//! verified config, so there is one series per peering and metric.
+
+use crate::bird;
+use crate::config::AppConfig;
+use crate::ipalloc::interface_name;
+use crate::wireguard;
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+/// How long a rendered scrape is reused, so frequent scrapes don't spawn `wg`/`birdc` each time
+const CACHE_TTL: Duration = Duration::from_secs(10);
+
+/// The last rendered scrape and when it was rendered, kept in [`RuntimeState`](super::state::RuntimeState)
+#[derive(Debug, Default)]
+pub(crate) struct ScrapeCache(Mutex<Option<(Instant, String)>>);
+
+/// Observed state of a single peering; `None` where the tool couldn't tell
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerMetrics {
+    pub asn: u32,
+    /// Seconds since the latest WireGuard handshake
+    pub handshake_age_secs: Option<u64>,
+    pub bgp_established: Option<bool>,
+    pub rx_bytes: Option<u64>,
+}
+
+/// ASNs with a verified config (`wg-as{asn}.conf`, not backups or other files), sorted
+pub(crate) fn verified_asns(verified_dir: &Path) -> Vec<u32> {
+    let Ok(entries) = std::fs::read_dir(verified_dir) else {
+        return Vec::new();
+    };
+
+    let mut asns: Vec<u32> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.strip_prefix("wg-as")?
+                .strip_suffix(".conf")?
+                .parse()
+                .ok()
+        })
+        .collect();
+    asns.sort_unstable();
+    asns
+}
+
+/// Query `wg` and `birdc` for one peering
+async fn collect_peer(config: &AppConfig, asn: u32) -> PeerMetrics {
+    let transfer = wireguard::deploy::peer_transfer(&config.system, &interface_name(asn)).await;
+    let bgp = bird::deploy::is_session_established(&config.system, asn).await;
+    if let Err(e) = &transfer {
+        debug!("No WireGuard metrics for ASN {}: {}", asn, e);
+    }
+    if let Err(e) = &bgp {
+        debug!("No BGP metrics for ASN {}: {}", asn, e);
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let transfer = transfer.ok();
+    PeerMetrics {
+        asn,
+        handshake_age_secs: transfer
+            .and_then(|t| t.latest_handshake)
+            .map(|handshake| now.saturating_sub(handshake)),
+        bgp_established: bgp.ok(),
+        rx_bytes: transfer.map(|t| t.rx_bytes),
+    }
+}
+
+/// Render peer metrics in the Prometheus text format
+pub fn format_metrics(peers: &[PeerMetrics]) -> String {
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, value: &dyn Fn(&PeerMetrics) -> Option<u64>| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for peer in peers {
+            if let Some(value) = value(peer) {
+                let _ = writeln!(out, "{}{{asn=\"{}\"}} {}", name, peer.asn, value);
+            }
+        }
+    };
+
+    gauge(
+        "autopeer_peer_handshake_age_seconds",
+        "Seconds since the latest WireGuard handshake with the peer",
+        &|peer| peer.handshake_age_secs,
+    );
+    gauge(
+        "autopeer_peer_bgp_established",
+        "Whether the BGP session with the peer is established",
+        &|peer| peer.bgp_established.map(u64::from),
+    );
+    gauge(
+        "autopeer_peer_rx_bytes",
+        "Bytes received from the peer over WireGuard",
+        &|peer| peer.rx_bytes,
+    );
+    out
+}
+
+/// Render the registry's age in the Prometheus text format (no sample while it is unknown)
+pub fn format_registry_age(age_secs: Option<u64>) -> String {
+    let mut out = String::from(
+        "# HELP autopeer_registry_age_seconds Seconds since the last successful registry sync\n\
+         # TYPE autopeer_registry_age_seconds gauge\n",
+    );
+    if let Some(age) = age_secs {
+        let _ = writeln!(out, "autopeer_registry_age_seconds {}", age);
+    }
+    out
+}
+
+/// GET /metrics - Per-peer metrics in the Prometheus text format
+pub async fn get_metrics(State(config): State<Arc<AppConfig>>) -> Response {
+    let cache = &config.runtime.metrics.0;
+    let cached = cache.lock().ok().and_then(|cache| {
+        cache
+            .as_ref()
+            .filter(|(at, _)| at.elapsed() < CACHE_TTL)
+            .map(|(_, body)| body.clone())
+    });
+
+    let body = match cached {
+        Some(body) => body,
+        None => {
+            let mut peers = Vec::new();
+            for asn in verified_asns(Path::new(&config.data_verified_dir)) {
+                peers.push(collect_peer(&config, asn).await);
+            }
+            let body = format_metrics(&peers);
+            if let Ok(mut cache) = cache.lock() {
+                *cache = Some((Instant::now(), body.clone()));
+            }
+            body
+        }
+    };
+
+    // Not cached: it is cheap, and a stale value would hide a sync that just recovered
+    let body = body
+        + &format_registry_age(
+            config
+                .runtime
+                .registry_syncs
+                .registry_age_secs(&config.registry.path),
+        );
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_metrics() {
+        let peers = [
+            PeerMetrics {
+                asn: 4242421816,
+                handshake_age_secs: Some(42),
+                bgp_established: Some(true),
+                rx_bytes: Some(1234),
+            },
+            // Never handshaked, BIRD not reachable
+            PeerMetrics {
+                asn: 4242422225,
+                handshake_age_secs: None,
+                bgp_established: None,
+                rx_bytes: Some(0),
+            },
+        ];
+
+        let metrics = format_metrics(&peers);
+        assert!(metrics.contains("# TYPE autopeer_peer_handshake_age_seconds gauge\n"));
+        assert!(metrics.contains("autopeer_peer_handshake_age_seconds{asn=\"4242421816\"} 42\n"));
+        assert!(metrics.contains("autopeer_peer_bgp_established{asn=\"4242421816\"} 1\n"));
+        assert!(metrics.contains("autopeer_peer_rx_bytes{asn=\"4242421816\"} 1234\n"));
+        assert!(metrics.contains("autopeer_peer_rx_bytes{asn=\"4242422225\"} 0\n"));
+        // Unknown values are left out rather than reported as 0
+        assert!(!metrics.contains("autopeer_peer_handshake_age_seconds{asn=\"4242422225\"}"));
+        assert!(!metrics.contains("autopeer_peer_bgp_established{asn=\"4242422225\"}"));
+    }
+
+    #[test]
+    fn test_format_registry_age() {
+        let metrics = format_registry_age(Some(120));
+        assert!(metrics.contains("# TYPE autopeer_registry_age_seconds gauge\n"));
+        assert!(metrics.contains("autopeer_registry_age_seconds 120\n"));
+        assert!(!format_registry_age(None)
+            .lines()
+            .any(|line| line.starts_with("autopeer_registry_age_seconds")));
+    }
+
+    #[test]
+    fn test_verified_asns_ignore_other_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for name in [
+            "wg-as4242422225.conf",
+            "wg-as4242421816.conf",
+            "wg-as4242421816.conf.bak.1",
+            "notes.txt",
+        ] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        assert_eq!(verified_asns(dir.path()), vec![4242421816, 4242422225]);
+    }
+}
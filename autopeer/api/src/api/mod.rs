@@ -1,10 +1,42 @@
+pub mod admin;
+mod asn_lock;
+mod attempts;
+mod bundle;
+mod conditional;
+mod config_diff;
+mod cooldown;
+mod health;
+mod history;
+mod metrics;
 pub mod peering;
+mod pending;
+mod probe;
+mod provisioning;
+mod request_email;
+mod state;
+mod throttle;
+mod tombstone;
 
 #[cfg(test)]
 pub mod test_helpers;
 
+pub use admin::{
+    get_peer_authz, get_peer_bird_config, get_peer_status, get_peer_status_batch,
+    list_deleted_peerings, list_tokens, reload_bird, revoke_tokens, set_maintenance, AuthzResponse,
+    BirdReloadRequest, BirdReloadResponse, DeletedPeering, MaintenanceRequest, MaintenanceResponse,
+};
+pub use config_diff::ConfigChange;
+pub use health::{get_readyz, ReadinessResponse};
+pub use metrics::get_metrics;
 pub use peering::{
-    activate_peering, deactivate_peering, delete_peering, deploy_peering, get_config, get_status,
-    init_peering, update_peering, verify_peering, ConfigResponse, DeployRequest, DeployResponse,
-    InitRequest, InitResponse, UpdateRequest, UpdateResponse, VerifyRequest, VerifyResponse,
+    activate_peering, deactivate_peering, delete_peering, deploy_peering, disable_bgp, enable_bgp,
+    get_bird_config, get_bird_snippet, get_config, get_deployment, get_provisioning,
+    get_request_email, get_server_info, get_state, get_status, get_systemd_bundle, init_peering,
+    preview_update, repair_peering, rollback_peering, test_endpoint, test_signature,
+    update_peering, verify_peering, ActionStatus, Allocation, ConfigResponse, DeployRequest,
+    DeployResponse, EndpointTestRequest, EndpointTestResponse, InitRequest, InitResponse,
+    PeeringState, ServerInfo, SignatureTestRequest, StateResponse, UpdatePreviewResponse,
+    UpdateRequest, UpdateResponse, VerifyQuery, VerifyRequest, VerifyResponse,
 };
+pub use provisioning::{ProvisioningState, ProvisioningStatus};
+pub use state::RuntimeState;
@@ -1,19 +1,50 @@
+use super::asn_lock;
+use super::attempts;
+use super::bundle;
+use super::conditional::Validators;
+use super::config_diff::{self, ConfigChange};
+use super::cooldown;
+use super::history;
+use super::metrics::verified_asns;
+use super::pending;
+use super::probe::{self, Reachability};
+use super::provisioning::{self, ProvisioningStatus};
+use super::request_email;
+use super::throttle;
+use super::tombstone;
 use crate::bird;
-use crate::challenge::{gpg::verify_signature, Challenge};
+use crate::challenge::{
+    gpg::{self, verify_signature},
+    minisign, stateless, Challenge,
+};
 use crate::config::AppConfig;
-use crate::ipalloc::{interface_name, wireguard_port, Ipv6LinkLocal};
-use crate::jwt::generate_token;
+use crate::ipalloc::{
+    interface_name, wireguard_port, AddressFamily, BgpTransport, Ipv4Transfer, Ipv6LinkLocal,
+    Ipv6Ula, NeighborMode,
+};
+use crate::jwt::{generate_challenge_token, JwtScope};
 use crate::middleware::JwtAuth;
-use crate::registry::{get_pgp_fingerprint_for_asn, verify_key_fingerprint};
+use crate::registry::{
+    self as registry, get_as_object, get_contact_persons_for_asn, get_maintainer_for_asn,
+    get_pgp_fingerprint_for_asn, get_route_objects_for_asn, get_routes_for_asn, peering_policy,
+    verify_key_fingerprint, MaintainerObject, PeeringPolicy, PersonObject, RouteObject,
+};
 use crate::validation;
-use crate::wireguard::{self, BgpConfig, InterfaceConfig, PeerConfig, WgConfig, WgKeypair};
+use crate::wireguard::{
+    self, BgpConfig, ChallengeConfig, InterfaceConfig, MetaConfig, PeerConfig, PresharedKey,
+    PublicKey, RegistryAuthConfig, WgConfig, WgKeypair,
+};
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
+use pgp::composed::{Deserializable, SignedPublicKey};
 use serde::{Deserialize, Serialize};
+use std::net::{Ipv4Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tower_cookies::{Cookie, Cookies};
 use tracing::{error, info, warn};
 
@@ -22,15 +53,65 @@ use tracing::{error, info, warn};
 pub struct InitRequest {
     /// The peer's ASN
     pub asn: u32,
+    /// Email or handle to notify about session changes (optional)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contact: Option<String>,
+    /// Which of our ASNs to peer with, for the allocation preview (defaults to `my_asn`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_asn: Option<u32>,
 }
 
 /// Response from peering initialization
 #[derive(Debug, Deserialize, Serialize)]
 pub struct InitResponse {
+    /// Id of this challenge, to pass to /peering/verify
+    pub challenge_id: String,
     /// The challenge code to sign
     pub challenge: String,
-    /// The GPG key fingerprint from DN42 registry
+    /// The GPG key fingerprint from DN42 registry (empty if the maintainer only lists minisign keys)
+    ///
+    /// If the maintainer lists no GPG key and `ALLOW_TECH_CONTACT_AUTH` is set, this is the
+    /// fingerprint of a tech-c or admin-c person instead.
     pub pgp_fingerprint: String,
+    /// Minisign keys from the DN42 registry, which may sign the challenge instead of GPG.
+    /// With `ALLOW_TECH_CONTACT_AUTH`, the tech-c and admin-c persons' keys are listed after
+    /// the maintainer's.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub minisign_keys: Vec<String>,
+    /// Peering policy the ASN declares in its aut-num remarks
+    pub peering_policy: PeeringPolicy,
+    /// What a default deploy will allocate, so the peer can prepare its side
+    pub allocated: Allocation,
+}
+
+/// Addresses and port a peering gets by default, derived from the ASN alone (nothing secret)
+///
+/// A deploy may still end up elsewhere, e.g. with an explicit `listen_port` or an IPv4-only session.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Allocation {
+    /// Our WireGuard interface address
+    pub interface_address: String,
+    /// Our WireGuard listen port
+    pub listen_port: u16,
+    /// Address we expect the peer's BGP session on
+    pub bgp_neighbor: String,
+}
+
+impl Allocation {
+    fn for_asn(config: &AppConfig, local_asn: u32, asn: u32) -> Result<Self, (StatusCode, String)> {
+        let ips = Ipv6LinkLocal::from_asns(local_asn, asn);
+        let ula = neighbor_ula(config, asn);
+        let (bgp_local, bgp_neighbor) = session_addrs(&ips, ula.as_ref());
+        let interface_address = match config.bgp_transport {
+            BgpTransport::LinkLocal => ips.local_addr(),
+            BgpTransport::TransferNet => bgp_local,
+        };
+        Ok(Allocation {
+            interface_address,
+            listen_port: wireguard_port(asn).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?,
+            bgp_neighbor,
+        })
+    }
 }
 
 /// POST /peering/init - Initialize a new peering
@@ -39,50 +120,157 @@ pub async fn init_peering(
     Json(req): Json<InitRequest>,
 ) -> Result<Json<InitResponse>, (StatusCode, String)> {
     info!("Peering init request for ASN {}", req.asn);
+    require_not_in_maintenance(&config)?;
 
     // Validate ASN
-    validation::validate_asn(req.asn)?;
-
-    // Generate challenge
-    let challenge = Challenge::generate(req.asn);
+    validation::validate_asn(req.asn, config.asn_range)?;
+    if let Some(ref contact) = req.contact {
+        validation::validate_contact(contact)?;
+        // Only the pending dir carries a contact on to /peering/verify
+        if config.stateless_challenges {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "contact is not supported with stateless challenges; leave it out".to_string(),
+            ));
+        }
+    }
+    let local_asn = config
+        .select_local_asn(req.local_asn)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
 
-    // Fetch PGP fingerprint from registry (required)
+    // Fetch the maintainer's keys from the registry (a PGP fingerprint or minisign key is required)
     let registry_path = &config.registry.path;
-    let pgp_fingerprint = get_pgp_fingerprint_for_asn(registry_path, req.asn)
-        .map_err(|e| {
-            error!("Failed to get PGP fingerprint for ASN {}: {}", req.asn, e);
-            (StatusCode::BAD_REQUEST, format!("No GPG key found in DN42 registry for ASN {}: {}", req.asn, e))
-        })?;
-
-    // Store only the challenge (no keypair yet)
-    let iface_name = interface_name(req.asn);
-    let challenge_path = format!("{}/{}.conf", config.data_pending_dir, iface_name);
-
-    // Ensure pending directory exists
-    std::fs::create_dir_all(&config.data_pending_dir)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create pending dir: {}", e)))?;
+    require_synced_registry(&config)?;
+    let contacts = contact_signers(&config, req.asn);
+    let maintainer = get_maintainer_for_asn(
+        registry_path,
+        &config.registry_data_subdir,
+        config.registry_layout,
+        req.asn,
+    )
+    .and_then(|maintainer| {
+        let maintainer = with_contact_keys(maintainer, &contacts);
+        if maintainer.auth_fingerprints.is_empty() && maintainer.minisign_keys.is_empty() {
+            return Err("No PGP fingerprint or minisign key found for maintainer".to_string());
+        }
+        Ok(maintainer)
+    })
+    .map_err(|e| {
+        error!("Failed to get PGP fingerprint for ASN {}: {}", req.asn, e);
+        (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "No GPG key found in DN42 registry for ASN {}: {}",
+                req.asn, e
+            ),
+        )
+    })?;
+
+    let challenge_id = pending::new_id();
+    let challenge = if config.stateless_challenges {
+        // Nothing is stored; the code carries its own expiry and MAC
+        let expires = chrono::Utc::now().timestamp() + config.challenge_ttl_secs as i64;
+        stateless::issue(
+            req.asn,
+            &config.challenge_prefix,
+            &challenge_id,
+            expires,
+            &config.jwt_secret,
+        )
+    } else {
+        let challenge = Challenge::generate(
+            req.asn,
+            &config.challenge_prefix,
+            config.challenge_random_bytes,
+        );
+        store_pending_challenge(&config, &challenge, &challenge_id, req.contact.as_deref())?;
+        challenge
+    };
 
-    // Save challenge to file
-    std::fs::write(&challenge_path, &challenge.code)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to save challenge: {}", e)))?;
+    // Declared peering policy (informational only)
+    let peering_policy = get_as_object(
+        registry_path,
+        &config.registry_data_subdir,
+        config.registry_layout,
+        req.asn,
+    )
+    .map(|as_obj| peering_policy(&as_obj))
+    .unwrap_or(PeeringPolicy::Unknown);
 
     Ok(Json(InitResponse {
+        challenge_id,
         challenge: challenge.code,
-        pgp_fingerprint,
+        pgp_fingerprint: maintainer
+            .auth_fingerprints
+            .first()
+            .cloned()
+            .unwrap_or_default(),
+        minisign_keys: maintainer.minisign_keys,
+        peering_policy,
+        allocated: Allocation::for_asn(&config, local_asn, req.asn)?,
     }))
 }
 
+/// Store a challenge (no keypair yet) under its own id so earlier flows stay valid
+fn store_pending_challenge(
+    config: &AppConfig,
+    challenge: &Challenge,
+    challenge_id: &str,
+    contact: Option<&str>,
+) -> Result<(), (StatusCode, String)> {
+    let challenge_path = pending::path(&config.data_pending_dir, challenge.asn, challenge_id);
+
+    // Ensure pending directory exists
+    std::fs::create_dir_all(&config.data_pending_dir).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to create pending dir: {}", e),
+        )
+    })?;
+    pending::prune(config, challenge.asn, pending::MAX_PENDING_PER_ASN - 1);
+
+    // Save challenge to file
+    std::fs::write(&challenge_path, &challenge.code).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to save challenge: {}", e),
+        )
+    })?;
+
+    // The contact rides along until verify puts it into the token
+    if let Some(contact) = contact {
+        std::fs::write(pending::contact_path(&challenge_path), contact).map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to save contact: {}", e),
+            )
+        })?;
+    }
+    Ok(())
+}
+
 /// Request to verify a peering
 #[derive(Debug, Deserialize, Serialize)]
 pub struct VerifyRequest {
     /// The peer's ASN
     pub asn: u32,
-    /// The signed challenge (cleartext signed message)
+    /// Which pending challenge to verify (optional while only one is pending)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub challenge_id: Option<String>,
+    /// The signed challenge (cleartext signed message, or a minisign signature)
     pub signed_challenge: String,
-    /// The peer's PGP public key
+    /// The peer's PGP public key (not needed for minisign signatures)
+    #[serde(default)]
     pub public_key: String,
 }
 
+/// Query parameters of POST /peering/verify
+#[derive(Debug, Default, Deserialize)]
+pub struct VerifyQuery {
+    /// Present (with any value) to ask for the browser redirect
+    pub redirect: Option<String>,
+}
+
 /// Deployment information (safe to show to user)
 #[derive(Debug, Deserialize, Serialize)]
 pub struct DeploymentInfo {
@@ -94,6 +282,11 @@ pub struct DeploymentInfo {
     pub our_public_key: String,
     /// Our WireGuard endpoint
     pub our_endpoint: String,
+    /// The peer's WireGuard public key, as we stored it
+    pub peer_public_key: Option<String>,
+    /// No endpoint is pinned for the peer; we wait for it to initiate
+    #[serde(default)]
+    pub dynamic_endpoint: bool,
     /// BGP configuration
     pub bgp_neighbor: String,
     pub bgp_local_as: u32,
@@ -113,59 +306,181 @@ pub struct VerifyResponse {
 pub async fn verify_peering(
     State(config): State<Arc<AppConfig>>,
     cookies: Cookies,
+    headers: HeaderMap,
+    Query(query): Query<VerifyQuery>,
     Json(req): Json<VerifyRequest>,
-) -> Result<Json<VerifyResponse>, (StatusCode, String)> {
+) -> Result<Response, (StatusCode, String)> {
     info!("Peering verify request for ASN {}", req.asn);
+    require_not_in_maintenance(&config)?;
 
     // Validate inputs
-    validation::validate_asn(req.asn)?;
-    validation::validate_pgp_key(&req.public_key)?;
+    validation::validate_asn(req.asn, config.asn_range)?;
     validation::validate_signed_challenge(&req.signed_challenge)?;
 
-    // Load pending challenge
-    let iface_name = interface_name(req.asn);
-    let challenge_path = format!("{}/{}.conf", config.data_pending_dir, iface_name);
+    // Minisign signatures are checked against the keys in the registry, PGP ones against the submitted key
+    let minisign_keys = registry_minisign_keys(&config, &req)?;
+    if minisign_keys.is_empty() {
+        validation::validate_pgp_key(&req.public_key)?;
+
+        // Enforce the PGP key algorithm policy before any expensive verification
+        let (parsed_key, _) = SignedPublicKey::from_string(&req.public_key).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Failed to parse public key: {}", e),
+            )
+        })?;
+        validation::check_key_policy(&parsed_key, &config)?;
+    }
 
-    let stored_challenge = std::fs::read_to_string(&challenge_path)
-        .map_err(|e| (StatusCode::NOT_FOUND, format!("Challenge not found: {}", e)))?;
+    // The challenge the peer had to sign, and where it is stored (nothing in stateless mode)
+    let (stored_challenge, challenge_path) = if config.stateless_challenges {
+        (verify_stateless_challenge(&config, &req)?, None)
+    } else {
+        let (code, path) = load_pending_challenge(&config, &req)?;
+        (code, Some(path))
+    };
+    let fail = |err| match &challenge_path {
+        Some(path) => attempts::fail(&config, path, err),
+        None => err,
+    };
 
-    // Verify GPG signature
-    let signature_valid = verify_signature(&stored_challenge, &req.signed_challenge, &req.public_key)
-        .map_err(|e| {
-            warn!("Signature verification failed for ASN {}: {}", req.asn, e);
-            (StatusCode::UNAUTHORIZED, format!("Signature verification failed: {}", e))
-        })?;
+    // Verify the signature, limiting how many run at once; it runs on the blocking pool so
+    // slow RSA checks don't hold up the runtime's workers
+    let permit = throttle::acquire(&config).await?;
+    let (challenge, signed, public_key, keys) = (
+        stored_challenge.clone(),
+        req.signed_challenge.clone(),
+        req.public_key.clone(),
+        minisign_keys.clone(),
+    );
+    let signature_valid = tokio::task::spawn_blocking(move || {
+        let _permit = permit;
+        if keys.is_empty() {
+            verify_signature(&challenge, &signed, &public_key)
+        } else {
+            minisign::verify_signature(&challenge, &signed, &keys)
+        }
+    })
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Signature verification task failed: {}", e),
+        )
+    })?;
+    let signature_valid = signature_valid.map_err(|e| {
+        warn!("Signature verification failed for ASN {}: {}", req.asn, e);
+        fail((
+            StatusCode::UNAUTHORIZED,
+            format!("Signature verification failed: {}", e),
+        ))
+    })?;
 
     if !signature_valid {
         warn!("Invalid signature for ASN {}", req.asn);
-        return Err((StatusCode::UNAUTHORIZED, "Invalid signature".to_string()));
+        return Err(fail((
+            StatusCode::UNAUTHORIZED,
+            "Invalid signature".to_string(),
+        )));
     }
 
-    // Verify public key matches DN42 registry
-    let registry_path = &config.registry.path;
-    let expected_fingerprint = get_pgp_fingerprint_for_asn(registry_path, req.asn)
-        .map_err(|e| {
-            error!("Failed to get registry fingerprint for ASN {}: {}", req.asn, e);
-            (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to get registry fingerprint: {}", e))
-        })?;
+    // Verify public key matches DN42 registry (minisign keys came from there already)
+    let authorized_key = if !minisign_keys.is_empty() {
+        minisign::signing_key(&req.signed_challenge, &minisign_keys)
+    } else {
+        let registry_path = &config.registry.path;
+        require_synced_registry(&config)?;
+        let contacts = contact_signers(&config, req.asn);
+        let expected_fingerprint = match get_pgp_fingerprint_for_asn(
+            registry_path,
+            &config.registry_data_subdir,
+            config.registry_layout,
+            req.asn,
+        ) {
+            Ok(fingerprint) => Some(fingerprint),
+            // A contact's key may still sign for a maintainer without one
+            Err(_)
+                if contacts
+                    .iter()
+                    .any(|person| !person.auth_fingerprints.is_empty()) =>
+            {
+                None
+            }
+            Err(e) => {
+                error!(
+                    "Failed to get registry fingerprint for ASN {}: {}",
+                    req.asn, e
+                );
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to get registry fingerprint: {}", e),
+                ));
+            }
+        };
 
-    verify_key_fingerprint(&req.public_key, &expected_fingerprint)
-        .map_err(|e| {
-            warn!("Key verification failed for ASN {}: {}", req.asn, e);
-            (StatusCode::UNAUTHORIZED, format!("Key verification failed: {}", e))
-        })?;
+        // The maintainer's key first, then the contacts' (only listed with ALLOW_TECH_CONTACT_AUTH)
+        let candidates = expected_fingerprint.into_iter().chain(
+            contacts
+                .into_iter()
+                .flat_map(|person| person.auth_fingerprints),
+        );
+        let mut matching = None;
+        for fingerprint in candidates {
+            let key_matches =
+                verify_key_fingerprint(&req.public_key, &fingerprint).map_err(|e| {
+                    warn!("Key verification failed for ASN {}: {}", req.asn, e);
+                    fail((
+                        StatusCode::UNAUTHORIZED,
+                        format!("Key verification failed: {}", e),
+                    ))
+                })?;
+            if key_matches {
+                matching = Some(fingerprint);
+                break;
+            }
+        }
+        // The signing key chains to this primary key (checked with the signature above)
+        if matching.is_none() {
+            warn!(
+                "Public key for ASN {} does not match the registry fingerprint",
+                req.asn
+            );
+            return Err(fail((
+                StatusCode::UNAUTHORIZED,
+                "Key verification failed: public key does not match the registry fingerprint"
+                    .to_string(),
+            )));
+        }
+        matching
+    };
 
     info!("Successfully verified ASN {}, issuing JWT token", req.asn);
 
     // Generate JWT token
-    let token = generate_token(req.asn, &config.jwt_secret)
-        .map_err(|e| {
-            error!("Failed to generate token for ASN {}: {}", req.asn, e);
-            (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to generate token: {}", e))
-        })?;
-
-    // Remove pending challenge file
-    let _ = std::fs::remove_file(&challenge_path);
+    let contact = challenge_path
+        .as_ref()
+        .and_then(|path| std::fs::read_to_string(pending::contact_path(path)).ok());
+    let scope = JwtScope::from_config(&config);
+    let token = generate_challenge_token(
+        req.asn,
+        stored_challenge.trim(),
+        contact.as_deref(),
+        authorized_key.as_deref(),
+        &config.jwt_secret,
+        &scope,
+    )
+    .map_err(|e| {
+        error!("Failed to generate token for ASN {}: {}", req.asn, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to generate token: {}", e),
+        )
+    })?;
+
+    // Remove pending challenge file (other pending challenges stay usable)
+    if let Some(path) = &challenge_path {
+        let _ = pending::remove(&config, path);
+    }
 
     // Set HTTP-only secure cookies for each domain
     for domain in &config.cookie_domains {
@@ -179,9 +494,206 @@ pub async fn verify_peering(
         cookies.add(cookie);
     }
 
-    Ok(Json(VerifyResponse {
-        token,
-    }))
+    // Browsers are sent on to the configured page (never to a URL from the request)
+    if let Some(target) = &config.post_verify_redirect {
+        if query.redirect.is_some() || prefers_html(&headers) {
+            return Ok(
+                (StatusCode::SEE_OTHER, [(header::LOCATION, target.clone())]).into_response(),
+            );
+        }
+    }
+
+    Ok(Json(VerifyResponse { token }).into_response())
+}
+
+/// Whether the `Accept` header ranks `text/html` above `application/json`
+fn prefers_html(headers: &HeaderMap) -> bool {
+    let Some(accept) = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+
+    let (mut html, mut json) = (0.0_f32, 0.0_f32);
+    for entry in accept.split(',') {
+        let mut parts = entry.split(';').map(str::trim);
+        let media_type = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let quality = parts
+            .find_map(|param| param.strip_prefix("q="))
+            .and_then(|q| q.parse().ok())
+            .unwrap_or(1.0);
+        match media_type.as_str() {
+            "text/html" => html = html.max(quality),
+            "application/json" | "*/*" => json = json.max(quality),
+            _ => {}
+        }
+    }
+    html > json
+}
+
+/// Minisign keys to check a minisign signature against, empty for PGP signatures
+fn registry_minisign_keys(
+    config: &AppConfig,
+    req: &VerifyRequest,
+) -> Result<Vec<String>, (StatusCode, String)> {
+    if !minisign::is_minisign_signature(&req.signed_challenge) {
+        return Ok(Vec::new());
+    }
+    if config.stateless_challenges {
+        // The challenge is read back from the signed text, which a minisign signature doesn't carry
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Minisign signatures need stored challenges; sign with GPG instead".to_string(),
+        ));
+    }
+
+    require_synced_registry(config)?;
+    let maintainer = get_maintainer_for_asn(
+        &config.registry.path,
+        &config.registry_data_subdir,
+        config.registry_layout,
+        req.asn,
+    )
+    .map_err(|e| {
+        error!("Failed to get maintainer for ASN {}: {}", req.asn, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to get registry maintainer: {}", e),
+        )
+    })?;
+    let maintainer = with_contact_keys(maintainer, &contact_signers(config, req.asn));
+    if maintainer.minisign_keys.is_empty() {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            format!(
+                "{} lists no ed25519-minisign key; sign the challenge with GPG",
+                maintainer.mntner
+            ),
+        ));
+    }
+    Ok(maintainer.minisign_keys)
+}
+
+/// The ASN's tech-c/admin-c persons, whose keys may sign too (none unless `ALLOW_TECH_CONTACT_AUTH`)
+fn contact_signers(config: &AppConfig, asn: u32) -> Vec<PersonObject> {
+    if !config.allow_tech_contact_auth {
+        return Vec::new();
+    }
+    get_contact_persons_for_asn(
+        &config.registry.path,
+        &config.registry_data_subdir,
+        config.registry_layout,
+        asn,
+    )
+    .unwrap_or_else(|e| {
+        warn!(
+            "Failed to get contact persons for ASN {}, only the maintainer may sign: {}",
+            asn, e
+        );
+        Vec::new()
+    })
+}
+
+/// The maintainer with its contacts' keys appended after its own
+fn with_contact_keys(
+    mut maintainer: MaintainerObject,
+    contacts: &[PersonObject],
+) -> MaintainerObject {
+    for person in contacts {
+        maintainer
+            .auth_fingerprints
+            .extend(person.auth_fingerprints.iter().cloned());
+        maintainer
+            .minisign_keys
+            .extend(person.minisign_keys.iter().cloned());
+    }
+    maintainer
+}
+
+/// Check a stateless challenge, taken from the signed text itself, and return its code
+fn verify_stateless_challenge(
+    config: &AppConfig,
+    req: &VerifyRequest,
+) -> Result<String, (StatusCode, String)> {
+    let code = gpg::signed_text(&req.signed_challenge).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Failed to read signed challenge: {}", e),
+        )
+    })?;
+
+    stateless::verify(
+        &code,
+        req.asn,
+        &config.challenge_prefix,
+        &config.jwt_secret,
+        chrono::Utc::now().timestamp(),
+    )
+    .map_err(|e| {
+        warn!("Stateless challenge rejected for ASN {}: {}", req.asn, e);
+        (StatusCode::UNAUTHORIZED, e)
+    })?;
+    Ok(code)
+}
+
+/// Read the pending challenge a verify refers to, returning its code and path
+fn load_pending_challenge(
+    config: &AppConfig,
+    req: &VerifyRequest,
+) -> Result<(String, String), (StatusCode, String)> {
+    let challenge_path = match &req.challenge_id {
+        Some(id) if pending::is_valid_id(id) => {
+            pending::path(&config.data_pending_dir, req.asn, id)
+        }
+        Some(_) => return Err((StatusCode::BAD_REQUEST, "Invalid challenge id".to_string())),
+        None => match pending::list(&config.data_pending_dir, req.asn).as_slice() {
+            [only] => only.to_string_lossy().to_string(),
+            [] => return Err((StatusCode::NOT_FOUND, "Challenge not found".to_string())),
+            _ => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "Several challenges are pending for this ASN, please pass challenge_id"
+                        .to_string(),
+                ))
+            }
+        },
+    };
+
+    let stored_challenge = std::fs::read_to_string(&challenge_path)
+        .map_err(|e| (StatusCode::NOT_FOUND, format!("Challenge not found: {}", e)))?;
+
+    // The stored code must belong to this ASN (and match the current prefix)
+    let parsed_challenge =
+        Challenge::parse(&stored_challenge, &config.challenge_prefix).map_err(|e| {
+            // A well-formed code for this ASN that no longer parses was issued under an older format
+            if Challenge::embedded_asn(&stored_challenge) == Some(req.asn) {
+                warn!(
+                    "Pending challenge for ASN {} uses an outdated format: {}",
+                    req.asn, e
+                );
+                (
+                    StatusCode::BAD_REQUEST,
+                    "Challenge format changed, please re-init with /peering/init".to_string(),
+                )
+            } else {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!(
+                        "Stored challenge is invalid, please restart with /peering/init: {}",
+                        e
+                    ),
+                )
+            }
+        })?;
+    if parsed_challenge.asn != req.asn {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Challenge was issued for a different ASN".to_string(),
+        ));
+    }
+
+    Ok((stored_challenge, challenge_path))
 }
 
 /// Request to deploy a peering
@@ -189,8 +701,47 @@ pub async fn verify_peering(
 pub struct DeployRequest {
     /// The peer's WireGuard public key
     pub wg_public_key: String,
-    /// The peer's public endpoint (IP:port)
-    pub endpoint: String,
+    /// The peer's public endpoint (IP:port), required unless `dynamic_endpoint` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// Import limit for the session (optional, may only lower the default)
+    pub max_prefixes: Option<u32>,
+    /// Address families to exchange (defaults to dual-stack)
+    #[serde(default)]
+    pub address_family: AddressFamily,
+    /// The peer's IPv4 tunnel address (required for IPv4-only sessions)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_ipv4: Option<Ipv4Addr>,
+    /// Our listen port for this peering (optional, derived from the ASN by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listen_port: Option<u16>,
+    /// BGP router id for the peer's side of the session (optional, IPv4 format)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub router_id: Option<String>,
+    /// Name for the session in BIRD, e.g. the peer's node name (defaults to `AS<asn>`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_name: Option<String>,
+    /// BGP graceful restart for the session (optional, off unless the peer supports it)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub graceful_restart: Option<bool>,
+    /// Return right away and configure the router in the background
+    #[serde(default)]
+    pub async_provisioning: bool,
+    /// WireGuard preshared key for the tunnel (optional, base64 like `wg genpsk` prints)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preshared_key: Option<String>,
+    /// Have us generate the preshared key; it comes back in the response
+    #[serde(default)]
+    pub generate_preshared_key: bool,
+    /// The peer's address changes: don't pin an endpoint and wait for the peer to initiate
+    #[serde(default)]
+    pub dynamic_endpoint: bool,
+    /// Bring the peering up right away even when deploys only store it (`deploy_auto_activate` off)
+    #[serde(default)]
+    pub deploy_and_activate: bool,
+    /// Which of our ASNs to peer with (defaults to `my_asn`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_asn: Option<u32>,
 }
 
 /// Response from peering deployment
@@ -198,6 +749,12 @@ pub struct DeployRequest {
 pub struct DeployResponse {
     /// Deployment information about what we configured
     pub deployment: DeploymentInfo,
+    /// Id to poll at `/peering/provisioning/{id}` for asynchronous deployments
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provisioning_id: Option<String>,
+    /// The preshared key we generated, for the peer to install (only with `generate_preshared_key`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preshared_key: Option<String>,
 }
 
 /// POST /peering/deploy - Deploy a verified peering configuration
@@ -208,104 +765,366 @@ pub async fn deploy_peering(
 ) -> Result<Json<DeployResponse>, (StatusCode, String)> {
     let asn = auth.asn;
     info!("Peering deploy request for ASN {}", asn);
+    require_not_in_maintenance(&config)?;
 
     // Validate WireGuard inputs
-    validation::validate_wg_pubkey(&req.wg_public_key)?;
-    validation::validate_endpoint(&req.endpoint)?;
+    let peer_public_key = validation::validate_wg_pubkey(&req.wg_public_key)?;
+    let endpoint = match (&req.endpoint, req.dynamic_endpoint) {
+        (Some(_), true) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Send either endpoint or dynamic_endpoint, not both".to_string(),
+            ))
+        }
+        (Some(endpoint), false) => {
+            validation::validate_endpoint(endpoint, &config)?;
+            Some(endpoint.clone())
+        }
+        (None, true) => None,
+        (None, false) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "endpoint is required unless dynamic_endpoint is set".to_string(),
+            ))
+        }
+    };
+    let max_prefixes = resolve_max_prefixes(&config, req.max_prefixes)?;
+    let ipv4 = ipv4_transfer(&config, req.address_family, req.peer_ipv4)?;
+    let listen_port = resolve_listen_port(&config, asn, req.listen_port)?;
+    let router_id = req
+        .router_id
+        .as_deref()
+        .map(validation::validate_router_id)
+        .transpose()?;
+    let peer_name = req
+        .peer_name
+        .as_deref()
+        .and_then(validation::sanitize_peer_name);
+    let local_asn = config
+        .select_local_asn(req.local_asn)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let preshared_key = match (&req.preshared_key, req.generate_preshared_key) {
+        (Some(_), true) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Send either preshared_key or generate_preshared_key, not both".to_string(),
+            ))
+        }
+        (Some(key), false) => Some(validation::validate_wg_preshared_key(key)?),
+        (None, true) => Some(PresharedKey::generate()),
+        (None, false) => None,
+    };
+    // Only a key we made has to travel back to the peer
+    let generated_preshared_key = preshared_key
+        .as_ref()
+        .filter(|_| req.generate_preshared_key)
+        .map(|key| key.expose().to_string());
+    if let Some(endpoint) = &endpoint {
+        check_duplicate_endpoint(&config, asn, endpoint).await?;
+    }
+
+    if Ipv6LinkLocal::collides(local_asn, asn) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "AS{} shares its last 4 digits with AS{}, so the tunnel addresses derived for it would collide with ours",
+                asn, local_asn
+            ),
+        ));
+    }
+
+    // A deploy that only stores must not swap the keys under a tunnel that is running
+    let store_only = !config.deploy_auto_activate && !req.deploy_and_activate;
+    if store_only && is_live(&config, asn).await {
+        return Err((
+            StatusCode::CONFLICT,
+            format!(
+                "The peering for ASN {} is active; deactivate it first or deploy with deploy_and_activate",
+                asn
+            ),
+        ));
+    }
+
+    // Reject rapid re-deploys
+    let cooldown = cooldown::acquire(&config, asn)?;
 
     // Generate WireGuard keypair for this peer
     info!("Generating WireGuard keypair for ASN {}", asn);
-    let keypair = WgKeypair::generate()
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to generate keypair: {}", e)))?;
+    let keypair = WgKeypair::generate(&config.system).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to generate keypair: {}", e),
+        )
+    })?;
 
     // Allocate IPs
-    let ips = Ipv6LinkLocal::from_asns(config.my_asn, asn);
+    let ips = Ipv6LinkLocal::from_asns(local_asn, asn);
+    let ula = neighbor_ula(&config, asn);
+    let (bgp_local, bgp_neighbor) = match ipv4 {
+        Some(ipv4) => (ipv4.local.to_string(), ipv4.peer.to_string()),
+        None => session_addrs(&ips, ula.as_ref()),
+    };
+    let bgp = BgpConfig {
+        enabled: true,
+        mpbgp: req.address_family == AddressFamily::Dual,
+        extended_next_hop: req.address_family == AddressFamily::Dual,
+        local: bgp_local,
+        neighbor: bgp_neighbor.clone(),
+        max_prefixes,
+        address_family: req.address_family,
+        router_id,
+        peer_name,
+        graceful_restart: req.graceful_restart,
+        transport: config.bgp_transport,
+        local_as: Some(local_asn),
+    };
 
     // Create complete WireGuard config
     let iface_name = interface_name(asn);
+    if !config.manage_wireguard {
+        require_external_interface(&config, asn, &iface_name).await?;
+    }
     let wg_config = WgConfig {
         interface: InterfaceConfig {
-            address: vec![ips.peer.clone()],
+            // Link-local unless only the transfer net is wanted; the ULA or IPv4 transfer address on top
+            address: (config.bgp_transport == BgpTransport::LinkLocal)
+                .then(|| ips.peer.clone())
+                .into_iter()
+                .chain(ula.as_ref().map(|ula| ula.local.clone()))
+                .chain(ipv4.map(|ipv4| format!("{}/32", ipv4.local)))
+                .collect(),
             private_key: keypair.private_key.clone(),
-            listen_port: wireguard_port(asn),
+            listen_port,
             table: Some("off".to_string()),
+            dns: Vec::new(),
         },
-        peer: Some(PeerConfig {
-            public_key: req.wg_public_key.clone(),
-            endpoint: Some(req.endpoint.clone()),
-            allowed_ips: vec!["0.0.0.0/0".to_string(), "::/0".to_string()],
+        peers: vec![PeerConfig {
+            public_key: peer_public_key,
+            // Without an endpoint the peer initiates; the keepalive keeps its NAT mapping open
+            endpoint,
+            allowed_ips: allowed_ips(&config, asn, req.address_family),
             persistent_keepalive: Some(25),
+            preshared_key,
+        }],
+        // Audit trail: which challenge authorized this deployment
+        challenge: auth.challenge.map(|code| ChallengeConfig { code, asn }),
+        bgp: Some(bgp.clone()),
+        meta: auth.contact.map(|contact| MetaConfig {
+            contact: Some(contact),
         }),
-        challenge: None,
-        bgp: Some(BgpConfig {
-            mpbgp: true,
-            extended_next_hop: true,
-            local: ips.local_addr(),
-            neighbor: ips.peer.clone(),
+        // Audit trail: which registry key authorized it, and when
+        registry: auth.fingerprint.map(|fingerprint| RegistryAuthConfig {
+            fingerprint,
+            authorized_at: auth.issued_at,
         }),
     };
 
     // Save to verified directory
     let verified_path = format!("{}/{}.conf", config.data_verified_dir, iface_name);
-    std::fs::create_dir_all(&config.data_verified_dir)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create verified dir: {}", e)))?;
-
-    wg_config
-        .to_file(&verified_path)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to save verified config: {}", e)))?;
+    std::fs::create_dir_all(&config.data_verified_dir).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to create verified dir: {}", e),
+        )
+    })?;
+
+    wg_config.to_file(&verified_path).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to save verified config: {}", e),
+        )
+    })?;
 
     // Generate WireGuard config string for deployment
-    let wg_config_str = wg_config
-        .as_string()
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to generate WireGuard config: {}", e)))?;
+    let wg_config_str = render_wg_quick_config(&config, asn, &wg_config)?;
+
+    // Generate BIRD configuration
+    let bird_config_str = render_bird_config(&config, asn, &bgp)?;
+
+    // Without auto-activation a deploy only records the peering; /peering/activate applies it
+    if store_only {
+        cooldown.commit();
+        info!(
+            "Stored peering for ASN {}; /peering/activate brings it up",
+            asn
+        );
+        let deployment = deployment_info(&config, asn, &wg_config, &keypair.public_key, false);
+        return Ok(Json(DeployResponse {
+            deployment,
+            provisioning_id: None,
+            preshared_key: generated_preshared_key,
+        }));
+    }
+
+    if req.async_provisioning {
+        let id = provisioning::queue(&config, asn)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        info!(
+            "Provisioning peering for ASN {} in the background ({})",
+            asn, id
+        );
+
+        let task_config = config.clone();
+        let task_id = id.clone();
+        let task_iface = iface_name.clone();
+        tokio::spawn(async move {
+            provisioning::start(&task_config, &task_id);
+            let result = provision(
+                &task_config,
+                asn,
+                &task_iface,
+                &wg_config_str,
+                &bird_config_str,
+            )
+            .await;
+            if result.is_ok() {
+                cooldown.commit();
+            }
+            provisioning::finish(&task_config, &task_id, result.map_err(|(_, e)| e));
+        });
+
+        // Nothing is up yet; the peer polls for the outcome
+        let deployment = deployment_info(&config, asn, &wg_config, &keypair.public_key, false);
+        return Ok(Json(DeployResponse {
+            deployment,
+            provisioning_id: Some(id),
+            preshared_key: generated_preshared_key,
+        }));
+    }
+
+    provision(&config, asn, &iface_name, &wg_config_str, &bird_config_str).await?;
+    cooldown.commit();
+
+    // Build deployment info to return
+    // Check if interface is active
+    let is_active = wireguard::deploy::is_interface_active(&config.system, &iface_name).await;
+    let deployment = deployment_info(&config, asn, &wg_config, &keypair.public_key, is_active);
+
+    Ok(Json(DeployResponse {
+        deployment,
+        provisioning_id: None,
+        preshared_key: generated_preshared_key,
+    }))
+}
+
+/// Configure the router for a new peering, undoing it if it runs past `deploy_timeout_secs`
+async fn provision(
+    config: &AppConfig,
+    asn: u32,
+    iface_name: &str,
+    wg_config_str: &str,
+    bird_config_str: &str,
+) -> Result<(), (StatusCode, String)> {
+    let deployed = within_deploy_timeout(
+        config,
+        asn,
+        "Deploying the peering",
+        deploy_to_router(config, asn, iface_name, wg_config_str, bird_config_str),
+    )
+    .await;
+
+    if let Err((StatusCode::GATEWAY_TIMEOUT, _)) = deployed {
+        // Don't leave a half-configured peering behind
+        if config.manage_wireguard {
+            if let Err(e) = wireguard::deploy::remove_config(&config.system, iface_name).await {
+                warn!(
+                    "Failed to bring down {} after a timed-out deploy: {}",
+                    iface_name, e
+                );
+            }
+        }
+        if let Err(e) = bird::deploy::remove_config(&config.system, asn).await {
+            warn!(
+                "Failed to remove BIRD config for ASN {} after a timed-out deploy: {}",
+                asn, e
+            );
+        }
+    }
+    deployed
+}
 
+/// Deploy WireGuard (if we manage it), then BIRD
+async fn deploy_to_router(
+    config: &AppConfig,
+    asn: u32,
+    iface_name: &str,
+    wg_config_str: &str,
+    bird_config_str: &str,
+) -> Result<(), (StatusCode, String)> {
     // Deploy WireGuard configuration
-    info!("Deploying WireGuard config for ASN {} ({})", asn, iface_name);
-    wireguard::deploy::deploy_config(&wg_config_str, &iface_name)
+    if config.manage_wireguard {
+        info!(
+            "Deploying WireGuard config for ASN {} ({})",
+            asn, iface_name
+        );
+        wireguard::deploy::deploy_config(
+            &config.system,
+            wg_config_str,
+            iface_name,
+            config.external_private_keys,
+        )
+        .await
         .map_err(|e| {
             error!("Failed to deploy WireGuard for ASN {}: {}", asn, e);
-            (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to deploy WireGuard: {}", e))
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to deploy WireGuard: {}", e),
+            )
         })?;
+    }
 
-    // Generate and deploy BIRD configuration
+    // Deploy BIRD configuration
     info!("Deploying BIRD config for ASN {}", asn);
-    let bird_peer_config = bird::BirdPeerConfig::new(
-        config.my_asn,
-        asn,
-        format!("AS{}", asn),
-        iface_name.clone(),
-    );
-
-    let bird_config_str = bird_peer_config
-        .to_config()
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to generate BIRD config: {}", e)))?;
-
-    bird::deploy::deploy_config(&bird_config_str, asn)
+    bird::deploy::deploy_config(&config.system, bird_config_str, asn)
+        .await
         .map_err(|e| {
             error!("Failed to deploy BIRD config for ASN {}: {}", asn, e);
-            (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to deploy BIRD config: {}", e))
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to deploy BIRD config: {}", e),
+            )
         })?;
 
     info!("Successfully deployed peering for ASN {}", asn);
+    Ok(())
+}
 
-    // Build deployment info to return
-    // Check if interface is active
-    let is_active = wireguard::deploy::is_interface_active(&iface_name);
-
-    let deployment = DeploymentInfo {
-        interface_address: ips.local_addr(),
-        listen_port: wireguard_port(asn),
-        our_public_key: keypair.public_key.clone(),
-        our_endpoint: format!("{}:{}", config.public_endpoint, wireguard_port(asn)),
-        bgp_neighbor: ips.peer.clone(),
-        bgp_local_as: config.my_asn,
-        bgp_remote_as: asn,
-        is_active,
-    };
+/// Run a change on the router, giving up with 504 once `deploy_timeout_secs` have passed
+///
+/// Dropping `step` kills the command it is waiting on.
+async fn within_deploy_timeout<T>(
+    config: &AppConfig,
+    asn: u32,
+    action: &str,
+    step: impl std::future::Future<Output = Result<T, (StatusCode, String)>>,
+) -> Result<T, (StatusCode, String)> {
+    let limit = std::time::Duration::from_secs(config.deploy_timeout_secs);
+    tokio::time::timeout(limit, step).await.unwrap_or_else(|_| {
+        error!(
+            "{} for ASN {} timed out after {}s",
+            action,
+            asn,
+            limit.as_secs()
+        );
+        Err((
+            StatusCode::GATEWAY_TIMEOUT,
+            format!("{} timed out after {}s", action, limit.as_secs()),
+        ))
+    })
+}
 
-    Ok(Json(DeployResponse {
-        deployment,
-    }))
+/// GET /peering/provisioning/{id} - Progress of an asynchronous deployment
+pub async fn get_provisioning(
+    State(config): State<Arc<AppConfig>>,
+    auth: JwtAuth,
+    Path(id): Path<String>,
+) -> Result<Json<ProvisioningStatus>, (StatusCode, String)> {
+    provisioning::status(&config, &id, auth.asn)
+        .map(Json)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            format!("No provisioning with id {}", id),
+        ))
 }
 
 /// Response from config retrieval
@@ -315,11 +1134,22 @@ pub struct ConfigResponse {
     pub wireguard_config: String,
 }
 
+/// Read a verified config file along with its cache validators
+fn load_verified_config(path: &str) -> Result<(WgConfig, Validators), String> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let wg_config = WgConfig::from_string(&content)?;
+    let validators = Validators::for_file(path, &content)?;
+    Ok((wg_config, validators))
+}
+
 /// GET /peering/config - Retrieve verified peering configuration (ASN from JWT)
+/// Supports If-None-Match / If-Modified-Since (304 Not Modified)
 pub async fn get_config(
     State(config): State<Arc<AppConfig>>,
     auth: JwtAuth,
-) -> Result<Json<ConfigResponse>, (StatusCode, String)> {
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
     let asn = auth.asn;
     info!("Config retrieval request for ASN {}", asn);
 
@@ -327,424 +1157,5525 @@ pub async fn get_config(
     let iface_name = interface_name(asn);
     let config_path = format!("{}/{}.conf", config.data_verified_dir, iface_name);
 
-    let wg_config = WgConfig::from_file(&config_path)
+    let (wg_config, validators) = load_verified_config(&config_path)
         .map_err(|e| (StatusCode::NOT_FOUND, format!("Config not found: {}", e)))?;
 
-    // Generate config string
-    let config_str = wg_config
-        .as_string()
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to generate config: {}", e)))?;
+    if validators.is_not_modified(&headers) {
+        return Ok(validators.not_modified());
+    }
 
-    Ok(Json(ConfigResponse {
-        wireguard_config: config_str,
-    }))
+    // Generate config string
+    let config_str = wg_config.as_string().map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to generate config: {}", e),
+        )
+    })?;
+
+    Ok(validators.apply(
+        Json(ConfigResponse {
+            wireguard_config: config_str,
+        })
+        .into_response(),
+    ))
 }
 
 /// GET /peering/status - Get deployment status (safe info only, no private keys)
 /// Returns 404 if not deployed yet (user is logged in but hasn't provided WG details)
+/// Supports If-None-Match / If-Modified-Since (304 Not Modified)
 pub async fn get_status(
     State(config): State<Arc<AppConfig>>,
     auth: JwtAuth,
-) -> Result<Json<DeploymentInfo>, (StatusCode, String)> {
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    info!("Status request for ASN {}", auth.asn);
+    peering_status(&config, auth.asn, &headers).await
+}
+
+/// GET /peering/deployment - The deploy response again, rebuilt from the stored config
+/// (for clients that lost it; the peering is not touched)
+pub async fn get_deployment(
+    State(config): State<Arc<AppConfig>>,
+    auth: JwtAuth,
+) -> Result<Json<DeployResponse>, (StatusCode, String)> {
     let asn = auth.asn;
-    info!("Status request for ASN {}", asn);
+    info!("Deployment request for ASN {}", asn);
+
+    let iface_name = interface_name(asn);
+    let config_path = format!("{}/{}.conf", config.data_verified_dir, iface_name);
+    let wg_config = WgConfig::from_file(&config_path).map_err(|_| {
+        (
+            StatusCode::NOT_FOUND,
+            "Deployment not found. Please provide WireGuard details to deploy.".to_string(),
+        )
+    })?;
+
+    let public_key = WgKeypair::derive_public_key(&config.system, &wg_config.interface.private_key)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to derive public key: {}", e),
+            )
+        })?;
+    let is_active = wireguard::deploy::is_interface_active(&config.system, &iface_name).await;
+
+    Ok(Json(DeployResponse {
+        deployment: deployment_info(&config, asn, &wg_config, &public_key, is_active),
+        provisioning_id: None,
+        preshared_key: None,
+    }))
+}
 
+/// Status response for an ASN, shared by the peer and admin status endpoints
+pub(crate) async fn peering_status(
+    config: &AppConfig,
+    asn: u32,
+    headers: &HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
     // Load verified config (returns 404 if not deployed yet)
     let iface_name = interface_name(asn);
     let config_path = format!("{}/{}.conf", config.data_verified_dir, iface_name);
 
-    let wg_config = WgConfig::from_file(&config_path)
-        .map_err(|_| (StatusCode::NOT_FOUND, "Deployment not found. Please provide WireGuard details to deploy.".to_string()))?;
+    let (wg_config, file_validators) = load_verified_config(&config_path).map_err(|_| {
+        (
+            StatusCode::NOT_FOUND,
+            "Deployment not found. Please provide WireGuard details to deploy.".to_string(),
+        )
+    })?;
 
-    // Extract safe info from config
-    let ips = Ipv6LinkLocal::from_asns(config.my_asn, asn);
+    // Check if interface is active
+    let is_active = wireguard::deploy::is_interface_active(&config.system, &iface_name).await;
+
+    // Interface state is part of the response, so it's part of the ETag too
+    let validators = Validators {
+        etag: format!(
+            "\"{}-{}\"",
+            file_validators.etag.trim_matches('"'),
+            if is_active { "up" } else { "down" }
+        ),
+        last_modified: file_validators.last_modified,
+    };
+    if validators.is_not_modified(headers) {
+        return Ok(validators.not_modified());
+    }
 
     // Derive public key from private key
-    let public_key = WgKeypair::derive_public_key(&wg_config.interface.private_key)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to derive public key: {}", e)))?;
+    let public_key = WgKeypair::derive_public_key(&config.system, &wg_config.interface.private_key)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to derive public key: {}", e),
+            )
+        })?;
 
-    // Check if interface is active
-    let is_active = wireguard::deploy::is_interface_active(&iface_name);
-
-    let deployment = DeploymentInfo {
-        interface_address: ips.local_addr(),
-        listen_port: wireguard_port(asn),
-        our_public_key: public_key,
-        our_endpoint: format!("{}:{}", config.public_endpoint, wireguard_port(asn)),
-        bgp_neighbor: ips.peer.clone(),
-        bgp_local_as: config.my_asn,
-        bgp_remote_as: asn,
-        is_active,
+    let deployment = deployment_info(config, asn, &wg_config, &public_key, is_active);
+    Ok(validators.apply(Json(deployment).into_response()))
+}
+
+/// What a peer may see about a deployed peering (nothing secret)
+pub(crate) fn deployment_info(
+    config: &AppConfig,
+    asn: u32,
+    wg_config: &WgConfig,
+    our_public_key: &PublicKey,
+    is_active: bool,
+) -> DeploymentInfo {
+    // Prefer the addresses stored at deploy time over deriving them again
+    let local_asn = local_asn(config, wg_config.bgp.as_ref());
+    let derived = Ipv6LinkLocal::from_asns(local_asn, asn);
+    let (ips, bgp_neighbor) = match &wg_config.bgp {
+        Some(bgp) => (
+            stored_session_addrs(bgp).0.unwrap_or(derived),
+            bgp.neighbor.clone(),
+        ),
+        None => {
+            let neighbor = session_addrs(&derived, neighbor_ula(config, asn).as_ref()).1;
+            (derived, neighbor)
+        }
+    };
+    let listen_port = wg_config.interface.listen_port;
+    // Without link-local on the tunnel, our address is the session's
+    let interface_address = match &wg_config.bgp {
+        Some(bgp) if bgp.transport == BgpTransport::TransferNet => bgp.local.clone(),
+        _ => ips.local_addr(),
     };
 
-    Ok(Json(deployment))
+    DeploymentInfo {
+        interface_address,
+        listen_port,
+        our_public_key: our_public_key.to_string(),
+        our_endpoint: format!("{}:{}", config.public_endpoint, listen_port),
+        peer_public_key: wg_config.peer().map(|peer| peer.public_key.to_string()),
+        dynamic_endpoint: wg_config.peer().is_some_and(|peer| peer.endpoint.is_none()),
+        bgp_neighbor,
+        bgp_local_as: local_asn,
+        bgp_remote_as: asn,
+        is_active,
+    }
 }
 
-/// Request to update a peering configuration
+/// What peers need to know about this server before peering
 #[derive(Debug, Deserialize, Serialize)]
-pub struct UpdateRequest {
-    /// New endpoint (optional)
-    pub endpoint: Option<String>,
+pub struct ServerInfo {
+    pub asn: u32,
+    /// Every ASN peers can pick with `local_asn`, `asn` first
+    #[serde(default)]
+    pub asns: Vec<u32>,
+    /// Our WireGuard endpoint host (the port is per peering)
+    pub endpoint: String,
+    /// Address families our endpoint is reachable over (`ipv4`, `ipv6`)
+    pub endpoint_families: Vec<String>,
+}
+
+/// GET /peering/server-info - Our ASN and which endpoint families we can reach (no auth)
+pub async fn get_server_info(State(config): State<Arc<AppConfig>>) -> Json<ServerInfo> {
+    let family = config.public_endpoint_family;
+    let endpoint_families = [("ipv4", family.has_ipv4()), ("ipv6", family.has_ipv6())]
+        .into_iter()
+        .filter(|(_, reachable)| *reachable)
+        .map(|(name, _)| name.to_string())
+        .collect();
+
+    Json(ServerInfo {
+        asn: config.my_asn,
+        asns: config.my_asns.clone(),
+        endpoint: config.public_endpoint.clone(),
+        endpoint_families,
+    })
+}
+
+/// Progress of a peering, from challenge to deployment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeeringState {
+    /// A challenge is outstanding in the pending directory
+    Pending,
+    /// Verified (JWT issued) but no config deployed yet
+    Verified,
+    /// A verified config exists
+    Deployed,
 }
 
-/// Response from peering update
+/// Response from state retrieval
 #[derive(Debug, Deserialize, Serialize)]
-pub struct UpdateResponse {
-    /// Status message
-    pub status: String,
+pub struct StateResponse {
+    /// Current peering state
+    pub state: PeeringState,
 }
 
-/// PATCH /peering/update - Update and re-deploy peering configuration
-pub async fn update_peering(
-    State(config): State<Arc<AppConfig>>,
-    auth: JwtAuth,
-    Json(req): Json<UpdateRequest>,
-) -> Result<Json<UpdateResponse>, (StatusCode, String)> {
+/// GET /peering/state - Where the peering stands (pending, verified or deployed)
+pub async fn get_state(State(config): State<Arc<AppConfig>>, auth: JwtAuth) -> Json<StateResponse> {
     let asn = auth.asn;
-    info!("Peering update request for ASN {}", asn);
+    info!("State request for ASN {}", asn);
 
-    // Validate endpoint if provided
-    if let Some(ref endpoint) = req.endpoint {
-        validation::validate_endpoint(endpoint)?;
-    }
-
-    // Load verified config
     let iface_name = interface_name(asn);
     let config_path = format!("{}/{}.conf", config.data_verified_dir, iface_name);
 
-    let mut wg_config = WgConfig::from_file(&config_path)
-        .map_err(|e| (StatusCode::NOT_FOUND, format!("Config not found: {}", e)))?;
-
-    // Update endpoint if provided
-    if let Some(endpoint) = req.endpoint {
-        if let Some(ref mut peer) = wg_config.peer {
-            peer.endpoint = Some(endpoint);
-        } else {
-            return Err((StatusCode::BAD_REQUEST, "No peer configuration to update".to_string()));
-        }
-    }
-
-    // Save updated config
-    wg_config
-        .to_file(&config_path)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to save config: {}", e)))?;
+    // A token that records the challenge it was issued for is verified, even if a challenge
+    // from another init is still outstanding; only tokens without one fall back to the files
+    let state = if std::path::Path::new(&config_path).exists() {
+        PeeringState::Deployed
+    } else if auth.challenge.is_none() && !pending::list(&config.data_pending_dir, asn).is_empty() {
+        PeeringState::Pending
+    } else {
+        PeeringState::Verified
+    };
 
-    // Generate config string
-    let wg_config_str = wg_config
-        .as_string()
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to generate config: {}", e)))?;
+    Json(StateResponse { state })
+}
 
-    // Re-deploy WireGuard
-    info!("Re-deploying WireGuard config for ASN {} ({})", asn, iface_name);
+/// GET /peering/bird-snippet - BIRD config for the peer's side of the session (text/plain)
+pub async fn get_bird_snippet(
+    State(config): State<Arc<AppConfig>>,
+    auth: JwtAuth,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let asn = auth.asn;
+    info!("BIRD snippet request for ASN {}", asn);
 
-    // First remove old config
-    if let Err(e) = wireguard::deploy::remove_config(&iface_name) {
-        warn!("Failed to remove old WireGuard config for ASN {}: {}", asn, e);
-    }
+    // Build our side (as deployed, if it is), then mirror it for the peer
+    let config_path = format!("{}/{}.conf", config.data_verified_dir, interface_name(asn));
+    let deployed = WgConfig::from_file(&config_path).ok();
+    let bird_peer_config =
+        bird_peer_config(&config, asn, deployed.as_ref().and_then(|c| c.bgp.as_ref()));
 
-    // Deploy new config
-    wireguard::deploy::deploy_config(&wg_config_str, &iface_name)
+    let local_asn = bird_peer_config.my_asn;
+    let snippet = bird_peer_config
+        .symmetric(format!("AS{}", local_asn), interface_name(local_asn))
+        .to_config()
         .map_err(|e| {
-            error!("Failed to re-deploy WireGuard for ASN {}: {}", asn, e);
-            (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to re-deploy WireGuard: {}", e))
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to generate BIRD snippet: {}", e),
+            )
         })?;
 
-    info!("Successfully updated peering for ASN {}", asn);
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        snippet,
+    ))
+}
 
-    Ok(Json(UpdateResponse {
-        status: "updated".to_string(),
-    }))
+/// GET /peering/bird-config - Our BIRD config for the session as it would be deployed (text/plain)
+pub async fn get_bird_config(
+    State(config): State<Arc<AppConfig>>,
+    auth: JwtAuth,
+) -> Result<Response, (StatusCode, String)> {
+    info!("BIRD config request for ASN {}", auth.asn);
+    bird_config_response(&config, auth.asn)
 }
 
-/// DELETE /peering - Delete peering configuration
-pub async fn delete_peering(
+/// Rendered BIRD config for an ASN, shared by the peer and admin endpoints
+///
+/// Uses the stored session settings once deployed, the defaults for a new peering otherwise.
+pub(crate) fn bird_config_response(
+    config: &AppConfig,
+    asn: u32,
+) -> Result<Response, (StatusCode, String)> {
+    let config_path = format!("{}/{}.conf", config.data_verified_dir, interface_name(asn));
+    let deployed = WgConfig::from_file(&config_path).ok();
+    let bird_config = bird_peer_config(config, asn, deployed.as_ref().and_then(|c| c.bgp.as_ref()))
+        .to_config()
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to generate BIRD config: {}", e),
+            )
+        })?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        bird_config,
+    )
+        .into_response())
+}
+
+/// GET /peering/systemd - Tarball with the peer's `wg-quick@` config and a README
+pub async fn get_systemd_bundle(
     State(config): State<Arc<AppConfig>>,
     auth: JwtAuth,
-) -> Result<Json<UpdateResponse>, (StatusCode, String)> {
+) -> Result<impl IntoResponse, (StatusCode, String)> {
     let asn = auth.asn;
-    info!("Peering delete request for ASN {}", asn);
+    info!("systemd bundle request for ASN {}", asn);
 
-    let iface_name = interface_name(asn);
+    let config_path = format!("{}/{}.conf", config.data_verified_dir, interface_name(asn));
+    let wg_config = WgConfig::from_file(&config_path)
+        .map_err(|e| (StatusCode::NOT_FOUND, format!("Config not found: {}", e)))?;
 
-    // Remove WireGuard config
-    wireguard::deploy::remove_config(&iface_name)
+    let public_key = WgKeypair::derive_public_key(&config.system, &wg_config.interface.private_key)
+        .await
         .map_err(|e| {
-            error!("Failed to remove WireGuard for ASN {}: {}", asn, e);
-            (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to remove WireGuard: {}", e))
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to derive public key: {}", e),
+            )
         })?;
 
-    // Remove BIRD config
-    bird::deploy::remove_config(asn)
-        .map_err(|e| {
-            error!("Failed to remove BIRD config for ASN {}: {}", asn, e);
-            (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to remove BIRD config: {}", e))
+    let archive =
+        bundle::build(&config, asn, &wg_config, &public_key.to_string()).map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to build bundle: {}", e),
+            )
         })?;
 
-    // Remove verified config file
-    let config_path = format!("{}/{}.conf", config.data_verified_dir, iface_name);
-    std::fs::remove_file(&config_path)
+    let disposition = format!(
+        "attachment; filename=\"{}.tar\"",
+        bundle::peer_interface(&config, &wg_config)
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-tar".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        archive,
+    ))
+}
+
+/// GET /peering/request-email - Our side of the peering as a DN42 peering request text block (text/plain)
+pub async fn get_request_email(
+    State(config): State<Arc<AppConfig>>,
+    auth: JwtAuth,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let asn = auth.asn;
+    info!("Peering request email for ASN {}", asn);
+
+    let config_path = format!("{}/{}.conf", config.data_verified_dir, interface_name(asn));
+    let wg_config = WgConfig::from_file(&config_path)
+        .map_err(|e| (StatusCode::NOT_FOUND, format!("Config not found: {}", e)))?;
+
+    let public_key = WgKeypair::derive_public_key(&config.system, &wg_config.interface.private_key)
+        .await
         .map_err(|e| {
-            error!("Failed to remove config file for ASN {}: {}", asn, e);
-            (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to remove config file: {}", e))
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to derive public key: {}", e),
+            )
         })?;
 
-    info!("Successfully deleted peering for ASN {}", asn);
+    // Whether the tunnel is up doesn't matter for the text, so don't ask the router
+    let info = deployment_info(&config, asn, &wg_config, &public_key, false);
+    let text = request_email::format(&info, wg_config.bgp.as_ref());
+    Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], text))
+}
 
-    Ok(Json(UpdateResponse {
-        status: "deleted".to_string(),
-    }))
+/// Request to test a peer endpoint
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EndpointTestRequest {
+    /// The peer's public endpoint (IP:port)
+    pub endpoint: String,
 }
 
-/// POST /peering/activate - Activate peering (copy config from verified dir to /etc/wireguard and deploy)
-pub async fn activate_peering(
+/// Result of an endpoint test
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EndpointTestResponse {
+    pub endpoint: String,
+    pub reachability: Reachability,
+    /// Human-readable explanation of the result
+    pub detail: String,
+}
+
+/// POST /peering/test-endpoint - Check an endpoint before deploying (nothing is changed)
+///
+/// WireGuard never answers unauthenticated packets, so this can only catch
+/// resolution/routing failures and closed ports, not confirm a working tunnel.
+pub async fn test_endpoint(
     State(config): State<Arc<AppConfig>>,
     auth: JwtAuth,
-) -> Result<Json<UpdateResponse>, (StatusCode, String)> {
-    let asn = auth.asn;
-    info!("Peering activate request for ASN {}", asn);
-
-    let iface_name = interface_name(asn);
-    let config_path = format!("{}/{}.conf", config.data_verified_dir, iface_name);
+    Json(req): Json<EndpointTestRequest>,
+) -> Result<Json<EndpointTestResponse>, (StatusCode, String)> {
+    info!(
+        "Endpoint test request for ASN {}: {}",
+        auth.asn, req.endpoint
+    );
 
-    // Check if config exists in verified directory
-    if !std::path::Path::new(&config_path).exists() {
-        return Err((
-            StatusCode::NOT_FOUND,
-            format!("No peering configuration found for ASN {}. Please deploy first.", asn),
-        ));
-    }
+    validation::validate_endpoint(&req.endpoint, &config)?;
 
-    // Load config
-    let wg_config = WgConfig::from_file(&config_path)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to load config: {}", e)))?;
+    let (reachability, detail) = if config.validate_endpoint_dns {
+        probe::probe_endpoint(&req.endpoint).await
+    } else {
+        (
+            Reachability::NotChecked,
+            "Endpoint format is valid; network checks are disabled".to_string(),
+        )
+    };
 
-    // Generate WireGuard config string for deployment
-    let wg_config_str = wg_config
-        .as_string()
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to generate WireGuard config: {}", e)))?;
+    Ok(Json(EndpointTestResponse {
+        endpoint: req.endpoint,
+        reachability,
+        detail,
+    }))
+}
 
-    // Deploy WireGuard
-    info!("Activating WireGuard config for ASN {} ({})", asn, iface_name);
-    wireguard::deploy::deploy_config(&wg_config_str, &iface_name)
-        .map_err(|e| {
-            error!("Failed to activate WireGuard for ASN {}: {}", asn, e);
-            (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to activate WireGuard: {}", e))
-        })?;
+/// Request to check a signature while debugging signing (nothing is verified for real)
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SignatureTestRequest {
+    /// The peer's ASN
+    pub asn: u32,
+    /// The text that should have been signed, e.g. the challenge from /peering/init
+    pub message: String,
+    /// The cleartext signed message
+    pub signed_message: String,
+    /// The PGP public key to check it against
+    pub public_key: String,
+}
 
-    // Deploy BIRD config if BGP is configured
-    if let Some(ref bgp_config) = wg_config.bgp {
-        info!("Activating BIRD config for ASN {}", asn);
+/// POST /peering/test-signature - Check a signature stage by stage (no auth; nothing is stored or issued)
+pub async fn test_signature(
+    State(config): State<Arc<AppConfig>>,
+    Json(req): Json<SignatureTestRequest>,
+) -> Result<Json<gpg::SignatureDiagnosis>, (StatusCode, String)> {
+    info!("Signature test request for ASN {}", req.asn);
+    validation::validate_asn(req.asn, config.asn_range)?;
+
+    // As expensive as a real verification, so it shares the limit and the blocking pool
+    let permit = throttle::acquire(&config).await?;
+    let diagnosis = tokio::task::spawn_blocking(move || {
+        let _permit = permit;
+        gpg::diagnose_signature(&req.message, &req.signed_message, &req.public_key)
+    })
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Signature check task failed: {}", e),
+        )
+    })?;
+    Ok(Json(diagnosis))
+}
 
-        // Generate BIRD configuration
-        let bird_peer_config = bird::BirdPeerConfig::new(
-            config.my_asn,
-            asn,
-            format!("AS{}", asn),
-            iface_name.clone(),
-        );
+/// With externally managed WireGuard, the interface must already be up
+async fn require_external_interface(
+    config: &AppConfig,
+    asn: u32,
+    iface_name: &str,
+) -> Result<(), (StatusCode, String)> {
+    if wireguard::deploy::is_interface_active(&config.system, iface_name).await {
+        return Ok(());
+    }
+    warn!(
+        "Interface {} for ASN {} is not up (WireGuard managed externally)",
+        iface_name, asn
+    );
+    Err((
+        StatusCode::CONFLICT,
+        format!(
+            "Interface {} is not up; WireGuard is managed externally on this router",
+            iface_name
+        ),
+    ))
+}
 
-        let bird_config_str = bird_peer_config
-            .to_config()
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to generate BIRD config: {}", e)))?;
+/// Fail with 503 until the first registry sync has cloned the registry
+fn require_synced_registry(config: &AppConfig) -> Result<(), (StatusCode, String)> {
+    if registry::is_synced(&config.registry.path, &config.registry_data_subdir) {
+        return Ok(());
+    }
+    warn!("Registry at {:?} is not synced yet", config.registry.path);
+    Err((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "DN42 registry not yet synced, please retry shortly".to_string(),
+    ))
+}
 
-        bird::deploy::deploy_config(&bird_config_str, asn)
-            .map_err(|e| {
-                error!("Failed to activate BIRD config for ASN {}: {}", asn, e);
-                // Try to rollback WireGuard
-                let _ = wireguard::deploy::remove_config(&iface_name);
-                (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to activate BIRD config: {}", e))
-            })?;
+/// Fail with 503 while maintenance mode refuses new peerings
+fn require_not_in_maintenance(config: &AppConfig) -> Result<(), (StatusCode, String)> {
+    if !config.maintenance_mode {
+        return Ok(());
     }
+    Err((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "Maintenance in progress, new peerings are not accepted right now; existing peerings are unaffected"
+            .to_string(),
+    ))
+}
 
-    info!("Successfully activated peering for ASN {}", asn);
+/// ULA transfer addresses for an ASN, if the session runs over ULA (always with `transfer_net`)
+fn neighbor_ula(config: &AppConfig, asn: u32) -> Option<Ipv6Ula> {
+    let over_ula = config.bgp_neighbor_mode == NeighborMode::Ula
+        || config.bgp_transport == BgpTransport::TransferNet;
+    match &config.ula_transfer_prefix {
+        Some(prefix) if over_ula => Some(Ipv6Ula::from_peer_asn(prefix, asn)),
+        _ => None,
+    }
+}
 
-    Ok(Json(UpdateResponse {
-        status: "activated".to_string(),
-    }))
+/// Session addresses stored in a `[BGP]` section: link-local, or else ULA (IPv4 sessions have neither)
+///
+/// Deployed peerings keep the addresses they were set up with, even if the allocation
+/// settings change afterwards, so their config is read back rather than derived again.
+pub(crate) fn stored_session_addrs(bgp: &BgpConfig) -> (Option<Ipv6LinkLocal>, Option<Ipv6Ula>) {
+    let Ok(local) = bgp.local.parse::<std::net::Ipv6Addr>() else {
+        return (None, None);
+    };
+    let local_cidr = format!("{}/64", bgp.local);
+    if local.is_unicast_link_local() {
+        (
+            Some(Ipv6LinkLocal {
+                local: local_cidr,
+                peer: bgp.neighbor.clone(),
+            }),
+            None,
+        )
+    } else {
+        (
+            None,
+            Some(Ipv6Ula {
+                local: local_cidr,
+                peer: bgp.neighbor.clone(),
+            }),
+        )
+    }
 }
 
-/// POST /peering/deactivate - Deactivate peering (remove from /etc/wireguard but keep config in verified dir)
-pub async fn deactivate_peering(
-    State(config): State<Arc<AppConfig>>,
-    auth: JwtAuth,
-) -> Result<Json<UpdateResponse>, (StatusCode, String)> {
-    let asn = auth.asn;
-    info!("Peering deactivate request for ASN {}", asn);
+/// Our BGP local address and the neighbor address for the session
+fn session_addrs(ips: &Ipv6LinkLocal, ula: Option<&Ipv6Ula>) -> (String, String) {
+    match ula {
+        Some(ula) => (ula.local_addr(), ula.peer.clone()),
+        None => (ips.local_addr(), ips.peer.clone()),
+    }
+}
 
-    let iface_name = interface_name(asn);
-    let config_path = format!("{}/{}.conf", config.data_verified_dir, iface_name);
+/// IPv4 transfer addresses for an IPv4-only session; other families don't use them
+fn ipv4_transfer(
+    config: &AppConfig,
+    family: AddressFamily,
+    peer_ipv4: Option<Ipv4Addr>,
+) -> Result<Option<Ipv4Transfer>, (StatusCode, String)> {
+    if family != AddressFamily::Ipv4 {
+        return Ok(None);
+    }
 
-    // Check if config exists in verified directory
-    if !std::path::Path::new(&config_path).exists() {
+    let local = config.ipv4_transfer_address.ok_or((
+        StatusCode::BAD_REQUEST,
+        "IPv4-only sessions require an IPv4 transfer allocation, which is not configured"
+            .to_string(),
+    ))?;
+    let peer = peer_ipv4.ok_or((
+        StatusCode::BAD_REQUEST,
+        "IPv4-only sessions require peer_ipv4".to_string(),
+    ))?;
+    if peer == local {
         return Err((
-            StatusCode::NOT_FOUND,
-            format!("No peering configuration found for ASN {}", asn),
+            StatusCode::BAD_REQUEST,
+            "peer_ipv4 must differ from our transfer address".to_string(),
         ));
     }
 
-    // Remove WireGuard config (this also brings down the interface)
-    info!("Deactivating WireGuard config for ASN {} ({})", asn, iface_name);
-    wireguard::deploy::remove_config(&iface_name)
-        .map_err(|e| {
-            error!("Failed to deactivate WireGuard for ASN {}: {}", asn, e);
-            (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to deactivate WireGuard: {}", e))
-        })?;
+    Ok(Some(Ipv4Transfer { local, peer }))
+}
 
-    // Remove BIRD config
-    info!("Deactivating BIRD config for ASN {}", asn);
-    bird::deploy::remove_config(asn)
-        .map_err(|e| {
-            error!("Failed to deactivate BIRD config for ASN {}: {}", asn, e);
-            (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to deactivate BIRD config: {}", e))
-        })?;
+/// Listen port for a peering: the requested one if it is in range, otherwise derived
+/// from the ASN. Either way it must not be used by another peering.
+fn resolve_listen_port(
+    config: &AppConfig,
+    asn: u32,
+    requested: Option<u16>,
+) -> Result<u16, (StatusCode, String)> {
+    let port = match requested {
+        Some(port) if !config.port_range.contains(port) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("listen_port must be within {}", config.port_range),
+            ));
+        }
+        Some(port) => port,
+        None => wireguard_port(asn).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?,
+    };
 
-    info!("Successfully deactivated peering for ASN {} (config preserved in {})", asn, config_path);
+    // A re-deploy may keep its own port
+    let others = verified_asns(std::path::Path::new(&config.data_verified_dir))
+        .into_iter()
+        .filter(|other| *other != asn);
+    for other in others {
+        let config_path = format!(
+            "{}/{}.conf",
+            config.data_verified_dir,
+            interface_name(other)
+        );
+        let Ok(other) = WgConfig::from_file(&config_path) else {
+            continue;
+        };
+        if other.interface.listen_port == port {
+            let hint = if requested.is_none() {
+                ", request a listen_port"
+            } else {
+                ""
+            };
+            return Err((
+                StatusCode::CONFLICT,
+                format!(
+                    "Listen port {} is already used by another peering{}",
+                    port, hint
+                ),
+            ));
+        }
+    }
 
-    Ok(Json(UpdateResponse {
-        status: "deactivated".to_string(),
-    }))
+    Ok(port)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_init_request_deserialization() {
-        let json = r#"{"asn": 4242420257}"#;
-        let req: InitRequest = serde_json::from_str(json).unwrap();
+/// How long resolving an endpoint hostname may take; one that doesn't resolve in time matches nothing
+const ENDPOINT_LOOKUP_TIMEOUT: Duration = Duration::from_secs(2);
 
-        assert_eq!(req.asn, 4242420257);
+/// Addresses a hostname endpoint resolves to
+async fn resolve_endpoint(endpoint: String) -> Vec<SocketAddr> {
+    match tokio::time::timeout(ENDPOINT_LOOKUP_TIMEOUT, tokio::net::lookup_host(endpoint)).await {
+        Ok(Ok(addrs)) => addrs.collect(),
+        _ => Vec::new(),
     }
+}
 
-    #[test]
-    fn test_init_response_serialization() {
-        let resp = InitResponse {
-            challenge: "AUTOPEER-4242420257-abc123".to_string(),
-            pgp_fingerprint: "1234567890ABCDEF".to_string(),
+/// Look for another peering configured with the same endpoint, which is almost always
+/// a copy-paste mistake. Logged as a warning, or 409 with `reject_duplicate_endpoints`.
+///
+/// Addresses compare parsed, so spellings of the same address match. Two hostnames compare
+/// as written; a hostname is only resolved when the other side is an address, and those
+/// lookups run together, each bounded by [`ENDPOINT_LOOKUP_TIMEOUT`].
+async fn check_duplicate_endpoint(
+    config: &AppConfig,
+    asn: u32,
+    endpoint: &str,
+) -> Result<(), (StatusCode, String)> {
+    let ours = endpoint.trim().to_lowercase();
+    let our_addr = ours.parse::<SocketAddr>().ok();
+    // Looked up once, and only if another peering has an address endpoint
+    let mut our_addrs: Option<Vec<SocketAddr>> = None;
+    let mut lookups = tokio::task::JoinSet::new();
+    let mut duplicates = Vec::new();
+
+    // A re-deploy may keep its own endpoint
+    let others = verified_asns(std::path::Path::new(&config.data_verified_dir))
+        .into_iter()
+        .filter(|other| *other != asn);
+    for other_asn in others {
+        let config_path = format!(
+            "{}/{}.conf",
+            config.data_verified_dir,
+            interface_name(other_asn)
+        );
+        let Some(other) = WgConfig::from_file(&config_path)
+            .ok()
+            .and_then(|other| other.peer().and_then(|peer| peer.endpoint.clone()))
+        else {
+            continue;
         };
-
-        let json = serde_json::to_string(&resp).unwrap();
-        assert!(json.contains("AUTOPEER-4242420257-abc123"));
-        assert!(json.contains("challenge"));
-        assert!(json.contains("pgp_fingerprint"));
+        let other = other.trim().to_lowercase();
+
+        match (our_addr, other.parse::<SocketAddr>()) {
+            (Some(ours), Ok(other)) if ours == other => duplicates.push(other_asn),
+            (None, Err(_)) if ours == other => duplicates.push(other_asn),
+            (Some(ours), Err(_)) => {
+                lookups.spawn(
+                    async move { (other_asn, resolve_endpoint(other).await.contains(&ours)) },
+                );
+            }
+            (None, Ok(other)) => {
+                if our_addrs.is_none() {
+                    our_addrs = Some(resolve_endpoint(ours.clone()).await);
+                }
+                if our_addrs
+                    .as_ref()
+                    .is_some_and(|addrs| addrs.contains(&other))
+                {
+                    duplicates.push(other_asn);
+                }
+            }
+            _ => {}
+        }
+    }
+    while let Some(lookup) = lookups.join_next().await {
+        if let Ok((other_asn, true)) = lookup {
+            duplicates.push(other_asn);
+        }
+    }
+    duplicates.sort_unstable();
+
+    for other_asn in duplicates {
+        let iface = interface_name(other_asn);
+        if config.reject_duplicate_endpoints {
+            return Err((
+                StatusCode::CONFLICT,
+                format!(
+                    "Endpoint {} is already used by the peering on {}",
+                    endpoint, iface
+                ),
+            ));
+        }
+        warn!(
+            "ASN {} deploys endpoint {}, which the peering on {} already uses",
+            asn, endpoint, iface
+        );
     }
 
-    // Endpoint handler tests
-    mod handler_tests {
-        use super::*;
-        use crate::api::test_helpers::test_config_with_temp_dirs;
-        use axum::{body::Body, http::{Request, StatusCode}, Router, routing::post};
-        use tower::ServiceExt;
+    Ok(())
+}
 
-        #[tokio::test]
-        async fn test_init_peering_success() {
-            let (config, _pending_dir, _verified_dir) = test_config_with_temp_dirs();
-            let test_asn = 4242420257; // Use a real ASN with GPG key in registry
+/// AllowedIPs for a peer: its registered routes in strict mode, otherwise everything,
+/// limited to the session's address families
+fn allowed_ips(config: &AppConfig, asn: u32, family: AddressFamily) -> Vec<String> {
+    let in_family = |prefix: &String| {
+        if prefix.contains(':') {
+            family.has_ipv6()
+        } else {
+            family.has_ipv4()
+        }
+    };
+    let wildcard = || {
+        ["0.0.0.0/0".to_string(), "::/0".to_string()]
+            .into_iter()
+            .filter(in_family)
+            .collect::<Vec<_>>()
+    };
+    if !config.strict_allowed_ips {
+        return wildcard();
+    }
 
-            let app = Router::new()
-                .route("/peering/init", post(init_peering))
-                .with_state(config.clone());
+    let routes = get_routes_for_asn(
+        &config.registry.path,
+        &config.registry_data_subdir,
+        config.registry_layout,
+        asn,
+    )
+    .map(|routes| routes.into_iter().filter(in_family).collect::<Vec<_>>());
+    match routes {
+        Ok(routes) if !routes.is_empty() => routes,
+        Ok(_) => {
+            warn!(
+                "ASN {} has no registered routes, falling back to wildcard AllowedIPs",
+                asn
+            );
+            wildcard()
+        }
+        Err(e) => {
+            warn!(
+                "Failed to look up routes for ASN {}, falling back to wildcard AllowedIPs: {}",
+                asn, e
+            );
+            wildcard()
+        }
+    }
+}
 
-            let request_body = serde_json::to_string(&InitRequest { asn: test_asn }).unwrap();
-            let request = Request::builder()
-                .method("POST")
-                .uri("/peering/init")
-                .header("content-type", "application/json")
-                .body(Body::from(request_body))
-                .unwrap();
+/// Import limit for a session: the peer may lower the configured default, never raise it
+fn resolve_max_prefixes(
+    config: &AppConfig,
+    requested: Option<u32>,
+) -> Result<Option<u32>, (StatusCode, String)> {
+    let default = (config.default_max_prefixes > 0).then_some(config.default_max_prefixes);
+
+    match (requested, default) {
+        (None, default) => Ok(default),
+        (Some(0), _) => Err((
+            StatusCode::BAD_REQUEST,
+            "max_prefixes must be greater than 0".to_string(),
+        )),
+        (Some(n), Some(limit)) if n > limit => Err((
+            StatusCode::BAD_REQUEST,
+            format!("max_prefixes cannot exceed {}", limit),
+        )),
+        (Some(n), _) => Ok(Some(n)),
+    }
+}
 
-            let response = app.oneshot(request).await.unwrap();
+/// Registered routes to restrict BGP imports to (empty when not enabled or none are registered)
+fn import_routes(config: &AppConfig, asn: u32) -> Vec<RouteObject> {
+    if !config.strict_import_filter {
+        return Vec::new();
+    }
 
-            assert_eq!(response.status(), StatusCode::OK);
+    match get_route_objects_for_asn(
+        &config.registry.path,
+        &config.registry_data_subdir,
+        config.registry_layout,
+        asn,
+    ) {
+        Ok(routes) => {
+            if routes.is_empty() {
+                warn!(
+                    "ASN {} has no registered routes, its imports are not restricted",
+                    asn
+                );
+            }
+            routes
+        }
+        Err(e) => {
+            warn!(
+                "Failed to look up routes for ASN {}, its imports are not restricted: {}",
+                asn, e
+            );
+            Vec::new()
+        }
+    }
+}
 
-            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
-                .await
-                .unwrap();
-            let init_response: InitResponse = serde_json::from_slice(&body).unwrap();
+/// Our BIRD peer config for an ASN, following the stored `[BGP]` section when there is one
+fn bird_peer_config(config: &AppConfig, asn: u32, bgp: Option<&BgpConfig>) -> bird::BirdPeerConfig {
+    let address_family = bgp.map(|bgp| bgp.address_family).unwrap_or_default();
+    // IPv4-only sessions keep their transfer addresses in Local/Neighbor
+    let ipv4 = bgp
+        .filter(|bgp| bgp.address_family == AddressFamily::Ipv4)
+        .and_then(|bgp| {
+            Some(Ipv4Transfer {
+                local: bgp.local.parse().ok()?,
+                peer: bgp.neighbor.parse().ok()?,
+            })
+        });
+
+    // Deployed sessions stay on their stored addresses; fresh ones follow the current settings
+    let (link_local, ula) = match bgp {
+        Some(bgp) => stored_session_addrs(bgp),
+        None => (None, neighbor_ula(config, asn)),
+    };
 
-            // Check challenge format
-            assert!(init_response.challenge.starts_with(&format!("AUTOPEER-{}-", test_asn)));
+    let peer_name = bgp
+        .and_then(|bgp| bgp.peer_name.clone())
+        .unwrap_or_else(|| format!("AS{}", asn));
+    bird::BirdPeerConfig::new(local_asn(config, bgp), asn, peer_name, interface_name(asn))
+        .with_link_local(link_local)
+        .with_scoped_neighbor(config.link_local_scope_suffix)
+        .with_ula(ula)
+        .with_max_prefixes(bgp.and_then(|bgp| bgp.max_prefixes))
+        .with_address_family(address_family)
+        .with_ipv4(ipv4)
+        .with_import_routes(import_routes(config, asn))
+        .with_peer_router_id(bgp.and_then(|bgp| bgp.router_id))
+        .with_graceful_restart(bgp.and_then(|bgp| bgp.graceful_restart))
+        .with_template(config.bird_peer_template.clone())
+        .with_channels(config.explicit_channels.clone())
+}
 
-            // Check that PGP fingerprint was returned
-            assert!(!init_response.pgp_fingerprint.is_empty());
-            assert_eq!(init_response.pgp_fingerprint, "8B7F0384CBE0272761D852EA0684E36E6CF9D4D4");
+/// Whether a peering is on the router: its tunnel is deployed or up (when we manage it), or
+/// its BIRD session is deployed. Only stored peerings are none of these.
+async fn is_live(config: &AppConfig, asn: u32) -> bool {
+    let iface = interface_name(asn);
+    let tunnel = config.manage_wireguard
+        && (wireguard::deploy::config_path(&config.system.wireguard_dir, &iface).exists()
+            || wireguard::deploy::is_interface_active(&config.system, &iface).await);
+    tunnel || bird::deploy::config_path(&config.system.bird_peers_dir, asn).exists()
+}
 
-            // Verify pending challenge file was created
-            let iface_name = crate::ipalloc::interface_name(test_asn);
-            let challenge_path = std::path::PathBuf::from(&config.data_pending_dir)
-                .join(format!("{}.conf", iface_name));
-            assert!(challenge_path.exists(), "Pending challenge file should exist at {:?}", challenge_path);
+/// Our ASN on a peering: the one it was deployed under, `my_asn` for configs from before
+/// `my_asns` (and for sessions that aren't deployed yet)
+pub(crate) fn local_asn(config: &AppConfig, bgp: Option<&BgpConfig>) -> u32 {
+    bgp.and_then(|bgp| bgp.local_as).unwrap_or(config.my_asn)
+}
 
-            // Verify challenge content matches
-            let stored_challenge = std::fs::read_to_string(&challenge_path).unwrap();
-            assert_eq!(stored_challenge, init_response.challenge);
-        }
+/// Render a WireGuard config for wg-quick, dropping `DNS` unless `allow_dns_directive` is set
+/// (wg-quick would hand it to resolvconf and replace the host's resolvers)
+fn render_wg_quick_config(
+    config: &AppConfig,
+    asn: u32,
+    wg_config: &WgConfig,
+) -> Result<String, (StatusCode, String)> {
+    let rendered = if config.allow_dns_directive || wg_config.interface.dns.is_empty() {
+        wg_config.as_string()
+    } else {
+        warn!(
+            "Dropping DNS = {} from the WireGuard config of ASN {}",
+            wg_config.interface.dns.join(", "),
+            asn
+        );
+        wg_config.without_dns().as_string()
+    };
+    rendered.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to generate WireGuard config: {}", e),
+        )
+    })
+}
 
-        #[tokio::test]
-        async fn test_init_peering_invalid_asn() {
-            let (config, _pending_dir, _verified_dir) = test_config_with_temp_dirs();
+/// Render our BIRD peer config for an ASN
+pub(crate) fn render_bird_config(
+    config: &AppConfig,
+    asn: u32,
+    bgp: &BgpConfig,
+) -> Result<String, (StatusCode, String)> {
+    bird_peer_config(config, asn, Some(bgp))
+        .to_config()
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to generate BIRD config: {}", e),
+            )
+        })
+}
 
-            let app = Router::new()
-                .route("/peering/init", post(init_peering))
-                .with_state(config);
+/// Request to update a peering configuration
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UpdateRequest {
+    /// New endpoint (optional)
+    pub endpoint: Option<String>,
+    /// New import limit (optional, may only lower the default)
+    pub max_prefixes: Option<u32>,
+    /// Turn BGP graceful restart on or off (optional)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub graceful_restart: Option<bool>,
+}
 
-            // Invalid ASN (too small for DN42)
-            let request_body = serde_json::to_string(&InitRequest { asn: 1000 }).unwrap();
-            let request = Request::builder()
-                .method("POST")
-                .uri("/peering/init")
-                .header("content-type", "application/json")
-                .body(Body::from(request_body))
-                .unwrap();
+/// Outcome of a change to a peering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionStatus {
+    Updated,
+    Deleted,
+    Activated,
+    /// Activate found the tunnel already up with the stored config
+    AlreadyActive,
+    /// The WireGuard config was written out again from the stored state
+    Repaired,
+    Deactivated,
+    RolledBack,
+    BgpDisabled,
+    BgpEnabled,
+}
 
-            let response = app.oneshot(request).await.unwrap();
+/// Response from a change to a peering (update, delete, activate, ...)
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UpdateResponse {
+    pub status: ActionStatus,
+    /// ASN the change applied to
+    pub asn: u32,
+    /// When the change was made (Unix timestamp)
+    pub timestamp: i64,
+}
 
-            // Should fail with 400 Bad Request
-            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+impl UpdateResponse {
+    pub fn new(status: ActionStatus, asn: u32) -> Self {
+        UpdateResponse {
+            status,
+            asn,
+            timestamp: chrono::Utc::now().timestamp(),
         }
+    }
+}
 
+/// Validate an update and apply it to a loaded config, returning the resolved import limit
+async fn apply_update(
+    config: &AppConfig,
+    asn: u32,
+    wg_config: &mut WgConfig,
+    req: &UpdateRequest,
+) -> Result<Option<u32>, (StatusCode, String)> {
+    // Validate endpoint if provided
+    if let Some(ref endpoint) = req.endpoint {
+        validation::validate_endpoint(endpoint, config)?;
+        check_duplicate_endpoint(config, asn, endpoint).await?;
+    }
+    let max_prefixes = req
+        .max_prefixes
+        .map(|n| resolve_max_prefixes(config, Some(n)))
+        .transpose()?
+        .flatten();
 
-        #[tokio::test]
-        async fn test_init_peering_creates_unique_challenges() {
-            let (config, _pending_dir, _verified_dir) = test_config_with_temp_dirs();
-            let test_asn = 4242420257; // Use a real ASN with GPG key in registry
-
-            // Create first peering
-            let app1 = Router::new()
-                .route("/peering/init", post(init_peering))
-                .with_state(config.clone());
+    // Update endpoint if provided
+    if let Some(endpoint) = &req.endpoint {
+        if let Some(peer) = wg_config.peer_mut() {
+            peer.endpoint = Some(endpoint.clone());
+        } else {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "No peer configuration to update".to_string(),
+            ));
+        }
+    }
 
-            let request_body = serde_json::to_string(&InitRequest { asn: test_asn }).unwrap();
+    // Update BGP settings if provided
+    if max_prefixes.is_some() || req.graceful_restart.is_some() {
+        let bgp = wg_config.bgp.as_mut().ok_or((
+            StatusCode::BAD_REQUEST,
+            "No BGP configuration for this peering".to_string(),
+        ))?;
+        if max_prefixes.is_some() {
+            bgp.max_prefixes = max_prefixes;
+        }
+        if req.graceful_restart.is_some() {
+            bgp.graceful_restart = req.graceful_restart;
+        }
+    }
+    Ok(max_prefixes)
+}
+
+/// Response from /peering/update/preview
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UpdatePreviewResponse {
+    pub asn: u32,
+    /// What the update would change, empty if nothing
+    pub changes: Vec<ConfigChange>,
+}
+
+/// POST /peering/update/preview - What /peering/update would change, without applying it
+pub async fn preview_update(
+    State(config): State<Arc<AppConfig>>,
+    auth: JwtAuth,
+    Json(req): Json<UpdateRequest>,
+) -> Result<Json<UpdatePreviewResponse>, (StatusCode, String)> {
+    let asn = auth.asn;
+    let config_path = format!("{}/{}.conf", config.data_verified_dir, interface_name(asn));
+    let current = WgConfig::from_file(&config_path)
+        .map_err(|e| (StatusCode::NOT_FOUND, format!("Config not found: {}", e)))?;
+
+    let mut updated = current.clone();
+    apply_update(&config, asn, &mut updated, &req).await?;
+
+    Ok(Json(UpdatePreviewResponse {
+        asn,
+        changes: config_diff::diff(&current, &updated),
+    }))
+}
+
+/// PATCH /peering/update - Update and re-deploy peering configuration
+pub async fn update_peering(
+    State(config): State<Arc<AppConfig>>,
+    auth: JwtAuth,
+    Json(req): Json<UpdateRequest>,
+) -> Result<Json<UpdateResponse>, (StatusCode, String)> {
+    let asn = auth.asn;
+    info!("Peering update request for ASN {}", asn);
+
+    // Reject rapid updates
+    let cooldown = cooldown::acquire(&config, asn)?;
+
+    // Load verified config
+    let iface_name = interface_name(asn);
+    let config_path = format!("{}/{}.conf", config.data_verified_dir, iface_name);
+
+    let mut wg_config = WgConfig::from_file(&config_path)
+        .map_err(|e| (StatusCode::NOT_FOUND, format!("Config not found: {}", e)))?;
+    let max_prefixes = apply_update(&config, asn, &mut wg_config, &req).await?;
+    // A stored or deactivated peering only gets its config rewritten
+    let live = is_live(&config, asn).await;
+
+    // Keep the current version for /peering/rollback, then save the updated config
+    history::backup(std::path::Path::new(&config_path), config.config_history).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to back up config: {}", e),
+        )
+    })?;
+    wg_config.to_file(&config_path).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to save config: {}", e),
+        )
+    })?;
+
+    // Generate config string
+    let wg_config_str = render_wg_quick_config(&config, asn, &wg_config)?;
+
+    // Re-deploy WireGuard
+    if config.manage_wireguard && live {
+        info!(
+            "Re-deploying WireGuard config for ASN {} ({})",
+            asn, iface_name
+        );
+
+        // First remove old config
+        if let Err(e) = wireguard::deploy::remove_config(&config.system, &iface_name).await {
+            warn!(
+                "Failed to remove old WireGuard config for ASN {}: {}",
+                asn, e
+            );
+        }
+
+        // Deploy new config
+        wireguard::deploy::deploy_config(
+            &config.system,
+            &wg_config_str,
+            &iface_name,
+            config.external_private_keys,
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to re-deploy WireGuard for ASN {}: {}", asn, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to re-deploy WireGuard: {}", e),
+            )
+        })?;
+    }
+
+    // Re-deploy BIRD with the new import limit or graceful restart setting
+    let bgp_changed = max_prefixes.is_some() || req.graceful_restart.is_some();
+    let enabled_bgp = wg_config.bgp.as_ref().filter(|bgp| bgp.enabled);
+    if let (true, true, Some(bgp)) = (live, bgp_changed, enabled_bgp) {
+        info!("Re-deploying BIRD config for ASN {}", asn);
+        let bird_config_str = render_bird_config(&config, asn, bgp)?;
+        bird::deploy::deploy_config(&config.system, &bird_config_str, asn)
+            .await
+            .map_err(|e| {
+                error!("Failed to re-deploy BIRD config for ASN {}: {}", asn, e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to re-deploy BIRD config: {}", e),
+                )
+            })?;
+    }
+
+    info!("Successfully updated peering for ASN {}", asn);
+    cooldown.commit();
+
+    Ok(Json(UpdateResponse::new(ActionStatus::Updated, asn)))
+}
+
+/// DELETE /peering - Delete peering configuration
+pub async fn delete_peering(
+    State(config): State<Arc<AppConfig>>,
+    auth: JwtAuth,
+) -> Result<Json<UpdateResponse>, (StatusCode, String)> {
+    let asn = auth.asn;
+    info!("Peering delete request for ASN {}", asn);
+
+    let iface_name = interface_name(asn);
+
+    within_deploy_timeout(&config, asn, "Removing the peering", async {
+        // Remove WireGuard config
+        if config.manage_wireguard {
+            wireguard::deploy::remove_config(&config.system, &iface_name)
+                .await
+                .map_err(|e| {
+                    error!("Failed to remove WireGuard for ASN {}: {}", asn, e);
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("Failed to remove WireGuard: {}", e),
+                    )
+                })?;
+        }
+
+        // Remove BIRD config
+        bird::deploy::remove_config(&config.system, asn)
+            .await
+            .map_err(|e| {
+                error!("Failed to remove BIRD config for ASN {}: {}", asn, e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to remove BIRD config: {}", e),
+                )
+            })
+    })
+    .await?;
+
+    // Remove verified config file, or keep it as a tombstone with the registry authorization
+    let config_path = format!("{}/{}.conf", config.data_verified_dir, iface_name);
+    if config.tombstone_deletes {
+        let deleted_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let tombstone = tombstone::bury(
+            std::path::Path::new(&config_path),
+            std::path::Path::new(&config.data_deleted_dir),
+            &iface_name,
+            deleted_at,
+        )
+        .map_err(|e| {
+            error!("Failed to tombstone config file for ASN {}: {}", asn, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to move config file: {}", e),
+            )
+        })?;
+        info!("Kept the config of ASN {} as {:?}", asn, tombstone);
+    } else {
+        std::fs::remove_file(&config_path).map_err(|e| {
+            error!("Failed to remove config file for ASN {}: {}", asn, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to remove config file: {}", e),
+            )
+        })?;
+    }
+    history::remove_all(std::path::Path::new(&config_path), config.config_history);
+
+    // Remove pending challenges left over from unfinished re-inits
+    for challenge_path in pending::list(&config.data_pending_dir, asn) {
+        match pending::remove(&config, &challenge_path) {
+            Ok(()) => info!(
+                "Removed pending challenge {:?} for ASN {}",
+                challenge_path, asn
+            ),
+            Err(e) => warn!("Failed to remove pending challenge for ASN {}: {}", asn, e),
+        }
+    }
+
+    info!("Successfully deleted peering for ASN {}", asn);
+
+    Ok(Json(UpdateResponse::new(ActionStatus::Deleted, asn)))
+}
+
+/// POST /peering/activate - Activate peering (copy config from verified dir to /etc/wireguard and deploy)
+pub async fn activate_peering(
+    State(config): State<Arc<AppConfig>>,
+    auth: JwtAuth,
+) -> Result<Json<UpdateResponse>, (StatusCode, String)> {
+    let asn = auth.asn;
+    info!("Peering activate request for ASN {}", asn);
+    let _lock = asn_lock::lock(&config, asn).await?;
+
+    let iface_name = interface_name(asn);
+    let config_path = format!("{}/{}.conf", config.data_verified_dir, iface_name);
+
+    // Check if config exists in verified directory
+    if !std::path::Path::new(&config_path).exists() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!(
+                "No peering configuration found for ASN {}. Please deploy first.",
+                asn
+            ),
+        ));
+    }
+
+    // Load config
+    let wg_config = WgConfig::from_file(&config_path).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to load config: {}", e),
+        )
+    })?;
+
+    let wg_config_str = render_wg_quick_config(&config, asn, &wg_config)?;
+
+    let mut already_active = false;
+    if !config.manage_wireguard {
+        require_external_interface(&config, asn, &iface_name).await?;
+    } else if wireguard::deploy::is_deployed(
+        &config.system,
+        &wg_config_str,
+        &iface_name,
+        config.external_private_keys,
+    )
+    .await
+    {
+        // Deploying is a no-op for the tunnel then; BIRD is still re-applied in case it was lost
+        info!(
+            "WireGuard interface {} is already up with the stored config",
+            iface_name
+        );
+        already_active = true;
+    } else if wireguard::deploy::is_interface_active(&config.system, &iface_name).await {
+        // Up with some other config: take it down so activating again re-applies the stored config
+        info!(
+            "WireGuard interface {} is already up, restarting it",
+            iface_name
+        );
+        if let Err(e) = wireguard::deploy::remove_config(&config.system, &iface_name).await {
+            warn!("Failed to bring down WireGuard for ASN {}: {}", asn, e);
+        }
+    }
+
+    if let Err(e) = bring_up(&config, asn, &iface_name, &wg_config, &wg_config_str).await {
+        // Tear down whatever part of the tunnel came up, so no orphan interface is left
+        if config.manage_wireguard {
+            if let Err(e) = wireguard::deploy::remove_config(&config.system, &iface_name).await {
+                error!(
+                    "Failed to bring down WireGuard for ASN {} after a failed activation: {}",
+                    asn, e
+                );
+            }
+        }
+        return Err(e);
+    }
+
+    if already_active {
+        info!("Peering for ASN {} was already active", asn);
+        return Ok(Json(UpdateResponse::new(ActionStatus::AlreadyActive, asn)));
+    }
+
+    info!("Successfully activated peering for ASN {}", asn);
+
+    Ok(Json(UpdateResponse::new(ActionStatus::Activated, asn)))
+}
+
+/// POST /peering/repair - Write the WireGuard config out again from the verified config and bring it up
+///
+/// For a `/etc/wireguard/wg-asN.conf` that went missing or was edited by hand. Safe to repeat;
+/// BIRD is left alone (`/peering/activate` re-applies both).
+pub async fn repair_peering(
+    State(config): State<Arc<AppConfig>>,
+    auth: JwtAuth,
+) -> Result<Json<UpdateResponse>, (StatusCode, String)> {
+    let asn = auth.asn;
+    info!("Peering repair request for ASN {}", asn);
+    if !config.manage_wireguard {
+        return Err((
+            StatusCode::CONFLICT,
+            "WireGuard is managed outside autopeer, so there is no config of ours to repair"
+                .to_string(),
+        ));
+    }
+    let _lock = asn_lock::lock(&config, asn).await?;
+
+    let iface_name = interface_name(asn);
+    let config_path = format!("{}/{}.conf", config.data_verified_dir, iface_name);
+    let wg_config = WgConfig::from_file(&config_path).map_err(|_| {
+        (
+            StatusCode::NOT_FOUND,
+            format!(
+                "No peering configuration found for ASN {}. Please deploy first.",
+                asn
+            ),
+        )
+    })?;
+    let wg_config_str = render_wg_quick_config(&config, asn, &wg_config)?;
+
+    within_deploy_timeout(&config, asn, "Repairing WireGuard", async {
+        let system = &config.system;
+        let external_key = config.external_private_keys;
+        let result =
+            if wireguard::deploy::is_deployed(system, &wg_config_str, &iface_name, external_key)
+                .await
+            {
+                Ok(())
+            } else if wireguard::deploy::is_interface_active(system, &iface_name).await {
+                // Running with a lost or edited file: restart so the interface matches the file again
+                wireguard::deploy::restart_config(system, &wg_config_str, &iface_name, external_key)
+                    .await
+            } else {
+                wireguard::deploy::deploy_config(system, &wg_config_str, &iface_name, external_key)
+                    .await
+            };
+        result.map_err(|e| {
+            error!("Failed to repair WireGuard for ASN {}: {}", asn, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to repair WireGuard: {}", e),
+            )
+        })
+    })
+    .await?;
+
+    info!("Repaired WireGuard config for ASN {}", asn);
+    Ok(Json(UpdateResponse::new(ActionStatus::Repaired, asn)))
+}
+
+/// Bring up the WireGuard tunnel (if we manage it) and then the BIRD session, if BGP is enabled
+///
+/// Stops at the first failure; the caller tears the tunnel down again.
+async fn bring_up(
+    config: &AppConfig,
+    asn: u32,
+    iface_name: &str,
+    wg_config: &WgConfig,
+    wg_config_str: &str,
+) -> Result<(), (StatusCode, String)> {
+    if config.manage_wireguard {
+        info!(
+            "Activating WireGuard config for ASN {} ({})",
+            asn, iface_name
+        );
+        wireguard::deploy::deploy_config(
+            &config.system,
+            wg_config_str,
+            iface_name,
+            config.external_private_keys,
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to activate WireGuard for ASN {}: {}", asn, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to activate WireGuard: {}", e),
+            )
+        })?;
+    }
+
+    // Deploy BIRD config if BGP is configured and not disabled
+    if let Some(bgp) = wg_config.bgp.as_ref().filter(|bgp| bgp.enabled) {
+        info!("Activating BIRD config for ASN {}", asn);
+        let bird_config_str = render_bird_config(config, asn, bgp)?;
+        bird::deploy::deploy_config(&config.system, &bird_config_str, asn)
+            .await
+            .map_err(|e| {
+                error!("Failed to activate BIRD config for ASN {}: {}", asn, e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to activate BIRD config: {}", e),
+                )
+            })?;
+    }
+
+    Ok(())
+}
+
+/// POST /peering/rollback - Restore the previous config version and re-deploy it
+pub async fn rollback_peering(
+    State(config): State<Arc<AppConfig>>,
+    auth: JwtAuth,
+) -> Result<Json<UpdateResponse>, (StatusCode, String)> {
+    let asn = auth.asn;
+    info!("Peering rollback request for ASN {}", asn);
+
+    let cooldown = cooldown::acquire(&config, asn)?;
+
+    let iface_name = interface_name(asn);
+    let config_path = format!("{}/{}.conf", config.data_verified_dir, iface_name);
+    let config_path = std::path::Path::new(&config_path);
+
+    let previous = history::latest(config_path)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            "No previous configuration to roll back to".to_string(),
+        ))?;
+    let wg_config = WgConfig::from_string(&previous).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Backup is not a valid config: {}", e),
+        )
+    })?;
+    // Backups are deployed as written, unless they carry a DNS line that has to go
+    let previous = if wg_config.interface.dns.is_empty() {
+        previous
+    } else {
+        render_wg_quick_config(&config, asn, &wg_config)?
+    };
+
+    // A stored or deactivated peering only gets its config restored
+    if !is_live(&config, asn).await {
+        history::restore_latest(config_path, config.config_history)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        info!("Rolled back the stored config for ASN {}", asn);
+        cooldown.commit();
+        return Ok(Json(UpdateResponse::new(ActionStatus::RolledBack, asn)));
+    }
+
+    // Re-deploy WireGuard
+    if config.manage_wireguard {
+        info!(
+            "Re-deploying previous WireGuard config for ASN {} ({})",
+            asn, iface_name
+        );
+        if let Err(e) = wireguard::deploy::remove_config(&config.system, &iface_name).await {
+            warn!(
+                "Failed to remove current WireGuard config for ASN {}: {}",
+                asn, e
+            );
+        }
+        wireguard::deploy::deploy_config(
+            &config.system,
+            &previous,
+            &iface_name,
+            config.external_private_keys,
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to roll back WireGuard for ASN {}: {}", asn, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to re-deploy WireGuard: {}", e),
+            )
+        })?;
+    }
+
+    // Re-deploy BIRD as the previous version had it
+    match wg_config.bgp.as_ref() {
+        Some(bgp) if bgp.enabled => {
+            let bird_config_str = render_bird_config(&config, asn, bgp)?;
+            bird::deploy::deploy_config(&config.system, &bird_config_str, asn).await
+        }
+        _ => bird::deploy::remove_config(&config.system, asn).await,
+    }
+    .map_err(|e| {
+        error!("Failed to roll back BIRD config for ASN {}: {}", asn, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to re-deploy BIRD config: {}", e),
+        )
+    })?;
+
+    history::restore_latest(config_path, config.config_history)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    info!("Successfully rolled back peering for ASN {}", asn);
+    cooldown.commit();
+
+    Ok(Json(UpdateResponse::new(ActionStatus::RolledBack, asn)))
+}
+
+/// POST /peering/deactivate - Deactivate peering (remove from /etc/wireguard but keep config in verified dir)
+pub async fn deactivate_peering(
+    State(config): State<Arc<AppConfig>>,
+    auth: JwtAuth,
+) -> Result<Json<UpdateResponse>, (StatusCode, String)> {
+    let asn = auth.asn;
+    info!("Peering deactivate request for ASN {}", asn);
+    let _lock = asn_lock::lock(&config, asn).await?;
+
+    let iface_name = interface_name(asn);
+    let config_path = format!("{}/{}.conf", config.data_verified_dir, iface_name);
+
+    // Check if config exists in verified directory
+    if !std::path::Path::new(&config_path).exists() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("No peering configuration found for ASN {}", asn),
+        ));
+    }
+
+    // Remove WireGuard config (this also brings down the interface)
+    if config.manage_wireguard {
+        info!(
+            "Deactivating WireGuard config for ASN {} ({})",
+            asn, iface_name
+        );
+        wireguard::deploy::remove_config(&config.system, &iface_name)
+            .await
+            .map_err(|e| {
+                error!("Failed to deactivate WireGuard for ASN {}: {}", asn, e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to deactivate WireGuard: {}", e),
+                )
+            })?;
+    }
+
+    // Remove BIRD config
+    info!("Deactivating BIRD config for ASN {}", asn);
+    bird::deploy::remove_config(&config.system, asn)
+        .await
+        .map_err(|e| {
+            error!("Failed to deactivate BIRD config for ASN {}: {}", asn, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to deactivate BIRD config: {}", e),
+            )
+        })?;
+
+    info!(
+        "Successfully deactivated peering for ASN {} (config preserved in {})",
+        asn, config_path
+    );
+
+    Ok(Json(UpdateResponse::new(ActionStatus::Deactivated, asn)))
+}
+
+/// POST /peering/bgp/disable - Remove the BIRD session but keep the WireGuard tunnel up
+pub async fn disable_bgp(
+    State(config): State<Arc<AppConfig>>,
+    auth: JwtAuth,
+) -> Result<Json<UpdateResponse>, (StatusCode, String)> {
+    let asn = auth.asn;
+    info!("BGP disable request for ASN {}", asn);
+
+    set_bgp_enabled(&config, asn, false).await?;
+
+    Ok(Json(UpdateResponse::new(ActionStatus::BgpDisabled, asn)))
+}
+
+/// POST /peering/bgp/enable - Re-deploy the BIRD session without touching WireGuard
+pub async fn enable_bgp(
+    State(config): State<Arc<AppConfig>>,
+    auth: JwtAuth,
+) -> Result<Json<UpdateResponse>, (StatusCode, String)> {
+    let asn = auth.asn;
+    info!("BGP enable request for ASN {}", asn);
+
+    set_bgp_enabled(&config, asn, true).await?;
+
+    Ok(Json(UpdateResponse::new(ActionStatus::BgpEnabled, asn)))
+}
+
+/// Deploy or remove the BIRD config for an ASN and record the state in `[BGP]`.
+/// A peering that is only stored just gets the flag; activating it applies it.
+async fn set_bgp_enabled(
+    config: &AppConfig,
+    asn: u32,
+    enabled: bool,
+) -> Result<(), (StatusCode, String)> {
+    let iface_name = interface_name(asn);
+    let config_path = format!("{}/{}.conf", config.data_verified_dir, iface_name);
+
+    let mut wg_config = WgConfig::from_file(&config_path)
+        .map_err(|e| (StatusCode::NOT_FOUND, format!("Config not found: {}", e)))?;
+
+    let bgp = wg_config.bgp.as_mut().ok_or((
+        StatusCode::BAD_REQUEST,
+        "No BGP configuration for this peering".to_string(),
+    ))?;
+
+    if !is_live(config, asn).await {
+        info!(
+            "Peering for ASN {} is not active, only storing the BGP state",
+            asn
+        );
+    } else if enabled {
+        let bird_config_str = render_bird_config(config, asn, bgp)?;
+        bird::deploy::deploy_config(&config.system, &bird_config_str, asn)
+            .await
+            .map_err(|e| {
+                error!("Failed to enable BGP for ASN {}: {}", asn, e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to deploy BIRD config: {}", e),
+                )
+            })?;
+    } else {
+        bird::deploy::remove_config(&config.system, asn)
+            .await
+            .map_err(|e| {
+                error!("Failed to disable BGP for ASN {}: {}", asn, e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to remove BIRD config: {}", e),
+                )
+            })?;
+    }
+
+    bgp.enabled = enabled;
+
+    wg_config.to_file(&config_path).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to save config: {}", e),
+        )
+    })?;
+
+    info!(
+        "BGP {} for ASN {}",
+        if enabled { "enabled" } else { "disabled" },
+        asn
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_request_deserialization() {
+        let json = r#"{"asn": 4242420257}"#;
+        let req: InitRequest = serde_json::from_str(json).unwrap();
+
+        assert_eq!(req.asn, 4242420257);
+    }
+
+    #[test]
+    fn test_init_response_serialization() {
+        let resp = InitResponse {
+            challenge_id: "0123456789abcdef".to_string(),
+            challenge: "AUTOPEER-4242420257-abc123".to_string(),
+            pgp_fingerprint: "1234567890ABCDEF".to_string(),
+            minisign_keys: Vec::new(),
+            peering_policy: PeeringPolicy::Open,
+            allocated: Allocation {
+                interface_address: "fe80::257".to_string(),
+                listen_port: 30257,
+                bgp_neighbor: "fe80::2225".to_string(),
+            },
+        };
+
+        let json = serde_json::to_string(&resp).unwrap();
+        assert!(json.contains("AUTOPEER-4242420257-abc123"));
+        assert!(json.contains("challenge"));
+        assert!(json.contains("pgp_fingerprint"));
+        assert!(json.contains(r#""challenge_id":"0123456789abcdef""#));
+        assert!(json.contains(r#""peering_policy":"open""#));
+        assert!(
+            json.contains(r#""allocated":{"interface_address":"fe80::257","listen_port":30257"#)
+        );
+    }
+
+    // Endpoint handler tests
+    mod handler_tests {
+        use super::*;
+        use crate::api::test_helpers::{
+            authed_request, test_config_with_system, test_config_with_temp_dirs,
+            verified_config_path, write_verified_config,
+        };
+        use crate::api::ProvisioningState;
+        use axum::{
+            body::Body,
+            http::{Request, StatusCode},
+            routing::{get, post},
+            Router,
+        };
+        use tower::ServiceExt;
+        use tower_cookies::CookieManagerLayer;
+
+        #[tokio::test]
+        async fn test_init_peering_success() {
+            let (config, _pending_dir, _verified_dir) = test_config_with_temp_dirs();
+            let test_asn = 4242420257; // Use a real ASN with GPG key in registry
+
+            let app = Router::new()
+                .route("/peering/init", post(init_peering))
+                .with_state(config.clone());
+
+            let request_body = serde_json::to_string(&InitRequest {
+                asn: test_asn,
+                contact: None,
+                local_asn: None,
+            })
+            .unwrap();
+            let request = Request::builder()
+                .method("POST")
+                .uri("/peering/init")
+                .header("content-type", "application/json")
+                .body(Body::from(request_body))
+                .unwrap();
+
+            let response = app.oneshot(request).await.unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let init_response: InitResponse = serde_json::from_slice(&body).unwrap();
+
+            // Check challenge format
+            assert!(init_response
+                .challenge
+                .starts_with(&format!("AUTOPEER-{}-", test_asn)));
+
+            // Check that PGP fingerprint was returned
+            assert!(!init_response.pgp_fingerprint.is_empty());
+            assert_eq!(
+                init_response.pgp_fingerprint,
+                "8B7F0384CBE0272761D852EA0684E36E6CF9D4D4"
+            );
+
+            // Verify pending challenge file was created
+            let challenge_path = std::path::PathBuf::from(&config.data_pending_dir)
+                .join(format!("{}-{}.conf", test_asn, init_response.challenge_id));
+            assert!(
+                challenge_path.exists(),
+                "Pending challenge file should exist at {:?}",
+                challenge_path
+            );
+
+            // Verify challenge content matches
+            let stored_challenge = std::fs::read_to_string(&challenge_path).unwrap();
+            assert_eq!(stored_challenge, init_response.challenge);
+        }
+
+        #[tokio::test]
+        async fn test_init_returns_allocation() {
+            let (config, root) = test_config_with_system();
+            let asn = 4242422244;
+            let signer = crate::api::test_helpers::TestSigner::generate();
+            let mut config = (*config).clone();
+            config.registry.path = signer.write_registry(root.path(), asn);
+            let config = Arc::new(config);
+
+            let app = Router::new()
+                .route("/peering/init", post(init_peering))
+                .with_state(config.clone());
+            let request = Request::builder()
+                .method("POST")
+                .uri("/peering/init")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::to_string(&InitRequest {
+                        asn,
+                        contact: None,
+                        local_asn: None,
+                    })
+                    .unwrap(),
+                ))
+                .unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let init: InitResponse = serde_json::from_slice(&body).unwrap();
+
+            let ips = Ipv6LinkLocal::from_asns(config.my_asn, asn);
+            assert_eq!(init.allocated.interface_address, ips.local_addr());
+            assert_eq!(init.allocated.listen_port, wireguard_port(asn).unwrap());
+            assert_eq!(init.allocated.bgp_neighbor, ips.peer);
+        }
+
+        async fn init_with_registry(registry_path: std::path::PathBuf) -> (StatusCode, String) {
+            let (config, _pending_dir, _verified_dir) = test_config_with_temp_dirs();
+            let mut config = (*config).clone();
+            config.registry.path = registry_path;
+
+            let app = Router::new()
+                .route("/peering/init", post(init_peering))
+                .with_state(Arc::new(config));
+            let request = Request::builder()
+                .method("POST")
+                .uri("/peering/init")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::to_string(&InitRequest {
+                        asn: 4242420257,
+                        contact: None,
+                        local_asn: None,
+                    })
+                    .unwrap(),
+                ))
+                .unwrap();
+
+            let response = app.oneshot(request).await.unwrap();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, String::from_utf8(body.to_vec()).unwrap())
+        }
+
+        #[tokio::test]
+        async fn test_init_peering_registry_not_synced() {
+            let dir = tempfile::TempDir::new().unwrap();
+
+            let (status, message) = init_with_registry(dir.path().join("dn42-registry")).await;
+            assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+            assert!(
+                message.contains("registry not yet synced"),
+                "unexpected error: {}",
+                message
+            );
+        }
+
+        #[tokio::test]
+        async fn test_init_peering_asn_not_in_registry() {
+            let dir = tempfile::TempDir::new().unwrap();
+            std::fs::create_dir_all(dir.path().join("data/aut-num")).unwrap();
+
+            let (status, message) = init_with_registry(dir.path().to_path_buf()).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert!(
+                message.contains("No GPG key found"),
+                "unexpected error: {}",
+                message
+            );
+        }
+
+        #[tokio::test]
+        async fn test_init_peering_invalid_asn() {
+            let (config, _pending_dir, _verified_dir) = test_config_with_temp_dirs();
+
+            let app = Router::new()
+                .route("/peering/init", post(init_peering))
+                .with_state(config);
+
+            // Invalid ASN (too small for DN42)
+            let request_body = serde_json::to_string(&InitRequest {
+                asn: 1000,
+                contact: None,
+                local_asn: None,
+            })
+            .unwrap();
+            let request = Request::builder()
+                .method("POST")
+                .uri("/peering/init")
+                .header("content-type", "application/json")
+                .body(Body::from(request_body))
+                .unwrap();
+
+            let response = app.oneshot(request).await.unwrap();
+
+            // Should fail with 400 Bad Request
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+
+        #[tokio::test]
+        async fn test_init_peering_creates_unique_challenges() {
+            let (config, _pending_dir, _verified_dir) = test_config_with_temp_dirs();
+            let test_asn = 4242420257; // Use a real ASN with GPG key in registry
+
+            // Create first peering
+            let app1 = Router::new()
+                .route("/peering/init", post(init_peering))
+                .with_state(config.clone());
+
+            let request_body = serde_json::to_string(&InitRequest {
+                asn: test_asn,
+                contact: None,
+                local_asn: None,
+            })
+            .unwrap();
             let request1 = Request::builder()
                 .method("POST")
                 .uri("/peering/init")
                 .header("content-type", "application/json")
-                .body(Body::from(request_body.clone()))
+                .body(Body::from(request_body.clone()))
+                .unwrap();
+
+            let response1 = app1.oneshot(request1).await.unwrap();
+            let body1 = axum::body::to_bytes(response1.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let resp1: InitResponse = serde_json::from_slice(&body1).unwrap();
+
+            // Create second peering with same ASN
+            let app2 = Router::new()
+                .route("/peering/init", post(init_peering))
+                .with_state(config);
+
+            let request2 = Request::builder()
+                .method("POST")
+                .uri("/peering/init")
+                .header("content-type", "application/json")
+                .body(Body::from(request_body))
+                .unwrap();
+
+            let response2 = app2.oneshot(request2).await.unwrap();
+            let body2 = axum::body::to_bytes(response2.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let resp2: InitResponse = serde_json::from_slice(&body2).unwrap();
+
+            // Challenges should be different (random)
+            assert_ne!(resp1.challenge, resp2.challenge);
+        }
+
+        #[tokio::test]
+        async fn test_bgp_toggle_leaves_wireguard_untouched() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422225;
+            write_verified_config(&config, asn);
+
+            // Pretend the tunnel is deployed
+            let wg_path =
+                wireguard::deploy::config_path(&config.system.wireguard_dir, &interface_name(asn));
+            std::fs::write(&wg_path, "[Interface]\n# deployed tunnel\n").unwrap();
+            let wg_before = std::fs::read(&wg_path).unwrap();
+
+            let bird_path = bird::deploy::config_path(&config.system.bird_peers_dir, asn);
+
+            let app = Router::new()
+                .route("/peering/bgp/disable", post(disable_bgp))
+                .route("/peering/bgp/enable", post(enable_bgp))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            // Disable: BIRD config removed, state recorded, tunnel untouched
+            std::fs::write(&bird_path, "protocol bgp old {}").unwrap();
+            let response = app
+                .clone()
+                .oneshot(authed_request(
+                    "POST",
+                    "/peering/bgp/disable",
+                    &config,
+                    asn,
+                    None,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(!bird_path.exists());
+            let stored = WgConfig::from_file(verified_config_path(&config, asn)).unwrap();
+            assert!(!stored.bgp.unwrap().enabled);
+            assert_eq!(std::fs::read(&wg_path).unwrap(), wg_before);
+
+            // Enable: BIRD config redeployed, tunnel still untouched
+            let response = app
+                .oneshot(authed_request(
+                    "POST",
+                    "/peering/bgp/enable",
+                    &config,
+                    asn,
+                    None,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let bird_conf = std::fs::read_to_string(&bird_path).unwrap();
+            assert!(bird_conf.contains("protocol bgp autopeer_as4242422225"));
+            let stored = WgConfig::from_file(verified_config_path(&config, asn)).unwrap();
+            assert!(stored.bgp.unwrap().enabled);
+            assert_eq!(std::fs::read(&wg_path).unwrap(), wg_before);
+        }
+
+        #[tokio::test]
+        async fn test_bgp_toggle_without_deployment() {
+            let (config, _root) = test_config_with_system();
+
+            let app = Router::new()
+                .route("/peering/bgp/disable", post(disable_bgp))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let response = app
+                .oneshot(authed_request(
+                    "POST",
+                    "/peering/bgp/disable",
+                    &config,
+                    4242422225,
+                    None,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+
+        /// GET with an optional If-None-Match header
+        fn conditional_get(
+            uri: &str,
+            config: &AppConfig,
+            asn: u32,
+            etag: Option<&str>,
+        ) -> Request<Body> {
+            let mut request = authed_request("GET", uri, config, asn, None);
+            if let Some(etag) = etag {
+                request
+                    .headers_mut()
+                    .insert(header::IF_NONE_MATCH, etag.parse().unwrap());
+            }
+            request
+        }
+
+        #[tokio::test]
+        async fn test_get_config_etag() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422225;
+            let mut wg_config = write_verified_config(&config, asn);
+
+            let app = Router::new()
+                .route("/peering/config", get(get_config))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let response = app
+                .clone()
+                .oneshot(conditional_get("/peering/config", &config, asn, None))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(response.headers().contains_key(header::LAST_MODIFIED));
+            let etag = response.headers()[header::ETAG]
+                .to_str()
+                .unwrap()
+                .to_string();
+
+            // Matching ETag: 304 with no body
+            let response = app
+                .clone()
+                .oneshot(conditional_get(
+                    "/peering/config",
+                    &config,
+                    asn,
+                    Some(&etag),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert!(body.is_empty());
+
+            // Changed config: full response with a new ETag
+            wg_config.peer_mut().unwrap().endpoint = Some("peer.example:51820".to_string());
+            wg_config
+                .to_file(verified_config_path(&config, asn))
+                .unwrap();
+
+            let response = app
+                .oneshot(conditional_get(
+                    "/peering/config",
+                    &config,
+                    asn,
+                    Some(&etag),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_ne!(response.headers()[header::ETAG].to_str().unwrap(), etag);
+        }
+
+        #[tokio::test]
+        async fn test_get_status_etag() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422225;
+            write_verified_config(&config, asn);
+
+            let app = Router::new()
+                .route("/peering/status", get(get_status))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let response = app
+                .clone()
+                .oneshot(conditional_get("/peering/status", &config, asn, None))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let etag = response.headers()[header::ETAG]
+                .to_str()
+                .unwrap()
+                .to_string();
+
+            let response = app
+                .oneshot(conditional_get(
+                    "/peering/status",
+                    &config,
+                    asn,
+                    Some(&etag),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        }
+
+        #[tokio::test]
+        async fn test_systemd_bundle_contents() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422225;
+            let mut ours = write_verified_config(&config, asn);
+            // Deploys keep our own side of the tunnel on the interface
+            ours.interface.address = vec!["fe80::2225:257:0/64".to_string()];
+            ours.to_file(verified_config_path(&config, asn)).unwrap();
+
+            let app = Router::new()
+                .route("/peering/systemd", get(get_systemd_bundle))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let response = app
+                .oneshot(authed_request(
+                    "GET",
+                    "/peering/systemd",
+                    &config,
+                    asn,
+                    None,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers()[header::CONTENT_TYPE],
+                "application/x-tar"
+            );
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let entries = crate::util::tar::read_entries(&body);
+            let iface = interface_name(config.my_asn);
+            let names: Vec<_> = entries.iter().map(|(name, _)| name.clone()).collect();
+            assert_eq!(
+                names,
+                vec![format!("{}.conf", iface), "README.md".to_string()]
+            );
+
+            let wg_conf = String::from_utf8(entries[0].1.clone()).unwrap();
+            assert!(
+                wg_conf.contains("Address = fe80::2225:257:1/64\n"),
+                "{}",
+                wg_conf
+            );
+            assert!(wg_conf.contains(&format!(
+                "PublicKey = {}",
+                crate::api::test_helpers::STUB_PUBLIC_KEY
+            )));
+            assert!(wg_conf.contains(&format!(
+                "Endpoint = dn42-test.example:{}",
+                wireguard_port(asn).unwrap()
+            )));
+            assert!(wg_conf.contains("ListenPort = 51820\n"));
+            // Neither our private key nor a key line for the peer to fill in
+            assert!(!wg_conf.contains(crate::api::test_helpers::STUB_PRIVATE_KEY));
+            assert!(!wg_conf.contains("PrivateKey"));
+
+            let readme = String::from_utf8(entries[1].1.clone()).unwrap();
+            assert!(readme.contains(&format!(
+                "systemctl enable --now wg-quick@{}.service",
+                iface
+            )));
+        }
+
+        async fn fetch_state(config: &Arc<AppConfig>, asn: u32) -> PeeringState {
+            let app = Router::new()
+                .route("/peering/state", get(get_state))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let response = app
+                .oneshot(authed_request("GET", "/peering/state", config, asn, None))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<StateResponse>(&body)
+                .unwrap()
+                .state
+        }
+
+        #[tokio::test]
+        async fn test_state_pending() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422225;
+            let challenge_path =
+                format!("{}/{}.conf", config.data_pending_dir, interface_name(asn));
+            std::fs::write(&challenge_path, "AUTOPEER-4242422225-test").unwrap();
+
+            assert_eq!(fetch_state(&config, asn).await, PeeringState::Pending);
+        }
+
+        #[tokio::test]
+        async fn test_state_verified_despite_other_pending_challenge() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422225;
+            std::fs::write(
+                pending::path(&config.data_pending_dir, asn, "bb"),
+                "AUTOPEER-4242422225-other",
+            )
+            .unwrap();
+
+            let app = Router::new()
+                .route("/peering/state", get(get_state))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let token = generate_challenge_token(
+                asn,
+                "AUTOPEER-4242422225-signed",
+                None,
+                None,
+                &config.jwt_secret,
+                &JwtScope::default(),
+            )
+            .unwrap();
+            let request = Request::builder()
+                .uri("/peering/state")
+                .header(header::COOKIE, format!("autopeer_token={}", token))
+                .body(Body::empty())
+                .unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(
+                serde_json::from_slice::<StateResponse>(&body)
+                    .unwrap()
+                    .state,
+                PeeringState::Verified
+            );
+        }
+
+        #[tokio::test]
+        async fn test_state_verified() {
+            let (config, _root) = test_config_with_system();
+
+            assert_eq!(
+                fetch_state(&config, 4242422225).await,
+                PeeringState::Verified
+            );
+        }
+
+        #[tokio::test]
+        async fn test_state_deployed() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422225;
+            write_verified_config(&config, asn);
+
+            assert_eq!(fetch_state(&config, asn).await, PeeringState::Deployed);
+        }
+
+        #[tokio::test]
+        async fn test_deploy_records_challenge() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422225;
+            let code = "AUTOPEER-4242422225-deadbeef";
+
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let token = crate::jwt::generate_challenge_token(
+                asn,
+                code,
+                None,
+                None,
+                &config.jwt_secret,
+                &JwtScope::default(),
+            )
+            .unwrap();
+            let body = serde_json::to_string(&DeployRequest {
+                wg_public_key: crate::api::test_helpers::PEER_PUBLIC_KEY.to_string(),
+                endpoint: Some("192.0.2.1:51820".to_string()),
+                max_prefixes: None,
+                address_family: AddressFamily::Dual,
+                peer_ipv4: None,
+                listen_port: None,
+                router_id: None,
+                peer_name: None,
+                graceful_restart: None,
+                async_provisioning: false,
+                preshared_key: None,
+                generate_preshared_key: false,
+                dynamic_endpoint: false,
+                deploy_and_activate: false,
+                local_asn: None,
+            })
+            .unwrap();
+            let request = Request::builder()
+                .method("POST")
+                .uri("/peering/deploy")
+                .header(header::COOKIE, format!("autopeer_token={}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap();
+
+            let response = app.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let stored = WgConfig::from_file(verified_config_path(&config, asn)).unwrap();
+            assert_eq!(
+                stored.challenge,
+                Some(ChallengeConfig {
+                    code: code.to_string(),
+                    asn
+                })
+            );
+        }
+
+        #[tokio::test]
+        async fn test_update_cooldown() {
+            let (config, _root) = test_config_with_system();
+            let config = Arc::new(AppConfig {
+                deploy_cooldown_secs: 1,
+                ..(*config).clone()
+            });
+            let asn = 4242422225;
+            write_verified_config(&config, asn);
+
+            let app = Router::new()
+                .route("/peering/update", axum::routing::patch(update_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let update = || {
+                let body = serde_json::to_string(&UpdateRequest {
+                    endpoint: Some("192.0.2.2:51820".to_string()),
+                    max_prefixes: None,
+                    graceful_restart: None,
+                })
+                .unwrap();
+                authed_request("PATCH", "/peering/update", &config, asn, Some(body))
+            };
+
+            let response = app.clone().oneshot(update()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            // Rapid second update is rejected
+            let response = app.clone().oneshot(update()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+            // Allowed again once the cooldown has passed
+            tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+            let response = app.oneshot(update()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        #[tokio::test]
+        async fn test_update_preview_reports_endpoint_change() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422225;
+            let stored = write_verified_config(&config, asn);
+
+            let app = Router::new()
+                .route("/peering/update/preview", post(preview_update))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let body = serde_json::to_string(&UpdateRequest {
+                endpoint: Some("192.0.2.2:51820".to_string()),
+                max_prefixes: None,
+                graceful_restart: None,
+            })
+            .unwrap();
+            let response = app
+                .oneshot(authed_request(
+                    "POST",
+                    "/peering/update/preview",
+                    &config,
+                    asn,
+                    Some(body),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let preview: UpdatePreviewResponse = serde_json::from_slice(&body).unwrap();
+            assert_eq!(
+                preview.changes,
+                vec![ConfigChange {
+                    field: "peer.endpoint".to_string(),
+                    old: Some("192.0.2.1:51820".to_string()),
+                    new: Some("192.0.2.2:51820".to_string()),
+                }]
+            );
+
+            // Nothing was applied
+            assert_eq!(
+                WgConfig::from_file(verified_config_path(&config, asn)).unwrap(),
+                stored
+            );
+            assert!(history::latest(&verified_config_path(&config, asn))
+                .unwrap()
+                .is_none());
+        }
+
+        #[tokio::test]
+        async fn test_redeploy_after_update() {
+            let (config, _root) = test_config_with_system();
+            let config = Arc::new(AppConfig {
+                reject_duplicate_endpoints: true,
+                ..(*config).clone()
+            });
+            let asn = 4242422234;
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .route("/peering/update", axum::routing::patch(update_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let response = app
+                .clone()
+                .oneshot(deploy_request(&config, asn))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = serde_json::to_string(&UpdateRequest {
+                endpoint: Some("192.0.2.99:51820".to_string()),
+                max_prefixes: None,
+                graceful_restart: None,
+            })
+            .unwrap();
+            let response = app
+                .clone()
+                .oneshot(authed_request(
+                    "PATCH",
+                    "/peering/update",
+                    &config,
+                    asn,
+                    Some(body),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(history::backup_path(&verified_config_path(&config, asn), 1).exists());
+
+            // The backup is not another peering holding the port or the original endpoint
+            let response = app.oneshot(deploy_request(&config, asn)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        #[tokio::test]
+        async fn test_external_keys_keep_stored_copies_private() {
+            use std::os::unix::fs::PermissionsExt;
+
+            let (config, _root) = test_config_with_system();
+            let config = Arc::new(AppConfig {
+                external_private_keys: true,
+                ..(*config).clone()
+            });
+            let asn = 4242422234;
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .route("/peering/update", axum::routing::patch(update_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let mode = |path: &std::path::Path| {
+                std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+            };
+
+            assert_eq!(
+                app.clone()
+                    .oneshot(deploy_request(&config, asn))
+                    .await
+                    .unwrap()
+                    .status(),
+                StatusCode::OK
+            );
+            let verified = verified_config_path(&config, asn);
+            let deployed =
+                wireguard::deploy::config_path(&config.system.wireguard_dir, &interface_name(asn));
+            assert!(!std::fs::read_to_string(deployed)
+                .unwrap()
+                .contains("PrivateKey"));
+            // The stored copy still needs the key, so only the owner may read it
+            assert_eq!(mode(&verified), 0o600);
+
+            let body = serde_json::to_string(&UpdateRequest {
+                endpoint: Some("192.0.2.99:51820".to_string()),
+                max_prefixes: None,
+                graceful_restart: None,
+            })
+            .unwrap();
+            let response = app
+                .oneshot(authed_request(
+                    "PATCH",
+                    "/peering/update",
+                    &config,
+                    asn,
+                    Some(body),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(mode(&verified), 0o600);
+            assert_eq!(mode(&history::backup_path(&verified, 1)), 0o600);
+        }
+
+        #[tokio::test]
+        async fn test_update_backup_and_rollback() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422234;
+            write_verified_config(&config, asn);
+            let config_path = verified_config_path(&config, asn);
+
+            let app = Router::new()
+                .route("/peering/update", axum::routing::patch(update_peering))
+                .route("/peering/rollback", post(rollback_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            // Nothing to roll back to yet
+            let response = app
+                .clone()
+                .oneshot(authed_request(
+                    "POST",
+                    "/peering/rollback",
+                    &config,
+                    asn,
+                    None,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            let body = serde_json::to_string(&UpdateRequest {
+                endpoint: Some("192.0.2.99:51820".to_string()),
+                max_prefixes: None,
+                graceful_restart: None,
+            })
+            .unwrap();
+            let response = app
+                .clone()
+                .oneshot(authed_request(
+                    "PATCH",
+                    "/peering/update",
+                    &config,
+                    asn,
+                    Some(body),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            // The pre-update version is kept as the newest backup
+            let backup = WgConfig::from_file(history::backup_path(&config_path, 1)).unwrap();
+            assert_eq!(
+                backup.peer().unwrap().endpoint.as_deref(),
+                Some("192.0.2.1:51820")
+            );
+
+            let response = app
+                .oneshot(authed_request(
+                    "POST",
+                    "/peering/rollback",
+                    &config,
+                    asn,
+                    None,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let restored = WgConfig::from_file(&config_path).unwrap();
+            assert_eq!(
+                restored.peer().unwrap().endpoint.as_deref(),
+                Some("192.0.2.1:51820")
+            );
+            assert!(!history::backup_path(&config_path, 1).exists());
+        }
+
+        #[tokio::test]
+        async fn test_update_max_prefixes_cannot_exceed_default() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422226;
+            write_verified_config(&config, asn);
+
+            let app = Router::new()
+                .route("/peering/update", axum::routing::patch(update_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let update = |max_prefixes| {
+                let body = serde_json::to_string(&UpdateRequest {
+                    endpoint: None,
+                    max_prefixes: Some(max_prefixes),
+                    graceful_restart: None,
+                })
+                .unwrap();
+                authed_request("PATCH", "/peering/update", &config, asn, Some(body))
+            };
+
+            let response = app
+                .clone()
+                .oneshot(update(config.default_max_prefixes + 1))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            let response = app.oneshot(update(100)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let verified_path =
+                format!("{}/{}.conf", config.data_verified_dir, interface_name(asn));
+            let (stored, _) = load_verified_config(&verified_path).unwrap();
+            assert_eq!(stored.bgp.unwrap().max_prefixes, Some(100));
+        }
+
+        #[tokio::test]
+        async fn test_update_graceful_restart() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422226;
+            write_verified_config(&config, asn);
+            // The session is deployed, so the update re-renders it
+            std::fs::write(
+                bird::deploy::config_path(&config.system.bird_peers_dir, asn),
+                "",
+            )
+            .unwrap();
+
+            let app = Router::new()
+                .route("/peering/update", axum::routing::patch(update_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let body = serde_json::to_string(&UpdateRequest {
+                endpoint: None,
+                max_prefixes: None,
+                graceful_restart: Some(true),
+            })
+            .unwrap();
+            let response = app
+                .oneshot(authed_request(
+                    "PATCH",
+                    "/peering/update",
+                    &config,
+                    asn,
+                    Some(body),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let stored = WgConfig::from_file(verified_config_path(&config, asn)).unwrap();
+            assert_eq!(stored.bgp.unwrap().graceful_restart, Some(true));
+            let bird_conf = std::fs::read_to_string(bird::deploy::config_path(
+                &config.system.bird_peers_dir,
+                asn,
+            ))
+            .unwrap();
+            assert!(bird_conf.contains("graceful restart on;"), "{}", bird_conf);
+        }
+
+        #[tokio::test]
+        async fn test_allocation_change_keeps_stored_addresses() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422225;
+
+            let app = |config: &Arc<AppConfig>| {
+                Router::new()
+                    .route("/peering/deploy", post(deploy_peering))
+                    .route("/peering/status", get(get_status))
+                    .route("/peering/activate", post(activate_peering))
+                    .layer(CookieManagerLayer::new())
+                    .with_state(config.clone())
+            };
+            let status = |config: Arc<AppConfig>| async move {
+                let response = app(&config)
+                    .oneshot(authed_request("GET", "/peering/status", &config, asn, None))
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                serde_json::from_slice::<DeploymentInfo>(&body).unwrap()
+            };
+
+            let response = app(&config)
+                .oneshot(deploy_request(&config, asn))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let before = status(config.clone()).await;
+            assert_eq!(before.bgp_neighbor, "fe80::2225:257:1");
+
+            // The operator switches new sessions to ULA transfer addresses
+            let config = Arc::new(AppConfig {
+                bgp_neighbor_mode: NeighborMode::Ula,
+                ula_transfer_prefix: Some("fd42:4242:257::/48".parse().unwrap()),
+                ..(*config).clone()
+            });
+            let after = status(config.clone()).await;
+            assert_eq!(after.interface_address, before.interface_address);
+            assert_eq!(after.bgp_neighbor, before.bgp_neighbor);
+            assert_eq!(after.listen_port, before.listen_port);
+
+            // Re-rendering BIRD keeps the live session where it is
+            let response = app(&config)
+                .oneshot(authed_request(
+                    "POST",
+                    "/peering/activate",
+                    &config,
+                    asn,
+                    None,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let bird_conf = std::fs::read_to_string(bird::deploy::config_path(
+                &config.system.bird_peers_dir,
+                asn,
+            ))
+            .unwrap();
+            assert!(
+                bird_conf.contains("local fe80::2225:257:0 as 4242420257"),
+                "{}",
+                bird_conf
+            );
+            assert!(bird_conf.contains("neighbor fe80::2225:257:1 as 4242422225"));
+            assert!(!bird_conf.contains("fd42:"));
+        }
+
+        #[tokio::test]
+        async fn test_deploy_transfer_net_only() {
+            let (config, _root) = test_config_with_system();
+            let config = Arc::new(AppConfig {
+                bgp_transport: BgpTransport::TransferNet,
+                ula_transfer_prefix: Some("fd42:4242:257::/48".parse().unwrap()),
+                ..(*config).clone()
+            });
+            let asn = 4242422225;
+
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .route("/peering/status", get(get_status))
+                .route("/peering/systemd", get(get_systemd_bundle))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let response = app
+                .clone()
+                .oneshot(deploy_request(&config, asn))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            // The peer's tunnel gets their side of the transfer net, not ours
+            let response = app
+                .clone()
+                .oneshot(authed_request(
+                    "GET",
+                    "/peering/systemd",
+                    &config,
+                    asn,
+                    None,
+                ))
+                .await
+                .unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let wg_conf =
+                String::from_utf8(crate::util::tar::read_entries(&body)[0].1.clone()).unwrap();
+            let addresses: Vec<_> = wg_conf
+                .lines()
+                .filter(|line| line.starts_with("Address = "))
+                .collect();
+            assert_eq!(addresses, vec!["Address = fd42:4242:257:2225::2/64"]);
+
+            // No link-local on the tunnel, only our side of the transfer net
+            let stored = WgConfig::from_file(verified_config_path(&config, asn)).unwrap();
+            assert_eq!(stored.interface.address, vec!["fd42:4242:257:2225::1/64"]);
+            let bgp = stored.bgp.unwrap();
+            assert_eq!(bgp.transport, BgpTransport::TransferNet);
+            assert_eq!(bgp.neighbor, "fd42:4242:257:2225::2");
+
+            let response = app
+                .oneshot(authed_request("GET", "/peering/status", &config, asn, None))
+                .await
+                .unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let info: DeploymentInfo = serde_json::from_slice(&body).unwrap();
+            assert_eq!(info.interface_address, "fd42:4242:257:2225::1");
+            assert_eq!(info.bgp_neighbor, "fd42:4242:257:2225::2");
+
+            let bird_conf = std::fs::read_to_string(bird::deploy::config_path(
+                &config.system.bird_peers_dir,
+                asn,
+            ))
+            .unwrap();
+            assert!(
+                bird_conf.contains("neighbor fd42:4242:257:2225::2 as 4242422225"),
+                "{}",
+                bird_conf
+            );
+            assert!(!bird_conf.contains("fe80:"));
+            assert!(!bird_conf.contains("Link-Local"));
+        }
+
+        #[tokio::test]
+        async fn test_action_statuses() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422235;
+            write_verified_config(&config, asn);
+
+            let app = Router::new()
+                .route("/peering/update", axum::routing::patch(update_peering))
+                .route("/peering/deactivate", post(deactivate_peering))
+                .route("/peering/activate", post(activate_peering))
+                .route("/peering", axum::routing::delete(delete_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let update = serde_json::to_string(&UpdateRequest {
+                endpoint: Some("192.0.2.99:51820".to_string()),
+                max_prefixes: None,
+                graceful_restart: None,
+            })
+            .unwrap();
+            let steps = [
+                (
+                    "PATCH",
+                    "/peering/update",
+                    Some(update),
+                    ActionStatus::Updated,
+                    "updated",
+                ),
+                (
+                    "POST",
+                    "/peering/deactivate",
+                    None,
+                    ActionStatus::Deactivated,
+                    "deactivated",
+                ),
+                (
+                    "POST",
+                    "/peering/activate",
+                    None,
+                    ActionStatus::Activated,
+                    "activated",
+                ),
+                ("DELETE", "/peering", None, ActionStatus::Deleted, "deleted"),
+            ];
+            for (method, uri, body, status, wire) in steps {
+                let response = app
+                    .clone()
+                    .oneshot(authed_request(method, uri, &config, asn, body))
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::OK, "{} {}", method, uri);
+
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                assert_eq!(json["status"], wire);
+                assert_eq!(json["asn"], asn);
+                assert!(json["timestamp"].as_i64().unwrap() > 0);
+                let parsed: UpdateResponse = serde_json::from_slice(&body).unwrap();
+                assert_eq!(parsed.status, status);
+            }
+        }
+
+        /// Config whose `wg-quick` stub logs its arguments; `wg show` succeeds when `up`
+        fn logging_wg_quick(up: bool) -> (Arc<AppConfig>, tempfile::TempDir, std::path::PathBuf) {
+            let (config, root) = test_config_with_system();
+            let bin_dir = root.path().join("bin");
+            let log = root.path().join("wg-quick.log");
+            let mut system = config.system.clone();
+            system.wg_quick_bin = crate::api::test_helpers::write_stub(
+                &bin_dir,
+                "wg-quick-log",
+                &format!("#!/bin/sh\necho \"$@\" >> {}\n", log.display()),
+            );
+            if up {
+                system.wg_bin = crate::api::test_helpers::write_stub(
+                    &bin_dir,
+                    "wg-up",
+                    &std::fs::read_to_string(&config.system.wg_bin)
+                        .unwrap()
+                        .replace("show) exit 1", "show) exit 0"),
+                );
+            }
+            let config = Arc::new(AppConfig {
+                system,
+                ..(*config).clone()
+            });
+            (config, root, log)
+        }
+
+        #[tokio::test]
+        async fn test_repair_restores_deleted_wireguard_config() {
+            // The interface is still up, but its config file is gone
+            let (config, _root, log) = logging_wg_quick(true);
+            let asn = 4242422225;
+            let stored = write_verified_config(&config, asn);
+            let live_path =
+                wireguard::deploy::config_path(&config.system.wireguard_dir, &interface_name(asn));
+            assert!(!live_path.exists());
+
+            let app = Router::new()
+                .route("/peering/repair", post(repair_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let repair = || async {
+                let response = app
+                    .clone()
+                    .oneshot(authed_request(
+                        "POST",
+                        "/peering/repair",
+                        &config,
+                        asn,
+                        None,
+                    ))
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                serde_json::from_slice::<UpdateResponse>(&body)
+                    .unwrap()
+                    .status
+            };
+
+            assert_eq!(repair().await, ActionStatus::Repaired);
+            assert_eq!(
+                std::fs::read_to_string(&live_path).unwrap(),
+                stored.as_string().unwrap()
+            );
+            // Restarted so the running interface matches the file again
+            assert_eq!(
+                std::fs::read_to_string(&log).unwrap(),
+                format!("down {iface}\nup {iface}\n", iface = interface_name(asn))
+            );
+
+            // Nothing left to do the second time
+            assert_eq!(repair().await, ActionStatus::Repaired);
+            assert_eq!(std::fs::read_to_string(&log).unwrap().lines().count(), 2);
+
+            let response = app
+                .clone()
+                .oneshot(authed_request(
+                    "POST",
+                    "/peering/repair",
+                    &config,
+                    4242422226,
+                    None,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+
+        #[tokio::test]
+        async fn test_activate_strips_dns_directive() {
+            let asn = 4242422225;
+            for allow_dns_directive in [false, true] {
+                let (config, _root, _log) = logging_wg_quick(true);
+                let config = Arc::new(AppConfig {
+                    allow_dns_directive,
+                    ..(*config).clone()
+                });
+                // A DNS line added to the verified config by hand
+                let mut stored = write_verified_config(&config, asn);
+                stored.interface.dns = vec!["172.20.0.53".to_string()];
+                stored.to_file(verified_config_path(&config, asn)).unwrap();
+
+                let app = Router::new()
+                    .route("/peering/activate", post(activate_peering))
+                    .layer(CookieManagerLayer::new())
+                    .with_state(config.clone());
+                let response = app
+                    .oneshot(authed_request(
+                        "POST",
+                        "/peering/activate",
+                        &config,
+                        asn,
+                        None,
+                    ))
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+
+                let live_path = wireguard::deploy::config_path(
+                    &config.system.wireguard_dir,
+                    &interface_name(asn),
+                );
+                let live = std::fs::read_to_string(&live_path).unwrap();
+                assert_eq!(
+                    live.contains("DNS = 172.20.0.53\n"),
+                    allow_dns_directive,
+                    "{}",
+                    live
+                );
+                // The stored config keeps it either way
+                let verified = WgConfig::from_file(verified_config_path(&config, asn)).unwrap();
+                assert_eq!(verified.interface.dns, vec!["172.20.0.53"]);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_activate_failure_brings_interface_down() {
+            // wg-quick up succeeds but the interface never shows up
+            let (config, _root, log) = logging_wg_quick(false);
+            let mut system = config.system.clone();
+            system.wg_up_timeout_secs = 1;
+            let config = Arc::new(AppConfig {
+                system,
+                ..(*config).clone()
+            });
+            let asn = 4242422225;
+            write_verified_config(&config, asn);
+
+            let app = Router::new()
+                .route("/peering/activate", post(activate_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let response = app
+                .oneshot(authed_request(
+                    "POST",
+                    "/peering/activate",
+                    &config,
+                    asn,
+                    None,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+            let iface = interface_name(asn);
+            assert_eq!(
+                std::fs::read_to_string(&log).unwrap(),
+                format!("up {}\ndown {}\n", iface, iface)
+            );
+            assert!(!wireguard::deploy::config_path(&config.system.wireguard_dir, &iface).exists());
+            assert!(!bird::deploy::config_path(&config.system.bird_peers_dir, asn).exists());
+        }
+
+        #[tokio::test]
+        async fn test_stored_peering_stays_down() {
+            let (config, _root) = test_config_with_system();
+            let config = Arc::new(AppConfig {
+                deploy_auto_activate: false,
+                ..(*config).clone()
+            });
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .route("/peering/update", axum::routing::patch(update_peering))
+                .route("/peering/rollback", post(rollback_peering))
+                .route("/peering/activate", post(activate_peering))
+                .route("/peering/bgp/disable", post(disable_bgp))
+                .route("/peering/bgp/enable", post(enable_bgp))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let asn = 4242422225;
+            let live = || {
+                (
+                    wireguard::deploy::config_path(
+                        &config.system.wireguard_dir,
+                        &interface_name(asn),
+                    )
+                    .exists(),
+                    bird::deploy::config_path(&config.system.bird_peers_dir, asn).exists(),
+                )
+            };
+            let send = |method: &'static str, uri: &'static str, body: Option<String>| {
+                let request = authed_request(method, uri, &config, asn, body);
+                let app = app.clone();
+                async move { app.oneshot(request).await.unwrap().status() }
+            };
+
+            assert_eq!(
+                app.clone()
+                    .oneshot(deploy_request(&config, asn))
+                    .await
+                    .unwrap()
+                    .status(),
+                StatusCode::OK
+            );
+
+            // Updates and rollbacks only rewrite the stored config
+            let update = serde_json::to_string(&UpdateRequest {
+                endpoint: Some("192.0.2.99:51820".to_string()),
+                max_prefixes: Some(500),
+                graceful_restart: None,
+            })
+            .unwrap();
+            assert_eq!(
+                send("PATCH", "/peering/update", Some(update)).await,
+                StatusCode::OK
+            );
+            let stored = WgConfig::from_file(verified_config_path(&config, asn)).unwrap();
+            assert_eq!(
+                stored.peer().unwrap().endpoint.as_deref(),
+                Some("192.0.2.99:51820")
+            );
+            assert_eq!(live(), (false, false));
+            assert_eq!(
+                send("POST", "/peering/rollback", None).await,
+                StatusCode::OK
+            );
+            let stored = WgConfig::from_file(verified_config_path(&config, asn)).unwrap();
+            assert_eq!(
+                stored.peer().unwrap().endpoint.as_deref(),
+                Some("192.0.2.1:51820")
+            );
+            assert_eq!(live(), (false, false));
+
+            // So does toggling BGP; the session only comes up with the activation
+            let bgp_enabled = || {
+                WgConfig::from_file(verified_config_path(&config, asn))
+                    .unwrap()
+                    .bgp
+                    .unwrap()
+                    .enabled
+            };
+            assert_eq!(
+                send("POST", "/peering/bgp/disable", None).await,
+                StatusCode::OK
+            );
+            assert!(!bgp_enabled());
+            assert_eq!(
+                send("POST", "/peering/bgp/enable", None).await,
+                StatusCode::OK
+            );
+            assert!(bgp_enabled());
+            assert_eq!(live(), (false, false));
+
+            // Once active, a store-only re-deploy would put new keys under the running tunnel
+            assert_eq!(
+                send("POST", "/peering/activate", None).await,
+                StatusCode::OK
+            );
+            assert_eq!(live(), (true, true));
+            let before = std::fs::read_to_string(verified_config_path(&config, asn)).unwrap();
+            let response = app
+                .clone()
+                .oneshot(deploy_request(&config, asn))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::CONFLICT);
+            assert_eq!(
+                std::fs::read_to_string(verified_config_path(&config, asn)).unwrap(),
+                before
+            );
+        }
+
+        #[tokio::test]
+        async fn test_deploy_without_auto_activate() {
+            let (config, _root) = test_config_with_system();
+            let config = Arc::new(AppConfig {
+                deploy_auto_activate: false,
+                ..(*config).clone()
+            });
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .route("/peering/activate", post(activate_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let live = |asn: u32| {
+                (
+                    wireguard::deploy::config_path(
+                        &config.system.wireguard_dir,
+                        &interface_name(asn),
+                    )
+                    .exists(),
+                    bird::deploy::config_path(&config.system.bird_peers_dir, asn).exists(),
+                )
+            };
+
+            // Stored only; the router is untouched until activation
+            let asn = 4242422225;
+            let response = app
+                .clone()
+                .oneshot(deploy_request(&config, asn))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert!(
+                !serde_json::from_slice::<DeployResponse>(&body)
+                    .unwrap()
+                    .deployment
+                    .is_active
+            );
+            assert!(verified_config_path(&config, asn).exists());
+            assert_eq!(live(asn), (false, false));
+
+            let response = app
+                .clone()
+                .oneshot(authed_request(
+                    "POST",
+                    "/peering/activate",
+                    &config,
+                    asn,
+                    None,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(live(asn), (true, true));
+
+            // Clients wanting both in one call still get them
+            let asn = 4242422226;
+            let mut request: DeployRequest = serde_json::from_slice(
+                &axum::body::to_bytes(deploy_request(&config, asn).into_body(), usize::MAX)
+                    .await
+                    .unwrap(),
+            )
+            .unwrap();
+            request.deploy_and_activate = true;
+            let body = serde_json::to_string(&request).unwrap();
+            let response = app
+                .clone()
+                .oneshot(authed_request(
+                    "POST",
+                    "/peering/deploy",
+                    &config,
+                    asn,
+                    Some(body),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(live(asn), (true, true));
+
+            // The default keeps deploy bringing the peering up
+            let (config, _root) = test_config_with_system();
+            assert!(config.deploy_auto_activate);
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let asn = 4242422227;
+            let response = app.oneshot(deploy_request(&config, asn)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(wireguard::deploy::config_path(
+                &config.system.wireguard_dir,
+                &interface_name(asn)
+            )
+            .exists());
+            assert!(bird::deploy::config_path(&config.system.bird_peers_dir, asn).exists());
+        }
+
+        #[tokio::test]
+        async fn test_activate_is_idempotent() {
+            let (config, _root, log) = logging_wg_quick(true);
+            let asn = 4242422225;
+            write_verified_config(&config, asn);
+
+            let app = Router::new()
+                .route("/peering/activate", post(activate_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            for expected in [ActionStatus::Activated, ActionStatus::AlreadyActive] {
+                let response = app
+                    .clone()
+                    .oneshot(authed_request(
+                        "POST",
+                        "/peering/activate",
+                        &config,
+                        asn,
+                        None,
+                    ))
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let response: UpdateResponse = serde_json::from_slice(&body).unwrap();
+                assert_eq!(response.status, expected);
+            }
+
+            // An interface running without our config is restarted once, then left alone
+            let iface = interface_name(asn);
+            assert_eq!(
+                std::fs::read_to_string(&log).unwrap(),
+                format!("down {}\nup {}\n", iface, iface)
+            );
+            assert!(bird::deploy::config_path(&config.system.bird_peers_dir, asn).exists());
+        }
+
+        #[tokio::test]
+        async fn test_concurrent_activate_deactivate_serialize() {
+            let (config, root) = test_config_with_system();
+            let asn = 4242422225;
+            write_verified_config(&config, asn);
+            // A slow wg-quick, so overlapping requests would interleave in the log
+            let log = root.path().join("wg-quick.log");
+            let mut system = config.system.clone();
+            system.wg_quick_bin = crate::api::test_helpers::write_stub(
+                &root.path().join("bin"),
+                "wg-quick-slow",
+                &format!(
+                    "#!/bin/sh\necho \"start $1\" >> {l}\nsleep 0.1\necho \"end $1\" >> {l}\n",
+                    l = log.display()
+                ),
+            );
+            let config = Arc::new(AppConfig {
+                system,
+                ..(*config).clone()
+            });
+
+            let app = Router::new()
+                .route("/peering/activate", post(activate_peering))
+                .route("/peering/deactivate", post(deactivate_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let requests: Vec<_> = ["/peering/activate", "/peering/deactivate"]
+                .repeat(3)
+                .into_iter()
+                .map(|uri| {
+                    tokio::spawn(
+                        app.clone()
+                            .oneshot(authed_request("POST", uri, &config, asn, None)),
+                    )
+                })
+                .collect();
+            for request in requests {
+                assert_eq!(request.await.unwrap().unwrap().status(), StatusCode::OK);
+            }
+
+            // Every wg-quick call finished before the next one started
+            let log = std::fs::read_to_string(&log).unwrap();
+            let lines: Vec<&str> = log.lines().collect();
+            assert_eq!(lines.len(), 12, "{}", log);
+            for pair in lines.chunks(2) {
+                assert_eq!(pair[0].replace("start", "end"), pair[1], "{}", log);
+            }
+
+            // WireGuard and BIRD agree on the outcome of whichever request ran last
+            let up = lines.last() == Some(&"end up");
+            let wg_path =
+                wireguard::deploy::config_path(&config.system.wireguard_dir, &interface_name(asn));
+            assert_eq!(wg_path.exists(), up);
+            assert_eq!(
+                bird::deploy::config_path(&config.system.bird_peers_dir, asn).exists(),
+                up
+            );
+        }
+
+        #[tokio::test]
+        async fn test_activate_already_active() {
+            let (config, _root, log) = logging_wg_quick(true);
+            let asn = 4242422225;
+            let wg_config = write_verified_config(&config, asn);
+            // The stored config is what wg-quick is running
+            std::fs::write(
+                wireguard::deploy::config_path(&config.system.wireguard_dir, &interface_name(asn)),
+                wg_config.as_string().unwrap(),
+            )
+            .unwrap();
+
+            let app = Router::new()
+                .route("/peering/activate", post(activate_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let response = app
+                .oneshot(authed_request(
+                    "POST",
+                    "/peering/activate",
+                    &config,
+                    asn,
+                    None,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let response: UpdateResponse = serde_json::from_slice(&body).unwrap();
+            assert_eq!(response.status, ActionStatus::AlreadyActive);
+
+            // wg-quick isn't touched, but the BGP session is re-applied
+            assert!(!log.exists());
+            assert!(bird::deploy::config_path(&config.system.bird_peers_dir, asn).exists());
+        }
+
+        #[tokio::test]
+        async fn test_delete_removes_pending_challenge() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422229;
+            write_verified_config(&config, asn);
+            let challenge_path =
+                format!("{}/{}.conf", config.data_pending_dir, interface_name(asn));
+            std::fs::write(&challenge_path, "AUTOPEER-4242422229-deadbeef").unwrap();
+
+            let app = Router::new()
+                .route("/peering", axum::routing::delete(delete_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let response = app
+                .oneshot(authed_request("DELETE", "/peering", &config, asn, None))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            assert!(!verified_config_path(&config, asn).exists());
+            assert!(!std::path::Path::new(&challenge_path).exists());
+            assert!(!std::path::Path::new(&config.data_deleted_dir).exists());
+        }
+
+        #[tokio::test]
+        async fn test_delete_with_tombstones_moves_config() {
+            let (config, _root) = test_config_with_system();
+            let config = Arc::new(AppConfig {
+                tombstone_deletes: true,
+                ..(*config).clone()
+            });
+            let asn = 4242422229;
+            write_verified_config(&config, asn);
+            let original = std::fs::read_to_string(verified_config_path(&config, asn)).unwrap();
+
+            let app = Router::new()
+                .route("/peering", axum::routing::delete(delete_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let response = app
+                .oneshot(authed_request("DELETE", "/peering", &config, asn, None))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            assert!(!verified_config_path(&config, asn).exists());
+            let tombstones =
+                tombstone::list(std::path::Path::new(&config.data_deleted_dir)).unwrap();
+            assert_eq!(tombstones.len(), 1);
+            assert_eq!(tombstones[0].asn, asn);
+            assert!(tombstones[0].deleted_at > 0);
+            // Kept without the private key
+            let kept = std::fs::read_to_string(&tombstones[0].path).unwrap();
+            assert!(!kept.contains("PrivateKey"));
+            let without_key: String = original
+                .lines()
+                .filter(|line| !line.starts_with("PrivateKey"))
+                .map(|line| format!("{}\n", line))
+                .collect();
+            assert_eq!(kept, without_key);
+        }
+
+        #[tokio::test]
+        async fn test_verify_discards_challenge_after_failed_attempts() {
+            let (config, _pending_dir, _verified_dir) = test_config_with_temp_dirs();
+            let asn = 4242420257;
+            let challenge =
+                Challenge::generate(asn, &config.challenge_prefix, config.challenge_random_bytes);
+            let challenge_path =
+                format!("{}/{}.conf", config.data_pending_dir, interface_name(asn));
+            std::fs::write(&challenge_path, &challenge.code).unwrap();
+
+            let app = Router::new()
+                .route("/peering/verify", post(verify_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config);
+            let verify = || {
+                let body = serde_json::to_string(&VerifyRequest {
+                    asn,
+                    challenge_id: None,
+                    signed_challenge: "not a signature".to_string(),
+                    public_key: crate::api::test_helpers::PEER_PGP_PUBLIC_KEY.to_string(),
+                })
+                .unwrap();
+                Request::builder()
+                    .method("POST")
+                    .uri("/peering/verify")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap()
+            };
+
+            for _ in 1..crate::api::attempts::MAX_VERIFY_ATTEMPTS {
+                let response = app.clone().oneshot(verify()).await.unwrap();
+                assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+                assert!(std::path::Path::new(&challenge_path).exists());
+            }
+
+            // The last allowed failure discards the challenge
+            let response = app.clone().oneshot(verify()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert!(!std::path::Path::new(&challenge_path).exists());
+
+            let response = app.oneshot(verify()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+
+        #[tokio::test]
+        async fn test_contact_carried_from_init_to_deployed_config() {
+            let (config, root) = test_config_with_system();
+            let asn = 4242422240;
+            let signer = crate::api::test_helpers::TestSigner::generate();
+            let mut config = (*config).clone();
+            config.registry.path = signer.write_registry(root.path(), asn);
+            let config = Arc::new(config);
+
+            let app = Router::new()
+                .route("/peering/init", post(init_peering))
+                .route("/peering/verify", post(verify_peering))
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let json = |uri: &str, body: String| {
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap()
+            };
+
+            // Malformed contacts are refused up front
+            let bad = serde_json::to_string(&InitRequest {
+                asn,
+                contact: Some("not a contact".to_string()),
+                local_asn: None,
+            })
+            .unwrap();
+            let response = app
+                .clone()
+                .oneshot(json("/peering/init", bad))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            let init = serde_json::to_string(&InitRequest {
+                asn,
+                contact: Some("noc@example.com".to_string()),
+                local_asn: None,
+            })
+            .unwrap();
+            let response = app
+                .clone()
+                .oneshot(json("/peering/init", init))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let init: InitResponse = serde_json::from_slice(&body).unwrap();
+
+            let verify = serde_json::to_string(&VerifyRequest {
+                asn,
+                challenge_id: None,
+                signed_challenge: signer.sign(&init.challenge),
+                public_key: signer.public_key.clone(),
+            })
+            .unwrap();
+            let response = app
+                .clone()
+                .oneshot(json("/peering/verify", verify))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let verified: VerifyResponse = serde_json::from_slice(&body).unwrap();
+
+            let deploy = serde_json::to_string(&DeployRequest {
+                wg_public_key: crate::api::test_helpers::PEER_PUBLIC_KEY.to_string(),
+                endpoint: Some("192.0.2.1:51820".to_string()),
+                max_prefixes: None,
+                address_family: AddressFamily::Dual,
+                peer_ipv4: None,
+                listen_port: None,
+                router_id: None,
+                peer_name: None,
+                graceful_restart: None,
+                async_provisioning: false,
+                preshared_key: None,
+                generate_preshared_key: false,
+                dynamic_endpoint: false,
+                deploy_and_activate: false,
+                local_asn: None,
+            })
+            .unwrap();
+            let mut request = json("/peering/deploy", deploy);
+            request.headers_mut().insert(
+                header::COOKIE,
+                format!("autopeer_token={}", verified.token)
+                    .parse()
+                    .unwrap(),
+            );
+            let response = app.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let stored = WgConfig::from_file(verified_config_path(&config, asn)).unwrap();
+            assert_eq!(
+                stored.meta.unwrap().contact.as_deref(),
+                Some("noc@example.com")
+            );
+            // Nothing is left behind in the pending dir
+            assert_eq!(
+                std::fs::read_dir(&config.data_pending_dir).unwrap().count(),
+                0
+            );
+        }
+
+        #[tokio::test]
+        async fn test_verify_redirects_browsers() {
+            let (config, root) = test_config_with_system();
+            let asn = 4242422243;
+            let signer = crate::api::test_helpers::TestSigner::generate();
+            let mut config = (*config).clone();
+            config.registry.path = signer.write_registry(root.path(), asn);
+            config.post_verify_redirect = Some("https://peering.example.com/dashboard".to_string());
+            let config = Arc::new(config);
+
+            let app = Router::new()
+                .route("/peering/init", post(init_peering))
+                .route("/peering/verify", post(verify_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config);
+            let verify = |uri: &'static str, accept: &'static str| {
+                let app = app.clone();
+                let signer = &signer;
+                async move {
+                    let init = serde_json::to_string(&InitRequest {
+                        asn,
+                        contact: None,
+                        local_asn: None,
+                    })
+                    .unwrap();
+                    let request = Request::builder()
+                        .method("POST")
+                        .uri("/peering/init")
+                        .header("content-type", "application/json")
+                        .body(Body::from(init))
+                        .unwrap();
+                    let response = app.clone().oneshot(request).await.unwrap();
+                    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                        .await
+                        .unwrap();
+                    let init: InitResponse = serde_json::from_slice(&body).unwrap();
+
+                    let body = serde_json::to_string(&VerifyRequest {
+                        asn,
+                        challenge_id: Some(init.challenge_id),
+                        signed_challenge: signer.sign(&init.challenge),
+                        public_key: signer.public_key.clone(),
+                    })
+                    .unwrap();
+                    let request = Request::builder()
+                        .method("POST")
+                        .uri(uri)
+                        .header("content-type", "application/json")
+                        .header(header::ACCEPT, accept)
+                        .body(Body::from(body))
+                        .unwrap();
+                    app.oneshot(request).await.unwrap()
+                }
+            };
+
+            // API clients keep getting JSON
+            for accept in [
+                "application/json",
+                "*/*",
+                "text/html;q=0.5, application/json",
+            ] {
+                let response = verify("/peering/verify", accept).await;
+                assert_eq!(response.status(), StatusCode::OK, "{}", accept);
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let verified: VerifyResponse = serde_json::from_slice(&body).unwrap();
+                assert!(!verified.token.is_empty());
+            }
+
+            // Browsers get the cookie and a redirect
+            let browser = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+            for (uri, accept) in [
+                ("/peering/verify", browser),
+                ("/peering/verify?redirect=1", "application/json"),
+            ] {
+                let response = verify(uri, accept).await;
+                assert_eq!(
+                    response.status(),
+                    StatusCode::SEE_OTHER,
+                    "{} {}",
+                    uri,
+                    accept
+                );
+                assert_eq!(
+                    response.headers()[header::LOCATION],
+                    "https://peering.example.com/dashboard"
+                );
+                let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+                assert!(cookie.starts_with("autopeer_token="), "{}", cookie);
+            }
+        }
+
+        /// App and signer for a stateless-challenge flow; the pending dir is never created
+        fn stateless_app(
+            asn: u32,
+        ) -> (
+            Router,
+            Arc<AppConfig>,
+            crate::api::test_helpers::TestSigner,
+            tempfile::TempDir,
+        ) {
+            let (config, root) = test_config_with_system();
+            let signer = crate::api::test_helpers::TestSigner::generate();
+            let mut config = (*config).clone();
+            config.registry.path = signer.write_registry(root.path(), asn);
+            config.stateless_challenges = true;
+            config.data_pending_dir = root.path().join("no-pending").to_string_lossy().to_string();
+            let config = Arc::new(config);
+
+            let app = Router::new()
+                .route("/peering/init", post(init_peering))
+                .route("/peering/verify", post(verify_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            (app, config, signer, root)
+        }
+
+        fn verify_request(asn: u32, signed_challenge: String, public_key: &str) -> Request<Body> {
+            let body = serde_json::to_string(&VerifyRequest {
+                asn,
+                challenge_id: None,
+                signed_challenge,
+                public_key: public_key.to_string(),
+            })
+            .unwrap();
+            Request::builder()
+                .method("POST")
+                .uri("/peering/verify")
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        }
+
+        #[tokio::test]
+        async fn test_stateless_challenge_flow() {
+            let asn = 4242422242;
+            let (app, config, signer, _root) = stateless_app(asn);
+
+            let init = serde_json::to_string(&InitRequest {
+                asn,
+                contact: None,
+                local_asn: None,
+            })
+            .unwrap();
+            let request = Request::builder()
+                .method("POST")
+                .uri("/peering/init")
+                .header("content-type", "application/json")
+                .body(Body::from(init))
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let init: InitResponse = serde_json::from_slice(&body).unwrap();
+            assert!(!std::path::Path::new(&config.data_pending_dir).exists());
+
+            let response = app
+                .oneshot(verify_request(
+                    asn,
+                    signer.sign(&init.challenge),
+                    &signer.public_key,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let verified: VerifyResponse = serde_json::from_slice(&body).unwrap();
+            let claims = crate::jwt::decode_claims(
+                &verified.token,
+                &config.jwt_secret,
+                &JwtScope::default(),
+            )
+            .unwrap();
+            assert_eq!(claims.asn, asn);
+            assert_eq!(claims.challenge.as_deref(), Some(init.challenge.as_str()));
+        }
+
+        #[tokio::test]
+        async fn test_stateless_init_rejects_contact() {
+            let asn = 4242422242;
+            let (app, config, _signer, _root) = stateless_app(asn);
+
+            let init = serde_json::to_string(&InitRequest {
+                asn,
+                contact: Some("noc@example.com".to_string()),
+                local_asn: None,
+            })
+            .unwrap();
+            let request = Request::builder()
+                .method("POST")
+                .uri("/peering/init")
+                .header("content-type", "application/json")
+                .body(Body::from(init))
+                .unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert!(String::from_utf8_lossy(&body).contains("contact"));
+            assert!(!std::path::Path::new(&config.data_pending_dir).exists());
+        }
+
+        #[tokio::test]
+        async fn test_verify_tech_contact_key() {
+            let asn = 4242422244;
+            let (_, config, maintainer, _root) = stateless_app(asn);
+            // The maintainer's key stays elsewhere; the tech contact signs with their own
+            let tech = crate::api::test_helpers::TestSigner::generate();
+            let data = config.registry.path.join("data");
+            std::fs::create_dir_all(data.join("person")).unwrap();
+            std::fs::write(
+                data.join(format!("aut-num/AS{}", asn)),
+                format!(
+                    "aut-num: AS{}\nas-name: TEST-AS\ntech-c: TECH-DN42\nmnt-by: TEST-MNT\n",
+                    asn
+                ),
+            )
+            .unwrap();
+            std::fs::write(
+                data.join("person/TECH-DN42"),
+                format!(
+                    "person: Tech Contact\nnic-hdl: TECH-DN42\nauth: pgp-fingerprint {}\n",
+                    tech.fingerprint
+                ),
+            )
+            .unwrap();
+
+            let verify = |config: Arc<AppConfig>, signer: &crate::api::test_helpers::TestSigner| {
+                let now = chrono::Utc::now().timestamp();
+                let challenge = stateless::issue(
+                    asn,
+                    &config.challenge_prefix,
+                    "0123456789abcdef",
+                    now + 600,
+                    &config.jwt_secret,
+                );
+                let request = verify_request(asn, signer.sign(&challenge.code), &signer.public_key);
+                let app = Router::new()
+                    .route("/peering/verify", post(verify_peering))
+                    .layer(CookieManagerLayer::new())
+                    .with_state(config);
+                async move { app.oneshot(request).await.unwrap() }
+            };
+
+            let response = verify(config.clone(), &tech).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+            let config = Arc::new(AppConfig {
+                allow_tech_contact_auth: true,
+                ..(*config).clone()
+            });
+            let response = verify(config.clone(), &tech).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let verified: VerifyResponse = serde_json::from_slice(&body).unwrap();
+            let claims = crate::jwt::decode_claims(
+                &verified.token,
+                &config.jwt_secret,
+                &JwtScope::default(),
+            )
+            .unwrap();
+            assert_eq!(
+                claims.fingerprint.as_deref(),
+                Some(tech.fingerprint.as_str())
+            );
+
+            // The maintainer's key keeps working, and unrelated keys still don't
+            let response = verify(config.clone(), &maintainer).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let other = crate::api::test_helpers::TestSigner::generate();
+            let response = verify(config, &other).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        #[tokio::test]
+        async fn test_stateless_challenge_expired_or_tampered() {
+            let asn = 4242422243;
+            let (app, config, signer, _root) = stateless_app(asn);
+            let now = chrono::Utc::now().timestamp();
+
+            let expired = stateless::issue(
+                asn,
+                &config.challenge_prefix,
+                "0123456789abcdef",
+                now - 1,
+                &config.jwt_secret,
+            );
+            let response = app
+                .clone()
+                .oneshot(verify_request(
+                    asn,
+                    signer.sign(&expired.code),
+                    &signer.public_key,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert!(String::from_utf8_lossy(&body).contains("expired"));
+
+            // Pushing the expiry out breaks the MAC, even with a valid signature over the result
+            let tampered = expired
+                .code
+                .replace(&format!("-{}-", now - 1), &format!("-{}-", now + 3600));
+            assert_ne!(tampered, expired.code);
+            let response = app
+                .oneshot(verify_request(
+                    asn,
+                    signer.sign(&tampered),
+                    &signer.public_key,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert!(String::from_utf8_lossy(&body).contains("altered"));
+        }
+
+        #[tokio::test]
+        async fn test_verify_minisign_signature() {
+            let (config, root) = test_config_with_system();
+            let asn = 4242422242;
+            let signer = crate::api::test_helpers::MinisignSigner::new(7);
+            let mut config = (*config).clone();
+            config.registry.path = signer.write_registry(root.path(), asn);
+            let config = Arc::new(config);
+
+            let app = Router::new()
+                .route("/peering/init", post(init_peering))
+                .route("/peering/verify", post(verify_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let json = |uri: &str, body: String| {
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap()
+            };
+
+            let body = serde_json::to_string(&InitRequest {
+                asn,
+                contact: None,
+                local_asn: None,
+            })
+            .unwrap();
+            let response = app
+                .clone()
+                .oneshot(json("/peering/init", body))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let init: InitResponse = serde_json::from_slice(&body).unwrap();
+            assert_eq!(init.minisign_keys, vec![signer.public_key()]);
+            assert!(init.pgp_fingerprint.is_empty());
+
+            // No PGP key needed; the signature is checked against the registry's minisign key
+            let verify = |signed_challenge: String| {
+                let body = serde_json::json!({
+                    "asn": asn,
+                    "challenge_id": init.challenge_id,
+                    "signed_challenge": signed_challenge,
+                });
+                json("/peering/verify", body.to_string())
+            };
+            let response = app
+                .clone()
+                .oneshot(verify(signer.sign("AUTOPEER-4242422242-0000", true)))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+            let response = app
+                .oneshot(verify(signer.sign(&init.challenge, true)))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let verified: VerifyResponse = serde_json::from_slice(&body).unwrap();
+            assert!(!verified.token.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_concurrent_challenges_verify_independently() {
+            let (config, root) = test_config_with_system();
+            let asn = 4242422241;
+            let signer = crate::api::test_helpers::TestSigner::generate();
+            let mut config = (*config).clone();
+            config.registry.path = signer.write_registry(root.path(), asn);
+            let config = Arc::new(config);
+
+            let app = Router::new()
+                .route("/peering/init", post(init_peering))
+                .route("/peering/verify", post(verify_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let json = |uri: &str, body: String| {
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap()
+            };
+            let init = || async {
+                let body = serde_json::to_string(&InitRequest {
+                    asn,
+                    contact: None,
+                    local_asn: None,
+                })
+                .unwrap();
+                let response = app
+                    .clone()
+                    .oneshot(json("/peering/init", body))
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                serde_json::from_slice::<InitResponse>(&body).unwrap()
+            };
+            let verify = |challenge_id: Option<&str>, challenge: &str| {
+                let body = serde_json::to_string(&VerifyRequest {
+                    asn,
+                    challenge_id: challenge_id.map(str::to_string),
+                    signed_challenge: signer.sign(challenge),
+                    public_key: signer.public_key.clone(),
+                })
+                .unwrap();
+                json("/peering/verify", body)
+            };
+
+            // Two clients start a flow for the same ASN
+            let first = init().await;
+            let second = init().await;
+            assert_ne!(first.challenge_id, second.challenge_id);
+
+            // Without an id it's ambiguous which one is meant
+            let response = app
+                .clone()
+                .oneshot(verify(None, &first.challenge))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            // A signature only counts for its own challenge
+            let response = app
+                .clone()
+                .oneshot(verify(Some(&first.challenge_id), &second.challenge))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+            let response = app
+                .clone()
+                .oneshot(verify(Some(&first.challenge_id), &first.challenge))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            // The second flow is unaffected by the first one finishing
+            let response = app
+                .clone()
+                .oneshot(verify(Some(&second.challenge_id), &second.challenge))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            // Both challenges are used up
+            let response = app
+                .oneshot(verify(Some(&first.challenge_id), &first.challenge))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+
+        #[tokio::test]
+        async fn test_verify_after_challenge_format_change() {
+            let (config, _pending_dir, _verified_dir) = test_config_with_temp_dirs();
+            let asn = 4242420257;
+
+            // Challenge issued under the old prefix, then the prefix changes before verify
+            let challenge =
+                Challenge::generate(asn, &config.challenge_prefix, config.challenge_random_bytes);
+            let challenge_path =
+                format!("{}/{}.conf", config.data_pending_dir, interface_name(asn));
+            std::fs::write(&challenge_path, &challenge.code).unwrap();
+            let config = Arc::new(AppConfig {
+                challenge_prefix: "MY-DN42".to_string(),
+                ..(*config).clone()
+            });
+
+            let app = Router::new()
+                .route("/peering/verify", post(verify_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config);
+            let body = serde_json::to_string(&VerifyRequest {
+                asn,
+                challenge_id: None,
+                signed_challenge: challenge.code.clone(),
+                public_key: crate::api::test_helpers::PEER_PGP_PUBLIC_KEY.to_string(),
+            })
+            .unwrap();
+            let request = Request::builder()
+                .method("POST")
+                .uri("/peering/verify")
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap();
+
+            let response = app.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let message = String::from_utf8(body.to_vec()).unwrap();
+            assert!(
+                message.contains("format changed"),
+                "unexpected error: {}",
+                message
+            );
+        }
+
+        #[tokio::test]
+        async fn test_deploy_strict_allowed_ips() {
+            let (config, root) = test_config_with_system();
+            let registry = root.path().join("registry");
+            std::fs::create_dir_all(registry.join("data/route")).unwrap();
+            std::fs::create_dir_all(registry.join("data/route6")).unwrap();
+            std::fs::write(
+                registry.join("data/route/172.20.0.0_27"),
+                "route: 172.20.0.0/27\norigin: AS4242422227\n",
+            )
+            .unwrap();
+            std::fs::write(
+                registry.join("data/route6/fd00:2227::_48"),
+                "route6: fd00:2227::/48\norigin: AS4242422227\n",
+            )
+            .unwrap();
+
+            let mut config = (*config).clone();
+            config.registry.path = registry;
+            config.strict_allowed_ips = true;
+            let config = Arc::new(config);
+
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            // Peer with registered routes gets exactly those
+            let response = app
+                .clone()
+                .oneshot(deploy_request(&config, 4242422227))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let stored = WgConfig::from_file(verified_config_path(&config, 4242422227)).unwrap();
+            assert_eq!(
+                stored.peer().unwrap().allowed_ips,
+                vec!["172.20.0.0/27", "fd00:2227::/48"]
+            );
+
+            // Peer without routes falls back to the wildcard
+            let response = app
+                .oneshot(deploy_request(&config, 4242422228))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let stored = WgConfig::from_file(verified_config_path(&config, 4242422228)).unwrap();
+            assert_eq!(
+                stored.peer().unwrap().allowed_ips,
+                vec!["0.0.0.0/0", "::/0"]
+            );
+        }
+
+        #[tokio::test]
+        async fn test_deploy_ipv4_only() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422230;
+            let ipv4_request = |config: &AppConfig| {
+                let body = serde_json::to_string(&DeployRequest {
+                    wg_public_key: crate::api::test_helpers::PEER_PUBLIC_KEY.to_string(),
+                    endpoint: Some("192.0.2.1:51820".to_string()),
+                    max_prefixes: None,
+                    address_family: AddressFamily::Ipv4,
+                    peer_ipv4: Some("172.22.108.1".parse().unwrap()),
+                    listen_port: None,
+                    router_id: None,
+                    peer_name: None,
+                    graceful_restart: None,
+                    async_provisioning: false,
+                    preshared_key: None,
+                    generate_preshared_key: false,
+                    dynamic_endpoint: false,
+                    deploy_and_activate: false,
+                    local_asn: None,
+                })
+                .unwrap();
+                authed_request("POST", "/peering/deploy", config, asn, Some(body))
+            };
+
+            // No IPv4 transfer allocation configured
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let response = app.oneshot(ipv4_request(&config)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            let config = Arc::new(AppConfig {
+                ipv4_transfer_address: Some("172.20.53.97".parse().unwrap()),
+                ..(*config).clone()
+            });
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let response = app.oneshot(ipv4_request(&config)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let stored = WgConfig::from_file(verified_config_path(&config, asn)).unwrap();
+            let bgp = stored.bgp.clone().unwrap();
+            assert_eq!(bgp.address_family, AddressFamily::Ipv4);
+            assert_eq!(bgp.neighbor, "172.22.108.1");
+            assert_eq!(stored.peer().unwrap().allowed_ips, vec!["0.0.0.0/0"]);
+            assert!(stored
+                .interface
+                .address
+                .contains(&"172.20.53.97/32".to_string()));
+
+            let bird_conf = std::fs::read_to_string(bird::deploy::config_path(
+                &config.system.bird_peers_dir,
+                asn,
+            ))
+            .unwrap();
+            assert!(bird_conf.contains("neighbor 172.22.108.1 as 4242422230"));
+            assert!(!bird_conf.contains("ipv6 {"));
+        }
+
+        #[tokio::test]
+        async fn test_deploy_preshared_key() {
+            let (config, _root) = test_config_with_system();
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let deploy = |asn: u32, extra: serde_json::Value| {
+                let mut body = serde_json::json!({
+                    "wg_public_key": crate::api::test_helpers::PEER_PUBLIC_KEY,
+                    "endpoint": "192.0.2.1:51820",
+                });
+                body.as_object_mut()
+                    .unwrap()
+                    .extend(extra.as_object().unwrap().clone());
+                let request = authed_request(
+                    "POST",
+                    "/peering/deploy",
+                    &config,
+                    asn,
+                    Some(body.to_string()),
+                );
+                let app = app.clone();
+                async move { app.oneshot(request).await.unwrap() }
+            };
+
+            // Generated by us and handed back once
+            let response = deploy(
+                4242422232,
+                serde_json::json!({ "generate_preshared_key": true }),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let deployed: DeployResponse = serde_json::from_slice(&body).unwrap();
+            let generated = deployed.preshared_key.unwrap();
+            let stored = WgConfig::from_file(verified_config_path(&config, 4242422232)).unwrap();
+            assert_eq!(
+                stored
+                    .peer()
+                    .unwrap()
+                    .preshared_key
+                    .as_ref()
+                    .unwrap()
+                    .expose(),
+                generated
+            );
+            let live =
+                std::fs::read_to_string(config.system.wireguard_dir.join("wg-as4242422232.conf"))
+                    .unwrap();
+            assert!(live.contains(&format!("PresharedKey = {}\n", generated)));
+
+            // Brought by the peer, so nothing to send back
+            let key = "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=";
+            let response = deploy(4242422233, serde_json::json!({ "preshared_key": key })).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert!(serde_json::from_slice::<DeployResponse>(&body)
+                .unwrap()
+                .preshared_key
+                .is_none());
+            let stored = WgConfig::from_file(verified_config_path(&config, 4242422233)).unwrap();
+            assert_eq!(
+                stored
+                    .peer()
+                    .unwrap()
+                    .preshared_key
+                    .as_ref()
+                    .unwrap()
+                    .expose(),
+                key
+            );
+
+            let response = deploy(
+                4242422234,
+                serde_json::json!({ "preshared_key": "tooshort" }),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let response = deploy(
+                4242422234,
+                serde_json::json!({ "preshared_key": key, "generate_preshared_key": true }),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            assert!(!verified_config_path(&config, 4242422234).exists());
+        }
+
+        #[tokio::test]
+        async fn test_deploy_dynamic_endpoint() {
+            let (config, _root) = test_config_with_system();
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let deploy = |asn: u32, body: serde_json::Value| {
+                let request = authed_request(
+                    "POST",
+                    "/peering/deploy",
+                    &config,
+                    asn,
+                    Some(body.to_string()),
+                );
+                let app = app.clone();
+                async move { app.oneshot(request).await.unwrap() }
+            };
+            let key = crate::api::test_helpers::PEER_PUBLIC_KEY;
+
+            let response = deploy(
+                4242422235,
+                serde_json::json!({ "wg_public_key": key, "dynamic_endpoint": true }),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let deployed: DeployResponse = serde_json::from_slice(&body).unwrap();
+            assert!(deployed.deployment.dynamic_endpoint);
+            let live =
+                std::fs::read_to_string(config.system.wireguard_dir.join("wg-as4242422235.conf"))
+                    .unwrap();
+            assert!(!live.contains("Endpoint"), "{}", live);
+            assert!(live.contains("PersistentKeepalive = 25\n"));
+
+            // The other checks still apply
+            let response = deploy(
+                4242422236,
+                serde_json::json!({ "wg_public_key": "notakey", "dynamic_endpoint": true }),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let response = deploy(
+                4242422236,
+                serde_json::json!({ "wg_public_key": key, "dynamic_endpoint": true, "endpoint": "192.0.2.1:51820" }),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let response = deploy(4242422236, serde_json::json!({ "wg_public_key": key })).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            assert!(!verified_config_path(&config, 4242422236).exists());
+
+            // A pinned endpoint isn't dynamic
+            let response = deploy(
+                4242422236,
+                serde_json::json!({ "wg_public_key": key, "endpoint": "192.0.2.1:51820" }),
+            )
+            .await;
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
                 .unwrap();
+            assert!(
+                !serde_json::from_slice::<DeployResponse>(&body)
+                    .unwrap()
+                    .deployment
+                    .dynamic_endpoint
+            );
+        }
 
-            let response1 = app1.oneshot(request1).await.unwrap();
-            let body1 = axum::body::to_bytes(response1.into_body(), usize::MAX).await.unwrap();
-            let resp1: InitResponse = serde_json::from_slice(&body1).unwrap();
+        #[tokio::test]
+        async fn test_deploy_under_different_local_asns() {
+            let asn = 4242422225;
+            let mut deployments = Vec::new();
+            for local_asn in [None, Some(4242420263)] {
+                let (config, _root) = test_config_with_system();
+                let config = Arc::new(AppConfig {
+                    my_asns: vec![4242420257, 4242420263],
+                    ..(*config).clone()
+                });
+                let app = Router::new()
+                    .route("/peering/deploy", post(deploy_peering))
+                    .layer(CookieManagerLayer::new())
+                    .with_state(config.clone());
+                let body = serde_json::json!({
+                    "wg_public_key": crate::api::test_helpers::PEER_PUBLIC_KEY,
+                    "endpoint": "192.0.2.1:51820",
+                    "local_asn": local_asn,
+                });
+                let response = app
+                    .oneshot(authed_request(
+                        "POST",
+                        "/peering/deploy",
+                        &config,
+                        asn,
+                        Some(body.to_string()),
+                    ))
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let deployment = serde_json::from_slice::<DeployResponse>(&body)
+                    .unwrap()
+                    .deployment;
+
+                let local_asn = local_asn.unwrap_or(config.my_asn);
+                assert_eq!(deployment.bgp_local_as, local_asn);
+                let bird_config = std::fs::read_to_string(bird::deploy::config_path(
+                    &config.system.bird_peers_dir,
+                    asn,
+                ))
+                .unwrap();
+                assert!(
+                    bird_config.contains(&format!(" as {};", local_asn)),
+                    "{}",
+                    bird_config
+                );
+                // Stored, so later re-deploys stay on the same local ASN
+                let stored = WgConfig::from_file(verified_config_path(&config, asn)).unwrap();
+                assert_eq!(stored.bgp.unwrap().local_as, Some(local_asn));
+                deployments.push(deployment);
+            }
+
+            assert_ne!(
+                deployments[0].interface_address,
+                deployments[1].interface_address
+            );
+            assert_ne!(deployments[0].bgp_neighbor, deployments[1].bgp_neighbor);
+            assert_eq!(
+                deployments[1].interface_address,
+                Ipv6LinkLocal::from_asns(4242420263, asn).local_addr()
+            );
+        }
 
-            // Create second peering with same ASN
-            let app2 = Router::new()
-                .route("/peering/init", post(init_peering))
+        #[tokio::test]
+        async fn test_deploy_rejects_unknown_local_asn() {
+            let (config, _root) = test_config_with_system();
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let body = serde_json::json!({
+                "wg_public_key": crate::api::test_helpers::PEER_PUBLIC_KEY,
+                "endpoint": "192.0.2.1:51820",
+                "local_asn": 4242420263u32,
+            });
+            let response = app
+                .oneshot(authed_request(
+                    "POST",
+                    "/peering/deploy",
+                    &config,
+                    4242422225,
+                    Some(body.to_string()),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            assert!(!verified_config_path(&config, 4242422225).exists());
+        }
+
+        fn port_request(config: &AppConfig, asn: u32, listen_port: u16) -> Request<Body> {
+            let body = serde_json::to_string(&DeployRequest {
+                wg_public_key: crate::api::test_helpers::PEER_PUBLIC_KEY.to_string(),
+                endpoint: Some("192.0.2.1:51820".to_string()),
+                max_prefixes: None,
+                address_family: AddressFamily::Dual,
+                peer_ipv4: None,
+                listen_port: Some(listen_port),
+                router_id: None,
+                peer_name: None,
+                graceful_restart: None,
+                async_provisioning: false,
+                preshared_key: None,
+                generate_preshared_key: false,
+                dynamic_endpoint: false,
+                deploy_and_activate: false,
+                local_asn: None,
+            })
+            .unwrap();
+            authed_request("POST", "/peering/deploy", config, asn, Some(body))
+        }
+
+        #[tokio::test]
+        async fn test_deploy_custom_listen_port() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422231;
+
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let response = app
+                .oneshot(port_request(&config, asn, 35000))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let deployed: DeployResponse = serde_json::from_slice(&body).unwrap();
+            assert_eq!(deployed.deployment.listen_port, 35000);
+            assert!(deployed.deployment.our_endpoint.ends_with(":35000"));
+
+            let stored = WgConfig::from_file(verified_config_path(&config, asn)).unwrap();
+            assert_eq!(stored.interface.listen_port, 35000);
+        }
+
+        #[tokio::test]
+        async fn test_deploy_listen_port_conflict() {
+            let (config, _root) = test_config_with_system();
+            let existing = write_verified_config(&config, 4242422232);
+            let taken = existing.interface.listen_port;
+
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let response = app
+                .clone()
+                .oneshot(port_request(&config, 4242422233, taken))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::CONFLICT);
+
+            // Outside the configured range
+            let response = app
+                .oneshot(port_request(&config, 4242422233, 51820))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+
+        #[tokio::test]
+        async fn test_deploy_duplicate_endpoint() {
+            let (config, _root) = test_config_with_system();
+            let mut existing = write_verified_config(&config, 4242422232);
+            existing.peer_mut().unwrap().endpoint = Some("[2001:db8::1]:51820".to_string());
+            existing
+                .to_file(verified_config_path(&config, 4242422232))
+                .unwrap();
+
+            let request = |config: &AppConfig| {
+                let body = serde_json::to_string(&DeployRequest {
+                    wg_public_key: crate::api::test_helpers::PEER_PUBLIC_KEY.to_string(),
+                    // The same address, spelled differently
+                    endpoint: Some("[2001:DB8:0:0::1]:51820".to_string()),
+                    max_prefixes: None,
+                    address_family: AddressFamily::Dual,
+                    peer_ipv4: None,
+                    listen_port: None,
+                    router_id: None,
+                    peer_name: None,
+                    graceful_restart: None,
+                    async_provisioning: false,
+                    preshared_key: None,
+                    generate_preshared_key: false,
+                    dynamic_endpoint: false,
+                    deploy_and_activate: false,
+                    local_asn: None,
+                })
+                .unwrap();
+                authed_request("POST", "/peering/deploy", config, 4242422233, Some(body))
+            };
+
+            let strict = Arc::new(AppConfig {
+                reject_duplicate_endpoints: true,
+                ..(*config).clone()
+            });
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(strict.clone());
+            let response = app.oneshot(request(&strict)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::CONFLICT);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert!(String::from_utf8_lossy(&body)
+                .contains("already used by the peering on wg-as4242422232"));
+            assert!(!verified_config_path(&config, 4242422233).exists());
+
+            // Only a warning by default
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let response = app.oneshot(request(&config)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        #[tokio::test]
+        async fn test_update_duplicate_endpoint() {
+            let (config, _root) = test_config_with_system();
+            let config = Arc::new(AppConfig {
+                reject_duplicate_endpoints: true,
+                ..(*config).clone()
+            });
+            let mut existing = write_verified_config(&config, 4242422232);
+            existing.peer_mut().unwrap().endpoint = Some("192.0.2.7:51820".to_string());
+            existing
+                .to_file(verified_config_path(&config, 4242422232))
+                .unwrap();
+            write_verified_config(&config, 4242422233);
+
+            let app = Router::new()
+                .route("/peering/update", axum::routing::patch(update_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let update = |endpoint: &str| {
+                let body = serde_json::to_string(&UpdateRequest {
+                    endpoint: Some(endpoint.to_string()),
+                    max_prefixes: None,
+                    graceful_restart: None,
+                })
+                .unwrap();
+                app.clone().oneshot(authed_request(
+                    "PATCH",
+                    "/peering/update",
+                    &config,
+                    4242422233,
+                    Some(body),
+                ))
+            };
+
+            assert_eq!(
+                update("192.0.2.7:51820").await.unwrap().status(),
+                StatusCode::CONFLICT
+            );
+            let stored = WgConfig::from_file(verified_config_path(&config, 4242422233)).unwrap();
+            assert_eq!(
+                stored.peer().unwrap().endpoint.as_deref(),
+                Some("192.0.2.1:51820")
+            );
+            assert_eq!(
+                update("192.0.2.8:51820").await.unwrap().status(),
+                StatusCode::OK
+            );
+        }
+
+        #[tokio::test]
+        async fn test_deploy_rejects_colliding_asn() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4201270000 + u32::from(crate::ipalloc::asn_short(config.my_asn));
+
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let response = app.oneshot(deploy_request(&config, asn)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            assert!(!verified_config_path(&config, asn).exists());
+        }
+
+        #[tokio::test]
+        async fn test_deploy_rejects_unreachable_endpoint_family() {
+            let (config, _root) = test_config_with_system();
+            let config = Arc::new(AppConfig {
+                public_endpoint_family: AddressFamily::Ipv4,
+                ..(*config).clone()
+            });
+            let asn = 4242422225;
+
+            let app = Router::new()
+                .route("/peering/server-info", get(get_server_info))
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri("/peering/server-info")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let info: ServerInfo = serde_json::from_slice(&body).unwrap();
+            assert_eq!(info.endpoint_families, vec!["ipv4"]);
+
+            let body = serde_json::to_string(&DeployRequest {
+                wg_public_key: crate::api::test_helpers::PEER_PUBLIC_KEY.to_string(),
+                endpoint: Some("[2001:db8::1]:51820".to_string()),
+                max_prefixes: None,
+                address_family: AddressFamily::Dual,
+                peer_ipv4: None,
+                listen_port: None,
+                router_id: None,
+                peer_name: None,
+                graceful_restart: None,
+                async_provisioning: false,
+                preshared_key: None,
+                generate_preshared_key: false,
+                dynamic_endpoint: false,
+                deploy_and_activate: false,
+                local_asn: None,
+            })
+            .unwrap();
+            let response = app
+                .oneshot(authed_request(
+                    "POST",
+                    "/peering/deploy",
+                    &config,
+                    asn,
+                    Some(body),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert!(String::from_utf8_lossy(&body).contains("only reachable over IPv4"));
+            assert!(!verified_config_path(&config, asn).exists());
+        }
+
+        #[tokio::test]
+        async fn test_deploy_peer_name() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422225;
+
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let body = serde_json::to_string(&DeployRequest {
+                wg_public_key: crate::api::test_helpers::PEER_PUBLIC_KEY.to_string(),
+                endpoint: Some("192.0.2.1:51820".to_string()),
+                max_prefixes: None,
+                address_family: AddressFamily::Dual,
+                peer_ipv4: None,
+                listen_port: None,
+                router_id: None,
+                peer_name: Some("fra1\";\n}\nprotocol bgp evil {".to_string()),
+                graceful_restart: None,
+                async_provisioning: false,
+                preshared_key: None,
+                generate_preshared_key: false,
+                dynamic_endpoint: false,
+                deploy_and_activate: false,
+                local_asn: None,
+            })
+            .unwrap();
+            let response = app
+                .oneshot(authed_request(
+                    "POST",
+                    "/peering/deploy",
+                    &config,
+                    asn,
+                    Some(body),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let bird_conf = std::fs::read_to_string(bird::deploy::config_path(
+                &config.system.bird_peers_dir,
+                asn,
+            ))
+            .unwrap();
+            assert!(
+                bird_conf.starts_with("# AutoPeer: fra1protocol bgp evil (AS4242422225)"),
+                "{}",
+                bird_conf
+            );
+            assert!(
+                bird_conf.contains("description \"AutoPeer - fra1protocol bgp evil - AS4242422225")
+            );
+            assert!(!bird_conf.contains("evil {"));
+
+            // Stored so later re-renders keep the name
+            let (stored, _) =
+                load_verified_config(&verified_config_path(&config, asn).to_string_lossy())
+                    .unwrap();
+            assert_eq!(
+                stored.bgp.unwrap().peer_name.as_deref(),
+                Some("fra1protocol bgp evil")
+            );
+        }
+
+        #[tokio::test]
+        async fn test_deploy_timeout_cleans_up() {
+            // wg-quick up hangs well past the deploy timeout
+            let (config, root) = test_config_with_system();
+            let log = root.path().join("wg-quick.log");
+            let mut system = config.system.clone();
+            system.wg_quick_bin = crate::api::test_helpers::write_stub(
+                &root.path().join("bin"),
+                "wg-quick-hang",
+                &format!(
+                    "#!/bin/sh\necho \"$@\" >> {}\n[ \"$1\" = up ] && sleep 10\nexit 0\n",
+                    log.display()
+                ),
+            );
+            let config = Arc::new(AppConfig {
+                system,
+                deploy_timeout_secs: 1,
+                ..(*config).clone()
+            });
+            let asn = 4242422225;
+
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let started = std::time::Instant::now();
+            let response = app.oneshot(deploy_request(&config, asn)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+            assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+            // The interface was brought down again and BIRD never got the session
+            let iface = interface_name(asn);
+            assert_eq!(
+                std::fs::read_to_string(&log).unwrap(),
+                format!("up {}\ndown {}\n", iface, iface)
+            );
+            assert!(!wireguard::deploy::config_path(&config.system.wireguard_dir, &iface).exists());
+            assert!(!bird::deploy::config_path(&config.system.bird_peers_dir, asn).exists());
+        }
+
+        #[tokio::test]
+        async fn test_get_request_email() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422225;
+            let app = Router::new()
+                .route("/peering/request-email", get(get_request_email))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let request = || authed_request("GET", "/peering/request-email", &config, asn, None);
+
+            let response = app.clone().oneshot(request()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            write_verified_config(&config, asn);
+            let response = app.oneshot(request()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers()[header::CONTENT_TYPE],
+                "text/plain; charset=utf-8"
+            );
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let text = String::from_utf8(body.to_vec()).unwrap();
+
+            assert!(
+                text.contains("AS number:             AS4242420257\n"),
+                "{}",
+                text
+            );
+            assert!(
+                text.contains(&format!(
+                    "WireGuard endpoint:    dn42-test.example:{}\n",
+                    wireguard_port(asn).unwrap()
+                )),
+                "{}",
+                text
+            );
+            assert!(
+                text.contains(&format!(
+                    "WireGuard public key:  {}\n",
+                    crate::api::test_helpers::STUB_PUBLIC_KEY
+                )),
+                "{}",
+                text
+            );
+            assert!(
+                text.contains("Our tunnel address:    fe80::2225:257:0\n"),
+                "{}",
+                text
+            );
+            assert!(
+                text.contains("Your tunnel address:   fe80::2225:257:1\n"),
+                "{}",
+                text
+            );
+            assert!(text.contains("Multiprotocol BGP:     yes\n"), "{}", text);
+            // Never anything secret
+            assert!(!text.contains(crate::api::test_helpers::STUB_PRIVATE_KEY));
+        }
+
+        #[tokio::test]
+        async fn test_get_bird_config() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422225;
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .route("/peering/bird-config", get(get_bird_config))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let response = app
+                .clone()
+                .oneshot(deploy_request(&config, asn))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let response = app
+                .oneshot(authed_request(
+                    "GET",
+                    "/peering/bird-config",
+                    &config,
+                    asn,
+                    None,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers()[header::CONTENT_TYPE],
+                "text/plain; charset=utf-8"
+            );
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let rendered = String::from_utf8(body.to_vec()).unwrap();
+            assert!(
+                rendered.contains("protocol bgp autopeer_as4242422225 from dnpeers {"),
+                "{}",
+                rendered
+            );
+            assert!(
+                rendered.contains("neighbor fe80::2225:257:1 as 4242422225;"),
+                "{}",
+                rendered
+            );
+
+            // Exactly what was deployed
+            let deployed = std::fs::read_to_string(bird::deploy::config_path(
+                &config.system.bird_peers_dir,
+                asn,
+            ))
+            .unwrap();
+            assert_eq!(rendered, deployed);
+        }
+
+        #[tokio::test]
+        async fn test_get_deployment_matches_deploy_response() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422225;
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .route("/peering/deployment", get(get_deployment))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let response = app
+                .clone()
+                .oneshot(authed_request(
+                    "GET",
+                    "/peering/deployment",
+                    &config,
+                    asn,
+                    None,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            let response = app
+                .clone()
+                .oneshot(deploy_request(&config, asn))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let deployed: DeployResponse = serde_json::from_slice(&body).unwrap();
+
+            let response = app
+                .oneshot(authed_request(
+                    "GET",
+                    "/peering/deployment",
+                    &config,
+                    asn,
+                    None,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let reissued: DeployResponse = serde_json::from_slice(&body).unwrap();
+
+            let (deployed, reissued) = (deployed.deployment, reissued.deployment);
+            assert_eq!(reissued.our_public_key, deployed.our_public_key);
+            assert_eq!(reissued.our_endpoint, deployed.our_endpoint);
+            assert_eq!(reissued.interface_address, deployed.interface_address);
+            assert_eq!(reissued.listen_port, deployed.listen_port);
+            assert_eq!(reissued.peer_public_key, deployed.peer_public_key);
+            assert_eq!(reissued.bgp_neighbor, deployed.bgp_neighbor);
+            assert_eq!(reissued.bgp_local_as, deployed.bgp_local_as);
+            assert_eq!(reissued.bgp_remote_as, deployed.bgp_remote_as);
+            assert_eq!(reissued.is_active, deployed.is_active);
+        }
+
+        fn deploy_request(config: &AppConfig, asn: u32) -> Request<Body> {
+            let body = serde_json::to_string(&DeployRequest {
+                wg_public_key: crate::api::test_helpers::PEER_PUBLIC_KEY.to_string(),
+                endpoint: Some("192.0.2.1:51820".to_string()),
+                max_prefixes: None,
+                address_family: AddressFamily::Dual,
+                peer_ipv4: None,
+                listen_port: None,
+                router_id: None,
+                peer_name: None,
+                graceful_restart: None,
+                async_provisioning: false,
+                preshared_key: None,
+                generate_preshared_key: false,
+                dynamic_endpoint: false,
+                deploy_and_activate: false,
+                local_asn: None,
+            })
+            .unwrap();
+            authed_request("POST", "/peering/deploy", config, asn, Some(body))
+        }
+
+        /// Poll a provisioning id until its state satisfies `done`
+        async fn poll_provisioning(
+            app: &Router,
+            config: &AppConfig,
+            asn: u32,
+            id: &str,
+            done: impl Fn(ProvisioningState) -> bool,
+        ) -> ProvisioningStatus {
+            for _ in 0..100 {
+                let response = app
+                    .clone()
+                    .oneshot(authed_request(
+                        "GET",
+                        &format!("/peering/provisioning/{}", id),
+                        config,
+                        asn,
+                        None,
+                    ))
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let status: ProvisioningStatus = serde_json::from_slice(&body).unwrap();
+                if done(status.state) {
+                    return status;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+            panic!("provisioning {} did not reach the expected state", id);
+        }
+
+        /// Deploy with `async_provisioning`, returning the app and the provisioning id
+        async fn deploy_async(config: &Arc<AppConfig>, asn: u32) -> (Router, String) {
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .route("/peering/provisioning/{id}", get(get_provisioning))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let mut request: DeployRequest = serde_json::from_slice(
+                &axum::body::to_bytes(deploy_request(config, asn).into_body(), usize::MAX)
+                    .await
+                    .unwrap(),
+            )
+            .unwrap();
+            request.async_provisioning = true;
+            let body = serde_json::to_string(&request).unwrap();
+            let response = app
+                .clone()
+                .oneshot(authed_request(
+                    "POST",
+                    "/peering/deploy",
+                    config,
+                    asn,
+                    Some(body),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let deployed: DeployResponse = serde_json::from_slice(&body).unwrap();
+            assert!(!deployed.deployment.is_active);
+            (
+                app,
+                deployed
+                    .provisioning_id
+                    .expect("async deploy returns a provisioning id"),
+            )
+        }
+
+        #[tokio::test]
+        async fn test_async_deploy_states() {
+            let (config, root) = test_config_with_system();
+            // birdc blocks until the test releases it
+            let release = root.path().join("release");
+            let mut system = config.system.clone();
+            system.birdc_bin = crate::api::test_helpers::write_stub(
+                &root.path().join("bin"),
+                "birdc-wait",
+                &format!(
+                    "#!/bin/sh\nwhile [ ! -f {} ]; do sleep 0.05; done\necho \"Reconfigured\"\n",
+                    release.display()
+                ),
+            );
+            let config = Arc::new(AppConfig {
+                system,
+                ..(*config).clone()
+            });
+            let asn = 4242422225;
+
+            let (app, id) = deploy_async(&config, asn).await;
+            let status = poll_provisioning(&app, &config, asn, &id, |state| {
+                state == ProvisioningState::Running
+            })
+            .await;
+            assert!(status.error.is_none());
+
+            std::fs::write(&release, "").unwrap();
+            let status = poll_provisioning(&app, &config, asn, &id, |state| {
+                state == ProvisioningState::Done
+            })
+            .await;
+            assert!(status.error.is_none());
+            assert!(bird::deploy::config_path(&config.system.bird_peers_dir, asn).exists());
+
+            // Other peers can't see it
+            let response = app
+                .oneshot(authed_request(
+                    "GET",
+                    &format!("/peering/provisioning/{}", id),
+                    &config,
+                    4242421816,
+                    None,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+
+        #[tokio::test]
+        async fn test_async_deploy_failure() {
+            let (config, root) = test_config_with_system();
+            let mut system = config.system.clone();
+            system.birdc_bin = crate::api::test_helpers::write_stub(
+                &root.path().join("bin"),
+                "birdc-fail",
+                "#!/bin/sh\nexit 1\n",
+            );
+            let config = Arc::new(AppConfig {
+                system,
+                ..(*config).clone()
+            });
+            let asn = 4242422225;
+
+            let (app, id) = deploy_async(&config, asn).await;
+            let status = poll_provisioning(&app, &config, asn, &id, |state| {
+                state == ProvisioningState::Failed
+            })
+            .await;
+            assert!(status
+                .error
+                .unwrap()
+                .contains("Failed to deploy BIRD config"));
+        }
+
+        #[tokio::test]
+        async fn test_status_returns_peer_public_key() {
+            let (config, _root) = test_config_with_system();
+            let asn = 4242422225;
+
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .route("/peering/status", get(get_status))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let response = app
+                .clone()
+                .oneshot(deploy_request(&config, asn))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let deployed: DeployResponse = serde_json::from_slice(&body).unwrap();
+            let peer_key = crate::api::test_helpers::PEER_PUBLIC_KEY;
+            assert_eq!(
+                deployed.deployment.peer_public_key.as_deref(),
+                Some(peer_key)
+            );
+
+            let response = app
+                .oneshot(authed_request("GET", "/peering/status", &config, asn, None))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let status: DeploymentInfo = serde_json::from_slice(&body).unwrap();
+            assert_eq!(status.peer_public_key.as_deref(), Some(peer_key));
+            assert_ne!(status.our_public_key, peer_key);
+        }
+
+        #[tokio::test]
+        async fn test_maintenance_blocks_deploy_not_status() {
+            let (config, _root) = test_config_with_system();
+            let config = Arc::new(AppConfig {
+                maintenance_mode: true,
+                ..(*config).clone()
+            });
+            let asn = 4242422225;
+
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .route("/peering/status", get(get_status))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let response = app
+                .clone()
+                .oneshot(deploy_request(&config, asn))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+            assert!(!verified_config_path(&config, asn).exists());
+
+            // An existing peering can still be looked at
+            write_verified_config(&config, asn);
+            let response = app
+                .oneshot(authed_request("GET", "/peering/status", &config, asn, None))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        #[tokio::test]
+        async fn test_deploy_with_external_wireguard() {
+            let (config, root) = test_config_with_system();
+            let mut system = config.system.clone();
+            // Externally managed interface is up
+            system.wg_bin = crate::api::test_helpers::write_stub(
+                &root.path().join("bin"),
+                "wg-up",
+                &std::fs::read_to_string(&config.system.wg_bin)
+                    .unwrap()
+                    .replace("show) exit 1", "show) exit 0"),
+            );
+            let config = Arc::new(AppConfig {
+                manage_wireguard: false,
+                system,
+                ..(*config).clone()
+            });
+            let asn = 4242422225;
+
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let response = app.oneshot(deploy_request(&config, asn)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let wg_path =
+                wireguard::deploy::config_path(&config.system.wireguard_dir, &interface_name(asn));
+            assert!(!wg_path.exists());
+            assert!(bird::deploy::config_path(&config.system.bird_peers_dir, asn).exists());
+        }
+
+        #[tokio::test]
+        async fn test_deploy_with_external_wireguard_interface_down() {
+            let (config, _root) = test_config_with_system();
+            let config = Arc::new(AppConfig {
+                manage_wireguard: false,
+                ..(*config).clone()
+            });
+            let asn = 4242422225;
+
+            let app = Router::new()
+                .route("/peering/deploy", post(deploy_peering))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+
+            let response = app.oneshot(deploy_request(&config, asn)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::CONFLICT);
+            assert!(!bird::deploy::config_path(&config.system.bird_peers_dir, asn).exists());
+        }
+
+        #[tokio::test]
+        async fn test_signature_diagnosis() {
+            let (config, _root) = test_config_with_system();
+            let app = Router::new()
+                .route("/peering/test-signature", post(test_signature))
                 .with_state(config);
+            let signer = crate::api::test_helpers::TestSigner::generate();
+            let message = "AUTOPEER-4242422225-deadbeef";
+            let check = |asn: u32, message: &str, signed_message: String, public_key: String| {
+                let body = serde_json::to_string(&SignatureTestRequest {
+                    asn,
+                    message: message.to_string(),
+                    signed_message,
+                    public_key,
+                })
+                .unwrap();
+                let request = Request::builder()
+                    .method("POST")
+                    .uri("/peering/test-signature")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap();
+                let app = app.clone();
+                async move {
+                    let response = app.oneshot(request).await.unwrap();
+                    let status = response.status();
+                    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                        .await
+                        .unwrap();
+                    (
+                        status,
+                        serde_json::from_slice::<gpg::SignatureDiagnosis>(&body).ok(),
+                    )
+                }
+            };
+
+            let (status, diagnosis) = check(
+                4242422225,
+                message,
+                signer.sign(message),
+                signer.public_key.clone(),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            let diagnosis = diagnosis.unwrap();
+            assert!(diagnosis.key_parsed && diagnosis.signature_parsed);
+            assert!(diagnosis.signature_verified && diagnosis.content_matches);
+            assert_eq!(diagnosis.error, None);
+
+            // Each stage is reported on its own
+            let (_, diagnosis) =
+                check(4242422225, message, signer.sign(message), String::new()).await;
+            assert!(!diagnosis.unwrap().key_parsed);
+            let (_, diagnosis) = check(
+                4242422225,
+                message,
+                "garbage".to_string(),
+                signer.public_key.clone(),
+            )
+            .await;
+            let diagnosis = diagnosis.unwrap();
+            assert!(diagnosis.key_parsed && !diagnosis.signature_parsed);
+            let other = crate::api::test_helpers::TestSigner::generate();
+            let (_, diagnosis) = check(
+                4242422225,
+                message,
+                other.sign(message),
+                signer.public_key.clone(),
+            )
+            .await;
+            let diagnosis = diagnosis.unwrap();
+            assert!(
+                diagnosis.signature_parsed
+                    && !diagnosis.signature_verified
+                    && diagnosis.content_matches
+            );
+            let (_, diagnosis) = check(
+                4242422225,
+                "AUTOPEER-4242422225-other",
+                signer.sign(message),
+                signer.public_key.clone(),
+            )
+            .await;
+            let diagnosis = diagnosis.unwrap();
+            assert!(diagnosis.signature_verified && !diagnosis.content_matches);
+
+            // The ASN still has to be a DN42 one
+            let (status, _) = check(
+                13335,
+                message,
+                signer.sign(message),
+                signer.public_key.clone(),
+            )
+            .await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
 
-            let request2 = Request::builder()
-                .method("POST")
-                .uri("/peering/init")
-                .header("content-type", "application/json")
-                .body(Body::from(request_body))
+        #[tokio::test]
+        async fn test_endpoint_format_only() {
+            let (config, _root) = test_config_with_system();
+            assert!(!config.validate_endpoint_dns);
+            let asn = 4242422225;
+
+            let app = Router::new()
+                .route("/peering/test-endpoint", post(test_endpoint))
+                .layer(CookieManagerLayer::new())
+                .with_state(config.clone());
+            let request = |endpoint: &str| {
+                let body = serde_json::to_string(&EndpointTestRequest {
+                    endpoint: endpoint.to_string(),
+                })
                 .unwrap();
+                authed_request("POST", "/peering/test-endpoint", &config, asn, Some(body))
+            };
 
-            let response2 = app2.oneshot(request2).await.unwrap();
-            let body2 = axum::body::to_bytes(response2.into_body(), usize::MAX).await.unwrap();
-            let resp2: InitResponse = serde_json::from_slice(&body2).unwrap();
+            let response = app
+                .clone()
+                .oneshot(request("192.0.2.1:51820"))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let result: EndpointTestResponse = serde_json::from_slice(&body).unwrap();
+            assert_eq!(result.reachability, Reachability::NotChecked);
 
-            // Challenges should be different (random)
-            assert_ne!(resp1.challenge, resp2.challenge);
+            // Format is still validated
+            let response = app.oneshot(request("not-an-endpoint")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
         }
     }
 }
@@ -0,0 +1,122 @@
+//! Pending challenges, one file per init (`{asn}-{id}.conf`) so concurrent flows don't clash
+
+use super::attempts;
+use crate::config::AppConfig;
+use crate::ipalloc::interface_name;
+use std::path::{Path, PathBuf};
+
+/// Pending challenges kept per ASN; older ones are dropped on init
+pub const MAX_PENDING_PER_ASN: usize = 5;
+
+/// Random id for a new challenge
+pub fn new_id() -> String {
+    hex::encode(rand::random::<[u8; 8]>())
+}
+
+/// Challenge ids are hex, so they can't escape the pending dir
+pub fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 32 && id.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Path of the challenge with the given id
+pub fn path(pending_dir: &str, asn: u32, id: &str) -> String {
+    format!("{}/{}-{}.conf", pending_dir, asn, id)
+}
+
+/// Path challenges were stored at before they had ids (`{iface}.conf`)
+fn legacy_path(pending_dir: &str, asn: u32) -> PathBuf {
+    Path::new(pending_dir).join(format!("{}.conf", interface_name(asn)))
+}
+
+/// Contact stored next to a pending challenge (`{asn}-{id}.contact`)
+pub fn contact_path<P: AsRef<Path>>(challenge_path: P) -> PathBuf {
+    challenge_path.as_ref().with_extension("contact")
+}
+
+/// All pending challenges of an ASN, oldest first
+pub fn list(pending_dir: &str, asn: u32) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(pending_dir) else {
+        return Vec::new();
+    };
+
+    let prefix = format!("{}-", asn);
+    let legacy = legacy_path(pending_dir, asn);
+    let mut challenges: Vec<_> = entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            (name.starts_with(&prefix) && name.ends_with(".conf")) || entry.path() == legacy
+        })
+        .map(|entry| {
+            let modified = entry.metadata().and_then(|m| m.modified()).ok();
+            (modified, entry.path())
+        })
+        .collect();
+    challenges.sort();
+    challenges.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Remove a pending challenge along with its contact and failure count
+pub fn remove<P: AsRef<Path>>(config: &AppConfig, challenge_path: P) -> std::io::Result<()> {
+    let challenge_path = challenge_path.as_ref();
+    let _ = std::fs::remove_file(contact_path(challenge_path));
+    attempts::clear(config, challenge_path);
+    std::fs::remove_file(challenge_path)
+}
+
+/// Drop the oldest challenges so that `keep` remain
+pub fn prune(config: &AppConfig, asn: u32, keep: usize) {
+    let challenges = list(&config.data_pending_dir, asn);
+    let excess = challenges.len().saturating_sub(keep);
+    for path in &challenges[..excess] {
+        let _ = remove(config, path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_helpers::test_config;
+
+    #[test]
+    fn test_list_and_prune() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = AppConfig {
+            data_pending_dir: dir.path().to_string_lossy().to_string(),
+            ..(*test_config()).clone()
+        };
+        let pending_dir = config.data_pending_dir.clone();
+        let asn = 4242422225;
+
+        std::fs::write(legacy_path(&pending_dir, asn), "old").unwrap();
+        for id in ["aa", "bb", "cc"] {
+            std::fs::write(path(&pending_dir, asn, id), id).unwrap();
+        }
+        std::fs::write(
+            contact_path(path(&pending_dir, asn, "aa")),
+            "noc@example.com",
+        )
+        .unwrap();
+        // Another ASN's challenge is not ours
+        std::fs::write(path(&pending_dir, 4242422226, "dd"), "dd").unwrap();
+
+        assert_eq!(list(&pending_dir, asn).len(), 4);
+
+        prune(&config, asn, 2);
+        let remaining = list(&pending_dir, asn);
+        assert_eq!(remaining.len(), 2);
+        assert!(!contact_path(path(&pending_dir, asn, "aa")).exists());
+        assert_eq!(list(&pending_dir, 4242422226).len(), 1);
+    }
+
+    #[test]
+    fn test_challenge_ids() {
+        let id = new_id();
+        assert!(is_valid_id(&id));
+        assert_ne!(id, new_id());
+
+        assert!(!is_valid_id(""));
+        assert!(!is_valid_id("../wg-as4242422225"));
+        assert!(!is_valid_id(&"a".repeat(33)));
+    }
+}
@@ -0,0 +1,105 @@
+//! Best-effort reachability probe for a peer's WireGuard endpoint
+//!
+//! WireGuard ignores packets it can't authenticate, so a silent endpoint is
+//! indistinguishable from a firewalled one. The probe can only detect
+//! resolution and routing failures, or a closed UDP port reported via ICMP.
+
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// How long to wait for resolution and for an ICMP answer
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Outcome of probing an endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reachability {
+    /// No network checks were run (format only)
+    NotChecked,
+    /// The endpoint could not be resolved
+    Unresolvable,
+    /// The host is not routable from here
+    Unreachable,
+    /// The host answered that nothing listens on the UDP port
+    PortClosed,
+    /// No negative answer; WireGuard may be listening (cannot be confirmed)
+    NoAnswer,
+}
+
+/// Probe `endpoint` (host:port) with a single UDP datagram
+pub async fn probe_endpoint(endpoint: &str) -> (Reachability, String) {
+    let addr = match tokio::time::timeout(PROBE_TIMEOUT, tokio::net::lookup_host(endpoint)).await {
+        Ok(Ok(mut addrs)) => match addrs.next() {
+            Some(addr) => addr,
+            None => {
+                return (
+                    Reachability::Unresolvable,
+                    format!("{} resolved to no addresses", endpoint),
+                )
+            }
+        },
+        Ok(Err(e)) => {
+            return (
+                Reachability::Unresolvable,
+                format!("Failed to resolve {}: {}", endpoint, e),
+            )
+        }
+        Err(_) => {
+            return (
+                Reachability::Unresolvable,
+                format!("Resolving {} timed out", endpoint),
+            )
+        }
+    };
+
+    let bind = if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = match UdpSocket::bind(bind).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            return (
+                Reachability::NotChecked,
+                format!("Failed to open probe socket: {}", e),
+            )
+        }
+    };
+
+    // Connected UDP sockets surface ICMP errors on send/recv
+    if let Err(e) = socket.connect(addr).await {
+        return (
+            Reachability::Unreachable,
+            format!("No route to {}: {}", addr, e),
+        );
+    }
+    if let Err(e) = socket.send(b"autopeer-probe").await {
+        return classify(addr, e);
+    }
+
+    let mut buf = [0u8; 64];
+    match tokio::time::timeout(PROBE_TIMEOUT, socket.recv(&mut buf)).await {
+        Ok(Ok(_)) => (Reachability::NoAnswer, format!("{} answered the probe", addr)),
+        Ok(Err(e)) => classify(addr, e),
+        Err(_) => (
+            Reachability::NoAnswer,
+            format!("{} did not reject the probe; WireGuard stays silent, so this is the expected result", addr),
+        ),
+    }
+}
+
+fn classify(addr: std::net::SocketAddr, e: std::io::Error) -> (Reachability, String) {
+    match e.kind() {
+        ErrorKind::ConnectionRefused => (
+            Reachability::PortClosed,
+            format!("{} reports the UDP port as closed", addr),
+        ),
+        _ => (
+            Reachability::Unreachable,
+            format!("Probe to {} failed: {}", addr, e),
+        ),
+    }
+}
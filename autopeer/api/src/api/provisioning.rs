@@ -0,0 +1,155 @@
+//! Deployments running in the background, polled via `GET /peering/provisioning/{id}`
+
+use super::pending;
+use crate::config::AppConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long finished provisioning results stay available
+const RETENTION: Duration = Duration::from_secs(3600);
+
+/// Progress of a background deployment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProvisioningState {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+/// Response from provisioning status
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ProvisioningStatus {
+    pub state: ProvisioningState,
+    /// Why the deployment failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    asn: u32,
+    status: ProvisioningStatus,
+    updated: Instant,
+}
+
+/// Provisioning ids and their progress, kept in [`RuntimeState`](super::state::RuntimeState)
+#[derive(Debug, Default)]
+pub(crate) struct Provisioning(Mutex<HashMap<String, Entry>>);
+
+/// Register a queued deployment for an ASN and return its id
+pub fn queue(config: &AppConfig, asn: u32) -> Result<String, String> {
+    let mut provisioning = config
+        .runtime
+        .provisioning
+        .0
+        .lock()
+        .map_err(|e| format!("Failed to acquire provisioning lock: {}", e))?;
+
+    // Forget results nobody polled for a while
+    provisioning.retain(|_, entry| {
+        matches!(
+            entry.status.state,
+            ProvisioningState::Queued | ProvisioningState::Running
+        ) || entry.updated.elapsed() < RETENTION
+    });
+
+    let id = pending::new_id();
+    provisioning.insert(
+        id.clone(),
+        Entry {
+            asn,
+            status: ProvisioningStatus {
+                state: ProvisioningState::Queued,
+                error: None,
+            },
+            updated: Instant::now(),
+        },
+    );
+    Ok(id)
+}
+
+/// Mark a deployment as started
+pub fn start(config: &AppConfig, id: &str) {
+    update(
+        config,
+        id,
+        ProvisioningStatus {
+            state: ProvisioningState::Running,
+            error: None,
+        },
+    );
+}
+
+/// Record the outcome of a deployment
+pub fn finish(config: &AppConfig, id: &str, result: Result<(), String>) {
+    let status = match result {
+        Ok(()) => ProvisioningStatus {
+            state: ProvisioningState::Done,
+            error: None,
+        },
+        Err(e) => ProvisioningStatus {
+            state: ProvisioningState::Failed,
+            error: Some(e),
+        },
+    };
+    update(config, id, status);
+}
+
+fn update(config: &AppConfig, id: &str, status: ProvisioningStatus) {
+    if let Ok(mut provisioning) = config.runtime.provisioning.0.lock() {
+        if let Some(entry) = provisioning.get_mut(id) {
+            entry.status = status;
+            entry.updated = Instant::now();
+        }
+    }
+}
+
+/// Status of a deployment, if the id exists and belongs to the ASN
+pub fn status(config: &AppConfig, id: &str, asn: u32) -> Option<ProvisioningStatus> {
+    let provisioning = config.runtime.provisioning.0.lock().ok()?;
+    provisioning
+        .get(id)
+        .filter(|entry| entry.asn == asn)
+        .map(|entry| entry.status.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_helpers::test_config;
+
+    #[test]
+    fn test_state_transitions() {
+        let config = test_config();
+        let id = queue(&config, 4242422225).unwrap();
+        assert_eq!(
+            status(&config, &id, 4242422225).unwrap().state,
+            ProvisioningState::Queued
+        );
+        // Only visible to the ASN that deployed
+        assert!(status(&config, &id, 4242421816).is_none());
+
+        start(&config, &id);
+        assert_eq!(
+            status(&config, &id, 4242422225).unwrap().state,
+            ProvisioningState::Running
+        );
+
+        finish(&config, &id, Err("birdc configure failed".to_string()));
+        let failed = status(&config, &id, 4242422225).unwrap();
+        assert_eq!(failed.state, ProvisioningState::Failed);
+        assert_eq!(failed.error.as_deref(), Some("birdc configure failed"));
+
+        let other = queue(&config, 4242422225).unwrap();
+        finish(&config, &other, Ok(()));
+        let done = status(&config, &other, 4242422225).unwrap();
+        assert_eq!(done.state, ProvisioningState::Done);
+        assert!(done.error.is_none());
+
+        assert!(status(&config, "0000", 4242422225).is_none());
+    }
+}
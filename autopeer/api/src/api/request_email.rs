@@ -0,0 +1,28 @@
+//! A peering described as the text block DN42 peering requests are sent as (mail, forums)
+
+use crate::api::peering::DeploymentInfo;
+use crate::wireguard::BgpConfig;
+use std::fmt::Write;
+
+/// Our side of a peering, for the peer to paste into a manual peering request or reply
+pub fn format(info: &DeploymentInfo, bgp: Option<&BgpConfig>) -> String {
+    let yes_no = |on: bool| if on { "yes" } else { "no" };
+    let mpbgp = bgp.is_some_and(|bgp| bgp.mpbgp);
+    let extended_next_hop = bgp.is_some_and(|bgp| bgp.extended_next_hop);
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "DN42 peering: AS{} <-> AS{}",
+        info.bgp_local_as, info.bgp_remote_as
+    );
+    let _ = writeln!(out);
+    let _ = writeln!(out, "AS number:             AS{}", info.bgp_local_as);
+    let _ = writeln!(out, "WireGuard endpoint:    {}", info.our_endpoint);
+    let _ = writeln!(out, "WireGuard public key:  {}", info.our_public_key);
+    let _ = writeln!(out, "Our tunnel address:    {}", info.interface_address);
+    let _ = writeln!(out, "Your tunnel address:   {}", info.bgp_neighbor);
+    let _ = writeln!(out, "Multiprotocol BGP:     {}", yes_no(mpbgp));
+    let _ = writeln!(out, "Extended next hop:     {}", yes_no(extended_next_hop));
+    out
+}
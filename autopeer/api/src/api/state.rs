@@ -0,0 +1,28 @@
+//! Process state shared by every request: it lives in the config handlers already
+//! extract, and a SIGHUP reload hands it on to the new config
+
+use super::{asn_lock, attempts, cooldown, metrics, provisioning, throttle};
+use crate::registry::sync::SyncTimes;
+use crate::revocation::TokenStore;
+use std::sync::Arc;
+
+/// Runtime state that outlives any one config snapshot
+#[derive(Debug, Default)]
+pub struct RuntimeState {
+    /// Signature verification slots and queue
+    pub(crate) verifications: throttle::Limiter,
+    /// When each ASN last changed its peering
+    pub(crate) cooldowns: cooldown::Cooldowns,
+    /// Serializes router changes per ASN
+    pub(crate) asn_locks: asn_lock::AsnLocks,
+    /// Failed verifications per pending challenge
+    pub(crate) failed_attempts: attempts::FailedAttempts,
+    /// Background deployments and their progress
+    pub(crate) provisioning: provisioning::Provisioning,
+    /// Last `/metrics` scrape, reused for a few seconds
+    pub(crate) metrics: metrics::ScrapeCache,
+    /// Issued and revoked tokens, loaded from `TOKEN_STORE` on first use
+    pub(crate) tokens: Arc<TokenStore>,
+    /// Last successful registry sync, shared with the background syncer
+    pub(crate) registry_syncs: Arc<SyncTimes>,
+}
@@ -1,12 +1,52 @@
-/// Test helpers for API endpoint testing
-///
-/// These helpers allow testing API endpoints without actually running
-/// system commands like wg-quick or birdc.
+//! Test helpers for API endpoint testing
+//!
+//! These helpers allow testing API endpoints without actually running
+//! system commands like wg-quick or birdc.
 
-use crate::config::AppConfig;
-use std::path::PathBuf;
+use crate::config::{AppConfig, SystemConfig};
+use crate::ipalloc::{interface_name, wireguard_port, AddressFamily, BgpTransport, Ipv6LinkLocal};
+use crate::wireguard::{BgpConfig, InterfaceConfig, PeerConfig, PrivateKey, PublicKey, WgConfig};
+use base64::Engine;
+use pgp::composed::{
+    ArmorOptions, CleartextSignedMessage, KeyType, SecretKeyParamsBuilder, SignedPublicKey,
+    SignedSecretKey, SubkeyParamsBuilder,
+};
+use pgp::types::{KeyDetails, Password};
+use rsa::rand_core::OsRng;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+/// Private key printed by the stub `wg genkey`
+pub const STUB_PRIVATE_KEY: &str = "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=";
+/// Public key printed by the stub `wg pubkey`
+pub const STUB_PUBLIC_KEY: &str = "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=";
+/// WireGuard public key used for the peer side in tests
+pub const PEER_PUBLIC_KEY: &str = "TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=";
+/// Ed25519 PGP key registered for AS4242420257 in the test registry
+pub const PEER_PGP_PUBLIC_KEY: &str = r#"-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEYVuS5RYJKwYBBAHaRw8BAQdAnJ1to/QytFqDfg3gtUrtiqmJRMSLNrG/fLNG
+BesjX5m0L0ZlcmRpbmFuZCBMaW5uZW5iZXJnIDxmZXJkaW5hbmRAbGlubmVuYmVy
+Zy5kZXY+iJAEExYIADgWIQSLfwOEy+AnJ2HYUuoGhONubPnU1AUCYVuS5QIbAwUL
+CQgHAgYVCgkICwIEFgIDAQIeAQIXgAAKCRAGhONubPnU1M2ZAP0drb1tbnLi1cU+
+Pc4NPTMjviTBBFmGFoDni/0mvMC5qAD6AlB24idciDkSeJFz3s/6wSog/Rj4ALpk
+RQ/v8Ls4gQa4OARhW5LlEgorBgEEAZdVAQUBAQdAci4cwabJdJGO+VF5wxEW+yuO
+Y+BPprEQpy4jFiN713sDAQgHiHgEGBYIACAWIQSLfwOEy+AnJ2HYUuoGhONubPnU
+1AUCYVuS5QIbDAAKCRAGhONubPnU1I79AQC7Weudp5yzofVqZQCa/ijohC5CuwXw
+LGZbH16nUawo9gEAw+6wvpgw2d7IS6rnT6jJZ1qm6inF/XzTZTNfq9rsmgM=
+=WrLZ
+-----END PGP PUBLIC KEY BLOCK-----"#;
+
+/// Small DN42 registry checked in under `tests/fixtures/registry`
+///
+/// Holds AS4242420257 (SCARJIT-MNT, the key in [`PEER_PGP_PUBLIC_KEY`]) and
+/// AS4242422601 (AUTOPEER-TEST-MNT, the key in `tests/fixtures/gpg/test-public.asc`),
+/// each with its aut-num, mntner and key-cert objects.
+pub fn test_registry_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/registry")
+}
+
 /// Create a test configuration with temporary directories
 pub fn test_config_with_temp_dirs() -> (Arc<AppConfig>, tempfile::TempDir, tempfile::TempDir) {
     let pending_dir = tempfile::TempDir::new().unwrap();
@@ -15,21 +55,327 @@ pub fn test_config_with_temp_dirs() -> (Arc<AppConfig>, tempfile::TempDir, tempf
     let config = Arc::new(AppConfig {
         registry: crate::config::RegistryConfig {
             url: "https://test.example".to_string(),
-            path: PathBuf::from("./data/dn42-registry"),
+            path: test_registry_path(),
             username: "test".to_string(),
             token: "test".to_string(),
         },
-        jwt_secret: "test-secret-key-for-testing-at-least-32-chars-long".to_string(),
-        my_asn: 4242420257,
-        bind_address: "127.0.0.1:3000".to_string(),
+        data_dir: pending_dir.path().to_string_lossy().to_string(),
         data_pending_dir: pending_dir.path().to_string_lossy().to_string(),
         data_verified_dir: verified_dir.path().to_string_lossy().to_string(),
-        cookie_domains: vec!["localhost".to_string()],
+        token_store: pending_dir.path().join("tokens.json"),
+        ..(*test_config()).clone()
     });
 
     (config, pending_dir, verified_dir)
 }
 
+/// Create a test configuration whose router paths and tools live in a temp dir
+///
+/// `wg`, `wg-quick` and `birdc` are replaced by stub scripts that succeed
+/// without touching the host.
+pub fn test_config_with_system() -> (Arc<AppConfig>, tempfile::TempDir) {
+    let root = tempfile::TempDir::new().unwrap();
+
+    let config = Arc::new(AppConfig {
+        data_dir: root.path().to_string_lossy().to_string(),
+        data_pending_dir: root.path().join("pending").to_string_lossy().to_string(),
+        data_verified_dir: root.path().join("verified").to_string_lossy().to_string(),
+        data_deleted_dir: root.path().join("deleted").to_string_lossy().to_string(),
+        system: test_system_config(root.path()),
+        token_store: root.path().join("tokens.json"),
+        ..(*test_config()).clone()
+    });
+
+    std::fs::create_dir_all(&config.data_pending_dir).unwrap();
+    std::fs::create_dir_all(&config.data_verified_dir).unwrap();
+
+    (config, root)
+}
+
+/// Create stub system tools and directories under `root`
+pub fn test_system_config(root: &Path) -> SystemConfig {
+    let bin_dir = root.join("bin");
+    let wireguard_dir = root.join("wireguard");
+    let bird_peers_dir = root.join("bird");
+    for dir in [&bin_dir, &wireguard_dir, &bird_peers_dir] {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+
+    let wg = format!(
+        "#!/bin/sh\ncase \"$1\" in\n  genkey) echo \"{}\" ;;\n  pubkey) cat >/dev/null; echo \"{}\" ;;\n  show) exit 1 ;;\nesac\n",
+        STUB_PRIVATE_KEY, STUB_PUBLIC_KEY
+    );
+
+    SystemConfig {
+        wireguard_dir,
+        bird_peers_dir,
+        wg_bin: write_stub(&bin_dir, "wg", &wg),
+        wg_quick_bin: write_stub(&bin_dir, "wg-quick", "#!/bin/sh\nexit 0\n"),
+        birdc_bin: write_stub(&bin_dir, "birdc", "#!/bin/sh\necho \"Reconfigured\"\n"),
+        wg_up_timeout_secs: 0,
+        birdc_timeout_secs: 5,
+        command_timeout_secs: 5,
+    }
+}
+
+/// Write an executable stub script and return its path
+pub fn write_stub(bin_dir: &Path, name: &str, script: &str) -> String {
+    let path = bin_dir.join(name);
+    std::fs::write(&path, script).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+/// Write a deployed-style verified config for an ASN
+pub fn write_verified_config(config: &AppConfig, asn: u32) -> WgConfig {
+    let ips = Ipv6LinkLocal::from_asns(config.my_asn, asn);
+    let wg_config = WgConfig {
+        interface: InterfaceConfig {
+            address: vec![ips.peer.clone()],
+            private_key: PrivateKey::new(STUB_PRIVATE_KEY).unwrap(),
+            listen_port: wireguard_port(asn).unwrap(),
+            table: Some("off".to_string()),
+            dns: Vec::new(),
+        },
+        peers: vec![PeerConfig {
+            public_key: PublicKey::new(PEER_PUBLIC_KEY).unwrap(),
+            endpoint: Some("192.0.2.1:51820".to_string()),
+            allowed_ips: vec!["0.0.0.0/0".to_string(), "::/0".to_string()],
+            persistent_keepalive: Some(25),
+            preshared_key: None,
+        }],
+        challenge: None,
+        meta: None,
+        registry: None,
+        bgp: Some(BgpConfig {
+            enabled: true,
+            mpbgp: true,
+            extended_next_hop: true,
+            local: ips.local_addr(),
+            neighbor: ips.peer.clone(),
+            max_prefixes: Some(1000),
+            address_family: AddressFamily::Dual,
+            router_id: None,
+            peer_name: None,
+            graceful_restart: None,
+            transport: BgpTransport::LinkLocal,
+            local_as: None,
+        }),
+    };
+
+    let path = verified_config_path(config, asn);
+    wg_config.to_file(&path).unwrap();
+    wg_config
+}
+
+/// Path of the verified config for an ASN
+pub fn verified_config_path(config: &AppConfig, asn: u32) -> PathBuf {
+    PathBuf::from(&config.data_verified_dir).join(format!("{}.conf", interface_name(asn)))
+}
+
+/// Cookie header value authenticating as the given ASN
+pub fn auth_cookie(config: &AppConfig, asn: u32) -> String {
+    let token = crate::jwt::generate_token(asn, &config.jwt_secret).unwrap();
+    format!("autopeer_token={}", token)
+}
+
+/// Build a request carrying the auth cookie for the given ASN
+pub fn authed_request(
+    method: &str,
+    uri: &str,
+    config: &AppConfig,
+    asn: u32,
+    json_body: Option<String>,
+) -> axum::http::Request<axum::body::Body> {
+    let builder = axum::http::Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(axum::http::header::COOKIE, auth_cookie(config, asn));
+
+    match json_body {
+        Some(body) => builder
+            .header("content-type", "application/json")
+            .body(axum::body::Body::from(body))
+            .unwrap(),
+        None => builder.body(axum::body::Body::empty()).unwrap(),
+    }
+}
+
+/// PGP key generated per test run, for signing challenges end to end
+pub struct TestSigner {
+    key: SignedSecretKey,
+    /// Sign with the first subkey instead of the primary key
+    sign_with_subkey: bool,
+    /// Armored public key, as sent to /peering/verify
+    pub public_key: String,
+    pub fingerprint: String,
+}
+
+impl TestSigner {
+    /// Generate a fresh Ed25519 key that may sign
+    pub fn generate() -> Self {
+        Self::generate_key(false)
+    }
+
+    /// Generate a certify-only Ed25519 key with a separate signing subkey
+    pub fn generate_with_signing_subkey() -> Self {
+        Self::generate_key(true)
+    }
+
+    fn generate_key(sign_with_subkey: bool) -> Self {
+        let mut params = SecretKeyParamsBuilder::default();
+        params
+            .key_type(KeyType::Ed25519Legacy)
+            .can_certify(true)
+            .can_sign(!sign_with_subkey)
+            .primary_user_id("Test Peer <peer@example.com>".into());
+        if sign_with_subkey {
+            params.subkey(
+                SubkeyParamsBuilder::default()
+                    .key_type(KeyType::Ed25519Legacy)
+                    .can_sign(true)
+                    .build()
+                    .unwrap(),
+            );
+        }
+        let key = params
+            .build()
+            .unwrap()
+            .generate(OsRng)
+            .unwrap()
+            .sign(OsRng, &Password::empty())
+            .unwrap();
+
+        let public_key = SignedPublicKey::from(key.clone())
+            .to_armored_string(ArmorOptions::default())
+            .unwrap();
+        let fingerprint = format!("{:X}", key.fingerprint());
+        TestSigner {
+            key,
+            sign_with_subkey,
+            public_key,
+            fingerprint,
+        }
+    }
+
+    /// Cleartext-sign a challenge code the way `echo $code | gpg --clearsign` does
+    pub fn sign(&self, text: &str) -> String {
+        let line = format!("{}\n", text);
+        let signed = if self.sign_with_subkey {
+            CleartextSignedMessage::sign(
+                OsRng,
+                &line,
+                &self.key.secret_subkeys[0].key,
+                &Password::empty(),
+            )
+        } else {
+            CleartextSignedMessage::sign(OsRng, &line, &self.key.primary_key, &Password::empty())
+        };
+        signed
+            .unwrap()
+            .to_armored_string(ArmorOptions::default())
+            .unwrap()
+    }
+
+    /// Write a registry under `root` in which `asn` is maintained by this key
+    pub fn write_registry(&self, root: &Path, asn: u32) -> PathBuf {
+        let registry = root.join("registry");
+        std::fs::create_dir_all(registry.join("data/aut-num")).unwrap();
+        std::fs::create_dir_all(registry.join("data/mntner")).unwrap();
+        std::fs::write(
+            registry.join(format!("data/aut-num/AS{}", asn)),
+            format!("aut-num: AS{}\nas-name: TEST-AS\nmnt-by: TEST-MNT\n", asn),
+        )
+        .unwrap();
+        std::fs::write(
+            registry.join("data/mntner/TEST-MNT"),
+            format!(
+                "mntner: TEST-MNT\nauth: pgp-fingerprint {}\n",
+                self.fingerprint
+            ),
+        )
+        .unwrap();
+        registry
+    }
+}
+
+/// Ed25519 key that signs challenges the way `minisign -Sm` does
+pub struct MinisignSigner {
+    key: ed25519_dalek::SigningKey,
+    key_id: [u8; 8],
+}
+
+impl MinisignSigner {
+    /// A fixed key per seed, so tests can tell keys apart
+    pub fn new(seed: u8) -> Self {
+        MinisignSigner {
+            key: ed25519_dalek::SigningKey::from_bytes(&[seed; 32]),
+            key_id: [seed; 8],
+        }
+    }
+
+    /// Public key as listed in `auth: ed25519-minisign <key>`
+    pub fn public_key(&self) -> String {
+        let mut bytes = b"Ed".to_vec();
+        bytes.extend_from_slice(&self.key_id);
+        bytes.extend_from_slice(self.key.verifying_key().as_bytes());
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    }
+
+    /// Write a registry under `root` in which `asn`'s maintainer only lists this minisign key
+    pub fn write_registry(&self, root: &Path, asn: u32) -> PathBuf {
+        let registry = root.join("registry");
+        std::fs::create_dir_all(registry.join("data/aut-num")).unwrap();
+        std::fs::create_dir_all(registry.join("data/mntner")).unwrap();
+        std::fs::write(
+            registry.join(format!("data/aut-num/AS{}", asn)),
+            format!("aut-num: AS{}\nas-name: TEST-AS\nmnt-by: TEST-MNT\n", asn),
+        )
+        .unwrap();
+        std::fs::write(
+            registry.join("data/mntner/TEST-MNT"),
+            format!(
+                "mntner: TEST-MNT\nauth: ed25519-minisign {}\n",
+                self.public_key()
+            ),
+        )
+        .unwrap();
+        registry
+    }
+
+    /// Sign a file holding `text` and a newline, as `echo $code > f; minisign -Sm f` would
+    pub fn sign(&self, text: &str, prehashed: bool) -> String {
+        self.sign_exact(&format!("{}\n", text), prehashed)
+    }
+
+    /// Sign exactly `text`
+    pub fn sign_exact(&self, text: &str, prehashed: bool) -> String {
+        use blake2::Digest;
+        use ed25519_dalek::Signer;
+
+        let (alg, message) = if prehashed {
+            (b"ED", blake2::Blake2b512::digest(text.as_bytes()).to_vec())
+        } else {
+            (b"Ed", text.as_bytes().to_vec())
+        };
+        let signature = self.key.sign(&message).to_bytes();
+        let trusted_comment = "timestamp:1700000000\tfile:challenge";
+        let mut global = signature.to_vec();
+        global.extend_from_slice(trusted_comment.as_bytes());
+
+        let mut line = alg.to_vec();
+        line.extend_from_slice(&self.key_id);
+        line.extend_from_slice(&signature);
+        let engine = base64::engine::general_purpose::STANDARD;
+        format!(
+            "untrusted comment: signature from minisign secret key\n{}\ntrusted comment: {}\n{}\n",
+            engine.encode(line),
+            trusted_comment,
+            engine.encode(self.key.sign(&global).to_bytes()),
+        )
+    }
+}
+
 /// Create a basic test configuration
 pub fn test_config() -> Arc<AppConfig> {
     Arc::new(AppConfig {
@@ -42,8 +388,74 @@ pub fn test_config() -> Arc<AppConfig> {
         jwt_secret: "test-secret-key-for-testing-at-least-32-chars-long".to_string(),
         my_asn: 4242420257,
         bind_address: "127.0.0.1:3000".to_string(),
+        data_dir: "/tmp/test-data".to_string(),
         data_pending_dir: "/tmp/test-pending".to_string(),
         data_verified_dir: "/tmp/test-verified".to_string(),
+        data_deleted_dir: "/tmp/test-deleted".to_string(),
+        system: SystemConfig {
+            wireguard_dir: PathBuf::from("/tmp/test-wireguard"),
+            bird_peers_dir: PathBuf::from("/tmp/test-bird-peers"),
+            wg_bin: "wg".to_string(),
+            wg_quick_bin: "wg-quick".to_string(),
+            birdc_bin: "birdc".to_string(),
+            wg_up_timeout_secs: 0,
+            birdc_timeout_secs: 5,
+            command_timeout_secs: 5,
+        },
         cookie_domains: vec!["localhost".to_string()],
+        public_endpoint: "dn42-test.example".to_string(),
+        allowed_pgp_algorithms: vec![
+            "ed25519".to_string(),
+            "ecdsa".to_string(),
+            "rsa2048".to_string(),
+        ],
+        template_dir: None,
+        sync_interval_secs: 0,
+        sync_jitter_secs: 0,
+        registry_layout: crate::registry::RegistryLayout::Auto,
+        deploy_cooldown_secs: 0,
+        link_local_scope_suffix: false,
+        manage_wireguard: true,
+        challenge_prefix: "AUTOPEER".to_string(),
+        challenge_random_bytes: 16,
+        bgp_neighbor_mode: crate::ipalloc::NeighborMode::LinkLocal,
+        ula_transfer_prefix: None,
+        validate_endpoint_dns: false,
+        default_max_prefixes: 1000,
+        strict_allowed_ips: false,
+        jwt_issuer: None,
+        jwt_audience: None,
+        ipv4_transfer_address: None,
+        port_range: "30000-39999".parse().unwrap(),
+        config_history: 5,
+        reject_private_endpoints: true,
+        allow_dn42_endpoints: false,
+        strict_import_filter: false,
+        token_store: PathBuf::from("/tmp/test-tokens.json"),
+        admin_token: None,
+        public_endpoint_family: AddressFamily::Dual,
+        stateless_challenges: false,
+        challenge_ttl_secs: 3600,
+        asn_range: crate::validation::AsnRange::DN42,
+        max_concurrent_verifications: 2,
+        verification_queue: 8,
+        maintenance_mode: false,
+        deploy_timeout_secs: 30,
+        registry_data_subdir: "data".to_string(),
+        post_verify_redirect: None,
+        reject_duplicate_endpoints: false,
+        external_private_keys: false,
+        bird_peer_template: "dnpeers".to_string(),
+        bgp_transport: crate::ipalloc::BgpTransport::LinkLocal,
+        max_registry_age_secs: 0,
+        allow_tech_contact_auth: false,
+        tombstone_deletes: false,
+        allowed_endpoint_domains: Vec::new(),
+        reject_ip_endpoints: false,
+        deploy_auto_activate: true,
+        explicit_channels: None,
+        allow_dns_directive: false,
+        my_asns: vec![4242420257],
+        runtime: Default::default(),
     })
 }
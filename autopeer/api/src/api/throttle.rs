@@ -0,0 +1,162 @@
+//! Bounded concurrency for signature verification, which is CPU-heavy for large RSA keys
+//!
+//! `MAX_CONCURRENT_VERIFICATIONS` checks run at once and up to `VERIFICATION_QUEUE`
+//! more wait for a slot; anything beyond that is turned away with 503.
+
+use crate::config::AppConfig;
+use axum::http::StatusCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Verification slots and queue, kept in [`RuntimeState`](super::state::RuntimeState)
+#[derive(Debug, Default)]
+pub(crate) struct Limiter {
+    /// Slots for the `max_concurrent_verifications` they were sized for
+    slots: Mutex<Option<(usize, Arc<Semaphore>)>>,
+    waiting: AtomicUsize,
+}
+
+impl Limiter {
+    /// Slots for the configured limit. A new limit (SIGHUP) gets new slots; verifications
+    /// holding or waiting for the old ones finish there.
+    fn slots(&self, max: usize) -> Result<Arc<Semaphore>, (StatusCode, String)> {
+        let mut slots = self.slots.lock().map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to acquire lock: {}", e),
+            )
+        })?;
+        match &*slots {
+            Some((size, semaphore)) if *size == max => Ok(semaphore.clone()),
+            _ => {
+                let semaphore = Arc::new(Semaphore::new(max));
+                *slots = Some((max, semaphore.clone()));
+                Ok(semaphore)
+            }
+        }
+    }
+}
+
+/// A place in the queue, given back however the wait ends (including a dropped request)
+struct QueueSlot<'a>(&'a AtomicUsize);
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Wait for a verification slot, or fail with 503 when the queue is full
+pub async fn acquire(config: &AppConfig) -> Result<OwnedSemaphorePermit, (StatusCode, String)> {
+    let limiter = &config.runtime.verifications;
+    let slots = limiter.slots(config.max_concurrent_verifications)?;
+
+    if let Ok(permit) = slots.clone().try_acquire_owned() {
+        return Ok(permit);
+    }
+
+    // Reserve a place in the queue, unless it is full
+    let queued = limiter
+        .waiting
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |waiting| {
+            (waiting < config.verification_queue).then_some(waiting + 1)
+        });
+    if queued.is_err() {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Too many signature verifications in progress, retry later".to_string(),
+        ));
+    }
+
+    let _queued = QueueSlot(&limiter.waiting);
+    slots.acquire_owned().await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Verification limiter closed: {}", e),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_queue_then_reject() {
+        let config = AppConfig {
+            max_concurrent_verifications: 1,
+            verification_queue: 1,
+            ..(*crate::api::test_helpers::test_config()).clone()
+        };
+
+        let running = acquire(&config).await.unwrap();
+
+        // The second request waits for the slot
+        let queued = tokio::spawn({
+            let config = config.clone();
+            async move { acquire(&config).await.map(|_| ()) }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!queued.is_finished());
+
+        // The queue is full, so the third is turned away
+        let (status, _) = acquire(&config).await.unwrap_err();
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        drop(running);
+        queued.await.unwrap().unwrap();
+        // Both the slot and the queue are free again
+        let _permit = acquire(&config).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_wait_frees_queue() {
+        let config = AppConfig {
+            max_concurrent_verifications: 1,
+            verification_queue: 1,
+            ..(*crate::api::test_helpers::test_config()).clone()
+        };
+        let running = acquire(&config).await.unwrap();
+
+        // A client that gives up while queued
+        let queued = tokio::spawn({
+            let config = config.clone();
+            async move { acquire(&config).await.map(|_| ()) }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        queued.abort();
+        assert!(queued.await.unwrap_err().is_cancelled());
+
+        // Its place in the queue is free again
+        let next = tokio::spawn({
+            let config = config.clone();
+            async move { acquire(&config).await.map(|_| ()) }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!next.is_finished());
+        drop(running);
+        next.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_limit_follows_reload() {
+        let config = AppConfig {
+            max_concurrent_verifications: 1,
+            verification_queue: 0,
+            ..(*crate::api::test_helpers::test_config()).clone()
+        };
+        let _running = acquire(&config).await.unwrap();
+        assert_eq!(
+            acquire(&config).await.unwrap_err().0,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        // A raised limit applies to the same runtime state right away
+        let reloaded = AppConfig {
+            max_concurrent_verifications: 2,
+            ..config.clone()
+        };
+        let _second = acquire(&reloaded).await.unwrap();
+    }
+}
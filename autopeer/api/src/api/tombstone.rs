@@ -0,0 +1,189 @@
+//! Configs of deleted peerings, kept as `{iface}-{deleted_at}.conf` with `tombstone_deletes`
+//!
+//! Tombstones are records, not backups: private and preshared keys are left out.
+
+use crate::util::private_file;
+use crate::wireguard::{IniDocument, RegistryAuthConfig};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Keys never kept in a tombstone
+const SECRET_KEYS: [&str; 2] = ["PrivateKey", "PresharedKey"];
+
+/// A deleted peering's config, as found in the tombstone directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tombstone {
+    pub asn: u32,
+    /// When the peering was deleted (Unix timestamp)
+    pub deleted_at: u64,
+    pub path: PathBuf,
+}
+
+/// Path of the tombstone for an interface deleted at `deleted_at`; `seq` numbers
+/// further deletions of the same interface within that second
+pub fn tombstone_path(deleted_dir: &Path, iface: &str, deleted_at: u64, seq: u32) -> PathBuf {
+    match seq {
+        0 => deleted_dir.join(format!("{}-{}.conf", iface, deleted_at)),
+        seq => deleted_dir.join(format!("{}-{}-{}.conf", iface, deleted_at, seq)),
+    }
+}
+
+/// The config without its secret key lines
+fn without_secrets(content: &str) -> String {
+    content
+        .lines()
+        .filter(|line| {
+            let key = line
+                .split_once('=')
+                .map(|(key, _)| key.trim())
+                .unwrap_or_default();
+            !SECRET_KEYS
+                .iter()
+                .any(|secret| secret.eq_ignore_ascii_case(key))
+        })
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+/// Keep a verified config, minus its keys, in the tombstone directory (owner-readable
+/// only) and remove the original, returning where the tombstone went
+pub fn bury(
+    config_path: &Path,
+    deleted_dir: &Path,
+    iface: &str,
+    deleted_at: u64,
+) -> Result<PathBuf, String> {
+    fs::create_dir_all(deleted_dir)
+        .map_err(|e| format!("Failed to create {:?}: {}", deleted_dir, e))?;
+    let content = fs::read_to_string(config_path)
+        .map_err(|e| format!("Failed to read {:?}: {}", config_path, e))?;
+    let content = without_secrets(&content);
+
+    let mut seq = 0;
+    let tombstone = loop {
+        let tombstone = tombstone_path(deleted_dir, iface, deleted_at, seq);
+        match private_file::create_new(&tombstone, &content) {
+            Ok(()) => break tombstone,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => seq += 1,
+            Err(e) => return Err(format!("Failed to write {:?}: {}", tombstone, e)),
+        }
+    };
+    fs::remove_file(config_path)
+        .map_err(|e| format!("Failed to remove {:?}: {}", config_path, e))?;
+    Ok(tombstone)
+}
+
+/// The registry authorization recorded in a tombstone, if any
+pub fn authorization(tombstone: &Path) -> Result<Option<RegistryAuthConfig>, String> {
+    let content = fs::read_to_string(tombstone)
+        .map_err(|e| format!("Failed to read {:?}: {}", tombstone, e))?;
+    RegistryAuthConfig::from_ini(&IniDocument::parse(&content))
+}
+
+/// Parse `wg-as{asn}-{deleted_at}.conf` or `wg-as{asn}-{deleted_at}-{seq}.conf`
+fn parse_name(name: &str) -> Option<(u32, u64, u32)> {
+    let mut parts = name
+        .strip_suffix(".conf")?
+        .strip_prefix("wg-as")?
+        .split('-');
+    let asn = parts.next()?.parse().ok()?;
+    let deleted_at = parts.next()?.parse().ok()?;
+    let seq = parts.next().map(str::parse).transpose().ok()?.unwrap_or(0);
+    parts.next().is_none().then_some((asn, deleted_at, seq))
+}
+
+/// All tombstones, oldest first (an absent directory has none)
+pub fn list(deleted_dir: &Path) -> Result<Vec<Tombstone>, String> {
+    let entries = match fs::read_dir(deleted_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", deleted_dir, e)),
+    };
+
+    let mut tombstones: Vec<(u32, Tombstone)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let (asn, deleted_at, seq) = parse_name(entry.file_name().to_str()?)?;
+            Some((
+                seq,
+                Tombstone {
+                    asn,
+                    deleted_at,
+                    path: entry.path(),
+                },
+            ))
+        })
+        .collect();
+    tombstones.sort_by_key(|(seq, tombstone)| (tombstone.deleted_at, tombstone.asn, *seq));
+    Ok(tombstones
+        .into_iter()
+        .map(|(_, tombstone)| tombstone)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bury_and_list() {
+        let root = tempfile::TempDir::new().unwrap();
+        let deleted_dir = root.path().join("deleted");
+        assert!(list(&deleted_dir).unwrap().is_empty());
+
+        let config_path = root.path().join("wg-as4242422225.conf");
+        fs::write(
+            &config_path,
+            "[Interface]\nPrivateKey = secret\n\n[Peer]\npresharedkey=secret\n",
+        )
+        .unwrap();
+        let tombstone = bury(&config_path, &deleted_dir, "wg-as4242422225", 1700000100).unwrap();
+        assert!(!config_path.exists());
+        // Keys are left out, and the rest is for the owner only
+        assert_eq!(
+            fs::read_to_string(&tombstone).unwrap(),
+            "[Interface]\n\n[Peer]\n"
+        );
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                fs::metadata(&tombstone).unwrap().permissions().mode() & 0o777,
+                0o600
+            );
+        }
+
+        // Deleted again within the same second: numbered rather than overwritten
+        fs::write(&config_path, "[Interface]\n").unwrap();
+        let again = bury(&config_path, &deleted_dir, "wg-as4242422225", 1700000100).unwrap();
+        assert_eq!(again, deleted_dir.join("wg-as4242422225-1700000100-1.conf"));
+        assert_eq!(
+            fs::read_to_string(&tombstone).unwrap(),
+            "[Interface]\n\n[Peer]\n"
+        );
+
+        // The same peering deleted again later gets its own tombstone
+        fs::write(&config_path, "[Interface]\n").unwrap();
+        bury(&config_path, &deleted_dir, "wg-as4242422225", 1700000300).unwrap();
+        let other = root.path().join("wg-as4242420257.conf");
+        fs::write(&other, "[Interface]\n").unwrap();
+        bury(&other, &deleted_dir, "wg-as4242420257", 1700000200).unwrap();
+        fs::write(deleted_dir.join("notes.txt"), "").unwrap();
+
+        let listed: Vec<(u32, u64)> = list(&deleted_dir)
+            .unwrap()
+            .iter()
+            .map(|tombstone| (tombstone.asn, tombstone.deleted_at))
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                (4242422225, 1700000100),
+                (4242422225, 1700000100),
+                (4242420257, 1700000200),
+                (4242422225, 1700000300)
+            ]
+        );
+        assert_eq!(list(&deleted_dir).unwrap()[1].path, again);
+    }
+}
@@ -0,0 +1,146 @@
+//! Explicit BGP channel settings, for setups whose base template doesn't provide them
+
+use std::str::FromStr;
+
+/// Import or export policy of a channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelFilter {
+    /// Our inline filter (valid networks, registered routes with `strict_import_filter`)
+    Builtin,
+    All,
+    None,
+    /// A filter defined in the main BIRD config
+    Named(String),
+}
+
+impl ChannelFilter {
+    /// What follows `import`/`export` in the channel, `None` for the inline filter
+    pub fn statement(&self) -> Option<String> {
+        match self {
+            ChannelFilter::Builtin => None,
+            ChannelFilter::All => Some("all".to_string()),
+            ChannelFilter::None => Some("none".to_string()),
+            ChannelFilter::Named(name) => Some(format!("filter {}", name)),
+        }
+    }
+}
+
+/// Whether `s` can be used verbatim as a BIRD symbol
+fn is_bird_symbol(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic())
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl FromStr for ChannelFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "builtin" => Ok(ChannelFilter::Builtin),
+            "all" => Ok(ChannelFilter::All),
+            "none" => Ok(ChannelFilter::None),
+            name if is_bird_symbol(name) => Ok(ChannelFilter::Named(name.to_string())),
+            other => Err(format!(
+                "Invalid channel filter {:?}: not builtin, all, none or a BIRD symbol",
+                other
+            )),
+        }
+    }
+}
+
+/// One channel's table and filters
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelConfig {
+    pub table: String,
+    pub import: ChannelFilter,
+    pub export: ChannelFilter,
+}
+
+impl ChannelConfig {
+    /// Defaults for a family: BIRD's master table and our inline filters
+    pub fn new(ipv6: bool) -> Self {
+        ChannelConfig {
+            table: if ipv6 { "master6" } else { "master4" }.to_string(),
+            import: ChannelFilter::Builtin,
+            export: ChannelFilter::Builtin,
+        }
+    }
+
+    /// Parse `table=...,import=...,export=...` (any subset) on top of the family's defaults
+    pub fn parse(s: &str, ipv6: bool) -> Result<Self, String> {
+        let mut channel = ChannelConfig::new(ipv6);
+        for setting in s.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| format!("Channel setting {:?} must look like key=value", setting))?;
+            match key.trim() {
+                "table" if is_bird_symbol(value.trim()) => channel.table = value.trim().to_string(),
+                "table" => {
+                    return Err(format!(
+                        "Invalid channel table {:?}: not a BIRD symbol",
+                        value
+                    ))
+                }
+                "import" => channel.import = value.trim().parse()?,
+                "export" => channel.export = value.trim().parse()?,
+                other => {
+                    return Err(format!(
+                        "Unknown channel setting {:?} (table, import, export)",
+                        other
+                    ))
+                }
+            }
+        }
+        Ok(channel)
+    }
+}
+
+/// Both channels, rendered explicitly instead of being left to the base template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplicitChannels {
+    pub ipv4: ChannelConfig,
+    pub ipv6: ChannelConfig,
+}
+
+impl Default for ExplicitChannels {
+    fn default() -> Self {
+        ExplicitChannels {
+            ipv4: ChannelConfig::new(false),
+            ipv6: ChannelConfig::new(true),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_channel_config() {
+        assert_eq!(
+            ChannelConfig::parse("", false).unwrap(),
+            ChannelConfig::new(false)
+        );
+        assert_eq!(ChannelConfig::new(true).table, "master6");
+
+        let channel =
+            ChannelConfig::parse("table=dn42_v4, import=dn42_import_v4,export=none", false)
+                .unwrap();
+        assert_eq!(channel.table, "dn42_v4");
+        assert_eq!(
+            channel.import,
+            ChannelFilter::Named("dn42_import_v4".to_string())
+        );
+        assert_eq!(channel.export, ChannelFilter::None);
+        assert_eq!(
+            channel.import.statement().as_deref(),
+            Some("filter dn42_import_v4")
+        );
+        assert_eq!(ChannelFilter::Builtin.statement(), None);
+
+        assert!(ChannelConfig::parse("table=master4; import all", false).is_err());
+        assert!(ChannelConfig::parse("import=filter { accept; }", false).is_err());
+        assert!(ChannelConfig::parse("table", false).is_err());
+        assert!(ChannelConfig::parse("next_hop=self", false).is_err());
+    }
+}
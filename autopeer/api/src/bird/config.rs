@@ -1,5 +1,8 @@
-use crate::ipalloc::Ipv6LinkLocal;
+use super::ExplicitChannels;
+use crate::ipalloc::{AddressFamily, Ipv4Transfer, Ipv6LinkLocal, Ipv6Ula};
+use crate::registry::RouteObject;
 use std::fs;
+use std::net::Ipv4Addr;
 use std::path::Path;
 use tera::Context;
 
@@ -10,8 +13,33 @@ pub struct BirdPeerConfig {
     pub peer_name: String,
     pub interface_name: String,
     pub ips: Ipv6LinkLocal,
+    /// Bind the neighbor address to the interface (`fe80::...%iface`)
+    pub scoped_neighbor: bool,
+    /// Run the session over ULA transfer addresses instead of link-local
+    pub ula: Option<Ipv6Ula>,
+    /// Import limit per channel; the session's imports are blocked once exceeded
+    pub max_prefixes: Option<u32>,
+    /// Channels to configure (`Ipv4` runs the session over `ipv4`)
+    pub address_family: AddressFamily,
+    /// IPv4 transfer addresses, required for `AddressFamily::Ipv4`
+    pub ipv4: Option<Ipv4Transfer>,
+    /// Registered routes imports are restricted to, per family (empty leaves the family unrestricted)
+    pub import_routes: Vec<RouteObject>,
+    /// Router id for this side's session (`None` leaves it to BIRD)
+    pub router_id: Option<Ipv4Addr>,
+    /// Router id the peer asked for, used by `symmetric`
+    pub peer_router_id: Option<Ipv4Addr>,
+    /// `graceful restart on/off` for the session (`None` leaves BIRD's default)
+    pub graceful_restart: Option<bool>,
+    /// Template in the main BIRD config the session inherits from
+    pub template: String,
+    /// Channel tables and filters to spell out (`None` leaves them to the template)
+    pub channels: Option<ExplicitChannels>,
 }
 
+/// Template name operators conventionally give DN42 peer sessions
+pub const DEFAULT_TEMPLATE: &str = "dnpeers";
+
 impl BirdPeerConfig {
     /// Create a new BIRD peer configuration
     pub fn new(my_asn: u32, peer_asn: u32, peer_name: String, interface_name: String) -> Self {
@@ -23,23 +51,219 @@ impl BirdPeerConfig {
             peer_name,
             interface_name,
             ips,
+            scoped_neighbor: false,
+            ula: None,
+            max_prefixes: None,
+            address_family: AddressFamily::Dual,
+            ipv4: None,
+            import_routes: Vec::new(),
+            router_id: None,
+            peer_router_id: None,
+            graceful_restart: None,
+            template: DEFAULT_TEMPLATE.to_string(),
+            channels: None,
+        }
+    }
+
+    /// Inherit from another BIRD template than `dnpeers`
+    pub fn with_template(mut self, template: String) -> Self {
+        self.template = template;
+        self
+    }
+
+    /// Render channel tables and filters explicitly (`None` leaves them to the template)
+    pub fn with_channels(mut self, channels: Option<ExplicitChannels>) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    /// Enable or disable BGP graceful restart (`None` leaves BIRD's default)
+    pub fn with_graceful_restart(mut self, graceful_restart: Option<bool>) -> Self {
+        self.graceful_restart = graceful_restart;
+        self
+    }
+
+    /// Set the router id the peer's side of the session should use
+    pub fn with_peer_router_id(mut self, router_id: Option<Ipv4Addr>) -> Self {
+        self.peer_router_id = router_id;
+        self
+    }
+
+    /// Select the address families (channels) of the session
+    pub fn with_address_family(mut self, address_family: AddressFamily) -> Self {
+        self.address_family = address_family;
+        self
+    }
+
+    /// Set the IPv4 transfer addresses used by IPv4-only sessions
+    pub fn with_ipv4(mut self, ipv4: Option<Ipv4Transfer>) -> Self {
+        self.ipv4 = ipv4;
+        self
+    }
+
+    /// Only import the given routes and their more-specifics up to each max-length
+    pub fn with_import_routes(mut self, routes: Vec<RouteObject>) -> Self {
+        self.import_routes = routes;
+        self
+    }
+
+    /// Set the per-channel import limit (`None` for unlimited)
+    pub fn with_max_prefixes(mut self, max_prefixes: Option<u32>) -> Self {
+        self.max_prefixes = max_prefixes;
+        self
+    }
+
+    /// Use the given link-local addresses instead of deriving them from the ASNs (`None` keeps them)
+    pub fn with_link_local(mut self, ips: Option<Ipv6LinkLocal>) -> Self {
+        if let Some(ips) = ips {
+            self.ips = ips;
+        }
+        self
+    }
+
+    /// Use ULA transfer addresses for the session (`None` keeps link-local)
+    pub fn with_ula(mut self, ula: Option<Ipv6Ula>) -> Self {
+        self.ula = ula;
+        self
+    }
+
+    /// Enable or disable the `%iface` scope suffix on the neighbor address
+    pub fn with_scoped_neighbor(mut self, scoped: bool) -> Self {
+        self.scoped_neighbor = scoped;
+        self
+    }
+
+    /// Neighbor address as rendered, with the scope suffix if enabled
+    fn neighbor_addr(&self) -> String {
+        if let Some(ula) = &self.ula {
+            return ula.peer.clone();
+        }
+        if self.scoped_neighbor && !self.ips.peer.contains('%') {
+            format!("{}%{}", self.ips.peer, self.interface_name)
+        } else {
+            self.ips.peer.clone()
+        }
+    }
+
+    /// Build the mirrored configuration for the peer's side of the session
+    ///
+    /// The peer's local AS and address become ours and vice versa, so the
+    /// rendered output can be dropped into the peer's own BIRD config.
+    pub fn symmetric(&self, peer_name: String, interface_name: String) -> Self {
+        BirdPeerConfig {
+            my_asn: self.peer_asn,
+            peer_asn: self.my_asn,
+            peer_name,
+            interface_name,
+            ips: Ipv6LinkLocal {
+                local: format!("{}/64", self.ips.peer),
+                peer: self.ips.local_addr(),
+            },
+            scoped_neighbor: self.scoped_neighbor,
+            ula: self.ula.as_ref().map(|ula| Ipv6Ula {
+                local: format!("{}/64", ula.peer),
+                peer: ula.local_addr(),
+            }),
+            max_prefixes: self.max_prefixes,
+            address_family: self.address_family,
+            ipv4: self.ipv4.map(|ipv4| Ipv4Transfer {
+                local: ipv4.peer,
+                peer: ipv4.local,
+            }),
+            // These are the peer's routes; their side filters ours
+            import_routes: Vec::new(),
+            router_id: self.peer_router_id,
+            peer_router_id: self.router_id,
+            // Graceful restart only works if both sides agree on it
+            graceful_restart: self.graceful_restart,
+            // Our template name means nothing on their router
+            template: DEFAULT_TEMPLATE.to_string(),
+            // Neither do our tables and filters
+            channels: None,
         }
     }
 
     /// Generate BIRD configuration as string using Tera template
     pub fn to_config(&self) -> Result<String, String> {
-        // Create context
+        crate::templates::render("peer.conf", &self.template_context()?)
+    }
+
+    /// Template variables for `peer.conf`
+    pub(crate) fn template_context(&self) -> Result<Context, String> {
         let mut context = Context::new();
         context.insert("my_asn", &self.my_asn);
         context.insert("peer_asn", &self.peer_asn);
         context.insert("peer_name", &self.peer_name);
+        context.insert("template", &self.template);
         context.insert("interface_name", &self.interface_name);
-        context.insert("local_ip", &self.ips.local_addr());
-        context.insert("peer_ip", &self.ips.peer);
+        context.insert("max_prefixes", &self.max_prefixes);
+        context.insert("router_id", &self.router_id);
+        context.insert(
+            "graceful_restart",
+            &self
+                .graceful_restart
+                .map(|on| if on { "on" } else { "off" }),
+        );
+        context.insert("ipv4_channel", &self.address_family.has_ipv4());
+        context.insert("ipv6_channel", &self.address_family.has_ipv6());
+        context.insert("import_prefixes_v4", &self.import_prefixes(false));
+        context.insert("import_prefixes_v6", &self.import_prefixes(true));
+        for (suffix, channel) in [
+            ("v4", self.channels.as_ref().map(|channels| &channels.ipv4)),
+            ("v6", self.channels.as_ref().map(|channels| &channels.ipv6)),
+        ] {
+            context.insert(
+                format!("channel_table_{}", suffix),
+                &channel.map(|channel| &channel.table),
+            );
+            context.insert(
+                format!("channel_import_{}", suffix),
+                &channel.and_then(|channel| channel.import.statement()),
+            );
+            context.insert(
+                format!("channel_export_{}", suffix),
+                &channel.and_then(|channel| channel.export.statement()),
+            );
+        }
+
+        let (local_ip, peer_ip) = match (self.address_family, &self.ula) {
+            (AddressFamily::Ipv4, _) => {
+                let ipv4 = self
+                    .ipv4
+                    .ok_or("IPv4-only sessions need IPv4 transfer addresses")?;
+                (ipv4.local.to_string(), ipv4.peer.to_string())
+            }
+            (_, Some(ula)) => (ula.local_addr(), self.neighbor_addr()),
+            (_, None) => (self.ips.local_addr(), self.neighbor_addr()),
+        };
+        context.insert("local_ip", &local_ip);
+        context.insert("peer_ip", &peer_ip);
+        // IPv4 routes with IPv6 next hops only when IPv4 rides on the IPv6 session
+        context.insert(
+            "extended_next_hop",
+            &(self.address_family == AddressFamily::Dual),
+        );
+        context.insert(
+            "link_local",
+            &(self.ula.is_none() && self.address_family != AddressFamily::Ipv4),
+        );
+        Ok(context)
+    }
 
-        // Render template
-        let template = include_str!("peer.conf.tera");
-        crate::templates::render_template("peer.conf", template, &context)
+    /// Import routes of one family as BIRD prefix set entries (`prefix{len,max}` for more-specifics)
+    fn import_prefixes(&self, ipv6: bool) -> Vec<String> {
+        self.import_routes
+            .iter()
+            .filter(|route| route.is_ipv6() == ipv6)
+            .map(|route| {
+                let length = route.prefix_length();
+                if route.max_length > length {
+                    format!("{}{{{},{}}}", route.prefix, length, route.max_length)
+                } else {
+                    route.prefix.clone()
+                }
+            })
+            .collect()
     }
 
     /// Write configuration to file
@@ -102,4 +326,332 @@ mod tests {
         assert!(bird_conf.contains("local fe80::2225:257:0 as 4242420257"));
         assert!(bird_conf.contains("neighbor fe80::2225:257:1 as 4242422225"));
     }
+
+    #[test]
+    fn test_symmetric_peer_snippet() {
+        let config = BirdPeerConfig::new(
+            4242420257,
+            4242422225,
+            "AS4242422225".to_string(),
+            "wg-as4242422225".to_string(),
+        );
+
+        let snippet = config
+            .symmetric("AS4242420257".to_string(), "wg-as4242420257".to_string())
+            .to_config()
+            .unwrap();
+
+        // Peer's AS and address are local, ours is the neighbor
+        assert!(snippet.contains("protocol bgp autopeer_as4242420257"));
+        assert!(snippet.contains("local fe80::2225:257:1 as 4242422225"));
+        assert!(snippet.contains("neighbor fe80::2225:257:0 as 4242420257"));
+        assert!(snippet.contains("interface \"wg-as4242420257\""));
+    }
+
+    #[test]
+    fn test_scoped_neighbor() {
+        let config = BirdPeerConfig::new(
+            4242420257,
+            4242422225,
+            "Test".to_string(),
+            "wg-as4242422225".to_string(),
+        )
+        .with_scoped_neighbor(true);
+
+        let bird_conf = config.to_config().unwrap();
+        assert!(bird_conf.contains("neighbor fe80::2225:257:1%wg-as4242422225 as 4242422225"));
+
+        // An address that already carries a scope is left alone
+        let mut config = config;
+        config.ips.peer = "fe80::2225:257:1%wg-as4242422225".to_string();
+        let bird_conf = config.to_config().unwrap();
+        assert!(bird_conf.contains("neighbor fe80::2225:257:1%wg-as4242422225 as"));
+        assert!(!bird_conf.contains("%wg-as4242422225%"));
+    }
+
+    #[test]
+    fn test_neighbor_modes() {
+        let link_local = BirdPeerConfig::new(
+            4242420257,
+            4242422225,
+            "Test".to_string(),
+            "wg-as4242422225".to_string(),
+        );
+        let prefix = "fd42:4242:257::/48".parse().unwrap();
+        let ula = BirdPeerConfig::new(
+            4242420257,
+            4242422225,
+            "Test".to_string(),
+            "wg-as4242422225".to_string(),
+        )
+        .with_scoped_neighbor(true)
+        .with_ula(Some(Ipv6Ula::from_peer_asn(&prefix, 4242422225)));
+
+        let bird_conf = link_local.to_config().unwrap();
+        assert!(bird_conf.contains("neighbor fe80::2225:257:1 as 4242422225"));
+
+        // ULA neighbors are global-scope, so no %iface suffix
+        let bird_conf = ula.to_config().unwrap();
+        assert!(bird_conf.contains("local fd42:4242:257:2225::1 as 4242420257"));
+        assert!(bird_conf.contains("neighbor fd42:4242:257:2225::2 as 4242422225"));
+
+        let snippet = ula
+            .symmetric("AS4242420257".to_string(), "wg-as4242420257".to_string())
+            .to_config()
+            .unwrap();
+        assert!(snippet.contains("local fd42:4242:257:2225::2 as 4242422225"));
+        assert!(snippet.contains("neighbor fd42:4242:257:2225::1 as 4242420257"));
+    }
+
+    #[test]
+    fn test_transfer_net_header() {
+        let config = BirdPeerConfig::new(
+            4242420257,
+            4242422225,
+            "Test".to_string(),
+            "wg-as4242422225".to_string(),
+        );
+        let bird_conf = config.to_config().unwrap();
+        assert!(bird_conf.contains("IPv6 Link-Local with MP-BGP"));
+        assert!(bird_conf.contains("- IPv6 LL with MP-BGP\";"));
+
+        let prefix = "fd42:4242:257::/48".parse().unwrap();
+        let bird_conf = config
+            .with_ula(Some(Ipv6Ula::from_peer_asn(&prefix, 4242422225)))
+            .to_config()
+            .unwrap();
+        assert!(!bird_conf.contains("Link-Local"));
+        assert!(!bird_conf.contains("fe80:"));
+        assert!(bird_conf.contains("IPv6 transfer net with MP-BGP and Extended Next Hop"));
+        assert!(bird_conf.contains("- IPv6 ULA with MP-BGP\";"));
+    }
+
+    #[test]
+    fn test_import_limit() {
+        let config = BirdPeerConfig::new(
+            4242420257,
+            4242422225,
+            "Test".to_string(),
+            "wg-as4242422225".to_string(),
+        );
+
+        let bird_conf = config.to_config().unwrap();
+        assert!(!bird_conf.contains("import limit"));
+
+        let bird_conf = config.with_max_prefixes(Some(250)).to_config().unwrap();
+        // One limit per channel (ipv4 and ipv6)
+        assert_eq!(
+            bird_conf.matches("import limit 250 action block;").count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_router_id() {
+        let config = BirdPeerConfig::new(
+            4242420257,
+            4242422225,
+            "Test".to_string(),
+            "wg-as4242422225".to_string(),
+        );
+
+        // Absent: BIRD picks the router id
+        let bird_conf = config.to_config().unwrap();
+        assert!(!bird_conf.contains("router id"));
+
+        // The peer's router id only goes into their side of the session
+        let config = config.with_peer_router_id(Some(Ipv4Addr::new(172, 20, 53, 97)));
+        assert!(!config.to_config().unwrap().contains("router id"));
+        let snippet = config
+            .symmetric("AS4242420257".to_string(), "wg-as4242420257".to_string())
+            .to_config()
+            .unwrap();
+        assert!(snippet.contains("    direct;\n    router id 172.20.53.97;\n"));
+    }
+
+    #[test]
+    fn test_import_routes_max_length() {
+        let config = BirdPeerConfig::new(
+            4242420257,
+            4242422225,
+            "Test".to_string(),
+            "wg-as4242422225".to_string(),
+        );
+        let unrestricted = config.to_config().unwrap();
+        assert!(!unrestricted.contains("net ~"));
+
+        let bird_conf = config
+            .with_import_routes(vec![
+                // Explicit max-length: more-specifics up to /29
+                RouteObject {
+                    prefix: "172.20.0.0/26".to_string(),
+                    max_length: 29,
+                },
+                // No max-length: exact match only
+                RouteObject {
+                    prefix: "fd00:2225::/48".to_string(),
+                    max_length: 48,
+                },
+            ])
+            .to_config()
+            .unwrap();
+
+        assert!(bird_conf.contains(
+            "if is_valid_network_v4() && !is_self_net_v4() && net ~ [ 172.20.0.0/26{26,29} ] then {"
+        ));
+        assert!(bird_conf.contains(
+            "if is_valid_network_v6() && !is_self_net_v6() && net ~ [ fd00:2225::/48 ] then {"
+        ));
+        // Exports are not restricted
+        assert_eq!(bird_conf.matches("net ~").count(), 2);
+    }
+
+    #[test]
+    fn test_graceful_restart() {
+        let config = family_config(AddressFamily::Dual);
+        assert!(!config.to_config().unwrap().contains("graceful restart"));
+
+        let bird_conf = config
+            .with_graceful_restart(Some(true))
+            .to_config()
+            .unwrap();
+        assert!(
+            bird_conf.contains("    graceful restart on;\n"),
+            "{}",
+            bird_conf
+        );
+
+        let bird_conf = family_config(AddressFamily::Dual)
+            .with_graceful_restart(Some(false))
+            .to_config()
+            .unwrap();
+        assert!(
+            bird_conf.contains("    graceful restart off;\n"),
+            "{}",
+            bird_conf
+        );
+    }
+
+    #[test]
+    fn test_custom_template() {
+        let bird_conf = family_config(AddressFamily::Dual)
+            .with_template("dn42_peers".to_string())
+            .to_config()
+            .unwrap();
+        assert!(
+            bird_conf.contains("protocol bgp autopeer_as4242422225 from dn42_peers {"),
+            "{}",
+            bird_conf
+        );
+
+        // The peer's side uses the conventional name, whatever ours is called
+        let theirs = family_config(AddressFamily::Dual)
+            .with_template("dn42_peers".to_string())
+            .symmetric("Us".to_string(), "wg-as4242420257".to_string());
+        assert!(theirs
+            .to_config()
+            .unwrap()
+            .contains("protocol bgp autopeer_as4242420257 from dnpeers {"));
+    }
+
+    #[test]
+    fn test_explicit_channels() {
+        let implicit = family_config(AddressFamily::Dual).to_config().unwrap();
+        assert!(!implicit.contains("table "));
+
+        let channels = ExplicitChannels {
+            ipv4: crate::bird::ChannelConfig::parse(
+                "table=dn42_v4,import=dn42_import_v4,export=all",
+                false,
+            )
+            .unwrap(),
+            ipv6: crate::bird::ChannelConfig::parse("export=none", true).unwrap(),
+        };
+        let config = family_config(AddressFamily::Dual)
+            .with_max_prefixes(Some(1000))
+            .with_channels(Some(channels));
+        let bird_conf = config.to_config().unwrap();
+        let ipv4 = [
+            "    ipv4 {",
+            "        table dn42_v4;",
+            "        extended next hop yes;",
+            "        import keep filtered;",
+            "        import limit 1000 action block;",
+            "        import filter dn42_import_v4;",
+            "        export all;",
+            "    };",
+        ]
+        .join("\n");
+        assert!(bird_conf.contains(&ipv4), "{}", bird_conf);
+        // Builtin keeps our inline filter inside the explicit channel
+        assert!(
+            bird_conf.contains("    ipv6 {\n        table master6;\n        import keep filtered;"),
+            "{}",
+            bird_conf
+        );
+        assert!(bird_conf.contains("if is_valid_network_v6() && !is_self_net_v6() then"));
+        assert!(
+            bird_conf.contains("        export none;\n    };\n}"),
+            "{}",
+            bird_conf
+        );
+        assert!(!bird_conf.contains("is_valid_network_v4"));
+
+        // The peer's router has its own tables and filters
+        let theirs = config.symmetric("Us".to_string(), "wg-as4242420257".to_string());
+        assert_eq!(theirs.channels, None);
+        assert!(!theirs.to_config().unwrap().contains("table "));
+    }
+
+    fn family_config(family: AddressFamily) -> BirdPeerConfig {
+        BirdPeerConfig::new(
+            4242420257,
+            4242422225,
+            "Test".to_string(),
+            "wg-as4242422225".to_string(),
+        )
+        .with_address_family(family)
+    }
+
+    #[test]
+    fn test_dual_stack_family() {
+        let bird_conf = family_config(AddressFamily::Dual).to_config().unwrap();
+        assert!(bird_conf.contains("ipv4 {"));
+        assert!(bird_conf.contains("ipv6 {"));
+        assert!(bird_conf.contains("extended next hop yes"));
+        assert!(bird_conf.contains("neighbor fe80::2225:257:1 as 4242422225"));
+    }
+
+    #[test]
+    fn test_ipv6_only_family() {
+        let bird_conf = family_config(AddressFamily::Ipv6).to_config().unwrap();
+        assert!(!bird_conf.contains("ipv4 {"));
+        assert!(bird_conf.contains("ipv6 {"));
+        assert!(!bird_conf.contains("extended next hop"));
+        assert!(bird_conf.contains("neighbor fe80::2225:257:1 as 4242422225"));
+    }
+
+    #[test]
+    fn test_ipv4_only_family() {
+        // Without transfer addresses there is nothing to run the session over
+        assert!(family_config(AddressFamily::Ipv4).to_config().is_err());
+
+        let config = family_config(AddressFamily::Ipv4).with_ipv4(Some(Ipv4Transfer {
+            local: "172.20.53.97".parse().unwrap(),
+            peer: "172.22.108.1".parse().unwrap(),
+        }));
+        let bird_conf = config.to_config().unwrap();
+        assert!(bird_conf.contains("ipv4 {"));
+        assert!(!bird_conf.contains("ipv6 {"));
+        assert!(!bird_conf.contains("extended next hop"));
+        assert!(bird_conf.contains("local 172.20.53.97 as 4242420257"));
+        assert!(bird_conf.contains("neighbor 172.22.108.1 as 4242422225"));
+
+        let snippet = config
+            .symmetric("AS4242420257".to_string(), "wg-as4242420257".to_string())
+            .to_config()
+            .unwrap();
+        assert!(snippet.contains("local 172.22.108.1 as 4242422225"));
+        assert!(snippet.contains("neighbor 172.20.53.97 as 4242420257"));
+    }
 }
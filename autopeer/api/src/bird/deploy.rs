@@ -1,60 +1,246 @@
-use std::path::PathBuf;
-use std::process::Command;
+use crate::config::SystemConfig;
+use crate::util::exec;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+/// Messages birdc prints (on stdout, often with exit 0) when the new config is rejected
+const RECONFIGURE_FAILURE_MARKERS: &[&str] =
+    &["Reconfiguration failed", "syntax error", "parse error"];
+
+/// Path of the BIRD peer config file for an ASN
+pub fn config_path(peers_dir: &Path, asn: u32) -> PathBuf {
+    peers_dir.join(format!("autopeer_as{}.conf", asn))
+}
 
 /// Deploy a BIRD BGP peer configuration
-pub fn deploy_config(config_content: &str, asn: u32) -> Result<(), String> {
-    let config_path = PathBuf::from("/etc/bird/peers").join(format!("autopeer_as{}.conf", asn));
+///
+/// If BIRD rejects it, the previous config (or none) is put back and BIRD reloaded again.
+pub async fn deploy_config(
+    system: &SystemConfig,
+    config_content: &str,
+    asn: u32,
+) -> Result<(), String> {
+    let config_path = config_path(&system.bird_peers_dir, asn);
 
     // Ensure the peers directory exists
-    let peers_dir = PathBuf::from("/etc/bird/peers");
+    let peers_dir = &system.bird_peers_dir;
     if !peers_dir.exists() {
-        std::fs::create_dir_all(&peers_dir)
+        tokio::fs::create_dir_all(peers_dir)
+            .await
             .map_err(|e| format!("Failed to create peers directory {:?}: {}", peers_dir, e))?;
     }
 
-    // Write config to /etc/bird/peers/
-    std::fs::write(&config_path, config_content)
-        .map_err(|e| format!("Failed to write config to {:?}: {}", config_path, e))?;
+    // Write config to the peers directory
+    let previous = tokio::fs::read(&config_path).await.ok();
+    write_atomic(&config_path, config_content.as_bytes()).await?;
 
     // Reload BIRD configuration using birdc configure
-    let output = Command::new("birdc")
-        .arg("configure")
-        .output()
-        .map_err(|e| format!("Failed to run birdc configure: {}", e))?;
+    let Err(e) = reload(system).await else {
+        return Ok(());
+    };
 
-    if !output.status.success() {
-        return Err(format!(
-            "birdc configure failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        ));
+    let restored = match &previous {
+        Some(previous) => write_atomic(&config_path, previous).await,
+        None => tokio::fs::remove_file(&config_path)
+            .await
+            .map_err(|e| format!("Failed to remove config file {:?}: {}", config_path, e)),
+    };
+    match restored {
+        Ok(()) => reload(system).await,
+        Err(e) => Err(e),
+    }
+    .map_err(|rollback| {
+        format!(
+            "{}; restoring the previous config also failed: {}",
+            e, rollback
+        )
+    })?;
+    Err(e)
+}
+
+/// Peer configs as they were before `write_configs` replaced them (`None` for new files)
+pub struct ConfigSnapshot {
+    previous: Vec<(PathBuf, Option<Vec<u8>>)>,
+}
+
+/// ASNs with a BIRD peer config in the peers directory, sorted
+pub fn deployed_asns(peers_dir: &Path) -> Vec<u32> {
+    let Ok(entries) = std::fs::read_dir(peers_dir) else {
+        return Vec::new();
+    };
+
+    let mut asns: Vec<u32> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.strip_prefix("autopeer_as")?
+                .strip_suffix(".conf")?
+                .parse()
+                .ok()
+        })
+        .collect();
+    asns.sort_unstable();
+    asns
+}
+
+/// Write several peer configs without reloading BIRD, for a single `reload` afterwards
+///
+/// If a write fails, the ones already written are put back before returning the error.
+pub async fn write_configs(
+    system: &SystemConfig,
+    configs: &[(u32, String)],
+) -> Result<ConfigSnapshot, String> {
+    let mut snapshot = ConfigSnapshot {
+        previous: Vec::new(),
+    };
+    for (asn, content) in configs {
+        let path = config_path(&system.bird_peers_dir, *asn);
+        let previous = tokio::fs::read(&path).await.ok();
+        if let Err(e) = write_atomic(&path, content.as_bytes()).await {
+            put_back(&snapshot).await.map_err(|rollback| {
+                format!(
+                    "{}; restoring the previous configs also failed: {}",
+                    e, rollback
+                )
+            })?;
+            return Err(e);
+        }
+        snapshot.previous.push((path, previous));
     }
+    Ok(snapshot)
+}
+
+/// Put back the configs `write_configs` replaced and reload BIRD
+pub async fn restore_configs(
+    system: &SystemConfig,
+    snapshot: &ConfigSnapshot,
+) -> Result<(), String> {
+    put_back(snapshot).await?;
+    reload(system).await
+}
 
+async fn put_back(snapshot: &ConfigSnapshot) -> Result<(), String> {
+    for (path, previous) in &snapshot.previous {
+        match previous {
+            Some(previous) => write_atomic(path, previous).await?,
+            None => tokio::fs::remove_file(path)
+                .await
+                .map_err(|e| format!("Failed to remove config file {:?}: {}", path, e))?,
+        }
+    }
     Ok(())
 }
 
+/// Write a file via a temp file and rename, so a full disk can't leave a truncated config
+/// where BIRD picks it up (the temp name doesn't match `*.conf`)
+async fn write_atomic(path: &Path, content: &[u8]) -> Result<(), String> {
+    let tmp = path.with_extension("conf.tmp");
+    let written = async {
+        let mut file = tokio::fs::File::create(&tmp).await?;
+        file.write_all(content).await?;
+        file.sync_all().await
+    }
+    .await;
+    if let Err(e) = written {
+        let _ = tokio::fs::remove_file(&tmp).await;
+        return Err(format!("Failed to write config to {:?}: {}", tmp, e));
+    }
+
+    tokio::fs::rename(&tmp, path)
+        .await
+        .map_err(|e| format!("Failed to move config into place at {:?}: {}", path, e))
+}
+
 /// Remove a BIRD BGP peer configuration
-pub fn remove_config(asn: u32) -> Result<(), String> {
-    let config_path = PathBuf::from("/etc/bird/peers").join(format!("autopeer_as{}.conf", asn));
+pub async fn remove_config(system: &SystemConfig, asn: u32) -> Result<(), String> {
+    let config_path = config_path(&system.bird_peers_dir, asn);
 
     // Remove config file if it exists
     if config_path.exists() {
-        std::fs::remove_file(&config_path)
+        tokio::fs::remove_file(&config_path)
+            .await
             .map_err(|e| format!("Failed to remove config file {:?}: {}", config_path, e))?;
     }
 
     // Reload BIRD configuration
-    let output = Command::new("birdc")
-        .arg("configure")
-        .output()
-        .map_err(|e| format!("Failed to run birdc configure: {}", e))?;
+    reload(system).await
+}
+
+/// Name of the BGP protocol BIRD runs for a peer
+pub fn protocol_name(asn: u32) -> String {
+    format!("autopeer_as{}", asn)
+}
+
+/// Check whether the BGP session with a peer is established using `birdc show protocols`
+pub async fn is_session_established(system: &SystemConfig, asn: u32) -> Result<bool, String> {
+    let name = protocol_name(asn);
+    let output = show_protocols(system, Some(&name)).await?;
+    Ok(protocol_established(&output, &name))
+}
+
+/// `birdc show protocols` for all protocols at once, to look up peers with `session_established`
+pub async fn show_all_protocols(system: &SystemConfig) -> Result<String, String> {
+    show_protocols(system, None).await
+}
+
+/// Whether a peer's BGP session is established according to `show protocols` output
+pub fn session_established(output: &str, asn: u32) -> bool {
+    protocol_established(output, &protocol_name(asn))
+}
+
+/// Run `birdc show protocols`, for one protocol or all of them
+async fn show_protocols(system: &SystemConfig, name: Option<&str>) -> Result<String, String> {
+    let mut args = vec!["show", "protocols"];
+    args.extend(name);
+    let output = exec::run(
+        &system.birdc_bin,
+        &args,
+        Duration::from_secs(system.birdc_timeout_secs),
+    )
+    .await?;
 
-    if !output.status.success() {
+    if !output.success() {
         return Err(format!(
-            "birdc configure failed: {}",
-            String::from_utf8_lossy(&output.stderr)
+            "birdc show protocols failed: {}",
+            output.stderr.trim()
         ));
     }
 
+    Ok(output.stdout)
+}
+
+/// Find the protocol's row in `show protocols` output and check its BGP state
+fn protocol_established(output: &str, name: &str) -> bool {
+    output
+        .lines()
+        .find(|line| line.split_whitespace().next() == Some(name))
+        .is_some_and(|line| line.contains("Established"))
+}
+
+/// Reload BIRD configuration using birdc configure
+pub async fn reload(system: &SystemConfig) -> Result<(), String> {
+    let output = exec::run(
+        &system.birdc_bin,
+        &["configure"],
+        Duration::from_secs(system.birdc_timeout_secs),
+    )
+    .await?;
+
+    // BIRD reports config errors on stdout, so look there too
+    let rejected = RECONFIGURE_FAILURE_MARKERS
+        .iter()
+        .any(|marker| output.stdout.contains(marker));
+
+    if !output.success() || rejected {
+        let details = [output.stdout.trim(), output.stderr.trim()]
+            .into_iter()
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        return Err(format!("birdc configure failed: {}", details));
+    }
+
     Ok(())
 }
 
@@ -67,12 +253,135 @@ mod tests {
         // Verify the expected path format
         let asn: u32 = 4242420257;
         let expected_path = PathBuf::from("/etc/bird/peers/autopeer_as4242420257.conf");
-        let actual_path = PathBuf::from("/etc/bird/peers").join(format!("autopeer_as{}.conf", asn));
+        let actual_path = config_path(Path::new("/etc/bird/peers"), asn);
 
         assert_eq!(actual_path, expected_path);
     }
 
-    // Note: Deployment tests are skipped as they require:
+    #[test]
+    fn test_protocol_established() {
+        let output = "BIRD 2.0.12 ready.\n\
+                      Name       Proto      Table      State  Since         Info\n\
+                      autopeer_as4242421816 BGP        ---        up     2024-01-01 12:00:00  Established\n\
+                      autopeer_as4242422225 BGP        ---        start  2024-01-01 12:00:00  Active        Socket: Connection refused\n";
+
+        assert!(protocol_established(output, "autopeer_as4242421816"));
+        assert!(!protocol_established(output, "autopeer_as4242422225"));
+        assert!(!protocol_established(output, "autopeer_as4242420257"));
+    }
+
+    // Note: Deployment tests against a real BIRD are skipped as they require:
     // 1. Root privileges to write to /etc/bird/peers/
     // 2. BIRD to be installed and running
+
+    fn system_with_birdc(root: &Path, script: &str) -> SystemConfig {
+        let mut system = crate::api::test_helpers::test_system_config(root);
+        system.birdc_bin =
+            crate::api::test_helpers::write_stub(&root.join("bin"), "birdc-test", script);
+        system
+    }
+
+    #[tokio::test]
+    async fn test_reload_success() {
+        let root = tempfile::TempDir::new().unwrap();
+        let system = system_with_birdc(
+            root.path(),
+            "#!/bin/sh\necho \"Reading configuration from /etc/bird/bird.conf\"\necho \"Reconfigured\"\n",
+        );
+
+        deploy_config(&system, "protocol bgp test {}", 4242422225)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reload_surfaces_stdout_parse_error() {
+        let root = tempfile::TempDir::new().unwrap();
+        // birdc exits 0 and reports the error on stdout
+        let system = system_with_birdc(
+            root.path(),
+            "#!/bin/sh\necho \"Reading configuration from /etc/bird/bird.conf\"\necho \"/etc/bird/peers/autopeer_as4242422225.conf:3:5 syntax error, unexpected CF_SYM_UNDEFINED\"\n",
+        );
+
+        let err = deploy_config(&system, "protocol bgp {", 4242422225)
+            .await
+            .unwrap_err();
+        assert!(err.contains("autopeer_as4242422225.conf:3:5 syntax error"));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_config_is_rolled_back() {
+        let root = tempfile::TempDir::new().unwrap();
+        let system = system_with_birdc(
+            root.path(),
+            "#!/bin/sh\necho \"Reconfiguration failed\"\necho \"bird: disk full\" >&2\nexit 1\n",
+        );
+        let path = config_path(&system.bird_peers_dir, 4242422225);
+
+        // New peering: the rejected file is removed
+        let err = deploy_config(&system, "protocol bgp {", 4242422225)
+            .await
+            .unwrap_err();
+        assert!(err.contains("Reconfiguration failed"));
+        assert!(err.contains("bird: disk full"));
+        assert!(!path.exists());
+
+        // Existing peering: the previous config is put back
+        std::fs::write(&path, "protocol bgp old {}").unwrap();
+        deploy_config(&system, "protocol bgp {", 4242422225)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "protocol bgp old {}"
+        );
+
+        // No temp files are left behind
+        let leftovers: Vec<_> = std::fs::read_dir(&system.bird_peers_dir)
+            .unwrap()
+            .flatten()
+            .filter(|entry| entry.path() != path)
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_write_and_restore_configs() {
+        let root = tempfile::TempDir::new().unwrap();
+        let system = system_with_birdc(root.path(), "#!/bin/sh\necho \"Reconfigured\"\n");
+        std::fs::create_dir_all(&system.bird_peers_dir).unwrap();
+        let existing = config_path(&system.bird_peers_dir, 4242421816);
+        std::fs::write(&existing, "protocol bgp old {}").unwrap();
+
+        let configs = [
+            (4242421816, "protocol bgp new {}".to_string()),
+            (4242422225, "protocol bgp added {}".to_string()),
+        ];
+        let snapshot = write_configs(&system, &configs).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&existing).unwrap(),
+            "protocol bgp new {}"
+        );
+        assert_eq!(
+            deployed_asns(&system.bird_peers_dir),
+            vec![4242421816, 4242422225]
+        );
+
+        restore_configs(&system, &snapshot).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&existing).unwrap(),
+            "protocol bgp old {}"
+        );
+        assert_eq!(deployed_asns(&system.bird_peers_dir), vec![4242421816]);
+    }
+
+    #[tokio::test]
+    async fn test_reload_timeout() {
+        let root = tempfile::TempDir::new().unwrap();
+        let mut system = system_with_birdc(root.path(), "#!/bin/sh\nexec sleep 10\n");
+        system.birdc_timeout_secs = 1;
+
+        let err = remove_config(&system, 4242422225).await.unwrap_err();
+        assert!(err.contains("timed out"));
+    }
 }
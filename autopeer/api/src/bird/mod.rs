@@ -1,5 +1,8 @@
+mod channel;
 mod config;
 pub mod deploy;
+pub mod status;
 
+pub use channel::{ChannelConfig, ChannelFilter, ExplicitChannels};
 pub use config::BirdPeerConfig;
 pub use deploy::{deploy_config, remove_config};
@@ -0,0 +1,96 @@
+//! BGP session states of all peers, compared across a BIRD reload
+
+use std::collections::BTreeMap;
+
+/// Whether each peer's session is established, by ASN
+pub type SessionStates = BTreeMap<u32, bool>;
+
+/// Read the state of every `autopeer_as*` protocol from `birdc show protocols` output
+pub fn parse_sessions(output: &str) -> SessionStates {
+    output
+        .lines()
+        .filter_map(|line| {
+            let asn = line
+                .split_whitespace()
+                .next()?
+                .strip_prefix("autopeer_as")?
+                .parse()
+                .ok()?;
+            Some((asn, line.contains("Established")))
+        })
+        .collect()
+}
+
+/// Peers whose session was established before and isn't (or is gone) after, sorted
+pub fn dropped_sessions(before: &SessionStates, after: &SessionStates) -> Vec<u32> {
+    before
+        .iter()
+        .filter(|(asn, established)| **established && !after.get(asn).copied().unwrap_or(false))
+        .map(|(asn, _)| *asn)
+        .collect()
+}
+
+/// Whether more sessions dropped than the reload may cost
+pub fn too_many_dropped(before: &SessionStates, after: &SessionStates, max_dropped: usize) -> bool {
+    dropped_sessions(before, after).len() > max_dropped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn states(sessions: &[(u32, bool)]) -> SessionStates {
+        sessions.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_parse_sessions() {
+        let output = "BIRD 2.0.12 ready.\n\
+                      Name       Proto      Table      State  Since         Info\n\
+                      device1    Device     ---        up     2024-01-01 12:00:00\n\
+                      autopeer_as4242421816 BGP        ---        up     2024-01-01 12:00:00  Established\n\
+                      autopeer_as4242422225 BGP        ---        start  2024-01-01 12:00:00  Active        Socket: Connection refused\n";
+
+        assert_eq!(
+            parse_sessions(output),
+            states(&[(4242421816, true), (4242422225, false)])
+        );
+    }
+
+    #[test]
+    fn test_dropped_sessions() {
+        let before = states(&[
+            (4242421816, true),
+            (4242422225, false),
+            (4242422601, true),
+            (4242420257, true),
+        ]);
+        // One re-established, one still down, one gone from the output, one that was down anyway
+        let after = states(&[(4242421816, true), (4242422225, false), (4242422601, false)]);
+
+        assert_eq!(
+            dropped_sessions(&before, &after),
+            vec![4242420257, 4242422601]
+        );
+        assert!(too_many_dropped(&before, &after, 0));
+        assert!(too_many_dropped(&before, &after, 1));
+        assert!(!too_many_dropped(&before, &after, 2));
+
+        // Sessions that come up during the reload never count against it
+        let recovered = states(&[
+            (4242421816, true),
+            (4242422225, true),
+            (4242422601, true),
+            (4242420257, true),
+        ]);
+        assert!(dropped_sessions(&before, &recovered).is_empty());
+        assert!(!too_many_dropped(&before, &recovered, 0));
+
+        // Without anything established before, nothing can drop
+        assert!(!too_many_dropped(
+            &SessionStates::new(),
+            &SessionStates::new(),
+            0
+        ));
+    }
+}
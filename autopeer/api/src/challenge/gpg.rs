@@ -1,91 +1,226 @@
-use pgp::composed::{Deserializable, DetachedSignature, Message, SignedPublicKey};
-
-/// Verify a GPG signature for the given message
-/// Returns Ok(true) if signature is valid, Ok(false) if invalid, Err on failure
-pub fn verify_signature(
-    message: &str,
-    signature: &str,
-    public_key_str: &str,
-) -> Result<bool, String> {
-    // Parse the public key
-    let (public_key, _headers) = SignedPublicKey::from_string(public_key_str)
-        .map_err(|e| format!("Failed to parse public key: {}", e))?;
-
-    // Try to parse as a cleartext signed message first
-    if signature.contains("BEGIN PGP SIGNED MESSAGE") {
-        // Extract the actual message and signature from cleartext format
-        let parts: Vec<&str> = signature.split("-----BEGIN PGP SIGNATURE-----").collect();
-        if parts.len() != 2 {
-            return Err("Invalid cleartext signature format".to_string());
-        }
+use pgp::composed::{
+    Deserializable, DetachedSignature, Message, SignedPublicKey, SignedPublicSubKey,
+};
+use pgp::types::KeyDetails;
+use serde::{Deserialize, Serialize};
+
+/// Split a cleartext signed message into the signed text and the signature block
+fn split_cleartext(signature: &str) -> Result<(String, String), String> {
+    // Extract the actual message and signature from cleartext format
+    let parts: Vec<&str> = signature.split("-----BEGIN PGP SIGNATURE-----").collect();
+    if parts.len() != 2 {
+        return Err("Invalid cleartext signature format".to_string());
+    }
 
-        // Extract message from cleartext section
-        let cleartext_section = parts[0];
-        let lines: Vec<&str> = cleartext_section.lines().collect();
-
-        // Find where the message starts (after the Hash: line and empty line)
-        let mut message_start = 0;
-        for (i, line) in lines.iter().enumerate() {
-            if line.starts_with("Hash:") || line.starts_with("-----BEGIN PGP SIGNED MESSAGE-----") {
-                // Message starts after next empty line
-                if i + 1 < lines.len() && lines[i + 1].is_empty() {
-                    message_start = i + 2;
-                    break;
-                }
+    // Extract message from cleartext section
+    let cleartext_section = parts[0];
+    let lines: Vec<&str> = cleartext_section.lines().collect();
+
+    // Find where the message starts (after the Hash: line and empty line)
+    let mut message_start = 0;
+    for (i, line) in lines.iter().enumerate() {
+        if line.starts_with("Hash:") || line.starts_with("-----BEGIN PGP SIGNED MESSAGE-----") {
+            // Message starts after next empty line
+            if i + 1 < lines.len() && lines[i + 1].is_empty() {
+                message_start = i + 2;
+                break;
             }
         }
+    }
 
-        let msg_lines: Vec<&str> = lines
-            .iter()
-            .skip(message_start)
-            .take_while(|line| !line.is_empty())
-            .copied()
-            .collect();
+    // Undo dash-escaping: lines starting with '-' are signed with a "- " prefix (RFC 4880, 7.1)
+    let msg_lines: Vec<&str> = lines
+        .iter()
+        .skip(message_start)
+        .take_while(|line| !line.is_empty())
+        .map(|line| line.strip_prefix("- ").unwrap_or(line))
+        .collect();
 
-        let extracted_message = msg_lines.join("\n");
+    // Build the signature block
+    let sig_block = format!("-----BEGIN PGP SIGNATURE-----{}", parts[1]);
 
-        // Build the signature block
-        let sig_block = format!("-----BEGIN PGP SIGNATURE-----{}", parts[1]);
+    Ok((msg_lines.join("\n"), sig_block))
+}
 
-        // Parse the detached signature
-        let (sig, _) = DetachedSignature::from_string(&sig_block)
-            .map_err(|e| format!("Failed to parse signature: {}", e))?;
+/// The text of a cleartext signed message (not verified; pass it to `verify_signature`)
+pub fn signed_text(signature: &str) -> Result<String, String> {
+    if !signature.contains("BEGIN PGP SIGNED MESSAGE") {
+        return Err("Expected a cleartext signed message (gpg --clearsign)".to_string());
+    }
+    split_cleartext(signature).map(|(message, _)| message.trim().to_string())
+}
 
-        // For cleartext signatures, PGP uses canonical text mode
-        // The message needs to have a trailing newline
-        let mut canonical_message = extracted_message.clone();
-        if !canonical_message.ends_with('\n') {
-            canonical_message.push('\n');
-        }
-        // Convert to CRLF for canonical text mode
-        let canonical_message = canonical_message.replace('\n', "\r\n");
+/// Check a signature with the primary key, then with the key's subkeys
+///
+/// Keys often sign with a subkey only, and the `pgp` crate checks just the key it is given.
+/// A subkey's signature only counts if the subkey chains to the primary key, which is the one
+/// whose fingerprint is compared against the registry: its binding signature (and the subkey's
+/// back-signature) must verify, and the binding must allow signing. Otherwise a key with the
+/// registry's primary could carry someone else's signing subkey.
+fn verify_with_any_key(
+    key: &SignedPublicKey,
+    verify_primary: impl FnOnce(&SignedPublicKey) -> pgp::errors::Result<()>,
+    verify_subkey: impl Fn(&SignedPublicSubKey) -> pgp::errors::Result<()>,
+) -> Result<(), String> {
+    let primary_error = match verify_primary(key) {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+
+    let Some(subkey) = key
+        .public_subkeys
+        .iter()
+        .find(|subkey| verify_subkey(subkey).is_ok())
+    else {
+        return Err(format!("Signature verification failed: {}", primary_error));
+    };
+
+    subkey.verify(&key.primary_key).map_err(|e| {
+        format!(
+            "Signing subkey {:X} is not bound to primary key {:X}: {}",
+            subkey.fingerprint(),
+            key.fingerprint(),
+            e
+        )
+    })?;
+    if !subkey.signatures.iter().any(|sig| sig.key_flags().sign()) {
+        return Err(format!(
+            "Subkey {:X} is not certified for signing",
+            subkey.fingerprint()
+        ));
+    }
+    Ok(())
+}
 
-        // Verify the signature
-        sig.verify(&public_key, canonical_message.as_bytes())
-            .map_err(|e| format!("Signature verification failed: {}", e))?;
+/// A parsed signature: cleartext signed (with the text it covers) or an inline signed message
+enum Signed<'a> {
+    Cleartext {
+        text: String,
+        signature: DetachedSignature,
+    },
+    Inline(Box<Message<'a>>),
+}
 
-        // Check that the message content matches
-        if extracted_message.trim() == message.trim() {
-            Ok(true)
+impl<'a> Signed<'a> {
+    fn parse(signature: &'a str) -> Result<Self, String> {
+        if signature.contains("BEGIN PGP SIGNED MESSAGE") {
+            let (text, sig_block) = split_cleartext(signature)?;
+            let (signature, _) = DetachedSignature::from_string(&sig_block)
+                .map_err(|e| format!("Failed to parse signature: {}", e))?;
+            Ok(Signed::Cleartext { text, signature })
         } else {
-            Err(format!(
+            let (message, _headers) = Message::from_string(signature)
+                .map_err(|e| format!("Failed to parse signature: {}", e))?;
+            Ok(Signed::Inline(Box::new(message)))
+        }
+    }
+
+    fn verify(&self, key: &SignedPublicKey) -> Result<(), String> {
+        match self {
+            Signed::Cleartext { text, signature } => {
+                // For cleartext signatures, PGP uses canonical text mode
+                // The message needs to have a trailing newline
+                let mut canonical_message = text.clone();
+                if !canonical_message.ends_with('\n') {
+                    canonical_message.push('\n');
+                }
+                // Convert to CRLF for canonical text mode
+                let canonical_message = canonical_message.replace('\n', "\r\n");
+
+                verify_with_any_key(
+                    key,
+                    |key| signature.verify(key, canonical_message.as_bytes()),
+                    |subkey| signature.verify(subkey, canonical_message.as_bytes()),
+                )
+            }
+            Signed::Inline(message) => verify_with_any_key(
+                key,
+                |key| message.verify(key).map(|_| ()),
+                |subkey| message.verify(subkey).map(|_| ()),
+            ),
+        }
+    }
+
+    /// Check that the signed text is the expected message (inline messages aren't compared)
+    fn check_content(&self, message: &str) -> Result<(), String> {
+        match self {
+            Signed::Cleartext { text, .. } if text.trim() != message.trim() => Err(format!(
                 "Message content does not match. Expected: '{}', Got: '{}'",
                 message.trim(),
-                extracted_message.trim()
-            ))
+                text.trim()
+            )),
+            _ => Ok(()),
         }
-    } else {
-        // Try parsing as a regular message
-        let (signed_msg, _headers) = Message::from_string(signature)
-            .map_err(|e| format!("Failed to parse signature: {}", e))?;
+    }
+}
+
+fn parse_public_key(public_key_str: &str) -> Result<SignedPublicKey, String> {
+    SignedPublicKey::from_string(public_key_str)
+        .map(|(key, _headers)| key)
+        .map_err(|e| format!("Failed to parse public key: {}", e))
+}
+
+/// Verify a GPG signature for the given message
+/// Returns Ok(true) if signature is valid, Ok(false) if invalid, Err on failure
+pub fn verify_signature(
+    message: &str,
+    signature: &str,
+    public_key_str: &str,
+) -> Result<bool, String> {
+    let public_key = parse_public_key(public_key_str)?;
+    let signed = Signed::parse(signature)?;
+    signed.verify(&public_key)?;
+    signed.check_content(message)?;
+    Ok(true)
+}
 
-        // Verify the signature
-        signed_msg
-            .verify(&public_key)
-            .map_err(|e| format!("Signature verification failed: {}", e))?;
+/// How far a signature gets through `verify_signature`, stage by stage
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignatureDiagnosis {
+    pub key_parsed: bool,
+    pub signature_parsed: bool,
+    /// The signature was made by the key (or a signing subkey bound to it)
+    pub signature_verified: bool,
+    /// The signed text is the expected message
+    pub content_matches: bool,
+    /// Why the first failing stage failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
 
-        Ok(true)
+/// Run the stages of `verify_signature` one by one, to tell a peer what is wrong with their signature
+///
+/// The content is compared as soon as the signature parses, so a signature over the wrong
+/// text shows up as such even if it doesn't verify either.
+pub fn diagnose_signature(
+    message: &str,
+    signature: &str,
+    public_key_str: &str,
+) -> SignatureDiagnosis {
+    let mut diagnosis = SignatureDiagnosis::default();
+    let mut errors = Vec::new();
+
+    let public_key = parse_public_key(public_key_str)
+        .map_err(|e| errors.push(e))
+        .ok();
+    diagnosis.key_parsed = public_key.is_some();
+
+    if let Ok(signed) = Signed::parse(signature).map_err(|e| errors.push(e)) {
+        diagnosis.signature_parsed = true;
+        if let Some(public_key) = &public_key {
+            diagnosis.signature_verified = signed
+                .verify(public_key)
+                .map_err(|e| errors.push(e))
+                .is_ok();
+        }
+        diagnosis.content_matches = signed
+            .check_content(message)
+            .map_err(|e| errors.push(e))
+            .is_ok();
     }
+
+    diagnosis.error = errors.into_iter().next();
+    diagnosis
 }
 
 #[cfg(test)]
@@ -102,6 +237,80 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_verify_generated_signature() {
+        let signer = crate::api::test_helpers::TestSigner::generate();
+        let message = "AUTOPEER-4242420257-deadbeef";
+
+        let signed = signer.sign(message);
+        assert_eq!(
+            verify_signature(message, &signed, &signer.public_key),
+            Ok(true)
+        );
+        assert!(
+            verify_signature("AUTOPEER-4242420257-other", &signed, &signer.public_key).is_err()
+        );
+    }
+
+    #[test]
+    fn test_verify_subkey_signature() {
+        let signer = crate::api::test_helpers::TestSigner::generate_with_signing_subkey();
+        let message = "AUTOPEER-4242420257-deadbeef";
+
+        let signed = signer.sign(message);
+        assert_eq!(
+            verify_signature(message, &signed, &signer.public_key),
+            Ok(true)
+        );
+
+        // The registry lists the primary fingerprint, which is what the key is checked against
+        assert!(
+            crate::registry::verify_key_fingerprint(&signer.public_key, &signer.fingerprint)
+                .unwrap()
+        );
+
+        // Another key's subkey doesn't verify
+        let other = crate::api::test_helpers::TestSigner::generate_with_signing_subkey();
+        assert!(verify_signature(message, &signed, &other.public_key).is_err());
+    }
+
+    #[test]
+    fn test_reject_subkey_bound_to_another_primary() {
+        use pgp::composed::ArmorOptions;
+
+        let owner = crate::api::test_helpers::TestSigner::generate_with_signing_subkey();
+        let attacker = crate::api::test_helpers::TestSigner::generate_with_signing_subkey();
+        let message = "AUTOPEER-4242420257-deadbeef";
+
+        // The owner's primary (matching the registry) carrying the attacker's signing subkey
+        let (mut crafted, _) = SignedPublicKey::from_string(&owner.public_key).unwrap();
+        let (attacker_key, _) = SignedPublicKey::from_string(&attacker.public_key).unwrap();
+        crafted.public_subkeys = attacker_key.public_subkeys;
+        let crafted = crafted.to_armored_string(ArmorOptions::default()).unwrap();
+        assert!(crate::registry::verify_key_fingerprint(&crafted, &owner.fingerprint).unwrap());
+
+        let err = verify_signature(message, &attacker.sign(message), &crafted).unwrap_err();
+        assert!(err.contains("is not bound to primary key"), "{}", err);
+    }
+
+    #[test]
+    fn test_verify_dash_escaped_message() {
+        let signer = crate::api::test_helpers::TestSigner::generate();
+        let message = "-AUTOPEER-4242420257-deadbeef";
+
+        let signed = signer.sign(message);
+        assert!(
+            signed.contains("\n- -AUTOPEER-4242420257-deadbeef"),
+            "{}",
+            signed
+        );
+        assert_eq!(signed_text(&signed).unwrap(), message);
+        assert_eq!(
+            verify_signature(message, &signed, &signer.public_key),
+            Ok(true)
+        );
+    }
+
     #[test]
     fn test_verify_real_signature() {
         let message = "AUTOPEER-AS4242420257-THISISATEST";
@@ -138,6 +347,67 @@ LGZbH16nUawo9gEAw+6wvpgw2d7IS6rnT6jJZ1qm6inF/XzTZTNfq9rsmgM=
         assert!(result.unwrap(), "Signature should be valid");
     }
 
+    #[test]
+    fn test_diagnose_signature_stages() {
+        let signer = crate::api::test_helpers::TestSigner::generate();
+        let message = "AUTOPEER-4242420257-deadbeef";
+        let signed = signer.sign(message);
+
+        let ok = diagnose_signature(message, &signed, &signer.public_key);
+        assert_eq!(
+            ok,
+            SignatureDiagnosis {
+                key_parsed: true,
+                signature_parsed: true,
+                signature_verified: true,
+                content_matches: true,
+                error: None,
+            }
+        );
+
+        // Unparsable key: the signature is still looked at
+        let diagnosis = diagnose_signature(message, &signed, "not a key");
+        assert!(!diagnosis.key_parsed);
+        assert!(
+            diagnosis.signature_parsed
+                && !diagnosis.signature_verified
+                && diagnosis.content_matches
+        );
+        assert!(diagnosis
+            .error
+            .unwrap()
+            .contains("Failed to parse public key"));
+
+        // Unparsable signature
+        let diagnosis = diagnose_signature(
+            message,
+            "-----BEGIN PGP SIGNED MESSAGE-----\ngarbage",
+            &signer.public_key,
+        );
+        assert!(diagnosis.key_parsed && !diagnosis.signature_parsed);
+        assert!(!diagnosis.signature_verified && !diagnosis.content_matches);
+        assert!(diagnosis
+            .error
+            .unwrap()
+            .contains("cleartext signature format"));
+
+        // Signed by another key
+        let other = crate::api::test_helpers::TestSigner::generate();
+        let diagnosis = diagnose_signature(message, &signed, &other.public_key);
+        assert!(diagnosis.key_parsed && diagnosis.signature_parsed && diagnosis.content_matches);
+        assert!(!diagnosis.signature_verified);
+        assert!(diagnosis
+            .error
+            .unwrap()
+            .contains("Signature verification failed"));
+
+        // Valid signature over the wrong text
+        let diagnosis =
+            diagnose_signature("AUTOPEER-4242420257-other", &signed, &signer.public_key);
+        assert!(diagnosis.signature_verified && !diagnosis.content_matches);
+        assert!(diagnosis.error.unwrap().contains("does not match"));
+    }
+
     #[test]
     fn test_verify_signature_wrong_message() {
         let message = "AUTOPEER-AS4242420257-WRONGMESSAGE";
@@ -0,0 +1,199 @@
+//! Verification of minisign/signify-style Ed25519 signatures, for maintainers that list an
+//! `auth: ed25519-minisign <pubkey>` line instead of (or next to) a PGP fingerprint
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use blake2::{Blake2b512, Digest};
+use ed25519_dalek::{Signature, VerifyingKey};
+
+const UNTRUSTED_COMMENT: &str = "untrusted comment:";
+const TRUSTED_COMMENT: &str = "trusted comment: ";
+
+/// Signature over the message itself
+const ALG_PURE: &[u8; 2] = b"Ed";
+/// Signature over the BLAKE2b-512 hash of the message (minisign's default since 0.8)
+const ALG_PREHASHED: &[u8; 2] = b"ED";
+
+/// A public key from the registry: `Ed` + 8 byte key id + 32 byte Ed25519 key, base64
+struct PublicKey {
+    key_id: [u8; 8],
+    key: VerifyingKey,
+}
+
+/// The four lines of a `.minisig` file
+struct SignatureFile {
+    prehashed: bool,
+    key_id: [u8; 8],
+    signature: Signature,
+    trusted_comment: String,
+    global_signature: Signature,
+}
+
+/// Whether a submitted signature looks like a minisign one rather than a PGP message
+pub fn is_minisign_signature(signed: &str) -> bool {
+    signed.trim_start().starts_with(UNTRUSTED_COMMENT)
+}
+
+fn parse_public_key(encoded: &str) -> Result<PublicKey, String> {
+    let bytes = STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| format!("Invalid minisign public key: {}", e))?;
+    if bytes.len() != 42 || &bytes[..2] != ALG_PURE {
+        return Err("Invalid minisign public key: expected an Ed25519 key".to_string());
+    }
+
+    let key = VerifyingKey::from_bytes(bytes[10..].try_into().expect("length checked above"))
+        .map_err(|e| format!("Invalid minisign public key: {}", e))?;
+    Ok(PublicKey {
+        key_id: bytes[2..10].try_into().expect("length checked above"),
+        key,
+    })
+}
+
+fn parse_signature(signed: &str) -> Result<SignatureFile, String> {
+    let lines: Vec<&str> = signed.trim().lines().map(str::trim_end).collect();
+    let [untrusted, signature, trusted, global] = lines.as_slice() else {
+        return Err("Invalid minisign signature: expected 4 lines".to_string());
+    };
+    if !untrusted.starts_with(UNTRUSTED_COMMENT) {
+        return Err("Invalid minisign signature: missing untrusted comment".to_string());
+    }
+    let trusted_comment = trusted
+        .strip_prefix(TRUSTED_COMMENT)
+        .ok_or("Invalid minisign signature: missing trusted comment")?;
+
+    let signature = STANDARD
+        .decode(signature)
+        .map_err(|e| format!("Invalid minisign signature: {}", e))?;
+    if signature.len() != 74 {
+        return Err("Invalid minisign signature: wrong length".to_string());
+    }
+    let prehashed = match &signature[..2] {
+        alg if alg == ALG_PURE => false,
+        alg if alg == ALG_PREHASHED => true,
+        _ => return Err("Invalid minisign signature: unknown algorithm".to_string()),
+    };
+
+    let global = STANDARD
+        .decode(global)
+        .map_err(|e| format!("Invalid minisign global signature: {}", e))?;
+    let global_signature = Signature::from_slice(&global)
+        .map_err(|e| format!("Invalid minisign global signature: {}", e))?;
+
+    Ok(SignatureFile {
+        prehashed,
+        key_id: signature[2..10].try_into().expect("length checked above"),
+        signature: Signature::from_slice(&signature[10..]).expect("length checked above"),
+        trusted_comment: trusted_comment.to_string(),
+        global_signature,
+    })
+}
+
+/// Verify a minisign signature over `message` against the registry's keys
+///
+/// The key is picked by the key id in the signature. Files signed with `minisign -Sm`
+/// usually end in a newline, so the message is accepted with or without one.
+pub fn verify_signature(
+    message: &str,
+    signed: &str,
+    public_keys: &[String],
+) -> Result<bool, String> {
+    let signature = parse_signature(signed)?;
+
+    let keys = public_keys
+        .iter()
+        .map(|key| parse_public_key(key))
+        .collect::<Result<Vec<_>, _>>()?;
+    let Some(key) = keys.iter().find(|key| key.key_id == signature.key_id) else {
+        return Err(format!(
+            "Signed with minisign key {:016X}, which is not listed in the registry",
+            u64::from_le_bytes(signature.key_id)
+        ));
+    };
+
+    let message = message.trim_end_matches('\n');
+    let signs_message = [message.to_string(), format!("{}\n", message)]
+        .iter()
+        .any(|candidate| {
+            let signed_bytes = if signature.prehashed {
+                Blake2b512::digest(candidate.as_bytes()).to_vec()
+            } else {
+                candidate.as_bytes().to_vec()
+            };
+            key.key
+                .verify_strict(&signed_bytes, &signature.signature)
+                .is_ok()
+        });
+    if !signs_message {
+        return Ok(false);
+    }
+
+    // The trusted comment is covered by the global signature
+    let mut global = signature.signature.to_bytes().to_vec();
+    global.extend_from_slice(signature.trusted_comment.as_bytes());
+    Ok(key
+        .key
+        .verify_strict(&global, &signature.global_signature)
+        .is_ok())
+}
+
+/// The registry key a minisign signature was made with, by its key id
+pub fn signing_key(signed: &str, public_keys: &[String]) -> Option<String> {
+    let signature = parse_signature(signed).ok()?;
+    public_keys
+        .iter()
+        .find(|key| parse_public_key(key).is_ok_and(|key| key.key_id == signature.key_id))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_helpers::MinisignSigner;
+
+    const CHALLENGE: &str = "AUTOPEER-4242422225-0123456789abcdef";
+
+    #[test]
+    fn test_verify_valid_signature() {
+        let signer = MinisignSigner::new(7);
+        let keys = vec![signer.public_key()];
+
+        assert!(is_minisign_signature(&signer.sign(CHALLENGE, true)));
+        assert!(verify_signature(CHALLENGE, &signer.sign(CHALLENGE, true), &keys).unwrap());
+        // Legacy (non-prehashed) signatures, and files without the trailing newline
+        assert!(verify_signature(CHALLENGE, &signer.sign(CHALLENGE, false), &keys).unwrap());
+        assert!(verify_signature(CHALLENGE, &signer.sign_exact(CHALLENGE, true), &keys).unwrap());
+
+        let other = MinisignSigner::new(9);
+        let keys = vec![other.public_key(), signer.public_key()];
+        assert_eq!(
+            signing_key(&signer.sign(CHALLENGE, true), &keys),
+            Some(signer.public_key())
+        );
+        assert_eq!(signing_key(&signer.sign(CHALLENGE, true), &keys[..1]), None);
+    }
+
+    #[test]
+    fn test_verify_invalid_signature() {
+        let signer = MinisignSigner::new(7);
+        let keys = vec![signer.public_key()];
+
+        // Another challenge
+        let other = signer.sign("AUTOPEER-4242422225-fedcba9876543210", true);
+        assert!(!verify_signature(CHALLENGE, &other, &keys).unwrap());
+
+        // Tampered trusted comment
+        let signed = signer
+            .sign(CHALLENGE, true)
+            .replace("timestamp:", "timestamp:1");
+        assert!(!verify_signature(CHALLENGE, &signed, &keys).unwrap());
+
+        // A key that isn't in the registry
+        let stranger = MinisignSigner::new(9);
+        let err = verify_signature(CHALLENGE, &stranger.sign(CHALLENGE, true), &keys).unwrap_err();
+        assert!(err.contains("not listed in the registry"), "{}", err);
+
+        // Not a minisign signature at all
+        assert!(!is_minisign_signature("-----BEGIN PGP SIGNED MESSAGE-----"));
+        assert!(verify_signature(CHALLENGE, "untrusted comment: x\nnot base64\n", &keys).is_err());
+    }
+}
@@ -1,8 +1,20 @@
 pub mod gpg;
+pub mod minisign;
+pub mod stateless;
 
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
+/// ASN as written in a challenge code: bare (`4242420257`, what `generate` writes) or
+/// with the `AS` prefix (`AS4242420257`) some older codes carry
+fn parse_code_asn(asn: &str) -> Result<u32, std::num::ParseIntError> {
+    let bare = asn
+        .strip_prefix("AS")
+        .or_else(|| asn.strip_prefix("as"))
+        .unwrap_or(asn);
+    bare.parse()
+}
+
 /// A challenge code for peer authentication
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Challenge {
@@ -11,14 +23,52 @@ pub struct Challenge {
 }
 
 impl Challenge {
-    /// Generate a new random challenge for the given ASN
-    pub fn generate(asn: u32) -> Self {
+    /// Generate a new random challenge (`{prefix}-{asn}-{hex}`, the ASN without `AS`) for the given ASN
+    pub fn generate(asn: u32, prefix: &str, random_len: usize) -> Self {
         let mut rng = rand::rng();
-        let random_bytes: [u8; 16] = rng.random();
-        let code = format!("AUTOPEER-{}-{}", asn, hex::encode(random_bytes));
+        let random_bytes: Vec<u8> = (0..random_len).map(|_| rng.random()).collect();
+        let code = format!("{}-{}-{}", prefix, asn, hex::encode(random_bytes));
 
         Challenge { code, asn }
     }
+
+    /// Parse a challenge code generated with `prefix`, extracting the ASN.
+    /// The prefix itself may contain dashes, and the ASN may carry an `AS` prefix.
+    pub fn parse(code: &str, prefix: &str) -> Result<Self, String> {
+        let code = code.trim();
+        let rest = code
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .ok_or_else(|| format!("Challenge does not start with {}-", prefix))?;
+
+        let (asn, random) = rest
+            .split_once('-')
+            .ok_or("Challenge is missing the random part")?;
+        if random.is_empty() || !random.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err("Challenge random part is not hex".to_string());
+        }
+
+        let asn = parse_code_asn(asn).map_err(|e| format!("Invalid ASN in challenge: {}", e))?;
+
+        Ok(Challenge {
+            code: code.to_string(),
+            asn,
+        })
+    }
+
+    /// Extract the ASN from a `{prefix}-{asn}-{hex}` code without knowing its prefix.
+    /// Lets callers tell a challenge issued under an older format apart from garbage.
+    pub fn embedded_asn(code: &str) -> Option<u32> {
+        let (head, random) = code.trim().rsplit_once('-')?;
+        if random.is_empty() || !random.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let (prefix, asn) = head.rsplit_once('-')?;
+        if prefix.is_empty() {
+            return None;
+        }
+        parse_code_asn(asn).ok()
+    }
 }
 
 #[cfg(test)]
@@ -28,7 +78,7 @@ mod tests {
     #[test]
     fn test_generate_challenge() {
         let asn = 4242421234;
-        let challenge = Challenge::generate(asn);
+        let challenge = Challenge::generate(asn, "AUTOPEER", 16);
 
         assert_eq!(challenge.asn, asn);
         assert!(challenge.code.starts_with("AUTOPEER-"));
@@ -38,8 +88,8 @@ mod tests {
     #[test]
     fn test_challenges_are_unique() {
         let asn = 4242421234;
-        let challenge1 = Challenge::generate(asn);
-        let challenge2 = Challenge::generate(asn);
+        let challenge1 = Challenge::generate(asn, "AUTOPEER", 16);
+        let challenge2 = Challenge::generate(asn, "AUTOPEER", 16);
 
         assert_ne!(challenge1.code, challenge2.code);
     }
@@ -57,4 +107,64 @@ mod tests {
         assert_eq!(challenge, deserialized);
     }
 
+    #[test]
+    fn test_custom_prefix_roundtrip() {
+        let asn = 4242421234;
+        let challenge = Challenge::generate(asn, "MY-DN42", 8);
+
+        assert!(challenge.code.starts_with("MY-DN42-4242421234-"));
+        // 8 random bytes, hex-encoded
+        assert_eq!(challenge.code.len(), "MY-DN42-4242421234-".len() + 16);
+
+        let parsed = Challenge::parse(&challenge.code, "MY-DN42").unwrap();
+        assert_eq!(parsed, challenge);
+    }
+
+    #[test]
+    fn test_parse_rejects_other_prefix() {
+        let challenge = Challenge::generate(4242421234, "AUTOPEER", 16);
+
+        assert!(Challenge::parse(&challenge.code, "MY-DN42").is_err());
+        assert!(Challenge::parse("AUTOPEER-notanasn-abcd", "AUTOPEER").is_err());
+        assert!(Challenge::parse("AUTOPEER-4242421234-", "AUTOPEER").is_err());
+    }
+
+    #[test]
+    fn test_parse_bare_and_as_prefixed_asn() {
+        let bare = Challenge::parse("AUTOPEER-4242420257-0123456789abcdef", "AUTOPEER").unwrap();
+        let prefixed =
+            Challenge::parse("AUTOPEER-AS4242420257-0123456789abcdef", "AUTOPEER").unwrap();
+        assert_eq!(bare.asn, 4242420257);
+        assert_eq!(prefixed.asn, 4242420257);
+        // The code is kept as written, since that is what was signed
+        assert_eq!(prefixed.code, "AUTOPEER-AS4242420257-0123456789abcdef");
+        assert_eq!(
+            Challenge::parse("MY-DN42-as4242420257-abcd", "MY-DN42")
+                .unwrap()
+                .asn,
+            4242420257
+        );
+
+        assert_eq!(
+            Challenge::embedded_asn("AUTOPEER-AS4242420257-abcd"),
+            Some(4242420257)
+        );
+        assert_eq!(
+            Challenge::embedded_asn("AUTOPEER-4242420257-abcd"),
+            Some(4242420257)
+        );
+
+        assert!(Challenge::parse("AUTOPEER-AS-abcd", "AUTOPEER").is_err());
+        assert!(Challenge::parse("AUTOPEER-ASAS4242420257-abcd", "AUTOPEER").is_err());
+    }
+
+    #[test]
+    fn test_embedded_asn_ignores_prefix() {
+        let challenge = Challenge::generate(4242421234, "MY-DN42", 16);
+
+        assert_eq!(Challenge::embedded_asn(&challenge.code), Some(4242421234));
+        assert_eq!(Challenge::embedded_asn("AUTOPEER-4242421234-"), None);
+        assert_eq!(Challenge::embedded_asn("-4242421234-abcd"), None);
+        assert_eq!(Challenge::embedded_asn("garbage"), None);
+    }
 }
@@ -0,0 +1,116 @@
+//! Self-contained challenges for running without a pending dir
+//!
+//! The code carries its own expiry and an HMAC over it,
+//! `{prefix}-{asn}-{nonce}-{expires}-{mac}`, so verify only needs the secret.
+
+use super::Challenge;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Keeps these MACs apart from anything else keyed with the same secret
+const DOMAIN: &[u8] = b"autopeer-challenge:";
+
+fn mac(secret: &str, payload: &str) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(DOMAIN);
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// Issue a challenge for `asn` that stays valid until `expires` (Unix timestamp)
+pub fn issue(asn: u32, prefix: &str, nonce: &str, expires: i64, secret: &str) -> Challenge {
+    let payload = format!("{}-{}-{}-{}", prefix, asn, nonce, expires);
+    let tag = hex::encode(mac(secret, &payload).finalize().into_bytes());
+
+    Challenge {
+        code: format!("{}-{}", payload, tag),
+        asn,
+    }
+}
+
+/// Check that `code` was issued by us for `asn` and hasn't expired at `now`
+pub fn verify(
+    code: &str,
+    asn: u32,
+    prefix: &str,
+    secret: &str,
+    now: i64,
+) -> Result<Challenge, String> {
+    let code = code.trim();
+    let (payload, tag) = code
+        .rsplit_once('-')
+        .ok_or("Challenge is not a stateless challenge")?;
+    let rest = payload
+        .strip_prefix(prefix)
+        .and_then(|rest| rest.strip_prefix('-'))
+        .ok_or_else(|| format!("Challenge does not start with {}-", prefix))?;
+    let [code_asn, _nonce, expires] = rest.split('-').collect::<Vec<_>>()[..] else {
+        return Err("Challenge is not a stateless challenge".to_string());
+    };
+
+    let tag = hex::decode(tag).map_err(|_| "Challenge MAC is not hex".to_string())?;
+    mac(secret, payload)
+        .verify_slice(&tag)
+        .map_err(|_| "Challenge was not issued by this server or has been altered".to_string())?;
+
+    // Everything below is covered by the MAC
+    if code_asn.parse::<u32>().ok() != Some(asn) {
+        return Err("Challenge was issued for a different ASN".to_string());
+    }
+    let expires: i64 = expires
+        .parse()
+        .map_err(|e| format!("Invalid challenge expiry: {}", e))?;
+    if now >= expires {
+        return Err("Challenge expired, please restart with /peering/init".to_string());
+    }
+
+    Ok(Challenge {
+        code: code.to_string(),
+        asn,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "test-secret-key-for-testing-at-least-32-chars-long";
+
+    #[test]
+    fn test_issue_and_verify() {
+        let challenge = issue(4242420257, "AUTOPEER", "0123456789abcdef", 2000, SECRET);
+        assert!(challenge
+            .code
+            .starts_with("AUTOPEER-4242420257-0123456789abcdef-2000-"));
+
+        let verified = verify(&challenge.code, 4242420257, "AUTOPEER", SECRET, 1000).unwrap();
+        assert_eq!(verified, challenge);
+
+        // Expired
+        let err = verify(&challenge.code, 4242420257, "AUTOPEER", SECRET, 2000).unwrap_err();
+        assert!(err.contains("expired"));
+        // Wrong ASN, secret or prefix
+        assert!(verify(&challenge.code, 4242421816, "AUTOPEER", SECRET, 1000).is_err());
+        assert!(verify(
+            &challenge.code,
+            4242420257,
+            "AUTOPEER",
+            "another-secret",
+            1000
+        )
+        .is_err());
+        assert!(verify(&challenge.code, 4242420257, "MY-DN42", SECRET, 1000).is_err());
+    }
+
+    #[test]
+    fn test_tampered_expiry_is_rejected() {
+        let challenge = issue(4242420257, "MY-DN42", "0123456789abcdef", 2000, SECRET);
+        let tampered = challenge.code.replace("-2000-", "-9999-");
+
+        let err = verify(&tampered, 4242420257, "MY-DN42", SECRET, 3000).unwrap_err();
+        assert!(err.contains("altered"), "{}", err);
+    }
+}
@@ -1,17 +1,147 @@
+use crate::api::RuntimeState;
+use crate::bird::{ChannelConfig, ExplicitChannels};
+use crate::ipalloc::{AddressFamily, BgpTransport, NeighborMode, PortRange, UlaPrefix};
+use crate::registry::RegistryLayout;
+use crate::validation::AsnRange;
+use arc_swap::ArcSwap;
+use axum::extract::FromRef;
+use std::collections::HashMap;
 use std::env;
+use std::net::{IpAddr, Ipv4Addr};
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Shortest `JWT_SECRET` accepted (HS256 wants at least 256 bits of key)
+pub const MIN_JWT_SECRET_LEN: usize = 32;
 
 /// Application configuration
 #[derive(Debug, Clone)]
 pub struct AppConfig {
     pub registry: RegistryConfig,
+    pub system: SystemConfig,
     pub jwt_secret: String,
     pub my_asn: u32,
     pub bind_address: String,
+    /// Root for local state; `pending/`, `verified/` and `deleted/` live below it unless overridden
+    pub data_dir: String,
     pub data_pending_dir: String,
     pub data_verified_dir: String,
+    /// Tombstones of deleted peerings (only written with `tombstone_deletes`)
+    pub data_deleted_dir: String,
     pub cookie_domains: Vec<String>,
     pub public_endpoint: String,
+    /// Accepted PGP primary key algorithms (`rsa<bits>` sets a minimum RSA size)
+    pub allowed_pgp_algorithms: Vec<String>,
+    /// Directory with Tera template overrides (`wg.conf.tera`, `peer.conf.tera`)
+    pub template_dir: Option<PathBuf>,
+    /// Background registry sync interval (0 disables background syncing)
+    pub sync_interval_secs: u64,
+    /// Maximum random offset added to each sync interval
+    pub sync_jitter_secs: u64,
+    /// Registry object layout (auto-detects sharded directories by default)
+    pub registry_layout: RegistryLayout,
+    /// Minimum time between deploy/update requests per ASN (0 disables)
+    pub deploy_cooldown_secs: u64,
+    /// Render link-local BGP neighbors as `fe80::...%iface`
+    pub link_local_scope_suffix: bool,
+    /// Deploy WireGuard ourselves; when false the interface is managed externally and only BIRD is handled
+    pub manage_wireguard: bool,
+    /// Prefix of generated challenge codes (`{prefix}-{asn}-{hex}`)
+    pub challenge_prefix: String,
+    /// Number of random bytes in a challenge code (hex-encoded)
+    pub challenge_random_bytes: usize,
+    /// Addresses the BGP session runs between (`link_local` or `ula`)
+    pub bgp_neighbor_mode: NeighborMode,
+    /// ULA /48 the transfer nets are taken from (required for `ula` mode)
+    pub ula_transfer_prefix: Option<UlaPrefix>,
+    /// Resolve and probe endpoints in /peering/test-endpoint (false checks format only)
+    pub validate_endpoint_dns: bool,
+    /// Default BIRD import limit per channel for new sessions (0 disables)
+    pub default_max_prefixes: u32,
+    /// Restrict AllowedIPs to the peer's registered route/route6 objects
+    pub strict_allowed_ips: bool,
+    /// `iss` claim issued and required in tokens (unset skips the check)
+    pub jwt_issuer: Option<String>,
+    /// `aud` claim issued and required in tokens (unset skips the check)
+    pub jwt_audience: Option<String>,
+    /// Our IPv4 tunnel address, the transfer allocation IPv4-only sessions run from
+    pub ipv4_transfer_address: Option<Ipv4Addr>,
+    /// Listen ports peers may request (derived ports fall in 30000-39999)
+    pub port_range: PortRange,
+    /// Previous versions of each verified config kept for /peering/rollback (0 disables)
+    pub config_history: usize,
+    /// Reject peer endpoints on loopback, link-local or private addresses
+    pub reject_private_endpoints: bool,
+    /// Accept DN42 endpoints (172.20.0.0/14, fd00::/8) anyway; for servers reachable over DN42
+    pub allow_dn42_endpoints: bool,
+    /// Restrict BGP imports to the peer's registered routes, honouring their max-length
+    pub strict_import_filter: bool,
+    /// Issued and revoked token ids (JSON), checked on every authenticated request
+    pub token_store: PathBuf,
+    /// Bearer token for the /admin endpoints (unset disables them)
+    pub admin_token: Option<String>,
+    /// Address families our endpoint is reachable over; peers' endpoints must use one of them
+    pub public_endpoint_family: AddressFamily,
+    /// Issue self-contained HMAC-signed challenges instead of storing them in the pending dir
+    pub stateless_challenges: bool,
+    /// How long a stateless challenge stays valid
+    pub challenge_ttl_secs: u64,
+    /// ASNs allowed to peer, and our own (DN42 by default)
+    pub asn_range: AsnRange,
+    /// Signature verifications run at once (default: CPU count)
+    pub max_concurrent_verifications: usize,
+    /// Verify requests that may wait for a slot before the rest get 503
+    pub verification_queue: usize,
+    /// Refuse new peerings (init/verify/deploy) while existing ones keep working
+    pub maintenance_mode: bool,
+    /// Seconds a deploy or removal on the router may take before it is abandoned with 504
+    pub deploy_timeout_secs: u64,
+    /// Directory of the object classes inside the registry checkout (`data`, or e.g. `registry/data` for nested mirrors)
+    pub registry_data_subdir: String,
+    /// Where browsers are sent (303) after a successful verify; API clients still get JSON
+    pub post_verify_redirect: Option<String>,
+    /// Refuse a deploy whose endpoint another peering already uses (otherwise only warn)
+    pub reject_duplicate_endpoints: bool,
+    /// Keep WireGuard private keys out of deployed configs, in a 0600 `<iface>.key` file loaded by `PostUp`
+    pub external_private_keys: bool,
+    /// BIRD template our peer sessions inherit from (`protocol bgp ... from <template>`)
+    pub bird_peer_template: String,
+    /// Addresses on the tunnel: link-local (default), or only the transfer net
+    pub bgp_transport: BgpTransport,
+    /// Registry age after which `/readyz` fails (0 disables the check)
+    pub max_registry_age_secs: u64,
+    /// Also accept challenges signed by a key of the ASN's tech-c/admin-c person
+    pub allow_tech_contact_auth: bool,
+    /// Move deleted peerings' configs to `data_deleted_dir` instead of removing them
+    pub tombstone_deletes: bool,
+    /// Domains whose hosts may be given as endpoint hostnames (empty: only IP endpoints)
+    pub allowed_endpoint_domains: Vec<String>,
+    /// With `allowed_endpoint_domains`, refuse IP endpoints so every peer uses an allowed hostname
+    pub reject_ip_endpoints: bool,
+    /// Bring a peering up on deploy; when off, deploy only stores it and `/peering/activate` applies it
+    pub deploy_auto_activate: bool,
+    /// Channel tables and filters rendered into every session (`None` leaves them to the BIRD template)
+    pub explicit_channels: Option<ExplicitChannels>,
+    /// Deploy `DNS` lines of WireGuard configs (wg-quick passes them to resolvconf)
+    pub allow_dns_directive: bool,
+    /// Every ASN this node peers as, `my_asn` first (the default for requests that pick none)
+    pub my_asns: Vec<u32>,
+    /// Limiters and other request state, handed on to the new config on reload
+    pub runtime: Arc<RuntimeState>,
+}
+
+/// Family of an IP-literal endpoint host; a hostname may resolve to either
+fn endpoint_family(host: &str) -> AddressFamily {
+    match host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        Ok(IpAddr::V4(_)) => AddressFamily::Ipv4,
+        Ok(IpAddr::V6(_)) => AddressFamily::Ipv6,
+        Err(_) => AddressFamily::Dual,
+    }
 }
 
 /// DN42 Registry configuration
@@ -23,61 +153,690 @@ pub struct RegistryConfig {
     pub token: String,
 }
 
+/// Router-side paths and tools used to deploy peerings
+#[derive(Debug, Clone)]
+pub struct SystemConfig {
+    /// Directory wg-quick reads interface configs from
+    pub wireguard_dir: PathBuf,
+    /// Directory included by the main BIRD config for peer sessions
+    pub bird_peers_dir: PathBuf,
+    pub wg_bin: String,
+    pub wg_quick_bin: String,
+    pub birdc_bin: String,
+    /// How long to wait for an interface to appear after `wg-quick up` (0 skips the check)
+    pub wg_up_timeout_secs: u64,
+    /// Kill `birdc configure` if it hasn't finished after this long
+    pub birdc_timeout_secs: u64,
+    /// Timeout for `wg` and `wg-quick` invocations
+    pub command_timeout_secs: u64,
+}
+
 impl AppConfig {
     /// Load configuration from environment variables
     pub fn from_env() -> Result<Self, String> {
-        let jwt_secret =
-            env::var("JWT_SECRET").map_err(|_| "JWT_SECRET not set".to_string())?;
+        Self::from_settings(&Settings::from_env())
+    }
+
+    /// Load configuration from `settings`
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        let jwt_secret = settings
+            .var("JWT_SECRET")
+            .map_err(|_| "JWT_SECRET not set".to_string())?;
+        if jwt_secret.len() < MIN_JWT_SECRET_LEN && !settings.flag("ALLOW_WEAK_JWT_SECRET", false) {
+            return Err(format!(
+                "JWT_SECRET must be at least {} bytes (got {}); set ALLOW_WEAK_JWT_SECRET=true for testing",
+                MIN_JWT_SECRET_LEN,
+                jwt_secret.len()
+            ));
+        }
 
-        let my_asn = env::var("MY_ASN")
+        let asn_range = settings
+            .var("ASN_RANGE")
+            .ok()
+            .map(|s| s.parse())
+            .transpose()?
+            .unwrap_or_default();
+
+        let my_asn = settings
+            .var("MY_ASN")
             .ok()
             .and_then(|s| s.parse().ok())
             .unwrap_or(4242420257);
+        crate::validation::validate_asn(my_asn, asn_range)
+            .map_err(|(_, e)| format!("Invalid MY_ASN: {}", e))?;
 
-        let bind_address = env::var("BIND_ADDRESS")
+        // Further ASNs peers may pick at init/deploy (anycast, multi-homing)
+        let mut my_asns = vec![my_asn];
+        let extra_asns = settings.var("MY_ASNS").unwrap_or_default();
+        for asn in extra_asns
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+        {
+            let asn = asn
+                .parse()
+                .map_err(|_| format!("Invalid MY_ASNS entry {:?}: not an ASN", asn))?;
+            crate::validation::validate_asn(asn, asn_range)
+                .map_err(|(_, e)| format!("Invalid MY_ASNS: {}", e))?;
+            if !my_asns.contains(&asn) {
+                my_asns.push(asn);
+            }
+        }
+
+        let bind_address = settings
+            .var("BIND_ADDRESS")
             .unwrap_or_else(|_| "127.0.0.1:3000".to_string());
 
-        let data_pending_dir = env::var("DATA_PENDING_DIR")
-            .unwrap_or_else(|_| "./data/pending".to_string());
+        let data_dir = settings
+            .var("DATA_DIR")
+            .unwrap_or_else(|_| "./data".to_string());
+        let data_dir = data_dir.trim_end_matches('/').to_string();
+
+        // The explicit per-directory settings still win over DATA_DIR
+        let data_pending_dir = settings
+            .var("DATA_PENDING_DIR")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| format!("{}/pending", data_dir));
 
-        let data_verified_dir = env::var("DATA_VERIFIED_DIR")
-            .unwrap_or_else(|_| "./data/verified".to_string());
+        let data_verified_dir = settings
+            .var("DATA_VERIFIED_DIR")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| format!("{}/verified", data_dir));
 
-        let cookie_domains = env::var("COOKIE_DOMAINS")
+        let data_deleted_dir = settings
+            .var("DATA_DELETED_DIR")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| format!("{}/deleted", data_dir));
+
+        let cookie_domains = settings
+            .var("COOKIE_DOMAINS")
             .unwrap_or_else(|_| "localhost".to_string())
             .split(',')
             .map(|s| s.trim().to_string())
             .collect();
 
-        let public_endpoint = env::var("PUBLIC_ENDPOINT")
+        let public_endpoint = settings
+            .var("PUBLIC_ENDPOINT")
             .unwrap_or_else(|_| "dn42-router.linnenberg.dev".to_string());
 
+        let allowed_pgp_algorithms = settings
+            .var("ALLOWED_PGP_ALGORITHMS")
+            .unwrap_or_else(|_| "ed25519,ecdsa,rsa2048".to_string())
+            .split(',')
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        let template_dir = settings.var("TEMPLATE_DIR").ok().map(PathBuf::from);
+
+        let sync_interval_secs = settings
+            .var("REGISTRY_SYNC_INTERVAL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(3600);
+
+        let sync_jitter_secs = settings
+            .var("REGISTRY_SYNC_JITTER_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(300);
+
+        let registry_layout = settings
+            .var("REGISTRY_LAYOUT")
+            .unwrap_or_else(|_| "auto".to_string())
+            .parse()?;
+
+        let deploy_cooldown_secs = settings
+            .var("DEPLOY_COOLDOWN_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(60);
+
+        let link_local_scope_suffix = settings.flag("LINK_LOCAL_SCOPE_SUFFIX", false);
+
+        let manage_wireguard = settings.flag("MANAGE_WIREGUARD", true);
+
+        let challenge_prefix = settings
+            .var("CHALLENGE_PREFIX")
+            .unwrap_or_else(|_| "AUTOPEER".to_string());
+        if challenge_prefix.is_empty() || challenge_prefix.contains(char::is_whitespace) {
+            return Err("CHALLENGE_PREFIX must be non-empty and contain no whitespace".to_string());
+        }
+
+        let challenge_random_bytes = settings
+            .var("CHALLENGE_RANDOM_BYTES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(16);
+        if challenge_random_bytes < 8 {
+            return Err("CHALLENGE_RANDOM_BYTES must be at least 8".to_string());
+        }
+
+        let bgp_neighbor_mode = settings
+            .var("BGP_NEIGHBOR_MODE")
+            .unwrap_or_else(|_| "link_local".to_string())
+            .parse()?;
+
+        let ula_transfer_prefix = settings
+            .var("ULA_TRANSFER_PREFIX")
+            .ok()
+            .map(|s| s.parse())
+            .transpose()?;
+        if bgp_neighbor_mode == NeighborMode::Ula && ula_transfer_prefix.is_none() {
+            return Err("BGP_NEIGHBOR_MODE=ula requires ULA_TRANSFER_PREFIX".to_string());
+        }
+
+        let validate_endpoint_dns = settings.flag("VALIDATE_ENDPOINT_DNS", true);
+
+        let default_max_prefixes = settings
+            .var("DEFAULT_MAX_PREFIXES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1000);
+
+        let strict_allowed_ips = settings.flag("STRICT_ALLOWED_IPS", false);
+
+        let jwt_issuer = settings.var("JWT_ISSUER").ok().filter(|s| !s.is_empty());
+
+        let jwt_audience = settings.var("JWT_AUDIENCE").ok().filter(|s| !s.is_empty());
+
+        let ipv4_transfer_address = settings
+            .var("IPV4_TRANSFER_ADDRESS")
+            .ok()
+            .map(|s| {
+                s.parse()
+                    .map_err(|e| format!("Invalid IPV4_TRANSFER_ADDRESS: {}", e))
+            })
+            .transpose()?;
+
+        let port_range = settings
+            .var("WG_PORT_RANGE")
+            .unwrap_or_else(|_| "30000-39999".to_string())
+            .parse()?;
+
+        let config_history = settings
+            .var("CONFIG_HISTORY")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(5);
+
+        let reject_private_endpoints = settings.flag("REJECT_PRIVATE_ENDPOINTS", true);
+        let allow_dn42_endpoints = settings.flag("ALLOW_DN42_ENDPOINTS", false);
+
+        let strict_import_filter = settings.flag("STRICT_IMPORT_FILTER", false);
+
+        let token_store = PathBuf::from(
+            settings
+                .var("TOKEN_STORE")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| format!("{}/tokens.json", data_dir)),
+        );
+
+        let admin_token = settings
+            .var("ADMIN_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty());
+
+        let public_endpoint_family = settings
+            .var("PUBLIC_ENDPOINT_FAMILY")
+            .ok()
+            .map(|s| s.parse())
+            .transpose()?
+            .unwrap_or_else(|| endpoint_family(&public_endpoint));
+
+        let stateless_challenges = settings.flag("STATELESS_CHALLENGES", false);
+
+        let challenge_ttl_secs = settings
+            .var("CHALLENGE_TTL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(3600);
+
+        let max_concurrent_verifications = settings
+            .var("MAX_CONCURRENT_VERIFICATIONS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or_else(|| {
+                std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1)
+            });
+
+        let verification_queue = settings
+            .var("VERIFICATION_QUEUE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(32);
+
+        let maintenance_mode = settings.flag("MAINTENANCE_MODE", false);
+
+        let deploy_timeout_secs = settings
+            .var("DEPLOY_TIMEOUT_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(60);
+
+        let registry_data_subdir = settings
+            .var("REGISTRY_DATA_SUBDIR")
+            .unwrap_or_else(|_| "data".to_string());
+
+        let post_verify_redirect = settings
+            .var("POST_VERIFY_REDIRECT")
+            .ok()
+            .filter(|s| !s.is_empty());
+
+        let reject_duplicate_endpoints = settings.flag("REJECT_DUPLICATE_ENDPOINTS", false);
+
+        let external_private_keys = settings.flag("EXTERNAL_PRIVATE_KEYS", false);
+
+        let bird_peer_template = settings
+            .var("BIRD_PEER_TEMPLATE")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "dnpeers".to_string());
+        // Ends up verbatim in the rendered config, so it must be a plain BIRD symbol
+        if !bird_peer_template.starts_with(|c: char| c.is_ascii_alphabetic())
+            || !bird_peer_template
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(format!(
+                "Invalid BIRD_PEER_TEMPLATE: {:?} is not a BIRD symbol",
+                bird_peer_template
+            ));
+        }
+
+        let bgp_transport: BgpTransport = settings
+            .var("BGP_TRANSPORT")
+            .unwrap_or_else(|_| "link_local".to_string())
+            .parse()?;
+        if bgp_transport == BgpTransport::TransferNet && ula_transfer_prefix.is_none() {
+            return Err("BGP_TRANSPORT=transfer_net requires ULA_TRANSFER_PREFIX".to_string());
+        }
+
+        let max_registry_age_secs = settings
+            .var("MAX_REGISTRY_AGE_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+
+        let allow_tech_contact_auth = settings.flag("ALLOW_TECH_CONTACT_AUTH", false);
+
+        let tombstone_deletes = settings.flag("TOMBSTONE_DELETES", false);
+
+        let allowed_endpoint_domains = settings
+            .var("ALLOWED_ENDPOINT_DOMAINS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().trim_matches('.').to_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        let reject_ip_endpoints = settings.flag("REJECT_IP_ENDPOINTS", false);
+
+        let deploy_auto_activate = settings.flag("DEPLOY_AUTO_ACTIVATE", true);
+
+        let explicit_channels = if settings.flag("EXPLICIT_CHANNELS", false) {
+            let channel = |var: &str, ipv6: bool| {
+                ChannelConfig::parse(&settings.var(var).unwrap_or_default(), ipv6)
+                    .map_err(|e| format!("Invalid {}: {}", var, e))
+            };
+            Some(ExplicitChannels {
+                ipv4: channel("BIRD_IPV4_CHANNEL", false)?,
+                ipv6: channel("BIRD_IPV6_CHANNEL", true)?,
+            })
+        } else {
+            None
+        };
+
+        let allow_dns_directive = settings.flag("ALLOW_DNS_DIRECTIVE", false);
+
         Ok(AppConfig {
-            registry: RegistryConfig::from_env()?,
+            registry: RegistryConfig::from_settings(settings)?,
+            system: SystemConfig::from_settings(settings),
             jwt_secret,
             my_asn,
             bind_address,
+            data_dir,
             data_pending_dir,
             data_verified_dir,
+            data_deleted_dir,
             cookie_domains,
             public_endpoint,
+            allowed_pgp_algorithms,
+            template_dir,
+            sync_interval_secs,
+            sync_jitter_secs,
+            registry_layout,
+            deploy_cooldown_secs,
+            link_local_scope_suffix,
+            manage_wireguard,
+            challenge_prefix,
+            challenge_random_bytes,
+            bgp_neighbor_mode,
+            ula_transfer_prefix,
+            validate_endpoint_dns,
+            default_max_prefixes,
+            strict_allowed_ips,
+            jwt_issuer,
+            jwt_audience,
+            ipv4_transfer_address,
+            port_range,
+            config_history,
+            reject_private_endpoints,
+            allow_dn42_endpoints,
+            strict_import_filter,
+            token_store,
+            admin_token,
+            public_endpoint_family,
+            stateless_challenges,
+            challenge_ttl_secs,
+            asn_range,
+            max_concurrent_verifications,
+            verification_queue,
+            maintenance_mode,
+            deploy_timeout_secs,
+            registry_data_subdir,
+            post_verify_redirect,
+            reject_duplicate_endpoints,
+            external_private_keys,
+            bird_peer_template,
+            bgp_transport,
+            max_registry_age_secs,
+            allow_tech_contact_auth,
+            tombstone_deletes,
+            allowed_endpoint_domains,
+            reject_ip_endpoints,
+            deploy_auto_activate,
+            explicit_channels,
+            allow_dns_directive,
+            my_asns,
+            runtime: Arc::default(),
         })
     }
 }
 
+/// Names of the top-level settings whose values differ (values are not compared
+/// as `PartialEq` and never logged, since some are secrets)
+macro_rules! changed_fields {
+    ($old:expr, $new:expr, [$($field:ident),* $(,)?]) => {{
+        let mut changed = Vec::new();
+        $(
+            if format!("{:?}", $old.$field) != format!("{:?}", $new.$field) {
+                changed.push(stringify!($field));
+            }
+        )*
+        changed
+    }};
+}
+
+/// Settings only read at startup (listener, templates, background sync)
+const STARTUP_ONLY: &[&str] = &[
+    "bind_address",
+    "template_dir",
+    "sync_interval_secs",
+    "sync_jitter_secs",
+    "registry",
+    // Toggled at runtime through POST /admin/maintenance, which a reload must not undo
+    "maintenance_mode",
+];
+
+impl AppConfig {
+    /// Settings that differ between two configs
+    pub fn changed_fields(&self, other: &AppConfig) -> Vec<&'static str> {
+        changed_fields!(
+            self,
+            other,
+            [
+                registry,
+                system,
+                jwt_secret,
+                my_asn,
+                bind_address,
+                data_dir,
+                data_pending_dir,
+                data_verified_dir,
+                data_deleted_dir,
+                cookie_domains,
+                public_endpoint,
+                allowed_pgp_algorithms,
+                template_dir,
+                sync_interval_secs,
+                sync_jitter_secs,
+                registry_layout,
+                deploy_cooldown_secs,
+                link_local_scope_suffix,
+                manage_wireguard,
+                challenge_prefix,
+                challenge_random_bytes,
+                bgp_neighbor_mode,
+                ula_transfer_prefix,
+                validate_endpoint_dns,
+                default_max_prefixes,
+                strict_allowed_ips,
+                jwt_issuer,
+                jwt_audience,
+                ipv4_transfer_address,
+                port_range,
+                config_history,
+                reject_private_endpoints,
+                allow_dn42_endpoints,
+                strict_import_filter,
+                token_store,
+                admin_token,
+                public_endpoint_family,
+                stateless_challenges,
+                challenge_ttl_secs,
+                asn_range,
+                max_concurrent_verifications,
+                verification_queue,
+                maintenance_mode,
+                deploy_timeout_secs,
+                registry_data_subdir,
+                post_verify_redirect,
+                reject_duplicate_endpoints,
+                external_private_keys,
+                bird_peer_template,
+                bgp_transport,
+                max_registry_age_secs,
+                allow_tech_contact_auth,
+                tombstone_deletes,
+                allowed_endpoint_domains,
+                reject_ip_endpoints,
+                deploy_auto_activate,
+                explicit_channels,
+                allow_dns_directive,
+                my_asns,
+            ]
+        )
+    }
+
+    /// The local ASN a request picked, `my_asn` if it picked none
+    pub fn select_local_asn(&self, requested: Option<u32>) -> Result<u32, String> {
+        match requested {
+            None => Ok(self.my_asn),
+            Some(asn) if self.my_asns.contains(&asn) => Ok(asn),
+            Some(asn) => Err(format!(
+                "AS{} is not one of our ASNs ({})",
+                asn,
+                self.my_asns
+                    .iter()
+                    .map(|asn| format!("AS{}", asn))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
+/// Live application config that handlers read per request and SIGHUP replaces.
+///
+/// Handlers keep extracting `State<Arc<AppConfig>>`; each request gets the
+/// snapshot that was current when it started. Loading a snapshot doesn't lock;
+/// changes are made one at a time.
+#[derive(Debug, Clone)]
+pub struct SharedConfig {
+    current: Arc<ArcSwap<AppConfig>>,
+    writer: Arc<Mutex<()>>,
+}
+
+impl SharedConfig {
+    pub fn new(config: Arc<AppConfig>) -> Self {
+        SharedConfig {
+            current: Arc::new(ArcSwap::new(config)),
+            writer: Arc::default(),
+        }
+    }
+
+    /// Current config snapshot
+    pub fn load(&self) -> Arc<AppConfig> {
+        self.current.load_full()
+    }
+
+    /// Hold off other changes while one is made from the current snapshot
+    fn write(&self) -> std::sync::MutexGuard<'_, ()> {
+        self.writer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Swap in a freshly loaded config and return the names of the settings that changed.
+    /// Startup-only settings keep their current values; changes to them are returned
+    /// separately so the caller can warn about them.
+    pub fn reload(&self, mut new: AppConfig) -> (Vec<&'static str>, Vec<&'static str>) {
+        let _writer = self.write();
+        let current = self.load();
+
+        let (ignored, applied): (Vec<_>, Vec<_>) = current
+            .changed_fields(&new)
+            .into_iter()
+            .partition(|field| STARTUP_ONLY.contains(field));
+
+        new.bind_address = current.bind_address.clone();
+        new.template_dir = current.template_dir.clone();
+        new.sync_interval_secs = current.sync_interval_secs;
+        new.sync_jitter_secs = current.sync_jitter_secs;
+        new.registry = current.registry.clone();
+        new.maintenance_mode = current.maintenance_mode;
+        new.runtime = current.runtime.clone();
+
+        self.current.store(Arc::new(new));
+        (applied, ignored)
+    }
+
+    /// Change a setting of the live config; requests starting afterwards see the change
+    pub fn update(&self, change: impl FnOnce(&mut AppConfig)) {
+        let _writer = self.write();
+        let mut new = (*self.load()).clone();
+        change(&mut new);
+        self.current.store(Arc::new(new));
+    }
+}
+
+/// Settings the config is loaded from, by variable name
+#[derive(Debug, Clone, Default)]
+pub struct Settings(HashMap<String, String>);
+
+impl Settings {
+    /// The process environment as it is now
+    pub fn from_env() -> Self {
+        Settings(env::vars().collect())
+    }
+
+    /// Value of a setting; unset like a missing env var
+    pub fn var(&self, name: &str) -> Result<String, env::VarError> {
+        self.0.get(name).cloned().ok_or(env::VarError::NotPresent)
+    }
+
+    /// Read a boolean setting (`true`/`1`/`yes`/`on`), falling back to `default` when unset
+    fn flag(&self, name: &str, default: bool) -> bool {
+        self.var(name)
+            .map(|v| matches!(v.to_lowercase().as_str(), "true" | "1" | "yes" | "on"))
+            .unwrap_or(default)
+    }
+}
+
+impl From<HashMap<String, String>> for Settings {
+    fn from(vars: HashMap<String, String>) -> Self {
+        Settings(vars)
+    }
+}
+
+/// Where settings come from: the environment the process was started with, and the
+/// config file (`.env` when none is given) for whatever the environment doesn't set.
+///
+/// The file is read again for every load and the process environment is never
+/// changed, so a reload sees exactly what a restart would.
+#[derive(Debug, Clone)]
+pub struct ConfigSource {
+    inherited: HashMap<String, String>,
+    config_file: Option<PathBuf>,
+}
+
+impl ConfigSource {
+    pub fn new(config_file: Option<PathBuf>) -> Self {
+        ConfigSource {
+            inherited: env::vars().collect(),
+            config_file,
+        }
+    }
+
+    /// Read the config file and layer the environment over it. A missing `.env` is
+    /// fine; a missing `--config` file is not.
+    pub fn settings(&self) -> Result<Settings, dotenvy::Error> {
+        let file: Vec<(String, String)> = match &self.config_file {
+            Some(path) => dotenvy::from_path_iter(path)?.collect::<Result<_, _>>()?,
+            None => match dotenvy::dotenv_iter() {
+                Ok(iter) => iter.collect::<Result<_, _>>()?,
+                Err(e) if e.not_found() => Vec::new(),
+                Err(e) => return Err(e),
+            },
+        };
+
+        let mut vars: HashMap<String, String> = file.into_iter().collect();
+        vars.extend(
+            self.inherited
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+        Ok(Settings(vars))
+    }
+}
+
+impl FromRef<SharedConfig> for Arc<AppConfig> {
+    fn from_ref(shared: &SharedConfig) -> Self {
+        shared.load()
+    }
+}
+
 impl RegistryConfig {
     /// Load registry configuration from environment variables
     pub fn from_env() -> Result<Self, String> {
-        let url = env::var("DN42_REGISTRY_URL")
+        Self::from_settings(&Settings::from_env())
+    }
+
+    /// Load registry configuration from `settings`
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        let url = settings
+            .var("DN42_REGISTRY_URL")
             .unwrap_or_else(|_| "https://git.dn42.dev/dn42/registry".to_string());
 
-        let path =
-            env::var("DN42_REGISTRY_PATH").unwrap_or_else(|_| "./data/dn42-registry".to_string());
+        let path = settings
+            .var("DN42_REGISTRY_PATH")
+            .unwrap_or_else(|_| "./data/dn42-registry".to_string());
 
-        let username =
-            env::var("DN42_GIT_USERNAME").map_err(|_| "DN42_GIT_USERNAME not set".to_string())?;
+        let username = settings
+            .var("DN42_GIT_USERNAME")
+            .map_err(|_| "DN42_GIT_USERNAME not set".to_string())?;
 
-        let token = env::var("DN42_GIT_TOKEN").map_err(|_| "DN42_GIT_TOKEN not set".to_string())?;
+        let token = settings
+            .var("DN42_GIT_TOKEN")
+            .map_err(|_| "DN42_GIT_TOKEN not set".to_string())?;
 
         Ok(RegistryConfig {
             url,
@@ -98,26 +857,270 @@ impl RegistryConfig {
     }
 }
 
+impl SystemConfig {
+    /// Load system paths from environment variables
+    pub fn from_env() -> Self {
+        Self::from_settings(&Settings::from_env())
+    }
+
+    /// Load system paths from `settings`
+    pub fn from_settings(settings: &Settings) -> Self {
+        let wireguard_dir = settings
+            .var("WIREGUARD_DIR")
+            .unwrap_or_else(|_| "/etc/wireguard".to_string());
+
+        let bird_peers_dir = settings
+            .var("BIRD_PEERS_DIR")
+            .unwrap_or_else(|_| "/etc/bird/peers".to_string());
+
+        SystemConfig {
+            wireguard_dir: PathBuf::from(wireguard_dir),
+            bird_peers_dir: PathBuf::from(bird_peers_dir),
+            wg_bin: settings.var("WG_BIN").unwrap_or_else(|_| "wg".to_string()),
+            wg_quick_bin: settings
+                .var("WG_QUICK_BIN")
+                .unwrap_or_else(|_| "wg-quick".to_string()),
+            birdc_bin: settings
+                .var("BIRDC_BIN")
+                .unwrap_or_else(|_| "birdc".to_string()),
+            wg_up_timeout_secs: settings
+                .var("WG_UP_TIMEOUT_SECS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(5),
+            birdc_timeout_secs: settings
+                .var("BIRDC_TIMEOUT_SECS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(10),
+            command_timeout_secs: settings
+                .var("COMMAND_TIMEOUT_SECS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(30),
+        }
+    }
+
+    /// Timeout for `wg` and `wg-quick` invocations
+    pub fn command_timeout(&self) -> Duration {
+        Duration::from_secs(self.command_timeout_secs)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// The environment with `.env` filled in, as the server loads it
+    fn local_settings() -> Settings {
+        ConfigSource::new(None).settings().unwrap()
+    }
+
     #[test]
     fn test_registry_config_from_env() {
-        dotenvy::dotenv().ok();
-
-        let config = RegistryConfig::from_env().unwrap();
+        let config = RegistryConfig::from_settings(&local_settings()).unwrap();
         assert_eq!(config.url, "https://git.dn42.dev/dn42/registry");
         assert!(!config.username.is_empty());
         assert!(!config.token.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_reload_is_seen_by_handlers() {
+        use axum::{body::Body, extract::State, http::Request, routing::get, Router};
+        use tower::ServiceExt;
+
+        let shared = SharedConfig::new(crate::api::test_helpers::test_config());
+        let app = Router::new()
+            .route(
+                "/asn",
+                get(
+                    |State(config): State<Arc<AppConfig>>| async move { config.my_asn.to_string() },
+                ),
+            )
+            .with_state(shared.clone());
+        let observed_asn = |app: Router| async move {
+            let response = app
+                .oneshot(Request::builder().uri("/asn").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            String::from_utf8(body.to_vec()).unwrap()
+        };
+        assert_eq!(observed_asn(app.clone()).await, "4242420257");
+
+        let runtime = shared.load().runtime.clone();
+        let mut new = (*shared.load()).clone();
+        new.runtime = Arc::default();
+        new.my_asn = 4242421234;
+        new.bind_address = "0.0.0.0:1".to_string();
+        let (applied, ignored) = shared.reload(new);
+
+        assert_eq!(applied, vec!["my_asn"]);
+        assert_eq!(ignored, vec!["bind_address"]);
+        assert_eq!(observed_asn(app).await, "4242421234");
+        // The listener can't move, so the old address is kept
+        assert_eq!(shared.load().bind_address, "127.0.0.1:3000");
+        // Limiters and locks carry over
+        assert!(Arc::ptr_eq(&shared.load().runtime, &runtime));
+    }
+
     #[test]
-    fn test_app_config_from_env() {
-        dotenvy::dotenv().ok();
+    fn test_reload_rereads_the_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("autopeer.env");
+        let source = ConfigSource {
+            inherited: HashMap::from([("PINNED".to_string(), "env".to_string())]),
+            config_file: Some(path.clone()),
+        };
+
+        std::fs::write(&path, "SETTING=a\nPINNED=file\n").unwrap();
+        let settings = source.settings().unwrap();
+        assert_eq!(settings.var("SETTING").as_deref(), Ok("a"));
+        // The environment wins over the file, on every load
+        assert_eq!(settings.var("PINNED").as_deref(), Ok("env"));
+
+        // Deleted from the file: unset again, as after a restart
+        std::fs::write(&path, "PINNED=file\n").unwrap();
+        let settings = source.settings().unwrap();
+        assert!(settings.var("SETTING").is_err());
+        assert_eq!(settings.var("PINNED").as_deref(), Ok("env"));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(source.settings().is_err());
+    }
+
+    /// Load the config from a complete environment with `overrides` applied
+    fn load_with_env(overrides: &[(&str, &str)]) -> Result<AppConfig, String> {
+        let Settings(mut vars) = local_settings();
+
+        // Everything else must load, so the overrides decide the outcome
+        let baseline = [
+            ("MY_ASN", "4242420257"),
+            ("JWT_SECRET", "0123456789abcdef0123456789abcdef"),
+            ("ALLOW_WEAK_JWT_SECRET", "false"),
+            ("DN42_GIT_USERNAME", "user"),
+            ("DN42_GIT_TOKEN", "token"),
+        ];
+        for (name, value) in baseline.iter().chain(overrides) {
+            vars.insert(name.to_string(), value.to_string());
+        }
+        AppConfig::from_settings(&Settings(vars))
+    }
 
-        let config = AppConfig::from_env().unwrap();
+    #[test]
+    fn test_app_config_from_env() {
+        let config = AppConfig::from_settings(&local_settings()).unwrap();
         assert_eq!(config.registry.url, "https://git.dn42.dev/dn42/registry");
         assert!(!config.jwt_secret.is_empty());
     }
+
+    #[test]
+    fn test_out_of_range_my_asn_is_rejected() {
+        let err = load_with_env(&[("MY_ASN", "13335")]).unwrap_err();
+        assert!(err.contains("MY_ASN"), "{}", err);
+        assert!(err.contains("13335"), "{}", err);
+    }
+
+    #[test]
+    fn test_custom_asn_range() {
+        let config = load_with_env(&[("ASN_RANGE", "64512-65534"), ("MY_ASN", "64512")]).unwrap();
+        assert_eq!(
+            config.asn_range,
+            AsnRange {
+                start: 64512,
+                end: 65534
+            }
+        );
+        assert_eq!(config.my_asn, 64512);
+
+        // Our own ASN has to be in the range too
+        assert!(load_with_env(&[("ASN_RANGE", "64512-65534")]).is_err());
+        assert!(load_with_env(&[("ASN_RANGE", "65534-64512"), ("MY_ASN", "64512")]).is_err());
+    }
+
+    #[test]
+    fn test_my_asns() {
+        let config = load_with_env(&[]).unwrap();
+        assert_eq!(config.my_asns, vec![config.my_asn]);
+        assert_eq!(config.select_local_asn(None), Ok(config.my_asn));
+
+        let config = load_with_env(&[("MY_ASNS", "4242420263, 4242420257,4242420264")]).unwrap();
+        assert_eq!(config.my_asns, vec![4242420257, 4242420263, 4242420264]);
+        assert_eq!(config.select_local_asn(Some(4242420263)), Ok(4242420263));
+        assert!(config
+            .select_local_asn(Some(4242422225))
+            .unwrap_err()
+            .contains("AS4242420264"));
+
+        assert!(load_with_env(&[("MY_ASNS", "4242420263,13335")]).is_err());
+        assert!(load_with_env(&[("MY_ASNS", "AS4242420263")]).is_err());
+    }
+
+    #[test]
+    fn test_jwt_secret_length() {
+        let err = load_with_env(&[("JWT_SECRET", "secret")]).unwrap_err();
+        assert!(err.contains("at least 32 bytes"), "{}", err);
+
+        let config = load_with_env(&[("JWT_SECRET", &"x".repeat(MIN_JWT_SECRET_LEN))]).unwrap();
+        assert_eq!(config.jwt_secret.len(), MIN_JWT_SECRET_LEN);
+
+        // Short secrets are fine when explicitly allowed
+        let config =
+            load_with_env(&[("JWT_SECRET", "secret"), ("ALLOW_WEAK_JWT_SECRET", "true")]).unwrap();
+        assert_eq!(config.jwt_secret, "secret");
+    }
+
+    #[test]
+    fn test_data_dir_derives_subdirs() {
+        let config = load_with_env(&[
+            ("DATA_DIR", "/var/lib/autopeer/"),
+            ("DATA_PENDING_DIR", ""),
+            ("DATA_VERIFIED_DIR", ""),
+            ("DATA_DELETED_DIR", ""),
+            ("TOKEN_STORE", ""),
+        ])
+        .unwrap();
+        assert_eq!(config.data_dir, "/var/lib/autopeer");
+        assert_eq!(config.data_pending_dir, "/var/lib/autopeer/pending");
+        assert_eq!(config.data_verified_dir, "/var/lib/autopeer/verified");
+        assert_eq!(config.data_deleted_dir, "/var/lib/autopeer/deleted");
+        assert_eq!(
+            config.token_store,
+            PathBuf::from("/var/lib/autopeer/tokens.json")
+        );
+
+        // The explicit overrides still win
+        let config = load_with_env(&[
+            ("DATA_DIR", "/var/lib/autopeer"),
+            ("DATA_PENDING_DIR", "/tmp/pending"),
+            ("DATA_VERIFIED_DIR", ""),
+            ("TOKEN_STORE", "/etc/autopeer/tokens.json"),
+        ])
+        .unwrap();
+        assert_eq!(config.data_pending_dir, "/tmp/pending");
+        assert_eq!(
+            config.token_store,
+            PathBuf::from("/etc/autopeer/tokens.json")
+        );
+        assert_eq!(config.data_verified_dir, "/var/lib/autopeer/verified");
+    }
+
+    #[test]
+    fn test_bird_peer_template() {
+        assert_eq!(
+            load_with_env(&[("BIRD_PEER_TEMPLATE", "")])
+                .unwrap()
+                .bird_peer_template,
+            "dnpeers"
+        );
+        assert_eq!(
+            load_with_env(&[("BIRD_PEER_TEMPLATE", "dn42_peers")])
+                .unwrap()
+                .bird_peer_template,
+            "dn42_peers"
+        );
+        assert!(load_with_env(&[("BIRD_PEER_TEMPLATE", "peers { }")]).is_err());
+    }
 }
@@ -1,3 +1,13 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// Short form of an ASN that addresses and ports are derived from: its last 4 digits
+pub fn asn_short(asn: u32) -> u16 {
+    (asn % 10000) as u16
+}
+
 /// Derive IPv6 link-local addresses from ASNs
 /// Format: fe80::{peer_asn}:{my_asn}:{0/1}/64
 pub struct Ipv6LinkLocal {
@@ -9,8 +19,8 @@ impl Ipv6LinkLocal {
     /// Generate IPv6 link-local addresses for a peering
     /// Format: fe80::{peer_short}:{my_short}:{0 for local, 1 for peer}
     pub fn from_asns(my_asn: u32, peer_asn: u32) -> Self {
-        let my_short = my_asn % 10000; // Last 4 digits
-        let peer_short = peer_asn % 10000; // Last 4 digits
+        let my_short = asn_short(my_asn);
+        let peer_short = asn_short(peer_asn);
 
         Ipv6LinkLocal {
             local: format!("fe80::{}:{}:0/64", peer_short, my_short),
@@ -22,6 +32,213 @@ impl Ipv6LinkLocal {
     pub fn local_addr(&self) -> String {
         self.local.split('/').next().unwrap().to_string()
     }
+
+    /// Whether the addresses for a peering can't tell the two sides apart: when both ASNs
+    /// share their short form, the peer deriving them the other way round gets ours
+    pub fn collides(my_asn: u32, peer_asn: u32) -> bool {
+        asn_short(my_asn) == asn_short(peer_asn)
+    }
+}
+
+/// Which addresses the BGP session runs between
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeighborMode {
+    /// Link-local addresses on the tunnel interface
+    LinkLocal,
+    /// Addresses from the ULA transfer net (link-local stays on the interface)
+    Ula,
+}
+
+impl FromStr for NeighborMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "link_local" => Ok(NeighborMode::LinkLocal),
+            "ula" => Ok(NeighborMode::Ula),
+            other => Err(format!("Unknown BGP neighbor mode: {}", other)),
+        }
+    }
+}
+
+/// Which addresses the tunnel interface carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BgpTransport {
+    /// Link-local addresses, plus the transfer net if the session runs over one
+    #[default]
+    LinkLocal,
+    /// Only the routed transfer net (ULA, or IPv4 for IPv4-only sessions), for BIRD setups
+    /// that don't want BGP on link-local
+    TransferNet,
+}
+
+impl FromStr for BgpTransport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "link_local" => Ok(BgpTransport::LinkLocal),
+            "transfer_net" => Ok(BgpTransport::TransferNet),
+            other => Err(format!("Unknown BGP transport: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for BgpTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BgpTransport::LinkLocal => "link_local",
+            BgpTransport::TransferNet => "transfer_net",
+        })
+    }
+}
+
+/// A ULA /48 transfer net, e.g. `fd42:4242:257::/48`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UlaPrefix([u16; 3]);
+
+impl FromStr for UlaPrefix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, len) = s
+            .split_once('/')
+            .ok_or_else(|| format!("ULA prefix {} is missing a prefix length", s))?;
+        if len != "48" {
+            return Err(format!("ULA prefix {} must be a /48", s));
+        }
+
+        let addr: Ipv6Addr = addr
+            .parse()
+            .map_err(|e| format!("Invalid ULA prefix {}: {}", s, e))?;
+        let segments = addr.segments();
+        if segments[0] & 0xff00 != 0xfd00 {
+            return Err(format!("{} is not a ULA (fd00::/8) prefix", s));
+        }
+
+        Ok(UlaPrefix([segments[0], segments[1], segments[2]]))
+    }
+}
+
+/// Derive ULA transfer addresses from the peer's ASN
+/// Format: {prefix}:{peer_short}::{1 for local, 2 for peer}/64
+#[derive(Debug, Clone)]
+pub struct Ipv6Ula {
+    pub local: String,
+    pub peer: String,
+}
+
+impl Ipv6Ula {
+    /// Generate ULA transfer addresses for a peering (the /48 is ours, so only the peer ASN varies)
+    pub fn from_peer_asn(prefix: &UlaPrefix, peer_asn: u32) -> Self {
+        let peer_short = asn_short(peer_asn);
+        let [a, b, c] = prefix.0;
+        let net = format!("{:x}:{:x}:{:x}:{}", a, b, c, peer_short);
+
+        Ipv6Ula {
+            local: format!("{}::1/64", net),
+            peer: format!("{}::2", net),
+        }
+    }
+
+    /// Get the local address without CIDR
+    pub fn local_addr(&self) -> String {
+        self.local.split('/').next().unwrap().to_string()
+    }
+}
+
+/// Address families a BGP session carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressFamily {
+    /// IPv4 only, over an IPv4 transfer net
+    Ipv4,
+    /// IPv6 only, over link-local or ULA
+    Ipv6,
+    /// Both, IPv4 via MP-BGP with extended next hop over the IPv6 session
+    #[default]
+    Dual,
+}
+
+impl AddressFamily {
+    pub fn has_ipv4(self) -> bool {
+        self != AddressFamily::Ipv6
+    }
+
+    pub fn has_ipv6(self) -> bool {
+        self != AddressFamily::Ipv4
+    }
+}
+
+impl FromStr for AddressFamily {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ipv4" => Ok(AddressFamily::Ipv4),
+            "ipv6" => Ok(AddressFamily::Ipv6),
+            "dual" => Ok(AddressFamily::Dual),
+            other => Err(format!("Unknown address family: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for AddressFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AddressFamily::Ipv4 => "ipv4",
+            AddressFamily::Ipv6 => "ipv6",
+            AddressFamily::Dual => "dual",
+        })
+    }
+}
+
+/// IPv4 transfer addresses for an IPv4-only session (ours and the peer's, both /32)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv4Transfer {
+    pub local: Ipv4Addr,
+    pub peer: Ipv4Addr,
+}
+
+/// Inclusive range of WireGuard listen ports peers may use, e.g. `30000-39999`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl PortRange {
+    pub fn contains(&self, port: u16) -> bool {
+        (self.start..=self.end).contains(&port)
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| format!("Port range {} must look like start-end", s))?;
+        let parse = |port: &str| {
+            port.trim()
+                .parse::<u16>()
+                .map_err(|e| format!("Invalid port in range {}: {}", s, e))
+        };
+        let (start, end) = (parse(start)?, parse(end)?);
+        if start == 0 || start > end {
+            return Err(format!("Invalid port range {}", s));
+        }
+
+        Ok(PortRange { start, end })
+    }
+}
+
+impl fmt::Display for PortRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
 }
 
 /// Generate WireGuard interface name from ASN
@@ -29,17 +246,54 @@ pub fn interface_name(asn: u32) -> String {
     format!("wg-as{}", asn)
 }
 
+/// First port of the range WireGuard ports are derived into
+const WIREGUARD_PORT_BASE: u32 = 30000;
+
 /// Derive WireGuard port from ASN
 /// Format: 3{last 4 digits of ASN}
-pub fn wireguard_port(asn: u32) -> u16 {
-    let short_asn = asn % 10000; // Last 4 digits
-    30000 + (short_asn as u16)
+pub fn wireguard_port(asn: u32) -> Result<u16, String> {
+    port_from_base(WIREGUARD_PORT_BASE, asn)
+}
+
+/// `base` plus the ASN's short form, if that is still a valid port
+fn port_from_base(base: u32, asn: u32) -> Result<u16, String> {
+    let short_asn = u32::from(asn_short(asn));
+    base.checked_add(short_asn)
+        .and_then(|port| u16::try_from(port).ok())
+        .filter(|port| *port != 0)
+        .ok_or_else(|| {
+            format!(
+                "Port base {} + {} for AS{} is not a valid port (1-65535)",
+                base, short_asn, asn
+            )
+        })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_asn_short() {
+        assert_eq!(asn_short(4242420257), 257);
+        assert_eq!(asn_short(4242422225), 2225);
+        assert_eq!(asn_short(4242420000), 0);
+        assert_eq!(asn_short(4242429999), 9999);
+        assert_eq!(asn_short(64512), 4512);
+        assert_eq!(asn_short(u32::MAX), 7295);
+
+        // Everything derived from an ASN agrees on its short form
+        let asn = 4242423088;
+        assert_eq!(wireguard_port(asn).unwrap(), 30000 + asn_short(asn));
+        assert!(Ipv6LinkLocal::from_asns(4242420257, asn)
+            .peer
+            .starts_with("fe80::3088:"));
+        let prefix: UlaPrefix = "fd42:4242:257::/48".parse().unwrap();
+        assert!(Ipv6Ula::from_peer_asn(&prefix, asn)
+            .peer
+            .contains(":3088::"));
+    }
+
     #[test]
     fn test_ipv6_from_asns() {
         // Your ASN: 4242420257, Peer ASN: 4242422225
@@ -49,6 +303,14 @@ mod tests {
         assert_eq!(ips.local_addr(), "fe80::2225:257:0");
     }
 
+    #[test]
+    fn test_colliding_short_asns() {
+        assert!(!Ipv6LinkLocal::collides(4242420257, 4242422225));
+        // Same last 4 digits, e.g. a NeoNetwork ASN or ourselves
+        assert!(Ipv6LinkLocal::collides(4242420257, 4201270257));
+        assert!(Ipv6LinkLocal::collides(4242420257, 4242420257));
+    }
+
     #[test]
     fn test_ipv6_from_asns_reverse() {
         // Your ASN: 4242420257, Peer ASN: 4242423088
@@ -57,6 +319,37 @@ mod tests {
         assert_eq!(ips.peer, "fe80::3088:257:1");
     }
 
+    #[test]
+    fn test_address_family_from_str() {
+        assert_eq!(
+            "ipv4".parse::<AddressFamily>().unwrap(),
+            AddressFamily::Ipv4
+        );
+        assert_eq!(
+            "IPv6".parse::<AddressFamily>().unwrap(),
+            AddressFamily::Ipv6
+        );
+        assert_eq!(
+            "dual".parse::<AddressFamily>().unwrap(),
+            AddressFamily::Dual
+        );
+        assert!("ipx".parse::<AddressFamily>().is_err());
+        assert_eq!(AddressFamily::default(), AddressFamily::Dual);
+    }
+
+    #[test]
+    fn test_port_range_from_str() {
+        let range: PortRange = "30000-39999".parse().unwrap();
+        assert!(range.contains(30000));
+        assert!(range.contains(39999));
+        assert!(!range.contains(40000));
+        assert_eq!(range.to_string(), "30000-39999");
+
+        assert!("39999-30000".parse::<PortRange>().is_err());
+        assert!("0-100".parse::<PortRange>().is_err());
+        assert!("30000".parse::<PortRange>().is_err());
+    }
+
     #[test]
     fn test_interface_name() {
         assert_eq!(interface_name(4242422225), "wg-as4242422225");
@@ -65,8 +358,44 @@ mod tests {
 
     #[test]
     fn test_wireguard_port() {
-        assert_eq!(wireguard_port(4242422225), 32225);
-        assert_eq!(wireguard_port(4242423088), 33088);
-        assert_eq!(wireguard_port(4242421234), 31234);
+        assert_eq!(wireguard_port(4242422225).unwrap(), 32225);
+        assert_eq!(wireguard_port(4242423088).unwrap(), 33088);
+        assert_eq!(wireguard_port(4242421234).unwrap(), 31234);
+    }
+
+    #[test]
+    fn test_port_base_out_of_range() {
+        assert_eq!(port_from_base(60000, 4242425535).unwrap(), 65535);
+        assert!(port_from_base(60000, 4242425536).is_err());
+        assert!(port_from_base(70000, 4242420001).is_err());
+        assert!(port_from_base(u32::MAX, 4242422225).is_err());
+        assert!(port_from_base(0, 4242420000).is_err());
+    }
+
+    #[test]
+    fn test_ula_from_asns() {
+        let prefix: UlaPrefix = "fd42:4242:257::/48".parse().unwrap();
+        let ips = Ipv6Ula::from_peer_asn(&prefix, 4242422225);
+        assert_eq!(ips.local, "fd42:4242:257:2225::1/64");
+        assert_eq!(ips.peer, "fd42:4242:257:2225::2");
+        assert_eq!(ips.local_addr(), "fd42:4242:257:2225::1");
+    }
+
+    #[test]
+    fn test_ula_prefix_validation() {
+        assert!("fd42:4242:257::/64".parse::<UlaPrefix>().is_err());
+        assert!("2001:db8::/48".parse::<UlaPrefix>().is_err());
+        assert!("fd42:4242:257::".parse::<UlaPrefix>().is_err());
+        assert_eq!(
+            "link_local".parse::<NeighborMode>().unwrap(),
+            NeighborMode::LinkLocal
+        );
+        assert_eq!("ula".parse::<NeighborMode>().unwrap(), NeighborMode::Ula);
+        assert_eq!(
+            "transfer_net".parse::<BgpTransport>().unwrap(),
+            BgpTransport::TransferNet
+        );
+        assert_eq!(BgpTransport::TransferNet.to_string(), "transfer_net");
+        assert!("routed".parse::<BgpTransport>().is_err());
     }
 }
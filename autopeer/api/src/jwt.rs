@@ -1,6 +1,10 @@
+use crate::config::AppConfig;
+use crate::revocation::TokenStore;
 use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
 
 /// JWT claims for authenticated ASNs
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -11,6 +15,60 @@ pub struct Claims {
     pub iat: i64,
     /// Expiration time (Unix timestamp)
     pub exp: i64,
+    /// Token id, for revocation (absent in older tokens)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    /// Challenge code that authorized this token (absent in older tokens)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<String>,
+    /// Contact given at /peering/init, for notifications
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contact: Option<String>,
+    /// Registry key that verified the challenge (PGP fingerprint or minisign public key)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// Issuer, when configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    /// Audience, when configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+}
+
+/// Optional issuer/audience that tokens are issued with and must carry
+#[derive(Debug, Clone, Default)]
+pub struct JwtScope {
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    /// Token store issued tokens are recorded in and checked against (unset skips both)
+    pub token_store: Option<(PathBuf, Arc<TokenStore>)>,
+}
+
+impl JwtScope {
+    /// Issuer and audience from `JWT_ISSUER` / `JWT_AUDIENCE`
+    pub fn from_config(config: &AppConfig) -> Self {
+        JwtScope {
+            issuer: config.jwt_issuer.clone(),
+            audience: config.jwt_audience.clone(),
+            token_store: Some((config.token_store.clone(), config.runtime.tokens.clone())),
+        }
+    }
+
+    /// Token validation that requires the configured claims (unset ones aren't checked)
+    fn validation(&self) -> Validation {
+        let mut validation = Validation::default();
+        let mut required = vec!["exp"];
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+            required.push("iss");
+        }
+        if let Some(audience) = &self.audience {
+            validation.set_audience(&[audience]);
+            required.push("aud");
+        }
+        validation.set_required_spec_claims(&required);
+        validation
+    }
 }
 
 impl Claims {
@@ -23,17 +81,51 @@ impl Claims {
             asn,
             iat: now.timestamp(),
             exp: expiration.timestamp(),
+            jti: Some(hex::encode(rand::random::<[u8; 16]>())),
+            challenge: None,
+            contact: None,
+            fingerprint: None,
+            iss: None,
+            aud: None,
         }
     }
 }
 
 /// Generate a JWT token for an authenticated ASN
 pub fn generate_token(asn: u32, secret: &str) -> Result<String, String> {
-    let claims = Claims::new(asn);
+    encode_claims(&Claims::new(asn), secret)
+}
+
+/// Generate a JWT token that records the challenge the ASN signed, the registry key it
+/// was verified with, and its contact (if given)
+pub fn generate_challenge_token(
+    asn: u32,
+    challenge: &str,
+    contact: Option<&str>,
+    fingerprint: Option<&str>,
+    secret: &str,
+    scope: &JwtScope,
+) -> Result<String, String> {
+    let claims = Claims {
+        challenge: Some(challenge.to_string()),
+        contact: contact.map(str::to_string),
+        fingerprint: fingerprint.map(str::to_string),
+        iss: scope.issuer.clone(),
+        aud: scope.audience.clone(),
+        ..Claims::new(asn)
+    };
+
+    let token = encode_claims(&claims, secret)?;
+    if let (Some((path, store)), Some(jti)) = (&scope.token_store, &claims.jti) {
+        store.record_issued(path, asn, jti, claims.exp)?;
+    }
+    Ok(token)
+}
 
+fn encode_claims(claims: &Claims, secret: &str) -> Result<String, String> {
     encode(
         &Header::default(),
-        &claims,
+        claims,
         &EncodingKey::from_secret(secret.as_bytes()),
     )
     .map_err(|e| format!("Failed to generate token: {}", e))
@@ -60,16 +152,27 @@ pub fn verify_token(token: &str, asn: u32, secret: &str) -> Result<(), String> {
 }
 
 /// Decode a token and extract the ASN (without validating against a specific ASN)
-pub fn decode_token(token: &str, secret: &str) -> Result<u32, String> {
+pub fn decode_token(token: &str, secret: &str, scope: &JwtScope) -> Result<u32, String> {
+    decode_claims(token, secret, scope).map(|claims| claims.asn)
+}
+
+/// Decode a token and return all of its claims
+pub fn decode_claims(token: &str, secret: &str, scope: &JwtScope) -> Result<Claims, String> {
     let claims = decode::<Claims>(
         token,
         &DecodingKey::from_secret(secret.as_bytes()),
-        &Validation::default(),
+        &scope.validation(),
     )
     .map(|data| data.claims)
     .map_err(|e| format!("Failed to decode token: {}", e))?;
 
-    Ok(claims.asn)
+    // Checked with or without a jti: revoking an ASN also cuts off tokens that have none
+    if let Some((path, store)) = &scope.token_store {
+        if store.is_revoked(path, claims.asn, claims.jti.as_deref(), claims.iat)? {
+            return Err("Token has been revoked".to_string());
+        }
+    }
+    Ok(claims)
 }
 
 #[cfg(test)]
@@ -124,4 +227,146 @@ mod tests {
         let expected_exp = claims.iat + (7 * 24 * 3600);
         assert_eq!(claims.exp, expected_exp);
     }
+
+    #[test]
+    fn test_challenge_token() {
+        let asn = 4242420257;
+        let scope = JwtScope::default();
+        let token = generate_challenge_token(
+            asn,
+            "AUTOPEER-4242420257-abc",
+            None,
+            None,
+            TEST_SECRET,
+            &scope,
+        )
+        .unwrap();
+
+        let claims = decode_claims(&token, TEST_SECRET, &scope).unwrap();
+        assert_eq!(claims.asn, asn);
+        assert_eq!(claims.challenge.as_deref(), Some("AUTOPEER-4242420257-abc"));
+
+        // Plain tokens carry no challenge
+        let token = generate_token(asn, TEST_SECRET).unwrap();
+        assert!(decode_claims(&token, TEST_SECRET, &scope)
+            .unwrap()
+            .challenge
+            .is_none());
+    }
+
+    #[test]
+    fn test_revoked_token_is_rejected() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("tokens.json");
+        let store = Arc::new(TokenStore::default());
+        let scope = JwtScope {
+            token_store: Some((path.clone(), store.clone())),
+            ..JwtScope::default()
+        };
+        let asn = 4242420257;
+
+        let revoked = generate_challenge_token(
+            asn,
+            "AUTOPEER-4242420257-abc",
+            None,
+            None,
+            TEST_SECRET,
+            &scope,
+        )
+        .unwrap();
+        assert!(decode_claims(&revoked, TEST_SECRET, &scope).is_ok());
+        assert_eq!(store.revoke_asn(&path, asn).unwrap(), 1);
+
+        let err = decode_claims(&revoked, TEST_SECRET, &scope).unwrap_err();
+        assert!(err.contains("revoked"));
+
+        // Tokens issued after the revocation work
+        let fresh = generate_challenge_token(
+            asn,
+            "AUTOPEER-4242420257-def",
+            None,
+            None,
+            TEST_SECRET,
+            &scope,
+        )
+        .unwrap();
+        assert_eq!(decode_token(&fresh, TEST_SECRET, &scope).unwrap(), asn);
+    }
+
+    #[test]
+    fn test_revocation_covers_tokens_without_jti() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("tokens.json");
+        let store = Arc::new(TokenStore::default());
+        let scope = JwtScope {
+            token_store: Some((path.clone(), store.clone())),
+            ..JwtScope::default()
+        };
+        let asn = 4242420257;
+
+        // As issued before tokens had ids
+        let claims = Claims {
+            jti: None,
+            iat: Utc::now().timestamp() - 60,
+            ..Claims::new(asn)
+        };
+        let legacy = encode_claims(&claims, TEST_SECRET).unwrap();
+        assert!(decode_claims(&legacy, TEST_SECRET, &scope).is_ok());
+
+        assert_eq!(store.revoke_asn(&path, asn).unwrap(), 0);
+        assert!(decode_claims(&legacy, TEST_SECRET, &scope)
+            .unwrap_err()
+            .contains("revoked"));
+    }
+
+    fn scope(issuer: &str, audience: &str) -> JwtScope {
+        JwtScope {
+            issuer: Some(issuer.to_string()),
+            audience: Some(audience.to_string()),
+            token_store: None,
+        }
+    }
+
+    #[test]
+    fn test_issuer_and_audience_match() {
+        let asn = 4242420257;
+        let scope = scope("autopeer", "dn42-gateway");
+        let token = generate_challenge_token(
+            asn,
+            "AUTOPEER-4242420257-abc",
+            None,
+            None,
+            TEST_SECRET,
+            &scope,
+        )
+        .unwrap();
+
+        let claims = decode_claims(&token, TEST_SECRET, &scope).unwrap();
+        assert_eq!(claims.iss.as_deref(), Some("autopeer"));
+        assert_eq!(claims.aud.as_deref(), Some("dn42-gateway"));
+        assert_eq!(decode_token(&token, TEST_SECRET, &scope).unwrap(), asn);
+    }
+
+    #[test]
+    fn test_issuer_and_audience_mismatch() {
+        let asn = 4242420257;
+        let token = generate_challenge_token(
+            asn,
+            "AUTOPEER-4242420257-abc",
+            None,
+            None,
+            TEST_SECRET,
+            &scope("autopeer", "dn42-gateway"),
+        )
+        .unwrap();
+
+        assert!(
+            decode_claims(&token, TEST_SECRET, &scope("someone-else", "dn42-gateway")).is_err()
+        );
+        assert!(decode_claims(&token, TEST_SECRET, &scope("autopeer", "other-service")).is_err());
+
+        // Tokens without the claims are rejected once they are configured
+        let plain = generate_token(asn, TEST_SECRET).unwrap();
+        assert!(decode_claims(&plain, TEST_SECRET, &scope("autopeer", "dn42-gateway")).is_err());
+    }
 }
@@ -6,13 +6,21 @@ pub mod ipalloc;
 pub mod jwt;
 pub mod middleware;
 pub mod registry;
+pub mod revocation;
 pub mod templates;
+pub mod util;
 pub mod validation;
 pub mod wireguard;
 
-use axum::{routing::{delete, get, patch, post}, Router};
+use axum::{
+    routing::{delete, get, patch, post},
+    Router,
+};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tower_cookies::CookieManagerLayer;
+use tracing::{error, info, warn};
 
 #[tokio::main]
 async fn main() {
@@ -20,37 +28,109 @@ async fn main() {
     tracing_subscriber::fmt()
         .with_env_filter(
             tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"))
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
         )
         .init();
 
-    // Load environment variables
-    dotenvy::dotenv().ok();
+    // Settings come from the environment and `.env` (`--config <file>` replaces it)
+    let config_source = config::ConfigSource::new(config_file_arg());
+    let settings = config_source
+        .settings()
+        .expect("Failed to read config file");
 
     // Load application config
     let app_config = Arc::new(
-        config::AppConfig::from_env().expect("Failed to load configuration")
+        config::AppConfig::from_settings(&settings).expect("Failed to load configuration"),
     );
 
+    // Make sure the state directories exist before the first request needs them
+    for dir in [&app_config.data_pending_dir, &app_config.data_verified_dir] {
+        std::fs::create_dir_all(dir)
+            .unwrap_or_else(|e| panic!("Failed to create data directory {}: {}", dir, e));
+    }
+    if let Some(dir) = app_config
+        .token_store
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(dir).unwrap_or_else(|e| {
+            panic!(
+                "Failed to create token store directory {}: {}",
+                dir.display(),
+                e
+            )
+        });
+    }
+
+    // Check templates (with operator overrides) before serving, failing fast with every problem found
+    if let Err(errors) = templates::validate(app_config.template_dir.as_deref()) {
+        panic!("Invalid templates:\n{}", errors.join("\n"));
+    }
+    templates::init(app_config.template_dir.as_deref()).expect("Failed to load templates");
+
+    // Keep the DN42 registry fresh in the background
+    if app_config.sync_interval_secs > 0 {
+        tokio::spawn(registry::sync::run_periodic(
+            app_config.registry.clone(),
+            app_config.runtime.registry_syncs.clone(),
+            Duration::from_secs(app_config.sync_interval_secs),
+            Duration::from_secs(app_config.sync_jitter_secs),
+        ));
+    }
+
     let bind_address = app_config.bind_address.clone();
+    let shared_config = config::SharedConfig::new(app_config);
+    tokio::spawn(reload_on_sighup(shared_config.clone(), config_source));
 
     let app = Router::new()
         .route("/", get(root))
+        .route("/readyz", get(api::get_readyz))
+        .route("/metrics", get(api::get_metrics))
+        .route("/peering/server-info", get(api::get_server_info))
         .route("/peering/init", post(api::init_peering))
         .route("/peering/verify", post(api::verify_peering))
         .route("/peering/deploy", post(api::deploy_peering))
         .route("/peering/config", get(api::get_config))
         .route("/peering/status", get(api::get_status))
+        .route("/peering/deployment", get(api::get_deployment))
+        .route("/peering/provisioning/{id}", get(api::get_provisioning))
+        .route("/peering/state", get(api::get_state))
+        .route("/peering/bird-snippet", get(api::get_bird_snippet))
+        .route("/peering/request-email", get(api::get_request_email))
+        .route("/peering/bird-config", get(api::get_bird_config))
+        .route("/peering/systemd", get(api::get_systemd_bundle))
+        .route("/peering/test-endpoint", post(api::test_endpoint))
+        .route("/peering/test-signature", post(api::test_signature))
         .route("/peering/update", patch(api::update_peering))
+        .route("/peering/update/preview", post(api::preview_update))
+        .route("/peering/rollback", post(api::rollback_peering))
         .route("/peering/activate", post(api::activate_peering))
         .route("/peering/deactivate", post(api::deactivate_peering))
+        .route("/peering/repair", post(api::repair_peering))
+        .route("/peering/bgp/disable", post(api::disable_bgp))
+        .route("/peering/bgp/enable", post(api::enable_bgp))
         .route("/peering", delete(api::delete_peering))
+        .route("/admin/tokens/{asn}", get(api::list_tokens))
+        .route("/admin/revoke/{asn}", post(api::revoke_tokens))
+        .route("/admin/peering/{asn}/status", get(api::get_peer_status))
+        .route(
+            "/admin/peering/{asn}/bird-config",
+            get(api::get_peer_bird_config),
+        )
+        .route("/admin/peering/{asn}/authz", get(api::get_peer_authz))
+        .route(
+            "/admin/peering/status-batch",
+            post(api::get_peer_status_batch),
+        )
+        .route("/admin/peering/deleted", get(api::list_deleted_peerings))
+        .route("/admin/maintenance", post(api::set_maintenance))
+        .route("/admin/bird/reload", post(api::reload_bird))
         .layer(CookieManagerLayer::new())
-        .with_state(app_config);
+        .with_state(shared_config);
 
     let listener = tokio::net::TcpListener::bind(&bind_address)
         .await
-        .expect(&format!("Failed to bind to {}", bind_address));
+        .unwrap_or_else(|_| panic!("Failed to bind to {}", bind_address));
 
     println!("AutoPeer API listening on {}", bind_address);
 
@@ -59,6 +139,73 @@ async fn main() {
     }
 }
 
+/// Path given with `--config <file>`, if any
+fn config_file_arg() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+/// Re-read the config file on SIGHUP and swap the live config if it is valid
+async fn reload_on_sighup(shared: config::SharedConfig, source: config::ConfigSource) {
+    let mut hangups = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            error!(
+                "Failed to install SIGHUP handler, config reload disabled: {}",
+                e
+            );
+            return;
+        }
+    };
+
+    while hangups.recv().await.is_some() {
+        info!("SIGHUP received, reloading configuration");
+
+        let settings = match source.settings() {
+            Ok(settings) => settings,
+            Err(e) => {
+                error!(
+                    "Failed to read config file, keeping current configuration: {}",
+                    e
+                );
+                continue;
+            }
+        };
+
+        let new_config = match config::AppConfig::from_settings(&settings) {
+            Ok(new_config) => new_config,
+            Err(e) => {
+                error!(
+                    "Invalid configuration, keeping current configuration: {}",
+                    e
+                );
+                continue;
+            }
+        };
+
+        let (applied, ignored) = shared.reload(new_config);
+        for setting in ignored {
+            warn!(
+                "{} can't change without a restart, ignoring the new value",
+                setting
+            );
+        }
+        if applied.is_empty() {
+            info!("Configuration reloaded, nothing changed");
+        } else {
+            info!("Configuration reloaded, changed: {}", applied.join(", "));
+        }
+    }
+}
+
 async fn root() -> &'static str {
     "AutoPeer API v0.1.0"
 }
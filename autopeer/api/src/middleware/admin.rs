@@ -0,0 +1,44 @@
+use crate::config::AppConfig;
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    http::{header, request::Parts, StatusCode},
+};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// Request authenticated with the `ADMIN_TOKEN` bearer token
+#[derive(Clone, Debug)]
+pub struct AdminAuth;
+
+impl<S> FromRequestParts<S> for AdminAuth
+where
+    S: Send + Sync,
+    Arc<AppConfig>: FromRef<S>,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let config = Arc::<AppConfig>::from_ref(state);
+        let expected = config.admin_token.as_deref().ok_or((
+            StatusCode::FORBIDDEN,
+            "Admin API is disabled (ADMIN_TOKEN is not set)".to_string(),
+        ))?;
+
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or((
+                StatusCode::UNAUTHORIZED,
+                "Missing admin bearer token".to_string(),
+            ))?;
+
+        // Compare digests so the comparison time doesn't depend on the token
+        if Sha256::digest(token.as_bytes()) != Sha256::digest(expected.as_bytes()) {
+            return Err((StatusCode::UNAUTHORIZED, "Invalid admin token".to_string()));
+        }
+
+        Ok(AdminAuth)
+    }
+}
@@ -1,5 +1,5 @@
 use crate::config::AppConfig;
-use crate::jwt::decode_token;
+use crate::jwt::{decode_claims, JwtScope};
 use axum::{
     extract::{FromRef, FromRequestParts},
     http::{request::Parts, StatusCode},
@@ -11,6 +11,14 @@ use tower_cookies::Cookies;
 #[derive(Clone, Debug)]
 pub struct JwtAuth {
     pub asn: u32,
+    /// Challenge code that was signed to obtain the token, if recorded
+    pub challenge: Option<String>,
+    /// Contact given at /peering/init, if any
+    pub contact: Option<String>,
+    /// Registry key the challenge was verified with, if recorded
+    pub fingerprint: Option<String>,
+    /// When the token was issued, i.e. when the ASN proved control of the key (Unix timestamp)
+    pub issued_at: i64,
 }
 
 impl<S> FromRequestParts<S> for JwtAuth
@@ -20,28 +28,44 @@ where
 {
     type Rejection = (StatusCode, String);
 
-    async fn from_request_parts(
-        parts: &mut Parts,
-        state: &S,
-    ) -> Result<Self, Self::Rejection> {
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let config = Arc::<AppConfig>::from_ref(state);
         // Extract cookies
         let cookies = Cookies::from_request_parts(parts, state)
             .await
-            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read cookies".to_string()))?;
+            .map_err(|_| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to read cookies".to_string(),
+                )
+            })?;
 
         // Get token from cookie
         let token = cookies
             .get("autopeer_token")
-            .ok_or((StatusCode::UNAUTHORIZED, "No authentication cookie".to_string()))?
+            .ok_or((
+                StatusCode::UNAUTHORIZED,
+                "No authentication cookie".to_string(),
+            ))?
             .value()
             .to_string();
 
         // Decode JWT to get ASN
-        let asn = decode_token(&token, &config.jwt_secret)
-            .map_err(|e| (StatusCode::UNAUTHORIZED, format!("Token decode failed: {}", e)))?;
-
-        Ok(JwtAuth { asn })
+        let claims = decode_claims(&token, &config.jwt_secret, &JwtScope::from_config(&config))
+            .map_err(|e| {
+                (
+                    StatusCode::UNAUTHORIZED,
+                    format!("Token decode failed: {}", e),
+                )
+            })?;
+
+        Ok(JwtAuth {
+            asn: claims.asn,
+            challenge: claims.challenge,
+            contact: claims.contact,
+            fingerprint: claims.fingerprint,
+            issued_at: claims.iat,
+        })
     }
 }
 
@@ -50,26 +74,13 @@ mod tests {
     use super::*;
     use crate::jwt::generate_token;
     use axum::http::{Request, StatusCode};
-    use tower_cookies::CookieManagerLayer;
+    use axum::{body::Body, routing::get, Router};
     use tower::ServiceExt;
-    use axum::{body::Body, Router, routing::get};
+    use tower_cookies::CookieManagerLayer;
 
     /// Helper to create a test AppConfig
     fn test_config() -> Arc<AppConfig> {
-        Arc::new(AppConfig {
-            registry: crate::config::RegistryConfig {
-                url: "https://test.example".to_string(),
-                path: std::path::PathBuf::from("/tmp/test"),
-                username: "test".to_string(),
-                token: "test".to_string(),
-            },
-            jwt_secret: "test-secret-key-for-testing".to_string(),
-            my_asn: 4242420257,
-            bind_address: "127.0.0.1:3000".to_string(),
-            data_pending_dir: "/tmp/pending".to_string(),
-            data_verified_dir: "/tmp/verified".to_string(),
-            cookie_domains: vec!["localhost".to_string()],
-        })
+        crate::api::test_helpers::test_config()
     }
 
     /// Test handler that requires JWT authentication
@@ -92,17 +103,13 @@ mod tests {
             .with_state(config);
 
         // Create a request with the token cookie
-        let mut request = Request::builder()
-            .uri("/test")
-            .body(Body::empty())
-            .unwrap();
+        let mut request = Request::builder().uri("/test").body(Body::empty()).unwrap();
 
         // Add cookie header
         let cookie_header = format!("autopeer_token={}", token);
-        request.headers_mut().insert(
-            axum::http::header::COOKIE,
-            cookie_header.parse().unwrap(),
-        );
+        request
+            .headers_mut()
+            .insert(axum::http::header::COOKIE, cookie_header.parse().unwrap());
 
         // Send the request
         let response = app.oneshot(request).await.unwrap();
@@ -122,10 +129,7 @@ mod tests {
             .with_state(config);
 
         // Create a request WITHOUT a cookie
-        let request = Request::builder()
-            .uri("/test")
-            .body(Body::empty())
-            .unwrap();
+        let request = Request::builder().uri("/test").body(Body::empty()).unwrap();
 
         // Send the request
         let response = app.oneshot(request).await.unwrap();
@@ -145,10 +149,7 @@ mod tests {
             .with_state(config);
 
         // Create a request with an invalid token
-        let mut request = Request::builder()
-            .uri("/test")
-            .body(Body::empty())
-            .unwrap();
+        let mut request = Request::builder().uri("/test").body(Body::empty()).unwrap();
 
         request.headers_mut().insert(
             axum::http::header::COOKIE,
@@ -177,16 +178,12 @@ mod tests {
             .with_state(config);
 
         // Create a request with the token signed with wrong secret
-        let mut request = Request::builder()
-            .uri("/test")
-            .body(Body::empty())
-            .unwrap();
+        let mut request = Request::builder().uri("/test").body(Body::empty()).unwrap();
 
         let cookie_header = format!("autopeer_token={}", token);
-        request.headers_mut().insert(
-            axum::http::header::COOKIE,
-            cookie_header.parse().unwrap(),
-        );
+        request
+            .headers_mut()
+            .insert(axum::http::header::COOKIE, cookie_header.parse().unwrap());
 
         // Send the request
         let response = app.oneshot(request).await.unwrap();
@@ -206,10 +203,7 @@ mod tests {
             .with_state(config);
 
         // Create a request with an empty cookie value
-        let mut request = Request::builder()
-            .uri("/test")
-            .body(Body::empty())
-            .unwrap();
+        let mut request = Request::builder().uri("/test").body(Body::empty()).unwrap();
 
         request.headers_mut().insert(
             axum::http::header::COOKIE,
@@ -234,14 +228,13 @@ mod tests {
             .with_state(config);
 
         // Create a request with a malformed JWT (not enough parts)
-        let mut request = Request::builder()
-            .uri("/test")
-            .body(Body::empty())
-            .unwrap();
+        let mut request = Request::builder().uri("/test").body(Body::empty()).unwrap();
 
         request.headers_mut().insert(
             axum::http::header::COOKIE,
-            "autopeer_token=not.a.valid.jwt.too.many.parts".parse().unwrap(),
+            "autopeer_token=not.a.valid.jwt.too.many.parts"
+                .parse()
+                .unwrap(),
         );
 
         // Send the request
@@ -266,16 +259,12 @@ mod tests {
             .with_state(config);
 
         // Create a request with the token cookie
-        let mut request = Request::builder()
-            .uri("/test")
-            .body(Body::empty())
-            .unwrap();
+        let mut request = Request::builder().uri("/test").body(Body::empty()).unwrap();
 
         let cookie_header = format!("autopeer_token={}", token);
-        request.headers_mut().insert(
-            axum::http::header::COOKIE,
-            cookie_header.parse().unwrap(),
-        );
+        request
+            .headers_mut()
+            .insert(axum::http::header::COOKIE, cookie_header.parse().unwrap());
 
         // Send the request
         let response = app.oneshot(request).await.unwrap();
@@ -306,16 +295,15 @@ mod tests {
             .with_state(config);
 
         // Create a request with multiple cookies including autopeer_token
-        let mut request = Request::builder()
-            .uri("/test")
-            .body(Body::empty())
-            .unwrap();
+        let mut request = Request::builder().uri("/test").body(Body::empty()).unwrap();
 
-        let cookie_header = format!("other_cookie=value; autopeer_token={}; another=cookie", token);
-        request.headers_mut().insert(
-            axum::http::header::COOKIE,
-            cookie_header.parse().unwrap(),
+        let cookie_header = format!(
+            "other_cookie=value; autopeer_token={}; another=cookie",
+            token
         );
+        request
+            .headers_mut()
+            .insert(axum::http::header::COOKIE, cookie_header.parse().unwrap());
 
         // Send the request
         let response = app.oneshot(request).await.unwrap();
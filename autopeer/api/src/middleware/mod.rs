@@ -1,3 +1,5 @@
+pub mod admin;
 pub mod auth;
 
+pub use admin::AdminAuth;
 pub use auth::JwtAuth;
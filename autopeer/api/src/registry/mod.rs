@@ -4,7 +4,9 @@ pub mod parser;
 pub mod sync;
 
 pub use parser::{
-    get_as_object, get_pgp_fingerprint_for_asn, verify_key_fingerprint, AsObject, KeyCert,
-    MaintainerObject,
+    get_as_object, get_contact_persons_for_asn, get_maintainer_for_asn,
+    get_pgp_fingerprint_for_asn, get_route_objects_for_asn, get_routes_for_asn, is_synced,
+    peering_policy, verify_key_fingerprint, AsObject, KeyCert, MaintainerObject, PeeringPolicy,
+    PersonObject, RegistryLayout, RouteObject,
 };
 pub use sync::RegistrySync;
@@ -1,6 +1,38 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// How objects are stored under each registry class directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistryLayout {
+    /// Try the flat layout first, then one level of shard subdirectories
+    Auto,
+    /// `data/{class}/{name}`
+    Flat,
+    /// `data/{class}/{shard}/{name}`
+    Sharded,
+}
+
+impl FromStr for RegistryLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(RegistryLayout::Auto),
+            "flat" => Ok(RegistryLayout::Flat),
+            "sharded" => Ok(RegistryLayout::Sharded),
+            other => Err(format!("Unknown registry layout: {}", other)),
+        }
+    }
+}
+
+/// Resolved object locations, keyed by their flat path
+static OBJECT_PATHS: Lazy<Mutex<HashMap<PathBuf, PathBuf>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
 
 /// Represents an AS object from the DN42 registry
 #[derive(Debug, Clone, PartialEq)]
@@ -11,6 +43,79 @@ pub struct AsObject {
     pub admin_c: String,
     pub tech_c: String,
     pub mnt_by: String,
+    pub remarks: Vec<String>,
+}
+
+/// A route/route6 object reduced to what import filters need
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteObject {
+    pub prefix: String,
+    /// Longest more-specific the origin may announce (`max-length:`, the prefix length if absent)
+    pub max_length: u8,
+}
+
+impl RouteObject {
+    /// Length of the prefix itself
+    pub fn prefix_length(&self) -> u8 {
+        self.prefix
+            .rsplit_once('/')
+            .and_then(|(_, len)| len.parse().ok())
+            .unwrap_or(0)
+    }
+
+    pub fn is_ipv6(&self) -> bool {
+        self.prefix.contains(':')
+    }
+}
+
+/// Peering policy an ASN declares in its `remarks:`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeeringPolicy {
+    Open,
+    Selective,
+    Closed,
+    /// No recognizable policy statement
+    Unknown,
+}
+
+/// Classify an AS's peering policy from its remarks.
+///
+/// Only remarks that talk about peering are considered, e.g.
+/// `Peering policy: open`, `Open for peering`, `peering: selective`,
+/// `Not accepting new peerings`. Closed wins over selective over open.
+pub fn peering_policy(as_obj: &AsObject) -> PeeringPolicy {
+    let mut policy = PeeringPolicy::Unknown;
+
+    for remark in &as_obj.remarks {
+        let remark = remark.to_lowercase();
+        if !remark.contains("peer") {
+            continue;
+        }
+
+        if [
+            "closed",
+            "restrictive",
+            "no peering",
+            "not accepting",
+            "no new peer",
+        ]
+        .iter()
+        .any(|k| remark.contains(k))
+        {
+            return PeeringPolicy::Closed;
+        }
+        if ["selective", "case by case", "case-by-case", "on request"]
+            .iter()
+            .any(|k| remark.contains(k))
+        {
+            policy = PeeringPolicy::Selective;
+        } else if remark.contains("open") && policy == PeeringPolicy::Unknown {
+            policy = PeeringPolicy::Open;
+        }
+    }
+
+    policy
 }
 
 /// Represents a maintainer object
@@ -19,6 +124,19 @@ pub struct MaintainerObject {
     pub mntner: String,
     pub description: String,
     pub auth_fingerprints: Vec<String>,
+    /// Keys from `auth: ed25519-minisign <pubkey>` lines
+    pub minisign_keys: Vec<String>,
+}
+
+/// Represents a person object (the `admin-c`/`tech-c` handles of other objects)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersonObject {
+    pub nic_hdl: String,
+    pub person: String,
+    /// From `auth: pgp-fingerprint` lines and the older `pgp-fingerprint:` attribute
+    pub auth_fingerprints: Vec<String>,
+    /// Keys from `auth: ed25519-minisign <pubkey>` lines
+    pub minisign_keys: Vec<String>,
 }
 
 /// Represents a PGP key certificate
@@ -108,6 +226,7 @@ pub fn parse_as_object(content: &str) -> Result<AsObject, String> {
         admin_c,
         tech_c,
         mnt_by,
+        remarks: fields.get("remarks").cloned().unwrap_or_default(),
     })
 }
 
@@ -127,24 +246,68 @@ pub fn parse_maintainer(content: &str) -> Result<MaintainerObject, String> {
         .cloned()
         .unwrap_or_default();
 
-    // Extract PGP fingerprints from auth fields
+    let (auth_fingerprints, minisign_keys) = parse_auth(&fields);
+
+    Ok(MaintainerObject {
+        mntner,
+        description,
+        auth_fingerprints,
+        minisign_keys,
+    })
+}
+
+/// PGP fingerprints and minisign keys from the `auth` fields of an object
+fn parse_auth(fields: &HashMap<String, Vec<String>>) -> (Vec<String>, Vec<String>) {
     let mut auth_fingerprints = Vec::new();
+    let mut minisign_keys = Vec::new();
     if let Some(auth_values) = fields.get("auth") {
         for auth in auth_values {
-            if let Some(fingerprint) = auth.strip_prefix("pgp-fingerprint ") {
-                auth_fingerprints.push(fingerprint.to_string());
+            // Any whitespace may separate the method from the fingerprint
+            match auth.split_once(char::is_whitespace) {
+                Some(("pgp-fingerprint", fingerprint)) => {
+                    auth_fingerprints.push(fingerprint.trim().to_string())
+                }
+                Some(("ed25519-minisign", key)) => minisign_keys.push(key.trim().to_string()),
+                _ => {}
             }
         }
     }
+    (auth_fingerprints, minisign_keys)
+}
 
-    Ok(MaintainerObject {
-        mntner,
-        description,
+/// Parse a person object
+pub fn parse_person(content: &str) -> Result<PersonObject, String> {
+    let fields = parse_registry_object(content);
+
+    let nic_hdl = fields
+        .get("nic-hdl")
+        .and_then(|v| v.first())
+        .ok_or("Missing nic-hdl field")?
+        .clone();
+
+    let person = fields
+        .get("person")
+        .and_then(|v| v.first())
+        .cloned()
+        .unwrap_or_default();
+
+    let (mut auth_fingerprints, minisign_keys) = parse_auth(&fields);
+    for fingerprint in fields.get("pgp-fingerprint").into_iter().flatten() {
+        if !auth_fingerprints.contains(fingerprint) {
+            auth_fingerprints.push(fingerprint.clone());
+        }
+    }
+
+    Ok(PersonObject {
+        nic_hdl,
+        person,
         auth_fingerprints,
+        minisign_keys,
     })
 }
 
-/// Parse a key-cert object
+/// Parse a key-cert object. For PGP key-certs the key rebuilt from the `certif:` lines
+/// must parse, so a mangled object is reported as such instead of failing later on.
 pub fn parse_key_cert(content: &str) -> Result<KeyCert, String> {
     let fields = parse_registry_object(content);
 
@@ -181,6 +344,10 @@ pub fn parse_key_cert(content: &str) -> Result<KeyCert, String> {
         }
     }
 
+    if method.eq_ignore_ascii_case("PGP") {
+        check_armored_key(&key_id, &public_key)?;
+    }
+
     Ok(KeyCert {
         key_id,
         method,
@@ -190,12 +357,87 @@ pub fn parse_key_cert(content: &str) -> Result<KeyCert, String> {
     })
 }
 
+/// Make sure a key rebuilt from `certif:` lines is a parseable armored PGP key
+fn check_armored_key(key_id: &str, public_key: &str) -> Result<(), String> {
+    use pgp::composed::{Deserializable, SignedPublicKey};
+
+    SignedPublicKey::from_string(public_key)
+        .map(|_| ())
+        .map_err(|e| format!("Registry key-cert {} is malformed: {}", key_id, e))
+}
+
+/// Whether the registry has been cloned yet (a fresh install has no `data/` until the first sync)
+///
+/// `data_subdir` is where the object classes live inside the checkout, `data` for the
+/// DN42 registry itself and e.g. `registry/data` for mirrors that nest it.
+pub fn is_synced<P: AsRef<Path>>(registry_path: P, data_subdir: &str) -> bool {
+    registry_path.as_ref().join(data_subdir).is_dir()
+}
+
+/// Locate a registry object file, honoring the configured layout
+fn resolve_object_path(
+    registry_path: &Path,
+    data_subdir: &str,
+    layout: RegistryLayout,
+    class: &str,
+    name: &str,
+) -> Result<PathBuf, String> {
+    if !is_synced(registry_path, data_subdir) {
+        return Err(format!(
+            "registry not yet synced ({:?} has no {} directory)",
+            registry_path, data_subdir
+        ));
+    }
+
+    let class_dir = registry_path.join(data_subdir).join(class);
+    let flat_path = class_dir.join(name);
+
+    if layout != RegistryLayout::Sharded && flat_path.exists() {
+        return Ok(flat_path);
+    }
+    if layout == RegistryLayout::Flat {
+        return Err(format!("{} not found in {:?}", name, class_dir));
+    }
+
+    // Reuse a previous shard lookup while it is still valid
+    let mut cache = OBJECT_PATHS
+        .lock()
+        .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+    if let Some(cached) = cache.get(&flat_path) {
+        if cached.exists() {
+            return Ok(cached.clone());
+        }
+    }
+
+    let shards =
+        fs::read_dir(&class_dir).map_err(|e| format!("Failed to read {:?}: {}", class_dir, e))?;
+    for shard in shards.flatten() {
+        let candidate = shard.path().join(name);
+        if shard.path().is_dir() && candidate.exists() {
+            cache.insert(flat_path, candidate.clone());
+            return Ok(candidate);
+        }
+    }
+
+    Err(format!("{} not found in {:?}", name, class_dir))
+}
+
 /// Get AS object from registry by ASN
-pub fn get_as_object<P: AsRef<Path>>(registry_path: P, asn: u32) -> Result<AsObject, String> {
-    let as_file = registry_path
-        .as_ref()
-        .join("data/aut-num")
-        .join(format!("AS{}", asn));
+pub fn get_as_object<P: AsRef<Path>>(
+    registry_path: P,
+    data_subdir: &str,
+    layout: RegistryLayout,
+    asn: u32,
+) -> Result<AsObject, String> {
+    let as_name = format!("AS{}", asn);
+    let as_file = resolve_object_path(
+        registry_path.as_ref(),
+        data_subdir,
+        layout,
+        "aut-num",
+        &as_name,
+    )
+    .map_err(|e| format!("Failed to read AS{}: {}", asn, e))?;
 
     let content =
         fs::read_to_string(&as_file).map_err(|e| format!("Failed to read AS{}: {}", asn, e))?;
@@ -206,9 +448,18 @@ pub fn get_as_object<P: AsRef<Path>>(registry_path: P, asn: u32) -> Result<AsObj
 /// Get maintainer object from registry
 pub fn get_maintainer<P: AsRef<Path>>(
     registry_path: P,
+    data_subdir: &str,
+    layout: RegistryLayout,
     mntner: &str,
 ) -> Result<MaintainerObject, String> {
-    let mnt_file = registry_path.as_ref().join("data/mntner").join(mntner);
+    let mnt_file = resolve_object_path(
+        registry_path.as_ref(),
+        data_subdir,
+        layout,
+        "mntner",
+        mntner,
+    )
+    .map_err(|e| format!("Failed to read {}: {}", mntner, e))?;
 
     let content =
         fs::read_to_string(&mnt_file).map_err(|e| format!("Failed to read {}: {}", mntner, e))?;
@@ -216,18 +467,162 @@ pub fn get_maintainer<P: AsRef<Path>>(
     parse_maintainer(&content)
 }
 
+/// Get person object from registry by its nic-hdl
+pub fn get_person<P: AsRef<Path>>(
+    registry_path: P,
+    data_subdir: &str,
+    layout: RegistryLayout,
+    nic_hdl: &str,
+) -> Result<PersonObject, String> {
+    let person_file = resolve_object_path(
+        registry_path.as_ref(),
+        data_subdir,
+        layout,
+        "person",
+        nic_hdl,
+    )
+    .map_err(|e| format!("Failed to read {}: {}", nic_hdl, e))?;
+
+    let content = fs::read_to_string(&person_file)
+        .map_err(|e| format!("Failed to read {}: {}", nic_hdl, e))?;
+
+    parse_person(&content)
+}
+
+/// Get the tech-c and admin-c persons of an ASN's aut-num object, tech-c first
+///
+/// A handle listed for both roles is returned once.
+pub fn get_contact_persons_for_asn<P: AsRef<Path>>(
+    registry_path: P,
+    data_subdir: &str,
+    layout: RegistryLayout,
+    asn: u32,
+) -> Result<Vec<PersonObject>, String> {
+    let as_obj = get_as_object(&registry_path, data_subdir, layout, asn)?;
+
+    let mut handles: Vec<&str> = Vec::new();
+    for handle in [as_obj.tech_c.as_str(), as_obj.admin_c.as_str()] {
+        if !handle.is_empty() && !handles.contains(&handle) {
+            handles.push(handle);
+        }
+    }
+
+    handles
+        .into_iter()
+        .map(|handle| get_person(&registry_path, data_subdir, layout, handle))
+        .collect()
+}
+
+/// Files of a registry class directory, honoring the configured layout
+fn list_objects(
+    registry_path: &Path,
+    data_subdir: &str,
+    layout: RegistryLayout,
+    class: &str,
+) -> Result<Vec<PathBuf>, String> {
+    let class_dir = registry_path.join(data_subdir).join(class);
+    let entries = match fs::read_dir(&class_dir) {
+        Ok(entries) => entries,
+        // A registry without the class simply has no such objects
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", class_dir, e)),
+    };
+
+    let mut objects = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_file() && layout != RegistryLayout::Sharded {
+            objects.push(path);
+        } else if path.is_dir() && layout != RegistryLayout::Flat {
+            let shard =
+                fs::read_dir(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+            objects.extend(shard.flatten().map(|e| e.path()).filter(|p| p.is_file()));
+        }
+    }
+
+    Ok(objects)
+}
+
+/// Get the prefixes an ASN has registered as `origin` in route/route6 objects
+pub fn get_routes_for_asn<P: AsRef<Path>>(
+    registry_path: P,
+    data_subdir: &str,
+    layout: RegistryLayout,
+    asn: u32,
+) -> Result<Vec<String>, String> {
+    let routes = get_route_objects_for_asn(registry_path, data_subdir, layout, asn)?;
+    Ok(routes.into_iter().map(|route| route.prefix).collect())
+}
+
+/// Get the route/route6 objects an ASN is `origin` of, with their max-length
+pub fn get_route_objects_for_asn<P: AsRef<Path>>(
+    registry_path: P,
+    data_subdir: &str,
+    layout: RegistryLayout,
+    asn: u32,
+) -> Result<Vec<RouteObject>, String> {
+    let origin = format!("AS{}", asn);
+    let mut routes = Vec::new();
+
+    for class in ["route", "route6"] {
+        for path in list_objects(registry_path.as_ref(), data_subdir, layout, class)? {
+            let content = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+            let fields = parse_registry_object(&content);
+
+            let is_origin = fields
+                .get("origin")
+                .is_some_and(|origins| origins.iter().any(|o| o.eq_ignore_ascii_case(&origin)));
+            let Some(prefix) = fields
+                .get(class)
+                .and_then(|v| v.first())
+                .filter(|_| is_origin)
+            else {
+                continue;
+            };
+
+            let mut route = RouteObject {
+                prefix: prefix.clone(),
+                max_length: 0,
+            };
+            let full_length = if route.is_ipv6() { 128 } else { 32 };
+            // Without max-length only the exact prefix may be announced
+            route.max_length = fields
+                .get("max-length")
+                .and_then(|v| v.first())
+                .and_then(|s| s.parse::<u8>().ok())
+                .unwrap_or(0)
+                .clamp(route.prefix_length(), full_length);
+            routes.push(route);
+        }
+    }
+
+    routes.sort_by(|a, b| a.prefix.cmp(&b.prefix));
+    routes.dedup_by(|a, b| a.prefix == b.prefix);
+    Ok(routes)
+}
+
+/// Get the maintainer of an ASN's aut-num object
+pub fn get_maintainer_for_asn<P: AsRef<Path>>(
+    registry_path: P,
+    data_subdir: &str,
+    layout: RegistryLayout,
+    asn: u32,
+) -> Result<MaintainerObject, String> {
+    let as_obj = get_as_object(&registry_path, data_subdir, layout, asn)?;
+    get_maintainer(&registry_path, data_subdir, layout, &as_obj.mnt_by)
+}
+
 /// Get PGP fingerprint for an ASN from the registry
 pub fn get_pgp_fingerprint_for_asn<P: AsRef<Path>>(
     registry_path: P,
+    data_subdir: &str,
+    layout: RegistryLayout,
     asn: u32,
 ) -> Result<String, String> {
-    // 1. Get AS object
-    let as_obj = get_as_object(&registry_path, asn)?;
-
-    // 2. Get maintainer
-    let mnt = get_maintainer(&registry_path, &as_obj.mnt_by)?;
+    let mnt = get_maintainer_for_asn(registry_path, data_subdir, layout, asn)?;
 
-    // 3. Get first PGP fingerprint
+    // Get first PGP fingerprint
     let fingerprint = mnt
         .auth_fingerprints
         .first()
@@ -238,6 +633,8 @@ pub fn get_pgp_fingerprint_for_asn<P: AsRef<Path>>(
 }
 
 /// Verify that a public key matches the expected fingerprint
+///
+/// The registry lists the primary key's fingerprint, even when challenges are signed by a subkey.
 pub fn verify_key_fingerprint(
     public_key: &str,
     expected_fingerprint: &str,
@@ -252,9 +649,16 @@ pub fn verify_key_fingerprint(
     // Get the fingerprint from the key
     let key_fingerprint = format!("{:X}", key.fingerprint());
 
-    // Normalize both fingerprints (remove spaces, uppercase)
-    let normalized_expected = expected_fingerprint.replace(' ', "").to_uppercase();
-    let normalized_key = key_fingerprint.replace(' ', "").to_uppercase();
+    // Normalize both fingerprints (remove all whitespace, including a stray `\r`, and uppercase)
+    let normalize = |fingerprint: &str| {
+        fingerprint
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_uppercase()
+    };
+    let normalized_expected = normalize(expected_fingerprint);
+    let normalized_key = normalize(&key_fingerprint);
 
     Ok(normalized_key == normalized_expected)
 }
@@ -302,43 +706,497 @@ source:             DN42
         );
     }
 
+    #[test]
+    fn test_parse_maintainer_crlf() {
+        let content = "mntner:             SCARJIT-MNT\r\n\
+                       descr:              SCARJIT https://linnenberg.dev/\r\n\
+                       auth:               pgp-fingerprint\t8B7F0384CBE0272761D852EA0684E36E6CF9D4D4 \r\n\
+                       mnt-by:             SCARJIT-MNT\r\n";
+
+        let mnt = parse_maintainer(content).unwrap();
+        assert_eq!(mnt.mntner, "SCARJIT-MNT");
+        assert_eq!(
+            mnt.auth_fingerprints,
+            vec!["8B7F0384CBE0272761D852EA0684E36E6CF9D4D4"]
+        );
+        assert!(mnt.minisign_keys.is_empty());
+    }
+
+    #[test]
+    fn test_parse_maintainer_minisign() {
+        let content = "mntner:             EXAMPLE-MNT\n\
+                       auth:               ed25519-minisign RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3\n\
+                       auth:               pgp-fingerprint 8B7F0384CBE0272761D852EA0684E36E6CF9D4D4\n";
+
+        let mnt = parse_maintainer(content).unwrap();
+        assert_eq!(
+            mnt.minisign_keys,
+            vec!["RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3"]
+        );
+        assert_eq!(
+            mnt.auth_fingerprints,
+            vec!["8B7F0384CBE0272761D852EA0684E36E6CF9D4D4"]
+        );
+
+        let public_key = crate::api::test_helpers::PEER_PGP_PUBLIC_KEY;
+        assert!(verify_key_fingerprint(public_key, &mnt.auth_fingerprints[0]).unwrap());
+        // An unparsed value with its line ending still matches
+        assert!(verify_key_fingerprint(
+            public_key,
+            "8b7f 0384 cbe0 2727 61d8  52ea 0684 e36e 6cf9 d4d4\r"
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn test_parse_person() {
+        let content = r#"
+person:             Jane Doe
+contact:            jane@example.dn42
+nic-hdl:            JANE-DN42
+auth:               pgp-fingerprint 922CA9191D9D5C1CD28E4D2B935300055E6B8E16
+auth:               ed25519-minisign RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3
+pgp-fingerprint:    8B7F0384CBE0272761D852EA0684E36E6CF9D4D4
+pgp-fingerprint:    922CA9191D9D5C1CD28E4D2B935300055E6B8E16
+mnt-by:             JANE-MNT
+source:             DN42
+"#;
+
+        let person = parse_person(content).unwrap();
+        assert_eq!(person.nic_hdl, "JANE-DN42");
+        assert_eq!(person.person, "Jane Doe");
+        // The legacy attribute adds keys, without repeating one already in an auth line
+        assert_eq!(
+            person.auth_fingerprints,
+            vec![
+                "922CA9191D9D5C1CD28E4D2B935300055E6B8E16",
+                "8B7F0384CBE0272761D852EA0684E36E6CF9D4D4"
+            ]
+        );
+        assert_eq!(
+            person.minisign_keys,
+            vec!["RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3"]
+        );
+
+        assert!(parse_person("person: Nobody\n").is_err());
+    }
+
+    #[test]
+    fn test_get_contact_persons_for_asn() {
+        let registry = sharded_registry();
+        let data = registry.path().join("data");
+        fs::create_dir_all(data.join("person")).unwrap();
+        fs::write(
+            data.join("aut-num/AS424242/AS4242420257"),
+            "aut-num: AS4242420257\nadmin-c: ADMIN-DN42\ntech-c: TECH-DN42\nmnt-by: SCARJIT-MNT\n",
+        )
+        .unwrap();
+        fs::write(
+            data.join("person/TECH-DN42"),
+            "person: Tech\nnic-hdl: TECH-DN42\n",
+        )
+        .unwrap();
+        fs::write(
+            data.join("person/ADMIN-DN42"),
+            "person: Admin\nnic-hdl: ADMIN-DN42\n",
+        )
+        .unwrap();
+
+        let persons =
+            get_contact_persons_for_asn(registry.path(), "data", RegistryLayout::Auto, 4242420257)
+                .unwrap();
+        let handles: Vec<&str> = persons.iter().map(|p| p.nic_hdl.as_str()).collect();
+        assert_eq!(handles, vec!["TECH-DN42", "ADMIN-DN42"]);
+
+        // One person in both roles is listed once; a missing person object is an error
+        fs::write(
+            data.join("aut-num/AS424242/AS4242420257"),
+            "aut-num: AS4242420257\nadmin-c: TECH-DN42\ntech-c: TECH-DN42\nmnt-by: SCARJIT-MNT\n",
+        )
+        .unwrap();
+        let persons =
+            get_contact_persons_for_asn(registry.path(), "data", RegistryLayout::Auto, 4242420257)
+                .unwrap();
+        assert_eq!(persons.len(), 1);
+        fs::remove_file(data.join("person/TECH-DN42")).unwrap();
+        assert!(get_contact_persons_for_asn(
+            registry.path(),
+            "data",
+            RegistryLayout::Auto,
+            4242420257
+        )
+        .is_err());
+    }
+
     #[test]
     fn test_get_as_object_from_registry() {
-        dotenvy::dotenv().ok();
-        let registry_path = std::env::var("DN42_REGISTRY_PATH")
-            .unwrap_or_else(|_| "./data/dn42-registry".to_string());
+        let registry_path = crate::api::test_helpers::test_registry_path();
 
-        let as_obj = get_as_object(&registry_path, 4242420257).unwrap();
+        let as_obj =
+            get_as_object(&registry_path, "data", RegistryLayout::Auto, 4242420257).unwrap();
         assert_eq!(as_obj.asn, 4242420257);
         assert_eq!(as_obj.as_name, "SCARJIT-AS");
+        assert_eq!(as_obj.mnt_by, "SCARJIT-MNT");
+
+        let as_obj =
+            get_as_object(&registry_path, "data", RegistryLayout::Auto, 4242422601).unwrap();
+        assert_eq!(as_obj.as_name, "AUTOPEER-TEST-AS");
     }
 
     #[test]
     fn test_get_pgp_fingerprint_and_verify() {
-        dotenvy::dotenv().ok();
-        let registry_path = std::env::var("DN42_REGISTRY_PATH")
-            .unwrap_or_else(|_| "./data/dn42-registry".to_string());
+        let registry_path = crate::api::test_helpers::test_registry_path();
 
         // Get fingerprint from registry
-        let fingerprint = get_pgp_fingerprint_for_asn(&registry_path, 4242420257).unwrap();
+        let fingerprint =
+            get_pgp_fingerprint_for_asn(&registry_path, "data", RegistryLayout::Auto, 4242420257)
+                .unwrap();
         assert_eq!(fingerprint, "8B7F0384CBE0272761D852EA0684E36E6CF9D4D4");
 
-        // Verify that your actual public key matches
-        let public_key = r#"-----BEGIN PGP PUBLIC KEY BLOCK-----
-
-mDMEYVuS5RYJKwYBBAHaRw8BAQdAnJ1to/QytFqDfg3gtUrtiqmJRMSLNrG/fLNG
-BesjX5m0L0ZlcmRpbmFuZCBMaW5uZW5iZXJnIDxmZXJkaW5hbmRAbGlubmVuYmVy
-Zy5kZXY+iJAEExYIADgWIQSLfwOEy+AnJ2HYUuoGhONubPnU1AUCYVuS5QIbAwUL
-CQgHAgYVCgkICwIEFgIDAQIeAQIXgAAKCRAGhONubPnU1M2ZAP0drb1tbnLi1cU+
-Pc4NPTMjviTBBFmGFoDni/0mvMC5qAD6AlB24idciDkSeJFz3s/6wSog/Rj4ALpk
-RQ/v8Ls4gQa4OARhW5LlEgorBgEEAZdVAQUBAQdAci4cwabJdJGO+VF5wxEW+yuO
-Y+BPprEQpy4jFiN713sDAQgHiHgEGBYIACAWIQSLfwOEy+AnJ2HYUuoGhONubPnU
-1AUCYVuS5QIbDAAKCRAGhONubPnU1I79AQC7Weudp5yzofVqZQCa/ijohC5CuwXw
-LGZbH16nUawo9gEAw+6wvpgw2d7IS6rnT6jJZ1qm6inF/XzTZTNfq9rsmgM=
-=WrLZ
------END PGP PUBLIC KEY BLOCK-----"#;
-
+        let public_key = crate::api::test_helpers::PEER_PGP_PUBLIC_KEY;
         let is_valid = verify_key_fingerprint(public_key, &fingerprint).unwrap();
         assert!(is_valid, "Public key fingerprint should match");
     }
+
+    #[test]
+    fn test_fixture_key_certs_match_maintainers() {
+        let registry_path = crate::api::test_helpers::test_registry_path();
+
+        for (asn, key_cert) in [
+            (4242420257, "PGPKEY-6CF9D4D4"),
+            (4242422601, "PGPKEY-5E6B8E16"),
+        ] {
+            let fingerprint =
+                get_pgp_fingerprint_for_asn(&registry_path, "data", RegistryLayout::Auto, asn)
+                    .unwrap();
+            let content =
+                fs::read_to_string(registry_path.join("data/key-cert").join(key_cert)).unwrap();
+            let cert = parse_key_cert(&content).unwrap();
+
+            assert!(
+                verify_key_fingerprint(&cert.public_key, &fingerprint).unwrap(),
+                "{}",
+                key_cert
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_key_cert_rejects_mangled_key() {
+        let registry_path = crate::api::test_helpers::test_registry_path();
+        let content =
+            fs::read_to_string(registry_path.join("data/key-cert/PGPKEY-6CF9D4D4")).unwrap();
+        let cert = parse_key_cert(&content).unwrap();
+        assert_eq!(cert.key_id, "PGPKEY-6CF9D4D4");
+        assert!(cert
+            .public_key
+            .starts_with("-----BEGIN PGP PUBLIC KEY BLOCK-----\n\n"));
+
+        // Continuation lines the parser doesn't pick up (here: without the `certif:` attribute)
+        let mut certif_seen = 0;
+        let mangled: String = content
+            .lines()
+            .map(|line| match line.strip_prefix("certif:") {
+                Some(rest) => {
+                    certif_seen += 1;
+                    if certif_seen > 4 {
+                        format!("       {}\n", rest)
+                    } else {
+                        format!("{}\n", line)
+                    }
+                }
+                None => format!("{}\n", line),
+            })
+            .collect();
+        let err = parse_key_cert(&mangled).unwrap_err();
+        assert!(
+            err.contains("Registry key-cert PGPKEY-6CF9D4D4 is malformed"),
+            "{}",
+            err
+        );
+
+        // A key-cert without any key at all
+        let err = parse_key_cert("key-cert: PGPKEY-DEADBEEF\nmethod: PGP\nowner: SOME-MNT\n")
+            .unwrap_err();
+        assert!(err.contains("is malformed"), "{}", err);
+
+        // Only PGP key-certs carry an armored key
+        assert!(parse_key_cert("key-cert: SSHKEY-1\nmethod: SSH\nowner: SOME-MNT\n").is_ok());
+    }
+
+    /// Write a minimal registry with aut-num sharded and mntner flat
+    fn sharded_registry() -> tempfile::TempDir {
+        let dir = tempfile::TempDir::new().unwrap();
+        let aut_num = dir.path().join("data/aut-num/AS424242");
+        let mntner = dir.path().join("data/mntner");
+        fs::create_dir_all(&aut_num).unwrap();
+        fs::create_dir_all(&mntner).unwrap();
+
+        fs::write(
+            aut_num.join("AS4242420257"),
+            "aut-num: AS4242420257\nas-name: SCARJIT-AS\nmnt-by: SCARJIT-MNT\n",
+        )
+        .unwrap();
+        fs::write(
+            mntner.join("SCARJIT-MNT"),
+            "mntner: SCARJIT-MNT\nauth: pgp-fingerprint 8B7F0384CBE0272761D852EA0684E36E6CF9D4D4\n",
+        )
+        .unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn test_unsynced_registry() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let missing = dir.path().join("dn42-registry");
+        assert!(!is_synced(&missing, "data"));
+
+        let err = get_pgp_fingerprint_for_asn(&missing, "data", RegistryLayout::Auto, 4242420257)
+            .unwrap_err();
+        assert!(
+            err.contains("registry not yet synced"),
+            "unexpected error: {}",
+            err
+        );
+
+        // A synced registry without the ASN reports the object as missing instead
+        let registry = sharded_registry();
+        assert!(is_synced(registry.path(), "data"));
+        let err =
+            get_pgp_fingerprint_for_asn(registry.path(), "data", RegistryLayout::Auto, 4242420001)
+                .unwrap_err();
+        assert!(!err.contains("not yet synced"));
+        assert!(err.contains("not found"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_get_routes_for_asn() {
+        let registry = tempfile::TempDir::new().unwrap();
+        let route = registry.path().join("data/route");
+        let route6 = registry.path().join("data/route6/fd00");
+        fs::create_dir_all(&route).unwrap();
+        fs::create_dir_all(&route6).unwrap();
+
+        fs::write(
+            route.join("172.20.0.0_27"),
+            "route: 172.20.0.0/27
+origin: AS4242420257
+",
+        )
+        .unwrap();
+        fs::write(
+            route.join("172.20.1.0_27"),
+            "route: 172.20.1.0/27
+origin: AS4242420001
+",
+        )
+        .unwrap();
+        fs::write(
+            route6.join("fd00:1234::_48"),
+            "route6: fd00:1234::/48
+origin: AS4242420257
+",
+        )
+        .unwrap();
+
+        let routes =
+            get_routes_for_asn(registry.path(), "data", RegistryLayout::Auto, 4242420257).unwrap();
+        assert_eq!(routes, vec!["172.20.0.0/27", "fd00:1234::/48"]);
+
+        // The flat layout doesn't look into shards
+        let routes =
+            get_routes_for_asn(registry.path(), "data", RegistryLayout::Flat, 4242420257).unwrap();
+        assert_eq!(routes, vec!["172.20.0.0/27"]);
+
+        assert!(
+            get_routes_for_asn(registry.path(), "data", RegistryLayout::Auto, 4242420002)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_route_objects_max_length() {
+        let registry = tempfile::TempDir::new().unwrap();
+        let route = registry.path().join("data/route");
+        let route6 = registry.path().join("data/route6");
+        fs::create_dir_all(&route).unwrap();
+        fs::create_dir_all(&route6).unwrap();
+
+        fs::write(
+            route.join("172.20.0.0_26"),
+            "route: 172.20.0.0/26\norigin: AS4242420257\nmax-length: 29\n",
+        )
+        .unwrap();
+        fs::write(
+            route6.join("fd00:1234::_48"),
+            "route6: fd00:1234::/48\norigin: AS4242420257\n",
+        )
+        .unwrap();
+        // A max-length shorter than the prefix can't widen it
+        fs::write(
+            route.join("172.20.1.0_27"),
+            "route: 172.20.1.0/27\norigin: AS4242420257\nmax-length: 24\n",
+        )
+        .unwrap();
+
+        let routes =
+            get_route_objects_for_asn(registry.path(), "data", RegistryLayout::Auto, 4242420257)
+                .unwrap();
+        assert_eq!(
+            routes,
+            vec![
+                RouteObject {
+                    prefix: "172.20.0.0/26".to_string(),
+                    max_length: 29
+                },
+                RouteObject {
+                    prefix: "172.20.1.0/27".to_string(),
+                    max_length: 27
+                },
+                RouteObject {
+                    prefix: "fd00:1234::/48".to_string(),
+                    max_length: 48
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_as_object_sharded() {
+        let registry = sharded_registry();
+
+        let as_obj =
+            get_as_object(registry.path(), "data", RegistryLayout::Auto, 4242420257).unwrap();
+        assert_eq!(as_obj.as_name, "SCARJIT-AS");
+
+        // Second lookup is served from the cache
+        let as_obj =
+            get_as_object(registry.path(), "data", RegistryLayout::Sharded, 4242420257).unwrap();
+        assert_eq!(as_obj.mnt_by, "SCARJIT-MNT");
+
+        let fingerprint =
+            get_pgp_fingerprint_for_asn(registry.path(), "data", RegistryLayout::Auto, 4242420257)
+                .unwrap();
+        assert_eq!(fingerprint, "8B7F0384CBE0272761D852EA0684E36E6CF9D4D4");
+    }
+
+    #[test]
+    fn test_get_as_object_forced_flat_layout() {
+        let registry = sharded_registry();
+
+        assert!(get_as_object(registry.path(), "data", RegistryLayout::Flat, 4242420257).is_err());
+    }
+
+    #[test]
+    fn test_nested_data_subdir() {
+        // A monorepo mirror with the registry under `registry/`
+        let dir = tempfile::TempDir::new().unwrap();
+        let data = dir.path().join("registry/data");
+        fs::create_dir_all(data.join("aut-num")).unwrap();
+        fs::create_dir_all(data.join("mntner")).unwrap();
+        fs::create_dir_all(data.join("route6")).unwrap();
+        fs::write(
+            data.join("aut-num/AS4242420257"),
+            "aut-num: AS4242420257\nas-name: SCARJIT-AS\nmnt-by: SCARJIT-MNT\n",
+        )
+        .unwrap();
+        fs::write(
+            data.join("mntner/SCARJIT-MNT"),
+            "mntner: SCARJIT-MNT\nauth: pgp-fingerprint 8B7F0384CBE0272761D852EA0684E36E6CF9D4D4\n",
+        )
+        .unwrap();
+        fs::write(
+            data.join("route6/fd00:257::_48"),
+            "route6: fd00:257::/48\norigin: AS4242420257\n",
+        )
+        .unwrap();
+
+        assert!(is_synced(dir.path(), "registry/data"));
+        assert!(!is_synced(dir.path(), "data"));
+
+        let nested = "registry/data";
+        assert_eq!(
+            get_as_object(dir.path(), nested, RegistryLayout::Auto, 4242420257)
+                .unwrap()
+                .mnt_by,
+            "SCARJIT-MNT"
+        );
+        assert_eq!(
+            get_pgp_fingerprint_for_asn(dir.path(), nested, RegistryLayout::Auto, 4242420257)
+                .unwrap(),
+            "8B7F0384CBE0272761D852EA0684E36E6CF9D4D4"
+        );
+        assert_eq!(
+            get_routes_for_asn(dir.path(), nested, RegistryLayout::Auto, 4242420257).unwrap(),
+            vec!["fd00:257::/48"]
+        );
+
+        // The default subdir doesn't find anything here
+        assert!(get_as_object(dir.path(), "data", RegistryLayout::Auto, 4242420257).is_err());
+    }
+
+    #[test]
+    fn test_registry_layout_from_str() {
+        assert_eq!(
+            "auto".parse::<RegistryLayout>().unwrap(),
+            RegistryLayout::Auto
+        );
+        assert_eq!(
+            "Sharded".parse::<RegistryLayout>().unwrap(),
+            RegistryLayout::Sharded
+        );
+        assert!("nested".parse::<RegistryLayout>().is_err());
+    }
+
+    fn as_with_remarks(remarks: &str) -> AsObject {
+        parse_as_object(&format!(
+            "aut-num: AS4242422225\nmnt-by: TEST-MNT\n{}",
+            remarks
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_remarks() {
+        let as_obj =
+            as_with_remarks("remarks: Peering policy: open\nremarks: Contact: noc@example.dn42\n");
+        assert_eq!(
+            as_obj.remarks,
+            vec!["Peering policy: open", "Contact: noc@example.dn42"]
+        );
+    }
+
+    #[test]
+    fn test_peering_policy() {
+        let cases = [
+            ("remarks: Peering policy: open\n", PeeringPolicy::Open),
+            (
+                "remarks: Open for peering, ping me on IRC\n",
+                PeeringPolicy::Open,
+            ),
+            ("remarks: peering: selective\n", PeeringPolicy::Selective),
+            (
+                "remarks: Peering on request only\n",
+                PeeringPolicy::Selective,
+            ),
+            (
+                "remarks: Not accepting new peerings\n",
+                PeeringPolicy::Closed,
+            ),
+            ("remarks: Peering policy: closed\n", PeeringPolicy::Closed),
+            ("remarks: Open source enthusiast\n", PeeringPolicy::Unknown),
+            ("", PeeringPolicy::Unknown),
+        ];
+
+        for (remarks, expected) in cases {
+            assert_eq!(
+                peering_policy(&as_with_remarks(remarks)),
+                expected,
+                "{:?}",
+                remarks
+            );
+        }
+    }
 }
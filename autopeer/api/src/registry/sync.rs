@@ -1,10 +1,53 @@
 use crate::config::RegistryConfig;
 use git2::{Cred, FetchOptions, RemoteCallbacks, Repository};
-use std::path::Path;
-use std::sync::Mutex;
+use rand::Rng;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tracing::{error, info, warn};
 
 static REGISTRY_LOCK: Mutex<()> = Mutex::new(());
 
+/// Time of the last successful sync per checkout, kept in the API's runtime state
+#[derive(Debug, Default)]
+pub struct SyncTimes(Mutex<HashMap<PathBuf, SystemTime>>);
+
+impl SyncTimes {
+    /// Remember that the checkout at `path` was synced successfully at `at`
+    pub fn record_success(&self, path: &Path, at: SystemTime) {
+        if let Ok(mut last) = self.0.lock() {
+            last.insert(path.to_path_buf(), at);
+        }
+    }
+
+    /// When the checkout at `path` was last synced successfully
+    ///
+    /// Before the first sync of this process, falls back to when git last wrote
+    /// `FETCH_HEAD` (every fetch) or `HEAD` (the clone), so a restart doesn't reset the age.
+    pub fn last_successful_sync(&self, path: &Path) -> Option<SystemTime> {
+        if let Some(at) = self.0.lock().ok().and_then(|last| last.get(path).copied()) {
+            return Some(at);
+        }
+        ["FETCH_HEAD", "HEAD"].iter().find_map(|file| {
+            std::fs::metadata(path.join(".git").join(file))
+                .and_then(|m| m.modified())
+                .ok()
+        })
+    }
+
+    /// Seconds since the checkout at `path` was last synced successfully
+    pub fn registry_age_secs(&self, path: &Path) -> Option<u64> {
+        let at = self.last_successful_sync(path)?;
+        Some(
+            SystemTime::now()
+                .duration_since(at)
+                .map(|age| age.as_secs())
+                .unwrap_or(0),
+        )
+    }
+}
+
 pub struct RegistrySync {
     config: RegistryConfig,
 }
@@ -21,7 +64,7 @@ impl RegistrySync {
             .lock()
             .map_err(|e| format!("Failed to acquire lock: {}", e))?;
 
-        if self.config.path.exists() {
+        let result = if self.config.path.exists() {
             // Try to pull, if it fails remove and re-clone
             match self.pull() {
                 Ok(()) => Ok(()),
@@ -35,7 +78,9 @@ impl RegistrySync {
             }
         } else {
             self.clone()
-        }
+        };
+
+        result
     }
 
     /// Clone the repository
@@ -134,6 +179,56 @@ impl RegistrySync {
     }
 }
 
+/// Delay before the first background sync, uniformly random within one interval
+///
+/// Spreads instances that start together (e.g. after a fleet deploy) across
+/// the interval instead of hitting the git server at the same moment.
+pub fn startup_delay(interval: Duration) -> Duration {
+    random_up_to(interval.saturating_sub(Duration::from_millis(1)))
+}
+
+/// Delay until the next background sync: the interval plus a random offset
+pub fn next_delay(interval: Duration, jitter: Duration) -> Duration {
+    interval + random_up_to(jitter)
+}
+
+fn random_up_to(max: Duration) -> Duration {
+    let max_ms = max.as_millis() as u64;
+    if max_ms == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(rand::rng().random_range(0..=max_ms))
+}
+
+/// Sync the registry forever, every `interval` plus up to `jitter`, recording successes in `times`
+pub async fn run_periodic(
+    config: RegistryConfig,
+    times: Arc<SyncTimes>,
+    interval: Duration,
+    jitter: Duration,
+) {
+    // A fresh install has nothing to serve from, so only delay existing checkouts
+    if config.path.exists() {
+        let delay = startup_delay(interval);
+        info!("First registry sync in {}s", delay.as_secs());
+        tokio::time::sleep(delay).await;
+    }
+
+    loop {
+        let sync = RegistrySync::new(config.clone());
+        match tokio::task::spawn_blocking(move || sync.sync()).await {
+            Ok(Ok(())) => {
+                times.record_success(&config.path, SystemTime::now());
+                info!("Registry sync completed")
+            }
+            Ok(Err(e)) => warn!("Registry sync failed: {}", e),
+            Err(e) => error!("Registry sync task panicked: {}", e),
+        }
+
+        tokio::time::sleep(next_delay(interval, jitter)).await;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -173,4 +268,40 @@ mod tests {
         assert!(result.is_ok(), "Sync failed: {:?}", result);
         assert!(sync.registry_path().exists());
     }
+
+    #[test]
+    fn test_registry_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let times = SyncTimes::default();
+        assert_eq!(times.registry_age_secs(dir.path()), None);
+
+        times.record_success(dir.path(), SystemTime::now() - Duration::from_secs(600));
+        let age = times.registry_age_secs(dir.path()).unwrap();
+        assert!((600..610).contains(&age), "{}", age);
+
+        // A clock that went backwards reads as fresh, not as an error
+        times.record_success(dir.path(), SystemTime::now() + Duration::from_secs(60));
+        assert_eq!(times.registry_age_secs(dir.path()), Some(0));
+    }
+
+    #[test]
+    fn test_startup_delay_within_interval() {
+        let interval = Duration::from_secs(3600);
+        for _ in 0..1000 {
+            assert!(startup_delay(interval) < interval);
+        }
+        assert_eq!(startup_delay(Duration::ZERO), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_next_delay_within_jitter() {
+        let interval = Duration::from_secs(3600);
+        let jitter = Duration::from_secs(300);
+        for _ in 0..1000 {
+            let delay = next_delay(interval, jitter);
+            assert!(delay >= interval);
+            assert!(delay <= interval + jitter);
+        }
+        assert_eq!(next_delay(interval, Duration::ZERO), interval);
+    }
 }
@@ -0,0 +1,206 @@
+//! Issued and revoked token ids (`jti`), persisted as JSON so revocations survive restarts
+//!
+//! Issued ids are tracked per ASN so all of an ASN's tokens can be revoked at
+//! once. Entries are dropped once the token would have expired anyway. Revoking an
+//! ASN also records when, so tokens without an id that were issued earlier are
+//! refused too.
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Contents of a token store file
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TokenLedger {
+    /// Unexpired tokens per ASN (jti -> exp)
+    issued: HashMap<u32, HashMap<String, i64>>,
+    /// Revoked tokens (jti -> exp)
+    revoked: HashMap<String, i64>,
+    /// Per ASN, tokens issued before this time are revoked (absent in older stores)
+    #[serde(default)]
+    revoked_before: HashMap<u32, i64>,
+}
+
+impl TokenLedger {
+    fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse token store {:?}: {}", path, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read token store {:?}: {}", path, e)),
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create token store dir: {}", e))?;
+        }
+        let content = serde_json::to_string(self)
+            .map_err(|e| format!("Failed to serialize token store: {}", e))?;
+
+        // Write then rename, so a crash never leaves a truncated store behind
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, content).map_err(|e| format!("Failed to write token store: {}", e))?;
+        std::fs::rename(&tmp, path).map_err(|e| format!("Failed to write token store: {}", e))
+    }
+
+    fn prune(&mut self, now: i64) {
+        for tokens in self.issued.values_mut() {
+            tokens.retain(|_, exp| *exp > now);
+        }
+        self.issued.retain(|_, tokens| !tokens.is_empty());
+        self.revoked.retain(|_, exp| *exp > now);
+    }
+}
+
+/// The ledger of the configured token store, held in the app's runtime state
+#[derive(Debug, Default)]
+pub struct TokenStore {
+    /// Loaded ledger and the path it was loaded from
+    ledger: Mutex<Option<(PathBuf, TokenLedger)>>,
+}
+
+impl TokenStore {
+    /// Run `f` on the ledger at `path`, saving it afterwards when `persist` is set.
+    /// A different path (`TOKEN_STORE` changed on reload) is loaded in place of the old one.
+    fn with_ledger<T>(
+        &self,
+        path: &Path,
+        persist: bool,
+        f: impl FnOnce(&mut TokenLedger) -> T,
+    ) -> Result<T, String> {
+        let mut loaded = self
+            .ledger
+            .lock()
+            .map_err(|e| format!("Failed to acquire token store lock: {}", e))?;
+
+        let ledger = match &mut *loaded {
+            Some((loaded_path, ledger)) if loaded_path == path => ledger,
+            slot => {
+                &mut slot
+                    .insert((path.to_path_buf(), TokenLedger::load(path)?))
+                    .1
+            }
+        };
+        ledger.prune(Utc::now().timestamp());
+
+        let result = f(ledger);
+        if persist {
+            ledger.save(path)?;
+        }
+        Ok(result)
+    }
+
+    /// Record a newly issued token for an ASN
+    pub fn record_issued(&self, path: &Path, asn: u32, jti: &str, exp: i64) -> Result<(), String> {
+        self.with_ledger(path, true, |ledger| {
+            ledger
+                .issued
+                .entry(asn)
+                .or_default()
+                .insert(jti.to_string(), exp);
+        })
+    }
+
+    /// Whether a token of `asn` issued at `iat` has been revoked, by its id or by a
+    /// revocation of the whole ASN after it was issued
+    pub fn is_revoked(
+        &self,
+        path: &Path,
+        asn: u32,
+        jti: Option<&str>,
+        iat: i64,
+    ) -> Result<bool, String> {
+        self.with_ledger(path, false, |ledger| {
+            jti.is_some_and(|jti| ledger.revoked.contains_key(jti))
+                || ledger
+                    .revoked_before
+                    .get(&asn)
+                    .is_some_and(|cutoff| iat < *cutoff)
+        })
+    }
+
+    /// Unexpired, unrevoked tokens of an ASN (jti and exp), soonest expiry first
+    pub fn active_tokens(&self, path: &Path, asn: u32) -> Result<Vec<(String, i64)>, String> {
+        self.with_ledger(path, false, |ledger| {
+            let mut tokens: Vec<_> = ledger
+                .issued
+                .get(&asn)
+                .into_iter()
+                .flatten()
+                .filter(|(jti, _)| !ledger.revoked.contains_key(*jti))
+                .map(|(jti, exp)| (jti.clone(), *exp))
+                .collect();
+            tokens.sort_by_key(|(_, exp)| *exp);
+            tokens
+        })
+    }
+
+    /// Revoke every token issued to an ASN so far, returning how many tracked ones were
+    /// revoked (tokens without an id are refused as well, but can't be counted)
+    pub fn revoke_asn(&self, path: &Path, asn: u32) -> Result<usize, String> {
+        self.with_ledger(path, true, |ledger| {
+            ledger.revoked_before.insert(asn, Utc::now().timestamp());
+            let tokens = ledger.issued.remove(&asn).unwrap_or_default();
+            let count = tokens.len();
+            ledger.revoked.extend(tokens);
+            count
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_revoke_asn() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("tokens.json");
+        let exp = Utc::now().timestamp() + 3600;
+        let store = TokenStore::default();
+
+        store.record_issued(&path, 4242422225, "a1", exp).unwrap();
+        store.record_issued(&path, 4242422225, "a2", exp).unwrap();
+        store.record_issued(&path, 4242421816, "b1", exp).unwrap();
+        // Already expired, nothing to revoke
+        store
+            .record_issued(&path, 4242422225, "old", Utc::now().timestamp() - 1)
+            .unwrap();
+        assert_eq!(store.active_tokens(&path, 4242422225).unwrap().len(), 2);
+
+        assert_eq!(store.revoke_asn(&path, 4242422225).unwrap(), 2);
+        let iat = Utc::now().timestamp() - 60;
+        assert!(store
+            .is_revoked(&path, 4242422225, Some("a1"), iat)
+            .unwrap());
+        assert!(!store
+            .is_revoked(&path, 4242421816, Some("b1"), iat)
+            .unwrap());
+        // Tokens without an id are caught by the time of the revocation
+        assert!(store.is_revoked(&path, 4242422225, None, iat).unwrap());
+        assert!(!store
+            .is_revoked(&path, 4242422225, None, Utc::now().timestamp() + 1)
+            .unwrap());
+        assert!(!store.is_revoked(&path, 4242421816, None, iat).unwrap());
+        assert!(store.active_tokens(&path, 4242422225).unwrap().is_empty());
+
+        // A moved store starts from its own file, and moving back reloads this one
+        let moved = dir.path().join("moved.json");
+        assert!(!store
+            .is_revoked(&moved, 4242422225, Some("a1"), iat)
+            .unwrap());
+        assert!(store
+            .is_revoked(&path, 4242422225, Some("a1"), iat)
+            .unwrap());
+
+        // Persisted for the next process
+        let ledger = TokenLedger::load(&path).unwrap();
+        assert!(ledger.revoked.contains_key("a2"));
+        assert!(ledger.revoked_before.contains_key(&4242422225));
+        assert!(ledger.issued.contains_key(&4242421816));
+    }
+}
@@ -0,0 +1,144 @@
+//! Async command execution with timeouts and captured output
+//!
+//! Runs external tools (`wg`, `wg-quick`, `birdc`) on tokio's process
+//! driver so deployments don't tie up runtime worker threads.
+
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Captured result of a finished command
+#[derive(Debug, Clone)]
+pub struct ExecOutput {
+    pub status: ExitStatus,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl ExecOutput {
+    /// Whether the command exited with status 0
+    pub fn success(&self) -> bool {
+        self.status.success()
+    }
+}
+
+/// Run `program` with `args`, killing it if it runs longer than `timeout`
+pub async fn run(program: &str, args: &[&str], timeout: Duration) -> Result<ExecOutput, String> {
+    run_with_stdin(program, args, None, timeout).await
+}
+
+/// Run `program` with `args`, optionally writing `stdin` to it first
+pub async fn run_with_stdin(
+    program: &str,
+    args: &[&str],
+    stdin: Option<&str>,
+    timeout: Duration,
+) -> Result<ExecOutput, String> {
+    let description = describe(program, args);
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Dropping the wait future on timeout kills the process
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", description, e))?;
+
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        pipe.write_all(input.as_bytes())
+            .await
+            .map_err(|e| format!("Failed to write to {} stdin: {}", description, e))?;
+        // Close stdin so the command sees EOF
+        drop(pipe);
+    }
+
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| format!("{} timed out after {}s", description, timeout.as_secs()))?
+        .map_err(|e| format!("Failed to wait for {}: {}", description, e))?;
+
+    Ok(ExecOutput {
+        status: output.status,
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+    })
+}
+
+/// Program name plus its first argument, for error messages (e.g. `wg-quick up`)
+fn describe(program: &str, args: &[&str]) -> String {
+    let name = std::path::Path::new(program)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| program.to_string());
+
+    match args.first() {
+        Some(arg) => format!("{} {}", name, arg),
+        None => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn test_run_captures_output() {
+        let output = run(
+            "sh",
+            &["-c", "echo out; echo err >&2; exit 3"],
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+
+        assert!(!output.success());
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(output.stdout, "out\n");
+        assert_eq!(output.stderr, "err\n");
+    }
+
+    #[tokio::test]
+    async fn test_run_with_stdin() {
+        let output = run_with_stdin("cat", &[], Some("private-key"), Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert!(output.success());
+        assert_eq!(output.stdout, "private-key");
+    }
+
+    #[tokio::test]
+    async fn test_hanging_command_is_killed() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let marker = dir.path().join("finished");
+        let script = format!("sleep 1; touch {}", marker.display());
+
+        let started = Instant::now();
+        let err = run("sh", &["-c", &script], Duration::from_millis(200))
+            .await
+            .unwrap_err();
+        assert!(err.contains("sh -c timed out"));
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // Killed before it could finish its work
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(!marker.exists());
+    }
+
+    #[tokio::test]
+    async fn test_missing_program() {
+        let err = run("/nonexistent/autopeer-tool", &[], Duration::from_secs(1))
+            .await
+            .unwrap_err();
+
+        assert!(err.contains("Failed to run"));
+    }
+}
@@ -0,0 +1,3 @@
+pub mod exec;
+pub mod private_file;
+pub mod tar;
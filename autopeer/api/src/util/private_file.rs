@@ -0,0 +1,32 @@
+//! Files that may hold key material, readable by their owner only
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+
+/// Mode of files that may hold secrets
+const MODE: u32 = 0o600;
+
+/// Write `content` to `path`, replacing it; an existing file is restricted too
+pub fn write<P: AsRef<Path>>(path: P, content: impl AsRef<[u8]>) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(MODE)
+        .open(&path)?;
+    // `mode` only applies to new files
+    file.set_permissions(fs::Permissions::from_mode(MODE))?;
+    file.write_all(content.as_ref())
+}
+
+/// Create `path` with `content`, failing with `AlreadyExists` if it is there
+pub fn create_new<P: AsRef<Path>>(path: P, content: impl AsRef<[u8]>) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(MODE)
+        .open(&path)?;
+    file.write_all(content.as_ref())
+}
@@ -0,0 +1,130 @@
+//! Minimal in-memory ustar writer for small download bundles
+
+/// Size of a tar block; headers and file contents are padded to it
+const BLOCK: usize = 512;
+
+/// Tar archive being built in memory
+pub struct TarBuilder {
+    data: Vec<u8>,
+    mtime: u64,
+}
+
+impl TarBuilder {
+    /// Start an archive whose entries carry `mtime` (Unix seconds)
+    pub fn new(mtime: u64) -> Self {
+        TarBuilder {
+            data: Vec::new(),
+            mtime,
+        }
+    }
+
+    /// Add a regular file
+    pub fn append(&mut self, path: &str, mode: u32, content: &[u8]) -> Result<(), String> {
+        if path.is_empty() || path.len() > 100 {
+            return Err(format!("Tar entry name must be 1-100 bytes: {}", path));
+        }
+
+        let mut header = [0u8; BLOCK];
+        header[..path.len()].copy_from_slice(path.as_bytes());
+        write_octal(&mut header[100..108], mode as u64);
+        write_octal(&mut header[108..116], 0);
+        write_octal(&mut header[116..124], 0);
+        write_octal(&mut header[124..136], content.len() as u64);
+        write_octal(&mut header[136..148], self.mtime);
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+
+        // The checksum is computed with its own field set to spaces
+        header[148..156].fill(b' ');
+        let checksum: u64 = header.iter().map(|&b| b as u64).sum();
+        write_octal(&mut header[148..155], checksum);
+
+        self.data.extend_from_slice(&header);
+        self.data.extend_from_slice(content);
+        self.data.resize(self.data.len().div_ceil(BLOCK) * BLOCK, 0);
+        Ok(())
+    }
+
+    /// Close the archive (two empty blocks) and return its bytes
+    pub fn finish(mut self) -> Vec<u8> {
+        self.data.resize(self.data.len() + 2 * BLOCK, 0);
+        self.data
+    }
+}
+
+/// Zero-padded octal number followed by a NUL, filling `field`
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    let text = format!("{:0width$o}", value, width = digits);
+    field[..digits].copy_from_slice(&text.as_bytes()[text.len() - digits..]);
+    field[digits] = 0;
+}
+
+/// Entries of an archive written by `TarBuilder` (name and content)
+#[cfg(test)]
+pub fn read_entries(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while offset + BLOCK <= archive.len() && archive[offset] != 0 {
+        let header = &archive[offset..offset + BLOCK];
+        let name_len = header[..100].iter().position(|&b| b == 0).unwrap_or(100);
+        let name = String::from_utf8(header[..name_len].to_vec()).unwrap();
+        let size = u64::from_str_radix(std::str::from_utf8(&header[124..135]).unwrap(), 8).unwrap()
+            as usize;
+
+        let start = offset + BLOCK;
+        entries.push((name, archive[start..start + size].to_vec()));
+        offset = start + size.div_ceil(BLOCK) * BLOCK;
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_roundtrip() {
+        let mut tar = TarBuilder::new(1700000000);
+        tar.append("wg-as4242420257.conf", 0o600, b"[Interface]\n")
+            .unwrap();
+        tar.append("README.md", 0o644, &[b'x'; 600]).unwrap();
+        let archive = tar.finish();
+
+        assert_eq!(archive.len() % BLOCK, 0);
+        let entries = read_entries(&archive);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0],
+            (
+                "wg-as4242420257.conf".to_string(),
+                b"[Interface]\n".to_vec()
+            )
+        );
+        assert_eq!(entries[1].1.len(), 600);
+
+        // Header checksum as tar verifies it
+        let header = &archive[..BLOCK];
+        let stored =
+            u64::from_str_radix(std::str::from_utf8(&header[148..154]).unwrap(), 8).unwrap();
+        let computed: u64 = header
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| {
+                if (148..156).contains(&i) {
+                    b' ' as u64
+                } else {
+                    b as u64
+                }
+            })
+            .sum();
+        assert_eq!(stored, computed);
+    }
+
+    #[test]
+    fn test_long_names_are_rejected() {
+        let mut tar = TarBuilder::new(0);
+        assert!(tar.append(&"a".repeat(101), 0o644, b"").is_err());
+    }
+}
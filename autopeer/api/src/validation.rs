@@ -1,33 +1,122 @@
+use crate::config::AppConfig;
+use crate::ipalloc::AddressFamily;
+use crate::wireguard::{PresharedKey, PublicKey};
 use axum::http::StatusCode;
 use once_cell::sync::Lazy;
+use pgp::composed::SignedPublicKey;
+use pgp::types::{PublicKeyTrait, PublicParams};
 use regex::Regex;
+use rsa::traits::PublicKeyParts;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
 
 // Compile regexes once at startup
-static IPV4_PATTERN: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r"^(\d{1,3}\.){3}\d{1,3}:\d{1,5}$").unwrap()
-});
+static IPV4_PATTERN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(\d{1,3}\.){3}\d{1,3}:\d{1,5}$").unwrap());
+
+static IPV6_PATTERN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\[([0-9a-fA-F:]+)\]:\d{1,5}$").unwrap());
 
-static IPV6_PATTERN: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r"^\[([0-9a-fA-F:]+)\]:\d{1,5}$").unwrap()
+static HOSTNAME_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^([A-Za-z0-9]([A-Za-z0-9-]{0,61}[A-Za-z0-9])?\.)+[A-Za-z]{2,63}\.?:\d{1,5}$")
+        .unwrap()
 });
 
-/// Validate ASN is within DN42 range
-pub fn validate_asn(asn: u32) -> Result<(), (StatusCode, String)> {
-    if asn < 4200000000 || asn > 4294967294 {
+static EMAIL_PATTERN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[^@\s]+@[^@\s]+\.[^@\s]+$").unwrap());
+
+static HANDLE_PATTERN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^@?[A-Za-z0-9][A-Za-z0-9._|\-]*(:[A-Za-z0-9.\-]+)?$").unwrap());
+
+/// Inclusive range of ASNs that may peer, e.g. `4200000000-4294967294`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AsnRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl AsnRange {
+    /// The DN42 range (including the private 32-bit ASNs DN42 allows)
+    pub const DN42: AsnRange = AsnRange {
+        start: 4200000000,
+        end: 4294967294,
+    };
+
+    pub fn contains(&self, asn: u32) -> bool {
+        (self.start..=self.end).contains(&asn)
+    }
+}
+
+impl Default for AsnRange {
+    fn default() -> Self {
+        AsnRange::DN42
+    }
+}
+
+impl FromStr for AsnRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| format!("ASN range {} must look like start-end", s))?;
+        let parse = |asn: &str| {
+            asn.trim()
+                .parse::<u32>()
+                .map_err(|e| format!("Invalid ASN in range {}: {}", s, e))
+        };
+        let (start, end) = (parse(start)?, parse(end)?);
+        if start == 0 || start > end {
+            return Err(format!("Invalid ASN range {}", s));
+        }
+
+        Ok(AsnRange { start, end })
+    }
+}
+
+impl fmt::Display for AsnRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// Validate ASN is within the configured range (DN42 by default)
+pub fn validate_asn(asn: u32, range: AsnRange) -> Result<(), (StatusCode, String)> {
+    if !range.contains(asn) {
         return Err((
             StatusCode::BAD_REQUEST,
-            format!("ASN {} is out of valid DN42 range (4200000000-4294967294)", asn),
+            format!("ASN {} is out of the allowed range ({})", asn, range),
         ));
     }
     Ok(())
 }
 
-/// Validate endpoint format (IP:port)
-pub fn validate_endpoint(endpoint: &str) -> Result<(), (StatusCode, String)> {
-    if !IPV4_PATTERN.is_match(endpoint) && !IPV6_PATTERN.is_match(endpoint) {
+/// Validate endpoint format (IP:port) and, with `reject_private_endpoints`,
+/// that the host can be reached from the internet. Hostnames (host:port) are only
+/// accepted under `allowed_endpoint_domains`.
+pub fn validate_endpoint(endpoint: &str, config: &AppConfig) -> Result<(), (StatusCode, String)> {
+    let is_ip = IPV4_PATTERN.is_match(endpoint) || IPV6_PATTERN.is_match(endpoint);
+    let allows_hostnames = !config.allowed_endpoint_domains.is_empty();
+    let is_hostname = allows_hostnames && HOSTNAME_PATTERN.is_match(endpoint);
+    if !is_ip && !is_hostname {
+        let expected = if allows_hostnames {
+            "IP:port, [IPv6]:port or hostname:port"
+        } else {
+            "IP:port or [IPv6]:port"
+        };
         return Err((
             StatusCode::BAD_REQUEST,
-            "Invalid endpoint format. Expected IP:port or [IPv6]:port".to_string(),
+            format!("Invalid endpoint format. Expected {}", expected),
+        ));
+    }
+    if allows_hostnames && is_ip && config.reject_ip_endpoints {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "IP endpoints aren't accepted; use a hostname under {}",
+                config.allowed_endpoint_domains.join(", ")
+            ),
         ));
     }
 
@@ -37,33 +126,187 @@ pub fn validate_endpoint(endpoint: &str) -> Result<(), (StatusCode, String)> {
         return Err((StatusCode::BAD_REQUEST, "Port cannot be 0".to_string()));
     }
 
+    if !is_ip {
+        // What a hostname resolves to is up to its DNS, so only the domain is checked
+        return check_endpoint_domain(endpoint, &config.allowed_endpoint_domains);
+    }
+
+    check_endpoint_family(endpoint, config.public_endpoint_family)?;
+
+    if config.reject_private_endpoints {
+        check_endpoint_host(endpoint, config.allow_dn42_endpoints)?;
+    }
+
     Ok(())
 }
 
-/// Validate WireGuard public key format (base64, 44 chars)
-pub fn validate_wg_pubkey(key: &str) -> Result<(), (StatusCode, String)> {
-    if key.len() != 44 {
+/// Reject hostname endpoints outside the allowed domains (the domain itself or any name below it)
+fn check_endpoint_domain(endpoint: &str, allowed: &[String]) -> Result<(), (StatusCode, String)> {
+    let host = endpoint
+        .rsplit_once(':')
+        .map_or(endpoint, |(host, _)| host)
+        .trim_end_matches('.')
+        .to_lowercase();
+    let is_allowed = allowed.iter().any(|domain| {
+        host == *domain
+            || host
+                .strip_suffix(domain.as_str())
+                .is_some_and(|sub| sub.ends_with('.'))
+    });
+    if is_allowed {
+        return Ok(());
+    }
+
+    Err((
+        StatusCode::BAD_REQUEST,
+        format!(
+            "Endpoint host {} is not under an allowed domain ({})",
+            host,
+            allowed.join(", ")
+        ),
+    ))
+}
+
+/// Reject endpoints in an address family our own endpoint isn't reachable over
+fn check_endpoint_family(endpoint: &str, ours: AddressFamily) -> Result<(), (StatusCode, String)> {
+    let (family, reachable) = if IPV6_PATTERN.is_match(endpoint) {
+        ("IPv6", ours.has_ipv6())
+    } else {
+        ("IPv4", ours.has_ipv4())
+    };
+    if reachable {
+        return Ok(());
+    }
+
+    let ours = if ours.has_ipv4() { "IPv4" } else { "IPv6" };
+    Err((
+        StatusCode::BAD_REQUEST,
+        format!(
+            "Endpoint {} is {}, but this server is only reachable over {} (see GET /peering/server-info)",
+            endpoint, family, ours
+        ),
+    ))
+}
+
+/// Reject endpoint hosts that can't work over the internet. DN42 addresses are
+/// private too, but fine when `allow_dn42` says this server is on DN42.
+fn check_endpoint_host(endpoint: &str, allow_dn42: bool) -> Result<(), (StatusCode, String)> {
+    let host = endpoint
+        .rsplit_once(':')
+        .map(|(host, _)| host.trim_start_matches('[').trim_end_matches(']'))
+        .unwrap_or(endpoint);
+    let ip: IpAddr = host.parse().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid endpoint address {}", host),
+        )
+    })?;
+
+    if allow_dn42 && is_dn42(ip) {
+        return Ok(());
+    }
+    if is_private(ip) {
         return Err((
             StatusCode::BAD_REQUEST,
-            "WireGuard public key must be 44 characters".to_string(),
+            format!(
+                "Endpoint {} is a loopback, link-local or private address and can't be reached",
+                ip
+            ),
         ));
     }
 
-    // Check if it's valid base64
-    if !key.chars().all(|c| c.is_alphanumeric() || c == '+' || c == '/' || c == '=') {
+    Ok(())
+}
+
+/// DN42 address space (172.20.0.0/14, fd00::/8)
+fn is_dn42(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.octets()[0] == 172 && (20..=23).contains(&v4.octets()[1]),
+        IpAddr::V6(v6) => v6.segments()[0] & 0xff00 == 0xfd00,
+    }
+}
+
+/// Loopback, unspecified, link-local and RFC1918 / ULA addresses
+fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_private_v4(v4),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_private_v4(v4),
+            None => is_private_v6(v6),
+        },
+    }
+}
+
+fn is_private_v4(ip: Ipv4Addr) -> bool {
+    ip.is_loopback() || ip.is_unspecified() || ip.is_link_local() || ip.is_private()
+}
+
+fn is_private_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // fe80::/10
+        || first & 0xffc0 == 0xfe80
+        // fc00::/7
+        || first & 0xfe00 == 0xfc00
+}
+
+/// Validate a notification contact: an email address or a handle (e.g. `@nick:matrix.org`)
+pub fn validate_contact(contact: &str) -> Result<(), (StatusCode, String)> {
+    if contact.len() > 254 || !(EMAIL_PATTERN.is_match(contact) || HANDLE_PATTERN.is_match(contact))
+    {
         return Err((
             StatusCode::BAD_REQUEST,
-            "WireGuard public key must be valid base64".to_string(),
+            "Invalid contact. Expected an email address or a handle".to_string(),
         ));
     }
-
     Ok(())
 }
 
+/// Validate a WireGuard public key (base64, 44 chars) and return it typed
+pub fn validate_wg_pubkey(key: &str) -> Result<PublicKey, (StatusCode, String)> {
+    PublicKey::new(key).map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+/// Validate a WireGuard preshared key (base64, 44 chars) and return it typed
+pub fn validate_wg_preshared_key(key: &str) -> Result<PresharedKey, (StatusCode, String)> {
+    PresharedKey::new(key).map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+/// Validate a BGP router id (IPv4 dotted-quad format, not 0.0.0.0)
+pub fn validate_router_id(id: &str) -> Result<Ipv4Addr, (StatusCode, String)> {
+    match id.parse::<Ipv4Addr>() {
+        Ok(id) if !id.is_unspecified() => Ok(id),
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Invalid router id '{}': expected a non-zero IPv4-format id",
+                id
+            ),
+        )),
+    }
+}
+
+/// Reduce a peer-chosen session name to what is safe inside BIRD strings and comments:
+/// ASCII letters, digits, spaces, `-`, `_` and `.`, at most 32 characters.
+/// Returns `None` if nothing usable is left.
+pub fn sanitize_peer_name(name: &str) -> Option<String> {
+    let sanitized: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.'))
+        .take(32)
+        .collect();
+    let sanitized = sanitized.trim();
+    (!sanitized.is_empty()).then(|| sanitized.to_string())
+}
+
 /// Validate PGP public key format (basic check - actual parsing happens later)
 pub fn validate_pgp_key(key: &str) -> Result<(), (StatusCode, String)> {
     if key.is_empty() {
-        return Err((StatusCode::BAD_REQUEST, "PGP key cannot be empty".to_string()));
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "PGP key cannot be empty".to_string(),
+        ));
     }
     // Real validation happens in verify_key_fingerprint
     Ok(())
@@ -72,59 +315,348 @@ pub fn validate_pgp_key(key: &str) -> Result<(), (StatusCode, String)> {
 /// Validate signed challenge format (basic check - actual parsing happens later)
 pub fn validate_signed_challenge(signed: &str) -> Result<(), (StatusCode, String)> {
     if signed.is_empty() {
-        return Err((StatusCode::BAD_REQUEST, "Signed message cannot be empty".to_string()));
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Signed message cannot be empty".to_string(),
+        ));
     }
     // Real validation happens in gpg::verify_signature
     Ok(())
 }
 
+/// Check a PGP key's primary algorithm against the configured policy
+pub fn check_key_policy(
+    key: &SignedPublicKey,
+    config: &AppConfig,
+) -> Result<(), (StatusCode, String)> {
+    let (algorithm, bits) = key_algorithm(key);
+
+    if !is_algorithm_allowed(algorithm, bits, &config.allowed_pgp_algorithms) {
+        let described = match bits {
+            Some(bits) => format!("{}{}", algorithm, bits),
+            None => algorithm.to_string(),
+        };
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!(
+                "PGP key algorithm {} is not allowed (allowed: {})",
+                described,
+                config.allowed_pgp_algorithms.join(", ")
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Classify the primary key algorithm, with the modulus size for RSA keys
+fn key_algorithm(key: &SignedPublicKey) -> (&'static str, Option<usize>) {
+    match key.primary_key.public_params() {
+        PublicParams::RSA(params) => ("rsa", Some(params.key.size() * 8)),
+        PublicParams::DSA(_) => ("dsa", None),
+        PublicParams::ECDSA(_) => ("ecdsa", None),
+        PublicParams::EdDSALegacy(_) | PublicParams::Ed25519(_) => ("ed25519", None),
+        PublicParams::Ed448(_) => ("ed448", None),
+        PublicParams::Elgamal(_) => ("elgamal", None),
+        _ => ("unknown", None),
+    }
+}
+
+/// Match an algorithm against policy entries (`rsa<bits>` is a minimum size)
+fn is_algorithm_allowed(algorithm: &str, bits: Option<usize>, allowed: &[String]) -> bool {
+    allowed.iter().any(|entry| match entry.strip_prefix("rsa") {
+        Some(min_bits) if algorithm == "rsa" => {
+            let min_bits = min_bits.parse::<usize>().unwrap_or(0);
+            bits.unwrap_or(0) >= min_bits
+        }
+        Some(_) => false,
+        None => entry == algorithm,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Config that only checks the endpoint format
+    fn format_only() -> AppConfig {
+        AppConfig {
+            reject_private_endpoints: false,
+            ..(*crate::api::test_helpers::test_config()).clone()
+        }
+    }
+
     #[test]
     fn test_validate_asn_valid() {
-        assert!(validate_asn(4242420257).is_ok());
-        assert!(validate_asn(4200000000).is_ok());
-        assert!(validate_asn(4294967294).is_ok());
+        assert!(validate_asn(4242420257, AsnRange::DN42).is_ok());
+        assert!(validate_asn(4200000000, AsnRange::DN42).is_ok());
+        assert!(validate_asn(4294967294, AsnRange::DN42).is_ok());
     }
 
     #[test]
     fn test_validate_asn_invalid() {
-        assert!(validate_asn(100).is_err());
-        assert!(validate_asn(4199999999).is_err());
-        assert!(validate_asn(4294967295).is_err());
+        assert!(validate_asn(100, AsnRange::DN42).is_err());
+        assert!(validate_asn(4199999999, AsnRange::DN42).is_err());
+        assert!(validate_asn(4294967295, AsnRange::DN42).is_err());
+    }
+
+    #[test]
+    fn test_validate_asn_custom_range() {
+        let range: AsnRange = "64512-65534".parse().unwrap();
+        assert_eq!(range.to_string(), "64512-65534");
+
+        assert!(validate_asn(64512, range).is_ok());
+        assert!(validate_asn(65534, range).is_ok());
+        let (status, message) = validate_asn(4242420257, range).unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(message.contains("64512-65534"));
+        assert!(validate_asn(65535, range).is_err());
+
+        assert!("65534-64512".parse::<AsnRange>().is_err());
+        assert!("64512".parse::<AsnRange>().is_err());
     }
 
     #[test]
     fn test_validate_endpoint_ipv4() {
-        assert!(validate_endpoint("192.168.1.1:51820").is_ok());
-        assert!(validate_endpoint("1.2.3.4:12345").is_ok());
+        let config = format_only();
+        assert!(validate_endpoint("192.168.1.1:51820", &config).is_ok());
+        assert!(validate_endpoint("1.2.3.4:12345", &config).is_ok());
     }
 
     #[test]
     fn test_validate_endpoint_ipv6() {
-        assert!(validate_endpoint("[2001:db8::1]:51820").is_ok());
-        assert!(validate_endpoint("[fe80::1]:12345").is_ok());
+        let config = format_only();
+        assert!(validate_endpoint("[2001:db8::1]:51820", &config).is_ok());
+        assert!(validate_endpoint("[fe80::1]:12345", &config).is_ok());
     }
 
     #[test]
     fn test_validate_endpoint_invalid() {
-        assert!(validate_endpoint("not-an-ip:1234").is_err());
-        assert!(validate_endpoint("192.168.1.1").is_err());
-        assert!(validate_endpoint("192.168.1.1:0").is_err());
+        let config = format_only();
+        assert!(validate_endpoint("not-an-ip:1234", &config).is_err());
+        assert!(validate_endpoint("192.168.1.1", &config).is_err());
+        assert!(validate_endpoint("192.168.1.1:0", &config).is_err());
+    }
+
+    #[test]
+    fn test_validate_endpoint_rejects_private_hosts() {
+        let config = crate::api::test_helpers::test_config();
+        for endpoint in [
+            "127.0.0.1:51820",
+            "10.1.2.3:51820",
+            "172.16.0.1:51820",
+            "192.168.1.1:51820",
+            "169.254.1.1:51820",
+            "0.0.0.0:51820",
+            "[::1]:51820",
+            "[fe80::1]:51820",
+            "[fd42:d42:d42::1]:51820",
+            "[::ffff:c0a8:101]:51820",
+            "172.20.0.1:51820",
+        ] {
+            let (status, message) = validate_endpoint(endpoint, &config).unwrap_err();
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", endpoint);
+            assert!(message.contains("private"), "{}: {}", endpoint, message);
+        }
+
+        assert!(validate_endpoint("1.2.3.4:51820", &config).is_ok());
+        assert!(validate_endpoint("[2001:db8::1]:51820", &config).is_ok());
+        assert!(validate_endpoint("172.32.0.1:51820", &config).is_ok());
+    }
+
+    #[test]
+    fn test_validate_endpoint_allows_dn42_hosts() {
+        let config = AppConfig {
+            allow_dn42_endpoints: true,
+            ..(*crate::api::test_helpers::test_config()).clone()
+        };
+
+        assert!(validate_endpoint("172.20.0.1:51820", &config).is_ok());
+        assert!(validate_endpoint("172.23.255.1:51820", &config).is_ok());
+        assert!(validate_endpoint("[fd42:d42:d42::1]:51820", &config).is_ok());
+        // Other private ranges stay rejected
+        assert!(validate_endpoint("192.168.1.1:51820", &config).is_err());
+        assert!(validate_endpoint("[fc00::1]:51820", &config).is_err());
+    }
+
+    #[test]
+    fn test_validate_endpoint_allowed_domains() {
+        let config = AppConfig {
+            allowed_endpoint_domains: vec!["dyndns.org".to_string(), "example.net".to_string()],
+            ..(*crate::api::test_helpers::test_config()).clone()
+        };
+
+        assert!(validate_endpoint("peer.dyndns.org:51820", &config).is_ok());
+        assert!(validate_endpoint("a.b.Example.NET.:51820", &config).is_ok());
+        assert!(validate_endpoint("example.net:51820", &config).is_ok());
+        for endpoint in [
+            "peer.evil.org:51820",
+            "peerdyndns.org:51820",
+            "dyndns.org.evil.com:51820",
+        ] {
+            let (status, message) = validate_endpoint(endpoint, &config).unwrap_err();
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", endpoint);
+            assert!(
+                message.contains("not under an allowed domain"),
+                "{}: {}",
+                endpoint,
+                message
+            );
+        }
+        assert!(validate_endpoint("peer.dyndns.org:0", &config).is_err());
+        assert!(validate_endpoint("peer.dyndns.org", &config).is_err());
+
+        // IP endpoints still go through the usual checks, unless refused outright
+        assert!(validate_endpoint("1.2.3.4:51820", &config).is_ok());
+        assert!(validate_endpoint("192.168.1.1:51820", &config).is_err());
+        let strict = AppConfig {
+            reject_ip_endpoints: true,
+            ..config
+        };
+        let (_, message) = validate_endpoint("1.2.3.4:51820", &strict).unwrap_err();
+        assert!(message.contains("use a hostname"), "{}", message);
+        assert!(validate_endpoint("peer.dyndns.org:51820", &strict).is_ok());
+
+        // Without the allowlist hostnames stay invalid, and the IP flag has nothing to apply to
+        let config = AppConfig {
+            reject_ip_endpoints: true,
+            ..format_only()
+        };
+        assert!(validate_endpoint("peer.dyndns.org:51820", &config).is_err());
+        assert!(validate_endpoint("1.2.3.4:51820", &config).is_ok());
+    }
+
+    #[test]
+    fn test_validate_endpoint_family() {
+        let config = AppConfig {
+            public_endpoint_family: AddressFamily::Ipv4,
+            ..format_only()
+        };
+        let (status, message) = validate_endpoint("[2001:db8::1]:51820", &config).unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(message.contains("only reachable over IPv4"), "{}", message);
+        assert!(validate_endpoint("1.2.3.4:51820", &config).is_ok());
+
+        let config = AppConfig {
+            public_endpoint_family: AddressFamily::Ipv6,
+            ..format_only()
+        };
+        assert!(validate_endpoint("1.2.3.4:51820", &config).is_err());
+        assert!(validate_endpoint("[2001:db8::1]:51820", &config).is_ok());
+    }
+
+    #[test]
+    fn test_validate_router_id() {
+        assert_eq!(
+            validate_router_id("172.20.53.97").unwrap(),
+            Ipv4Addr::new(172, 20, 53, 97)
+        );
+        assert!(validate_router_id("0.0.0.0").is_err());
+        assert!(validate_router_id("fe80::1").is_err());
+        assert!(validate_router_id("172.20.53").is_err());
+        assert!(validate_router_id("").is_err());
+    }
+
+    #[test]
+    fn test_sanitize_peer_name() {
+        assert_eq!(
+            sanitize_peer_name("node-1.fra_01").as_deref(),
+            Some("node-1.fra_01")
+        );
+        assert_eq!(sanitize_peer_name("  edge 2 ").as_deref(), Some("edge 2"));
+        assert_eq!(sanitize_peer_name("a\";}\n#b").as_deref(), Some("ab"));
+        assert_eq!(sanitize_peer_name(&"x".repeat(40)).unwrap().len(), 32);
+        assert!(sanitize_peer_name("\"{};").is_none());
+    }
+
+    #[test]
+    fn test_validate_contact() {
+        assert!(validate_contact("noc@example.com").is_ok());
+        assert!(validate_contact("scarjit").is_ok());
+        assert!(validate_contact("@scarjit:matrix.org").is_ok());
+
+        assert!(validate_contact("").is_err());
+        assert!(validate_contact("two words").is_err());
+        assert!(validate_contact("noc@example.com\n[Peer]").is_err());
+        assert!(validate_contact(&"a".repeat(255)).is_err());
     }
 
     #[test]
     fn test_validate_wg_pubkey_valid() {
-        let key = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQR";
+        let key = "TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=";
         assert_eq!(key.len(), 44);
         assert!(validate_wg_pubkey(key).is_ok());
+        // 44 characters but missing the base64 padding
+        assert!(validate_wg_pubkey("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQR").is_err());
     }
 
     #[test]
     fn test_validate_wg_pubkey_invalid() {
         assert!(validate_wg_pubkey("tooshort").is_err());
-        assert!(validate_wg_pubkey("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ").is_err());
+        assert!(
+            validate_wg_pubkey("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ").is_err()
+        );
+    }
+
+    #[test]
+    fn test_validate_wg_preshared_key() {
+        assert!(validate_wg_preshared_key("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=").is_ok());
+        let (status, message) = validate_wg_preshared_key("tooshort").unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(message.contains("preshared"), "{}", message);
+        assert!(validate_wg_preshared_key("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3f!mk=").is_err());
+    }
+
+    fn policy(entries: &[&str]) -> AppConfig {
+        AppConfig {
+            allowed_pgp_algorithms: entries.iter().map(|s| s.to_string()).collect(),
+            ..(*crate::api::test_helpers::test_config()).clone()
+        }
+    }
+
+    #[test]
+    fn test_check_key_policy_ed25519_allowed() {
+        use pgp::composed::Deserializable;
+
+        let public_key = r#"-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEYVuS5RYJKwYBBAHaRw8BAQdAnJ1to/QytFqDfg3gtUrtiqmJRMSLNrG/fLNG
+BesjX5m0L0ZlcmRpbmFuZCBMaW5uZW5iZXJnIDxmZXJkaW5hbmRAbGlubmVuYmVy
+Zy5kZXY+iJAEExYIADgWIQSLfwOEy+AnJ2HYUuoGhONubPnU1AUCYVuS5QIbAwUL
+CQgHAgYVCgkICwIEFgIDAQIeAQIXgAAKCRAGhONubPnU1M2ZAP0drb1tbnLi1cU+
+Pc4NPTMjviTBBFmGFoDni/0mvMC5qAD6AlB24idciDkSeJFz3s/6wSog/Rj4ALpk
+RQ/v8Ls4gQa4OARhW5LlEgorBgEEAZdVAQUBAQdAci4cwabJdJGO+VF5wxEW+yuO
+Y+BPprEQpy4jFiN713sDAQgHiHgEGBYIACAWIQSLfwOEy+AnJ2HYUuoGhONubPnU
+1AUCYVuS5QIbDAAKCRAGhONubPnU1I79AQC7Weudp5yzofVqZQCa/ijohC5CuwXw
+LGZbH16nUawo9gEAw+6wvpgw2d7IS6rnT6jJZ1qm6inF/XzTZTNfq9rsmgM=
+=WrLZ
+-----END PGP PUBLIC KEY BLOCK-----"#;
+
+        let (key, _) = SignedPublicKey::from_string(public_key).unwrap();
+        assert!(check_key_policy(&key, &policy(&["ed25519", "rsa4096"])).is_ok());
+    }
+
+    #[test]
+    fn test_check_key_policy_weak_rsa_rejected() {
+        use pgp::composed::Deserializable;
+
+        // Test fixture key is RSA 2048
+        let public_key = include_str!("../tests/fixtures/gpg/test-public.asc");
+        let (key, _) = SignedPublicKey::from_string(public_key).unwrap();
+
+        assert!(check_key_policy(&key, &policy(&["ed25519", "rsa2048"])).is_ok());
+
+        let err = check_key_policy(&key, &policy(&["ed25519", "rsa4096"])).unwrap_err();
+        assert_eq!(err.0, StatusCode::FORBIDDEN);
+        assert!(err.1.contains("rsa2048"));
+    }
+
+    #[test]
+    fn test_is_algorithm_allowed() {
+        let allowed = vec!["ed25519".to_string(), "rsa2048".to_string()];
+        assert!(is_algorithm_allowed("ed25519", None, &allowed));
+        assert!(is_algorithm_allowed("rsa", Some(4096), &allowed));
+        assert!(!is_algorithm_allowed("rsa", Some(1024), &allowed));
+        assert!(!is_algorithm_allowed("dsa", None, &allowed));
     }
 }
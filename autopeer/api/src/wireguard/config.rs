@@ -1,7 +1,10 @@
+use super::ini::{IniDocument, IniSection};
+use super::keys::{PresharedKey, PrivateKey, PublicKey};
+use crate::ipalloc::{AddressFamily, BgpTransport};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::fmt;
 use std::fs;
+use std::net::Ipv4Addr;
 use std::path::Path;
 use tera::Context;
 
@@ -9,18 +12,22 @@ use tera::Context;
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct InterfaceConfig {
     pub address: Vec<String>,
-    pub private_key: String,
+    pub private_key: PrivateKey,
     pub listen_port: u16,
     pub table: Option<String>,
+    /// `DNS` servers; wg-quick hands them to resolvconf, so they are only deployed with
+    /// `allow_dns_directive`
+    pub dns: Vec<String>,
 }
 
 /// WireGuard peer configuration
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PeerConfig {
-    pub public_key: String,
+    pub public_key: PublicKey,
     pub endpoint: Option<String>,
     pub allowed_ips: Vec<String>,
     pub persistent_keepalive: Option<u16>,
+    pub preshared_key: Option<PresharedKey>,
 }
 
 /// Custom Challenge section for autopeer
@@ -33,19 +40,60 @@ pub struct ChallengeConfig {
 /// Custom BGP section for autopeer
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct BgpConfig {
+    /// Whether the BIRD session is deployed (the tunnel stays up either way)
+    pub enabled: bool,
     pub mpbgp: bool,
     pub extended_next_hop: bool,
     pub local: String,
     pub neighbor: String,
+    /// BIRD import limit for this session (`None` means unlimited)
+    pub max_prefixes: Option<u32>,
+    /// Address families carried by the session
+    pub address_family: AddressFamily,
+    /// BGP router id the peer asked for on their side (`None` leaves it to BIRD)
+    pub router_id: Option<Ipv4Addr>,
+    /// Session name the peer chose for BIRD (`None` uses `AS<asn>`)
+    pub peer_name: Option<String>,
+    /// BGP graceful restart for the session (`None` leaves BIRD's default)
+    pub graceful_restart: Option<bool>,
+    /// Whether the tunnel carries link-local addresses or only the transfer net
+    pub transport: BgpTransport,
+    /// Our ASN on this session, for nodes with several (`None` is `my_asn`)
+    pub local_as: Option<u32>,
+}
+
+/// Custom Meta section for autopeer (who to notify about the peering)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MetaConfig {
+    pub contact: Option<String>,
+}
+
+/// Custom Registry section for autopeer: the registry key that authorized the peering
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RegistryAuthConfig {
+    /// PGP fingerprint, or minisign public key, the challenge was verified with
+    pub fingerprint: String,
+    /// When the challenge was verified (Unix timestamp)
+    pub authorized_at: i64,
+}
+
+impl RegistryAuthConfig {
+    /// The `[Registry]` section of a parsed config file, if it has one
+    pub fn from_ini(doc: &IniDocument) -> Result<Option<Self>, String> {
+        doc.section("Registry").map(parse_registry).transpose()
+    }
 }
 
 /// Complete WireGuard configuration file
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct WgConfig {
     pub interface: InterfaceConfig,
-    pub peer: Option<PeerConfig>,
+    /// One per `[Peer]` section; peerings have a single peer, hub interfaces several
+    pub peers: Vec<PeerConfig>,
     pub challenge: Option<ChallengeConfig>,
     pub bgp: Option<BgpConfig>,
+    pub meta: Option<MetaConfig>,
+    pub registry: Option<RegistryAuthConfig>,
 }
 
 impl WgConfig {
@@ -58,49 +106,85 @@ impl WgConfig {
 
     /// Parse a WireGuard config from string
     pub fn from_string(content: &str) -> Result<Self, String> {
-        let sections = parse_ini_sections(content)?;
+        let doc = IniDocument::parse(content);
 
         // Parse Interface section (required)
-        let interface = parse_interface(&sections)?;
+        let interface = parse_interface(&doc)?;
 
-        // Parse Peer section (optional)
-        let peer = parse_peer(&sections).ok();
+        // Parse Peer sections (any number). A malformed section fails the whole config rather
+        // than being dropped, so writing the config back can't lose it.
+        let peers = doc
+            .sections_named("Peer")
+            .map(parse_peer)
+            .collect::<Result<Vec<_>, _>>()?;
 
         // Parse Challenge section (optional)
-        let challenge = parse_challenge(&sections).ok();
+        let challenge = doc.section("Challenge").map(parse_challenge).transpose()?;
 
         // Parse BGP section (optional)
-        let bgp = parse_bgp(&sections).ok();
+        let bgp = doc.section("BGP").map(parse_bgp).transpose()?;
+
+        // Parse Meta section (optional)
+        let meta = doc.section("Meta").map(parse_meta).transpose()?;
+
+        // Parse Registry section (optional)
+        let registry = doc.section("Registry").map(parse_registry).transpose()?;
 
         Ok(WgConfig {
             interface,
-            peer,
+            peers,
             challenge,
             bgp,
+            meta,
+            registry,
         })
     }
 
-    /// Write config to file
+    /// The peer of a point-to-point interface (the first one if there are several)
+    pub fn peer(&self) -> Option<&PeerConfig> {
+        self.peers.first()
+    }
+
+    /// Mutable [`peer`](Self::peer)
+    pub fn peer_mut(&mut self) -> Option<&mut PeerConfig> {
+        self.peers.first_mut()
+    }
+
+    /// The config without `DNS`, as deployed unless `allow_dns_directive` is set
+    pub fn without_dns(&self) -> WgConfig {
+        let mut config = self.clone();
+        config.interface.dns.clear();
+        config
+    }
+
+    /// Write config to file, readable by the owner only (it holds the private key)
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
         let content = self.as_string()?;
-        fs::write(path, content).map_err(|e| format!("Failed to write file: {}", e))
+        crate::util::private_file::write(path, content)
+            .map_err(|e| format!("Failed to write file: {}", e))
     }
 
     /// Convert config to string representation using Tera template
     pub fn as_string(&self) -> Result<String, String> {
-        // Create context
+        crate::templates::render("wg.conf", &self.template_context())
+    }
+
+    /// Template variables for `wg.conf`
+    pub(crate) fn template_context(&self) -> Context {
         let mut context = Context::new();
         context.insert("interface_address", &self.interface.address);
-        context.insert("interface_private_key", &self.interface.private_key);
+        context.insert("interface_private_key", self.interface.private_key.expose());
         context.insert("interface_listen_port", &self.interface.listen_port);
         context.insert("interface_table", &self.interface.table);
-        context.insert("peer", &self.peer);
+        context.insert("interface_dns", &self.interface.dns);
+        context.insert("peers", &self.peers);
+        // Custom templates written for single-peer configs still find `peer`
+        context.insert("peer", &self.peer());
         context.insert("challenge", &self.challenge);
         context.insert("bgp", &self.bgp);
-
-        // Render template
-        let template = include_str!("wg.conf.tera");
-        crate::templates::render_template("wg.conf", template, &context)
+        context.insert("meta", &self.meta);
+        context.insert("registry", &self.registry);
+        context
     }
 }
 
@@ -113,170 +197,116 @@ impl fmt::Display for WgConfig {
     }
 }
 
-/// Parse INI-style sections from config content
-fn parse_ini_sections(
-    content: &str,
-) -> Result<HashMap<String, HashMap<String, Vec<String>>>, String> {
-    let mut sections: HashMap<String, HashMap<String, Vec<String>>> = HashMap::new();
-    let mut current_section: Option<String> = None;
-
-    for line in content.lines() {
-        let line = line.trim();
-
-        // Skip empty lines and comments
-        if line.is_empty() || line.starts_with('#') {
-            continue;
-        }
-
-        // Section header
-        if line.starts_with('[') && line.ends_with(']') {
-            current_section = Some(line[1..line.len() - 1].to_string());
-            sections.insert(current_section.clone().unwrap(), HashMap::new());
-            continue;
-        }
-
-        // Key-value pair
-        if let Some(section_name) = &current_section {
-            if let Some((key, value)) = line.split_once('=') {
-                let key = key.trim().to_string();
-                let value = value.trim().to_string();
-
-                sections
-                    .get_mut(section_name)
-                    .unwrap()
-                    .entry(key)
-                    .or_default()
-                    .push(value);
-            }
-        }
-    }
-
-    Ok(sections)
-}
-
-fn parse_interface(
-    sections: &HashMap<String, HashMap<String, Vec<String>>>,
-) -> Result<InterfaceConfig, String> {
-    let section = sections
-        .get("Interface")
-        .ok_or("Missing [Interface] section")?;
-
-    let address = section
-        .get("Address")
-        .ok_or("Missing Address in [Interface]")?
-        .clone();
-
-    let private_key = section
-        .get("PrivateKey")
-        .and_then(|v| v.first())
-        .ok_or("Missing PrivateKey in [Interface]")?
-        .clone();
+fn parse_interface(doc: &IniDocument) -> Result<InterfaceConfig, String> {
+    let section = doc.require_section("Interface")?;
 
+    let address = section.require_all("Address")?;
+    let private_key = section.require("PrivateKey")?.parse::<PrivateKey>()?;
     let listen_port = section
-        .get("ListenPort")
-        .and_then(|v| v.first())
-        .ok_or("Missing ListenPort in [Interface]")?
-        .parse::<u16>()
-        .map_err(|e| format!("Invalid ListenPort: {}", e))?;
-
-    let table = section.get("Table").and_then(|v| v.first()).cloned();
+        .parse::<u16>("ListenPort")?
+        .ok_or("Missing ListenPort in [Interface]")?;
+    let table = section.get("Table").map(str::to_string);
+    let dns = section
+        .get_all("DNS")
+        .iter()
+        .flat_map(|servers| servers.split(','))
+        .map(str::trim)
+        .filter(|server| !server.is_empty())
+        .map(str::to_string)
+        .collect();
 
     Ok(InterfaceConfig {
         address,
         private_key,
         listen_port,
         table,
+        dns,
     })
 }
 
-fn parse_peer(
-    sections: &HashMap<String, HashMap<String, Vec<String>>>,
-) -> Result<PeerConfig, String> {
-    let section = sections.get("Peer").ok_or("Missing [Peer] section")?;
-
-    let public_key = section
-        .get("PublicKey")
-        .and_then(|v| v.first())
-        .ok_or("Missing PublicKey in [Peer]")?
-        .clone();
-
-    let endpoint = section.get("Endpoint").and_then(|v| v.first()).cloned();
-
-    let allowed_ips = section
-        .get("AllowedIPs")
-        .ok_or("Missing AllowedIPs in [Peer]")?
-        .clone();
-
-    let persistent_keepalive = section
-        .get("PersistentKeepalive")
-        .and_then(|v| v.first())
-        .and_then(|s| s.parse::<u16>().ok());
+fn parse_peer(section: &IniSection) -> Result<PeerConfig, String> {
+    let public_key = section.require("PublicKey")?.parse::<PublicKey>()?;
+    let endpoint = section.get("Endpoint").map(str::to_string);
+    let allowed_ips = section.require_all("AllowedIPs")?;
+    let persistent_keepalive = section.parse::<u16>("PersistentKeepalive").ok().flatten();
+    let preshared_key = section.parse::<PresharedKey>("PresharedKey")?;
 
     Ok(PeerConfig {
         public_key,
         endpoint,
         allowed_ips,
         persistent_keepalive,
+        preshared_key,
     })
 }
 
-fn parse_challenge(
-    sections: &HashMap<String, HashMap<String, Vec<String>>>,
-) -> Result<ChallengeConfig, String> {
-    let section = sections
-        .get("Challenge")
-        .ok_or("Missing [Challenge] section")?;
-
-    let code = section
-        .get("Code")
-        .and_then(|v| v.first())
-        .ok_or("Missing Code in [Challenge]")?
-        .clone();
-
+fn parse_challenge(section: &IniSection) -> Result<ChallengeConfig, String> {
+    let code = section.require("Code")?.to_string();
     let asn = section
-        .get("ASN")
-        .and_then(|v| v.first())
-        .ok_or("Missing ASN in [Challenge]")?
-        .parse::<u32>()
-        .map_err(|e| format!("Invalid ASN: {}", e))?;
+        .parse::<u32>("ASN")?
+        .ok_or("Missing ASN in [Challenge]")?;
 
     Ok(ChallengeConfig { code, asn })
 }
 
-fn parse_bgp(
-    sections: &HashMap<String, HashMap<String, Vec<String>>>,
-) -> Result<BgpConfig, String> {
-    let section = sections.get("BGP").ok_or("Missing [BGP] section")?;
-
-    let mpbgp = section
-        .get("MPBGP")
-        .and_then(|v| v.first())
-        .map(|s| s.to_lowercase() == "on" || s.to_lowercase() == "true")
-        .unwrap_or(false);
-
-    let extended_next_hop = section
-        .get("ExtendedNextHop")
-        .and_then(|v| v.first())
-        .map(|s| s.to_lowercase() == "true" || s.to_lowercase() == "on")
-        .unwrap_or(false);
-
-    let local = section
-        .get("Local")
-        .and_then(|v| v.first())
-        .ok_or("Missing Local in [BGP]")?
-        .clone();
-
-    let neighbor = section
-        .get("Neighbor")
-        .and_then(|v| v.first())
-        .ok_or("Missing Neighbor in [BGP]")?
-        .clone();
+fn parse_bgp(section: &IniSection) -> Result<BgpConfig, String> {
+    // Configs written before BGP could be toggled have no Enabled key
+    let enabled = section.flag("Enabled").unwrap_or(true);
+    let mpbgp = section.flag("MPBGP").unwrap_or(false);
+    let extended_next_hop = section.flag("ExtendedNextHop").unwrap_or(false);
+
+    let local = section.require("Local")?.to_string();
+    let neighbor = section.require("Neighbor")?.to_string();
+    let max_prefixes = section.parse::<u32>("MaxPrefixes")?;
+
+    // Configs written before address families were selectable are dual-stack
+    let address_family = section
+        .get("AddressFamily")
+        .map(str::parse::<AddressFamily>)
+        .transpose()?
+        .unwrap_or_default();
+
+    let router_id = section.parse::<Ipv4Addr>("RouterId")?;
+    let peer_name = section.get("PeerName").map(str::to_string);
+    let graceful_restart = section.flag("GracefulRestart");
+
+    // Configs written before the transport was selectable use link-local
+    let transport = section
+        .parse::<BgpTransport>("Transport")?
+        .unwrap_or_default();
+    let local_as = section.parse::<u32>("LocalAS")?;
 
     Ok(BgpConfig {
+        enabled,
         mpbgp,
         extended_next_hop,
         local,
         neighbor,
+        max_prefixes,
+        address_family,
+        router_id,
+        peer_name,
+        graceful_restart,
+        transport,
+        local_as,
+    })
+}
+
+fn parse_meta(section: &IniSection) -> Result<MetaConfig, String> {
+    let contact = section.get("Contact").map(str::to_string);
+
+    Ok(MetaConfig { contact })
+}
+
+fn parse_registry(section: &IniSection) -> Result<RegistryAuthConfig, String> {
+    let fingerprint = section.require("Fingerprint")?.to_string();
+    let authorized_at = section
+        .parse::<i64>("AuthorizedAt")?
+        .ok_or("Missing AuthorizedAt in [Registry]")?;
+
+    Ok(RegistryAuthConfig {
+        fingerprint,
+        authorized_at,
     })
 }
 
@@ -284,6 +314,9 @@ fn parse_bgp(
 mod tests {
     use super::*;
 
+    const TEST_PRIVATE_KEY: &str = "MA3Oj1xzJzoGfIkMJagCXOHmGIkLkK49XUFfqS1Xjmo=";
+    const TEST_PUBLIC_KEY: &str = "uS1AYe7zTGAP48XeNn0vppNjg7q0hawyh8Y0bvvAWhk=";
+
     #[test]
     fn test_parse_basic_config() {
         let config_str = r#"
@@ -304,9 +337,9 @@ PersistentKeepalive = 25
         let config = WgConfig::from_string(config_str).unwrap();
         assert_eq!(config.interface.listen_port, 51827);
         assert_eq!(config.interface.address, vec!["fe80::2225:1/64"]);
-        assert!(config.peer.is_some());
+        assert_eq!(config.peers.len(), 1);
         assert_eq!(
-            config.peer.as_ref().unwrap().endpoint,
+            config.peer().unwrap().endpoint,
             Some("dn42-de.maraun.de:20257".to_string())
         );
     }
@@ -316,7 +349,7 @@ PersistentKeepalive = 25
         let config_str = r#"
 [Interface]
 Address = fe80::1/64
-PrivateKey = test123
+PrivateKey = MA3Oj1xzJzoGfIkMJagCXOHmGIkLkK49XUFfqS1Xjmo=
 ListenPort = 31234
 
 [Challenge]
@@ -335,6 +368,46 @@ Neighbor = fe80::2
         assert_eq!(config.challenge.as_ref().unwrap().asn, 4242421234);
         assert!(config.bgp.is_some());
         assert!(config.bgp.as_ref().unwrap().mpbgp);
+        // Missing Enabled defaults to true
+        assert!(config.bgp.as_ref().unwrap().enabled);
+    }
+
+    #[test]
+    fn test_bgp_enabled_roundtrip() {
+        let original = WgConfig {
+            interface: InterfaceConfig {
+                address: vec!["fe80::1/64".to_string()],
+                private_key: PrivateKey::new(TEST_PRIVATE_KEY).unwrap(),
+                listen_port: 31234,
+                table: Some("off".to_string()),
+                dns: Vec::new(),
+            },
+            peers: Vec::new(),
+            challenge: None,
+            meta: None,
+            registry: None,
+            bgp: Some(BgpConfig {
+                enabled: false,
+                mpbgp: true,
+                extended_next_hop: true,
+                local: "fe80::1".to_string(),
+                neighbor: "fe80::2".to_string(),
+                max_prefixes: Some(1000),
+                address_family: AddressFamily::Ipv6,
+                router_id: None,
+                peer_name: None,
+                graceful_restart: None,
+                transport: BgpTransport::LinkLocal,
+                local_as: None,
+            }),
+        };
+
+        let serialized = original.as_string().unwrap();
+        assert!(serialized.contains("Enabled = false"));
+        assert!(serialized.contains("AddressFamily = ipv6"));
+
+        let parsed = WgConfig::from_string(&serialized).unwrap();
+        assert_eq!(original, parsed);
     }
 
     #[test]
@@ -342,21 +415,231 @@ Neighbor = fe80::2
         let original = WgConfig {
             interface: InterfaceConfig {
                 address: vec!["fe80::1/64".to_string()],
-                private_key: "testkey123".to_string(),
+                private_key: PrivateKey::new(TEST_PRIVATE_KEY).unwrap(),
                 listen_port: 31234,
                 table: Some("off".to_string()),
+                dns: Vec::new(),
             },
-            peer: None,
+            peers: Vec::new(),
             challenge: Some(ChallengeConfig {
                 code: "AUTOPEER-TEST".to_string(),
                 asn: 4242421234,
             }),
             bgp: None,
+            meta: Some(MetaConfig {
+                contact: Some("noc@example.com".to_string()),
+            }),
+            registry: Some(RegistryAuthConfig {
+                fingerprint: "8B7F0384CBE0272761D852EA0684E36E6CF9D4D4".to_string(),
+                authorized_at: 1700000000,
+            }),
+        };
+
+        let serialized = original.as_string().unwrap();
+        let parsed = WgConfig::from_string(&serialized).unwrap();
+
+        assert_eq!(original, parsed);
+    }
+
+    #[test]
+    fn test_challenge_roundtrip_with_all_sections() {
+        let original = WgConfig {
+            interface: InterfaceConfig {
+                address: vec!["fe80::1/64".to_string()],
+                private_key: PrivateKey::new(TEST_PRIVATE_KEY).unwrap(),
+                listen_port: 31234,
+                table: Some("off".to_string()),
+                dns: Vec::new(),
+            },
+            peers: vec![PeerConfig {
+                public_key: PublicKey::new(TEST_PUBLIC_KEY).unwrap(),
+                endpoint: Some("192.0.2.1:51820".to_string()),
+                allowed_ips: vec!["::/0".to_string()],
+                persistent_keepalive: Some(25),
+                preshared_key: None,
+            }],
+            challenge: Some(ChallengeConfig {
+                code: "AUTOPEER-4242421234-abc".to_string(),
+                asn: 4242421234,
+            }),
+            bgp: Some(BgpConfig {
+                enabled: true,
+                mpbgp: true,
+                extended_next_hop: true,
+                local: "fe80::1".to_string(),
+                neighbor: "fe80::2".to_string(),
+                max_prefixes: Some(1000),
+                address_family: AddressFamily::Dual,
+                router_id: Some(Ipv4Addr::new(172, 20, 53, 97)),
+                peer_name: Some("node-1.fra".to_string()),
+                graceful_restart: Some(true),
+                transport: BgpTransport::TransferNet,
+                local_as: Some(4242420263),
+            }),
+            meta: Some(MetaConfig {
+                contact: Some("@peer:matrix.org".to_string()),
+            }),
+            registry: Some(RegistryAuthConfig {
+                fingerprint: "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3".to_string(),
+                authorized_at: 1700000000,
+            }),
         };
 
         let serialized = original.as_string().unwrap();
+        assert!(serialized.contains("RouterId = 172.20.53.97"));
+        assert!(serialized.contains("PeerName = node-1.fra"));
+        assert!(serialized.contains("GracefulRestart = on"));
+        assert!(serialized.contains("Transport = transfer_net"));
+        assert!(serialized.contains("LocalAS = 4242420263"));
         let parsed = WgConfig::from_string(&serialized).unwrap();
 
         assert_eq!(original, parsed);
     }
+
+    #[test]
+    fn test_multi_peer_roundtrip() {
+        let original = WgConfig {
+            interface: InterfaceConfig {
+                address: vec!["fe80::1/64".to_string()],
+                private_key: PrivateKey::new(TEST_PRIVATE_KEY).unwrap(),
+                listen_port: 31234,
+                table: Some("off".to_string()),
+                dns: Vec::new(),
+            },
+            peers: vec![
+                PeerConfig {
+                    public_key: PublicKey::new(TEST_PUBLIC_KEY).unwrap(),
+                    endpoint: Some("192.0.2.1:51820".to_string()),
+                    allowed_ips: vec!["fd00::/8".to_string()],
+                    persistent_keepalive: Some(25),
+                    preshared_key: None,
+                },
+                PeerConfig {
+                    public_key: PublicKey::new("TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=")
+                        .unwrap(),
+                    endpoint: None,
+                    allowed_ips: vec!["172.20.0.0/14".to_string(), "10.0.0.0/8".to_string()],
+                    persistent_keepalive: None,
+                    preshared_key: None,
+                },
+            ],
+            challenge: None,
+            bgp: None,
+            meta: None,
+            registry: None,
+        };
+
+        let serialized = original.as_string().unwrap();
+        assert_eq!(serialized.matches("[Peer]").count(), 2);
+        // Peers are separated by a blank line
+        assert!(serialized.contains("PersistentKeepalive = 25\n\n[Peer]\n"));
+
+        let parsed = WgConfig::from_string(&serialized).unwrap();
+        assert_eq!(original, parsed);
+        assert_eq!(
+            parsed.peer().unwrap().endpoint.as_deref(),
+            Some("192.0.2.1:51820")
+        );
+
+        // A malformed second peer fails parsing instead of being left out of the rewrite
+        let broken = serialized.replace("AllowedIPs = 172.20.0.0/14\n", "");
+        assert_ne!(broken, serialized);
+        let broken = broken.replace("AllowedIPs = 10.0.0.0/8\n", "");
+        assert!(WgConfig::from_string(&broken)
+            .unwrap_err()
+            .contains("Missing AllowedIPs"));
+
+        // Likewise for the optional sections, while leaving one out is fine
+        let bgp = "[BGP]\nLocal = fe80::1\n";
+        assert!(WgConfig::from_string(&format!("{}\n{}", serialized, bgp)).is_err());
+        assert!(WgConfig::from_string(&serialized).unwrap().bgp.is_none());
+    }
+
+    #[test]
+    fn test_preshared_key_roundtrip() {
+        const PRESHARED_KEY: &str = "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=";
+        let mut config = WgConfig::from_string(&format!(
+            "[Interface]\nAddress = fe80::1/64\nPrivateKey = {}\nListenPort = 31234\n\n\
+             [Peer]\nPublicKey = {}\nPresharedKey = {}\nAllowedIPs = fd00::/8\n",
+            TEST_PRIVATE_KEY, TEST_PUBLIC_KEY, PRESHARED_KEY
+        ))
+        .unwrap();
+        let preshared_key = config.peer().unwrap().preshared_key.clone().unwrap();
+        assert_eq!(preshared_key.expose(), PRESHARED_KEY);
+
+        // Rendered right after the public key, and read back unchanged
+        let serialized = config.as_string().unwrap();
+        assert!(serialized.contains(&format!(
+            "PublicKey = {}\nPresharedKey = {}\n",
+            TEST_PUBLIC_KEY, PRESHARED_KEY
+        )));
+        assert_eq!(WgConfig::from_string(&serialized).unwrap(), config);
+
+        config.peer_mut().unwrap().preshared_key = None;
+        assert!(!config.as_string().unwrap().contains("PresharedKey"));
+
+        // A bad key makes the peer malformed, and so the config
+        let broken = serialized.replace(PRESHARED_KEY, "tooshort");
+        let section = IniDocument::parse(&broken)
+            .require_section("Peer")
+            .unwrap()
+            .clone();
+        assert!(parse_peer(&section)
+            .unwrap_err()
+            .contains("Invalid PresharedKey"));
+        assert!(WgConfig::from_string(&broken)
+            .unwrap_err()
+            .contains("Invalid PresharedKey"));
+    }
+
+    #[test]
+    fn test_dynamic_endpoint_renders_without_endpoint() {
+        let config = WgConfig {
+            interface: InterfaceConfig {
+                address: vec!["fe80::1/64".to_string()],
+                private_key: PrivateKey::new(TEST_PRIVATE_KEY).unwrap(),
+                listen_port: 31234,
+                table: Some("off".to_string()),
+                dns: Vec::new(),
+            },
+            peers: vec![PeerConfig {
+                public_key: PublicKey::new(TEST_PUBLIC_KEY).unwrap(),
+                endpoint: None,
+                allowed_ips: vec!["fd00::/8".to_string()],
+                persistent_keepalive: Some(25),
+                preshared_key: None,
+            }],
+            challenge: None,
+            bgp: None,
+            meta: None,
+            registry: None,
+        };
+
+        let serialized = config.as_string().unwrap();
+        assert!(!serialized.contains("Endpoint"), "{}", serialized);
+        assert!(serialized.contains("PersistentKeepalive = 25\n"));
+        assert_eq!(WgConfig::from_string(&serialized).unwrap(), config);
+    }
+
+    #[test]
+    fn test_dns_roundtrip_and_strip() {
+        let config = WgConfig::from_string(&format!(
+            "[Interface]\nAddress = fe80::1/64\nPrivateKey = {}\nListenPort = 31234\nDNS = 172.20.0.53, fd42:d42:d42:54::1\n\n\
+             [Peer]\nPublicKey = {}\nAllowedIPs = fd00::/8\n",
+            TEST_PRIVATE_KEY, TEST_PUBLIC_KEY
+        ))
+        .unwrap();
+        assert_eq!(
+            config.interface.dns,
+            vec!["172.20.0.53", "fd42:d42:d42:54::1"]
+        );
+
+        let serialized = config.as_string().unwrap();
+        assert!(serialized.contains("DNS = 172.20.0.53\nDNS = fd42:d42:d42:54::1\n\n[Peer]\n"));
+        assert_eq!(WgConfig::from_string(&serialized).unwrap(), config);
+
+        let stripped = config.without_dns().as_string().unwrap();
+        assert!(!stripped.contains("DNS"), "{}", stripped);
+        assert!(stripped.contains("ListenPort = 31234\n\n[Peer]\n"));
+    }
 }
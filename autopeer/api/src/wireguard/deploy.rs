@@ -1,63 +1,40 @@
+use super::keys::{PrivateKey, PublicKey};
+use crate::config::SystemConfig;
+use crate::util::exec;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Delay between interface checks after `wg-quick up`
+const UP_POLL_INTERVAL: Duration = Duration::from_millis(200);
 
 /// WireGuard keypair (private and public key)
 #[derive(Debug, Clone)]
 pub struct WgKeypair {
-    pub private_key: String,
-    pub public_key: String,
+    pub private_key: PrivateKey,
+    pub public_key: PublicKey,
 }
 
 impl WgKeypair {
     /// Generate a new WireGuard keypair using wg command
-    pub fn generate() -> Result<Self, String> {
+    pub async fn generate(system: &SystemConfig) -> Result<Self, String> {
         // Generate private key
-        let private_output = Command::new("wg")
-            .arg("genkey")
-            .output()
-            .map_err(|e| format!("Failed to run wg genkey: {}", e))?;
+        let private_output =
+            exec::run(&system.wg_bin, &["genkey"], system.command_timeout()).await?;
 
-        if !private_output.status.success() {
-            return Err(format!(
-                "wg genkey failed: {}",
-                String::from_utf8_lossy(&private_output.stderr)
-            ));
+        if !private_output.success() {
+            return Err(format!("wg genkey failed: {}", private_output.stderr));
         }
 
-        let private_key = String::from_utf8_lossy(&private_output.stdout)
-            .trim()
-            .to_string();
+        let private_key: PrivateKey = private_output
+            .stdout
+            .parse()
+            .map_err(|e| format!("wg genkey returned an invalid key: {}", e))?;
 
         // Generate public key from private key
-        let mut child = Command::new("wg")
-            .arg("pubkey")
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .spawn()
-            .map_err(|e| format!("Failed to spawn wg pubkey: {}", e))?;
-
-        // Write private key to stdin
-        use std::io::Write;
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin
-                .write_all(private_key.as_bytes())
-                .map_err(|e| format!("Failed to write to wg pubkey stdin: {}", e))?;
-        }
-
-        let public_output = child
-            .wait_with_output()
-            .map_err(|e| format!("Failed to wait for wg pubkey: {}", e))?;
-
-        if !public_output.status.success() {
-            return Err(format!(
-                "wg pubkey failed: {}",
-                String::from_utf8_lossy(&public_output.stderr)
-            ));
-        }
-
-        let public_key = String::from_utf8_lossy(&public_output.stdout)
-            .trim()
-            .to_string();
+        let public_key = Self::derive_public_key(system, &private_key).await?;
 
         Ok(WgKeypair {
             private_key,
@@ -66,103 +43,414 @@ impl WgKeypair {
     }
 
     /// Derive public key from a private key using wg command
-    pub fn derive_public_key(private_key: &str) -> Result<String, String> {
-        let mut child = Command::new("wg")
-            .arg("pubkey")
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .spawn()
-            .map_err(|e| format!("Failed to spawn wg pubkey: {}", e))?;
-
-        // Write private key to stdin
-        use std::io::Write;
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin
-                .write_all(private_key.as_bytes())
-                .map_err(|e| format!("Failed to write to wg pubkey stdin: {}", e))?;
+    pub async fn derive_public_key(
+        system: &SystemConfig,
+        private_key: &PrivateKey,
+    ) -> Result<PublicKey, String> {
+        // Private key goes in on stdin, never on the command line
+        let public_output = exec::run_with_stdin(
+            &system.wg_bin,
+            &["pubkey"],
+            Some(private_key.expose()),
+            system.command_timeout(),
+        )
+        .await?;
+
+        if !public_output.success() {
+            return Err(format!("wg pubkey failed: {}", public_output.stderr));
         }
 
-        let public_output = child
-            .wait_with_output()
-            .map_err(|e| format!("Failed to wait for wg pubkey: {}", e))?;
+        public_output
+            .stdout
+            .parse()
+            .map_err(|e| format!("wg pubkey returned an invalid key: {}", e))
+    }
+}
 
-        if !public_output.status.success() {
-            return Err(format!(
-                "wg pubkey failed: {}",
-                String::from_utf8_lossy(&public_output.stderr)
-            ));
+/// Path of the wg-quick config file for an interface
+pub fn config_path(wireguard_dir: &Path, interface_name: &str) -> PathBuf {
+    wireguard_dir.join(format!("{}.conf", interface_name))
+}
+
+/// Path of the private key file kept next to an interface's config
+pub fn key_path(wireguard_dir: &Path, interface_name: &str) -> PathBuf {
+    wireguard_dir.join(format!("{}.key", interface_name))
+}
+
+/// What is written to the wg-quick directory for an interface
+struct ConfigFiles {
+    config_path: PathBuf,
+    key_path: PathBuf,
+    config: String,
+    /// Private key for the `.key` file, when it is kept out of the config
+    key: Option<String>,
+}
+
+impl ConfigFiles {
+    /// With `external_private_key`, the config's `PrivateKey` line is swapped for a `PostUp`
+    /// that loads the key from the `.key` file
+    fn new(
+        system: &SystemConfig,
+        config_content: &str,
+        interface_name: &str,
+        external_private_key: bool,
+    ) -> Self {
+        let config_path = config_path(&system.wireguard_dir, interface_name);
+        let key_path = key_path(&system.wireguard_dir, interface_name);
+        if !external_private_key {
+            return ConfigFiles {
+                config_path,
+                key_path,
+                config: config_content.to_string(),
+                key: None,
+            };
         }
 
-        let public_key = String::from_utf8_lossy(&public_output.stdout)
-            .trim()
-            .to_string();
+        let mut config = String::new();
+        let mut key = None;
+        for line in config_content.lines() {
+            match line.split_once('=') {
+                Some((name, value)) if name.trim() == "PrivateKey" && key.is_none() => {
+                    key = Some(value.trim().to_string());
+                    let _ = writeln!(
+                        config,
+                        "PostUp = wg set %i private-key {}",
+                        key_path.display()
+                    );
+                }
+                _ => {
+                    let _ = writeln!(config, "{}", line);
+                }
+            }
+        }
+        ConfigFiles {
+            config_path,
+            key_path,
+            config,
+            key,
+        }
+    }
 
-        Ok(public_key)
+    /// Whether the config on disk is exactly this one, key file included
+    async fn on_disk(&self) -> bool {
+        let config_matches = tokio::fs::read_to_string(&self.config_path)
+            .await
+            .is_ok_and(|existing| existing == self.config);
+        let key_matches = match &self.key {
+            Some(key) => tokio::fs::read_to_string(&self.key_path)
+                .await
+                .is_ok_and(|existing| existing.trim() == key),
+            None => true,
+        };
+        config_matches && key_matches
+    }
+
+    async fn write(&self) -> Result<(), String> {
+        match &self.key {
+            Some(key) => write_key(&self.key_path, key).await?,
+            // Left over from a deploy with external keys
+            None if self.key_path.exists() => remove_key(&self.key_path).await?,
+            None => {}
+        }
+        write_config(&self.config_path, &self.config).await
     }
 }
 
 /// Deploy a WireGuard configuration
-pub fn deploy_config(config_content: &str, interface_name: &str) -> Result<(), String> {
-    let config_path = PathBuf::from("/etc/wireguard").join(format!("{}.conf", interface_name));
+///
+/// Safe to repeat: a config left behind (e.g. after a reboot) is brought up as-is when it
+/// matches, and torn down and replaced when it doesn't. With `external_private_key` the
+/// private key goes to a `0600` file next to the config instead of into it.
+pub async fn deploy_config(
+    system: &SystemConfig,
+    config_content: &str,
+    interface_name: &str,
+    external_private_key: bool,
+) -> Result<(), String> {
+    let files = ConfigFiles::new(system, config_content, interface_name, external_private_key);
+
+    if files.on_disk().await {
+        if is_interface_active(system, interface_name).await {
+            return Ok(());
+        }
+    } else if files.config_path.exists() {
+        // A stale config from an earlier deploy, which wg-quick would refuse to bring up over
+        bring_down(system, interface_name).await?;
+        files.write().await?;
+    } else {
+        files.write().await?;
+    }
 
-    // Write config to /etc/wireguard/
-    std::fs::write(&config_path, config_content)
-        .map_err(|e| format!("Failed to write config to {:?}: {}", config_path, e))?;
+    quick_up(system, interface_name).await
+}
 
-    // Bring up the interface using wg-quick
-    let output = Command::new("wg-quick")
-        .arg("up")
-        .arg(interface_name)
-        .output()
-        .map_err(|e| format!("Failed to run wg-quick up: {}", e))?;
+/// Write a configuration and restart its interface with it
+///
+/// For an interface that is still up after its config file went missing: wg-quick needs
+/// the file to bring the interface down.
+pub async fn restart_config(
+    system: &SystemConfig,
+    config_content: &str,
+    interface_name: &str,
+    external_private_key: bool,
+) -> Result<(), String> {
+    ConfigFiles::new(system, config_content, interface_name, external_private_key)
+        .write()
+        .await?;
+    bring_down(system, interface_name).await?;
+    quick_up(system, interface_name).await
+}
 
-    if !output.status.success() {
-        return Err(format!(
-            "wg-quick up failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        ));
+/// Bring up an interface with wg-quick and wait for it to appear
+async fn quick_up(system: &SystemConfig, interface_name: &str) -> Result<(), String> {
+    let output = exec::run(
+        &system.wg_quick_bin,
+        &["up", interface_name],
+        system.command_timeout(),
+    )
+    .await?;
+
+    if !output.success() {
+        return Err(format!("wg-quick up failed: {}", output.stderr));
     }
 
+    // wg-quick can exit 0 without the interface ever coming up
+    if system.wg_up_timeout_secs > 0 {
+        wait_for_interface(
+            system,
+            interface_name,
+            Duration::from_secs(system.wg_up_timeout_secs),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Whether exactly `config_content` is deployed and its interface is up
+pub async fn is_deployed(
+    system: &SystemConfig,
+    config_content: &str,
+    interface_name: &str,
+    external_private_key: bool,
+) -> bool {
+    ConfigFiles::new(system, config_content, interface_name, external_private_key)
+        .on_disk()
+        .await
+        && is_interface_active(system, interface_name).await
+}
+
+/// Write config to the wg-quick directory
+async fn write_config(config_path: &Path, config_content: &str) -> Result<(), String> {
+    tokio::fs::write(config_path, config_content)
+        .await
+        .map_err(|e| format!("Failed to write config to {:?}: {}", config_path, e))
+}
+
+/// Write a private key file readable by root only
+async fn write_key(key_path: &Path, key: &str) -> Result<(), String> {
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true).mode(0o600);
+    let mut file = options
+        .open(key_path)
+        .await
+        .map_err(|e| format!("Failed to write private key to {:?}: {}", key_path, e))?;
+    // `mode` only applies to new files
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))
+        .await
+        .map_err(|e| format!("Failed to restrict {:?}: {}", key_path, e))?;
+    tokio::io::AsyncWriteExt::write_all(&mut file, format!("{}\n", key).as_bytes())
+        .await
+        .map_err(|e| format!("Failed to write private key to {:?}: {}", key_path, e))
+}
+
+async fn remove_key(key_path: &Path) -> Result<(), String> {
+    tokio::fs::remove_file(key_path)
+        .await
+        .map_err(|e| format!("Failed to remove private key {:?}: {}", key_path, e))
+}
+
+/// Bring down an interface with wg-quick, which is fine if it isn't running
+async fn bring_down(system: &SystemConfig, interface_name: &str) -> Result<(), String> {
+    let output = exec::run(
+        &system.wg_quick_bin,
+        &["down", interface_name],
+        system.command_timeout(),
+    )
+    .await?;
+
+    if !output.success() && !output.stderr.contains("is not a WireGuard interface") {
+        return Err(format!("wg-quick down failed: {}", output.stderr));
+    }
     Ok(())
 }
 
+/// Poll until the interface is active or the timeout passes
+async fn wait_for_interface(
+    system: &SystemConfig,
+    interface_name: &str,
+    timeout: Duration,
+) -> Result<(), String> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if is_interface_active(system, interface_name).await {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(format!(
+                "Interface {} did not come up within {}s after wg-quick up",
+                interface_name,
+                timeout.as_secs()
+            ));
+        }
+        tokio::time::sleep(UP_POLL_INTERVAL).await;
+    }
+}
+
 /// Check if a WireGuard interface is currently active
-pub fn is_interface_active(interface_name: &str) -> bool {
+pub async fn is_interface_active(system: &SystemConfig, interface_name: &str) -> bool {
     // Check if the interface exists using `wg show <interface>`
-    let output = Command::new("wg")
-        .arg("show")
-        .arg(interface_name)
-        .output();
+    exec::run(
+        &system.wg_bin,
+        &["show", interface_name],
+        system.command_timeout(),
+    )
+    .await
+    .is_ok_and(|output| output.success())
+}
+
+/// Handshake and transfer counters of an interface's peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerTransfer {
+    /// Unix time of the latest handshake, `None` if there hasn't been one
+    pub latest_handshake: Option<u64>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
 
-    match output {
-        Ok(result) => result.status.success(),
-        Err(_) => false,
+/// Read the peer counters of an interface using `wg show <interface> dump`
+pub async fn peer_transfer(
+    system: &SystemConfig,
+    interface_name: &str,
+) -> Result<PeerTransfer, String> {
+    let output = exec::run(
+        &system.wg_bin,
+        &["show", interface_name, "dump"],
+        system.command_timeout(),
+    )
+    .await?;
+
+    if !output.success() {
+        return Err(format!("wg show dump failed: {}", output.stderr));
     }
+
+    parse_dump(&output.stdout)
 }
 
-/// Remove a WireGuard configuration
-pub fn remove_config(interface_name: &str) -> Result<(), String> {
-    // Bring down the interface
-    let output = Command::new("wg-quick")
-        .arg("down")
-        .arg(interface_name)
-        .output()
-        .map_err(|e| format!("Failed to run wg-quick down: {}", e))?;
-
-    if !output.status.success() {
-        // If it's not running, that's okay
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        if !stderr.contains("is not a WireGuard interface") {
-            return Err(format!("wg-quick down failed: {}", stderr));
+/// Parse `wg show <interface> dump`: an interface line, then one tab-separated line per peer
+/// (public key, preshared key, endpoint, allowed ips, latest handshake, rx, tx, keepalive)
+fn parse_dump(dump: &str) -> Result<PeerTransfer, String> {
+    let peer = dump
+        .lines()
+        .nth(1)
+        .ok_or_else(|| "wg show dump lists no peer".to_string())?;
+    parse_peer_fields(&peer.split('\t').collect::<Vec<_>>())
+}
+
+/// Counters from the fields of a dump peer line, starting at the public key
+fn parse_peer_fields(fields: &[&str]) -> Result<PeerTransfer, String> {
+    if fields.len() < 7 {
+        return Err(format!(
+            "Unexpected wg show dump line: {}",
+            fields.join("\t")
+        ));
+    }
+
+    let number = |index: usize| {
+        fields[index]
+            .parse::<u64>()
+            .map_err(|e| format!("Invalid number {:?} in wg show dump: {}", fields[index], e))
+    };
+    let latest_handshake = number(4)?;
+    Ok(PeerTransfer {
+        latest_handshake: (latest_handshake > 0).then_some(latest_handshake),
+        rx_bytes: number(5)?,
+        tx_bytes: number(6)?,
+    })
+}
+
+/// An interface as listed by `wg show all dump`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceDump {
+    pub public_key: String,
+    /// Counters of the interface's (first) peer, `None` if it has none
+    pub peer: Option<PeerTransfer>,
+}
+
+/// Every active interface with its peer counters, from a single `wg show all dump`
+pub async fn dump_all(system: &SystemConfig) -> Result<HashMap<String, InterfaceDump>, String> {
+    let output = exec::run(
+        &system.wg_bin,
+        &["show", "all", "dump"],
+        system.command_timeout(),
+    )
+    .await?;
+
+    if !output.success() {
+        return Err(format!("wg show all dump failed: {}", output.stderr));
+    }
+
+    parse_dump_all(&output.stdout)
+}
+
+/// Parse `wg show all dump`: like the per-interface dump, with the interface name prepended to
+/// every line. Interface lines have 5 fields (name, private key, public key, port, fwmark).
+fn parse_dump_all(dump: &str) -> Result<HashMap<String, InterfaceDump>, String> {
+    let mut interfaces = HashMap::new();
+    for line in dump.lines().filter(|line| !line.trim().is_empty()) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields.as_slice() {
+            [name, _private_key, public_key, _port, _fwmark] => {
+                interfaces.insert(
+                    name.to_string(),
+                    InterfaceDump {
+                        public_key: public_key.to_string(),
+                        peer: None,
+                    },
+                );
+            }
+            [name, peer @ ..] => {
+                let transfer = parse_peer_fields(peer)?;
+                let interface = interfaces.get_mut(*name).ok_or_else(|| {
+                    format!(
+                        "wg show all dump lists a peer before its interface: {}",
+                        line
+                    )
+                })?;
+                interface.peer.get_or_insert(transfer);
+            }
+            [] => {}
         }
     }
+    Ok(interfaces)
+}
+
+/// Remove a WireGuard configuration
+pub async fn remove_config(system: &SystemConfig, interface_name: &str) -> Result<(), String> {
+    // Bring down the interface
+    bring_down(system, interface_name).await?;
 
     // Remove config file
-    let config_path = PathBuf::from("/etc/wireguard").join(format!("{}.conf", interface_name));
+    let config_path = config_path(&system.wireguard_dir, interface_name);
     if config_path.exists() {
-        std::fs::remove_file(&config_path)
+        tokio::fs::remove_file(&config_path)
+            .await
             .map_err(|e| format!("Failed to remove config file {:?}: {}", config_path, e))?;
     }
+    let key_path = key_path(&system.wireguard_dir, interface_name);
+    if key_path.exists() {
+        remove_key(&key_path).await?;
+    }
 
     Ok(())
 }
@@ -171,32 +459,200 @@ pub fn remove_config(interface_name: &str) -> Result<(), String> {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_generate_keypair() {
-        let keypair = WgKeypair::generate().unwrap();
-
-        // Keys should be base64-encoded and non-empty
-        assert!(!keypair.private_key.is_empty());
-        assert!(!keypair.public_key.is_empty());
+    #[tokio::test]
+    async fn test_generate_keypair() {
+        let keypair = WgKeypair::generate(&SystemConfig::from_env())
+            .await
+            .unwrap();
 
-        // Keys should be different
-        assert_ne!(keypair.private_key, keypair.public_key);
-
-        // Keys should be base64 (44 characters for WireGuard keys)
-        assert_eq!(keypair.private_key.len(), 44);
-        assert_eq!(keypair.public_key.len(), 44);
+        // Both keys are validated on construction; they should be different
+        assert_ne!(keypair.private_key.expose(), keypair.public_key.as_str());
     }
 
-    #[test]
-    fn test_generate_multiple_keypairs() {
-        let keypair1 = WgKeypair::generate().unwrap();
-        let keypair2 = WgKeypair::generate().unwrap();
+    #[tokio::test]
+    async fn test_generate_multiple_keypairs() {
+        let system = SystemConfig::from_env();
+        let keypair1 = WgKeypair::generate(&system).await.unwrap();
+        let keypair2 = WgKeypair::generate(&system).await.unwrap();
 
         // Each generation should produce unique keys
         assert_ne!(keypair1.private_key, keypair2.private_key);
         assert_ne!(keypair1.public_key, keypair2.public_key);
     }
 
-    // Note: Deployment tests are skipped as they require root privileges
-    // and would modify system configuration
+    /// Stub tools where `wg show` only succeeds from the second call on
+    fn system_with_delayed_interface(root: &Path) -> SystemConfig {
+        let mut system = crate::api::test_helpers::test_system_config(root);
+        let marker = root.join("seen");
+        system.wg_bin = crate::api::test_helpers::write_stub(
+            &root.join("bin"),
+            "wg-delayed",
+            &format!(
+                "#!/bin/sh\n[ -f {m} ] && exit 0\ntouch {m}\nexit 1\n",
+                m = marker.display()
+            ),
+        );
+        system.wg_up_timeout_secs = 2;
+        system
+    }
+
+    #[test]
+    fn test_parse_dump() {
+        let dump = "cHJpdmF0ZQ==\tcHVibGlj\t34321\toff\n\
+                    yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=\t(none)\t192.0.2.1:51820\tfe80::/64\t1700000000\t1234\t5678\toff\n";
+        assert_eq!(
+            parse_dump(dump).unwrap(),
+            PeerTransfer {
+                latest_handshake: Some(1700000000),
+                rx_bytes: 1234,
+                tx_bytes: 5678
+            }
+        );
+
+        // No handshake yet is reported as 0
+        let dump = dump.replace("1700000000", "0");
+        assert_eq!(parse_dump(&dump).unwrap().latest_handshake, None);
+
+        assert!(parse_dump("cHJpdmF0ZQ==\tcHVibGlj\t34321\toff\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_dump_all() {
+        let dump = "wg-as4242422225\tcHJpdmF0ZQ==\tcHVibGljMQ==\t32225\toff\n\
+                    wg-as4242422225\tTrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=\t(none)\t192.0.2.1:51820\tfe80::/64\t1700000000\t1234\t5678\t25\n\
+                    wg-as4242421816\tcHJpdmF0ZQ==\tcHVibGljMg==\t31816\toff\n\
+                    wg-as4242421816\tyAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=\t(none)\t(none)\tfe80::/64\t0\t0\t0\toff\n\
+                    wg0\tcHJpdmF0ZQ==\tcHVibGljMw==\t51820\toff\n";
+
+        let interfaces = parse_dump_all(dump).unwrap();
+        assert_eq!(interfaces.len(), 3);
+        assert_eq!(
+            interfaces["wg-as4242422225"],
+            InterfaceDump {
+                public_key: "cHVibGljMQ==".to_string(),
+                peer: Some(PeerTransfer {
+                    latest_handshake: Some(1700000000),
+                    rx_bytes: 1234,
+                    tx_bytes: 5678
+                }),
+            }
+        );
+        assert_eq!(
+            interfaces["wg-as4242421816"].peer.unwrap().latest_handshake,
+            None
+        );
+        // An interface without peers is still listed
+        assert_eq!(interfaces["wg0"].peer, None);
+
+        assert!(parse_dump_all("").unwrap().is_empty());
+        assert!(parse_dump_all("wg-as1\tkey\t(none)\t(none)\tfe80::/64\t0\t0\t0\toff\n").is_err());
+        assert!(parse_dump_all("wg-as1\tkey\t(none)\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_deploy_waits_for_interface() {
+        let root = tempfile::TempDir::new().unwrap();
+        let system = system_with_delayed_interface(root.path());
+
+        deploy_config(&system, "[Interface]\n", "wg-as4242422225", false)
+            .await
+            .unwrap();
+        assert!(config_path(&system.wireguard_dir, "wg-as4242422225").exists());
+    }
+
+    #[tokio::test]
+    async fn test_deploy_fails_if_interface_never_appears() {
+        let root = tempfile::TempDir::new().unwrap();
+        let mut system = crate::api::test_helpers::test_system_config(root.path());
+        system.wg_up_timeout_secs = 1;
+
+        let err = deploy_config(&system, "[Interface]\n", "wg-as4242422225", false)
+            .await
+            .unwrap_err();
+        assert!(err.contains("did not come up"));
+    }
+
+    /// Stub tools where the interface is down and wg-quick logs its calls, with `existing` left behind
+    fn system_with_stale_config(root: &Path, existing: &str) -> (SystemConfig, PathBuf) {
+        let mut system = crate::api::test_helpers::test_system_config(root);
+        let log = root.join("wg-quick.log");
+        system.wg_quick_bin = crate::api::test_helpers::write_stub(
+            &root.join("bin"),
+            "wg-quick-logging",
+            &format!(
+                "#!/bin/sh
+echo \"$1\" >> {}
+[ \"$1\" = down ] && echo \"$2 is not a WireGuard interface\" >&2 && exit 1
+exit 0
+",
+                log.display()
+            ),
+        );
+        std::fs::write(
+            config_path(&system.wireguard_dir, "wg-as4242422225"),
+            existing,
+        )
+        .unwrap();
+        (system, log)
+    }
+
+    #[tokio::test]
+    async fn test_deploy_brings_up_matching_stale_config() {
+        let root = tempfile::TempDir::new().unwrap();
+        let (system, log) = system_with_stale_config(root.path(), "[Interface]\n");
+
+        deploy_config(&system, "[Interface]\n", "wg-as4242422225", false)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "up\n");
+    }
+
+    #[tokio::test]
+    async fn test_deploy_replaces_differing_stale_config() {
+        let root = tempfile::TempDir::new().unwrap();
+        let (system, log) = system_with_stale_config(root.path(), "[Interface]\nListenPort = 1\n");
+
+        deploy_config(&system, "[Interface]\n", "wg-as4242422225", false)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "down\nup\n");
+        assert_eq!(
+            std::fs::read_to_string(config_path(&system.wireguard_dir, "wg-as4242422225")).unwrap(),
+            "[Interface]\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deploy_external_private_key() {
+        let root = tempfile::TempDir::new().unwrap();
+        let (system, log) = system_with_stale_config(root.path(), "");
+        let key = crate::api::test_helpers::STUB_PRIVATE_KEY;
+        let content = format!("[Interface]\nPrivateKey = {}\nListenPort = 32225\n", key);
+
+        deploy_config(&system, &content, "wg-as4242422225", true)
+            .await
+            .unwrap();
+
+        let key_file = key_path(&system.wireguard_dir, "wg-as4242422225");
+        let deployed =
+            std::fs::read_to_string(config_path(&system.wireguard_dir, "wg-as4242422225")).unwrap();
+        assert!(!deployed.contains(key));
+        assert_eq!(
+            deployed,
+            format!(
+                "[Interface]\nPostUp = wg set %i private-key {}\nListenPort = 32225\n",
+                key_file.display()
+            )
+        );
+        assert_eq!(std::fs::read_to_string(&key_file).unwrap().trim(), key);
+        assert_eq!(
+            std::fs::metadata(&key_file).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "down\nup\n");
+
+        // Removing the peering takes the key with it
+        remove_config(&system, "wg-as4242422225").await.unwrap();
+        assert!(!key_file.exists());
+    }
 }
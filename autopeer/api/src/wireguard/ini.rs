@@ -0,0 +1,241 @@
+//! Ordered view of a wg-quick style INI file
+//!
+//! Sections and keys keep the order they appear in, and a section may repeat
+//! (wg-quick allows one `[Peer]` per peer).
+
+use std::fmt;
+use std::str::FromStr;
+
+/// One `[Name]` section and its `Key = Value` lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IniSection {
+    name: String,
+    entries: Vec<(String, String)>,
+}
+
+/// A parsed INI file
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct IniDocument {
+    sections: Vec<IniSection>,
+}
+
+impl IniDocument {
+    /// Parse INI content. Blank lines, `#` comments and keys before the first
+    /// section header are skipped; values are split at the first `=`.
+    pub fn parse(content: &str) -> Self {
+        let mut sections: Vec<IniSection> = Vec::new();
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                sections.push(IniSection {
+                    name: name.to_string(),
+                    entries: Vec::new(),
+                });
+                continue;
+            }
+
+            if let (Some(section), Some((key, value))) = (sections.last_mut(), line.split_once('='))
+            {
+                section
+                    .entries
+                    .push((key.trim().to_string(), value.trim().to_string()));
+            }
+        }
+
+        IniDocument { sections }
+    }
+
+    /// All sections, in file order
+    pub fn sections(&self) -> &[IniSection] {
+        &self.sections
+    }
+
+    /// Every section with this name, in file order
+    pub fn sections_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a IniSection> {
+        self.sections
+            .iter()
+            .filter(move |section| section.name == name)
+    }
+
+    /// The section with this name; if it repeats, the last one wins
+    pub fn section(&self, name: &str) -> Option<&IniSection> {
+        self.sections
+            .iter()
+            .rev()
+            .find(|section| section.name == name)
+    }
+
+    /// Like [`section`](Self::section), failing with `Missing [Name] section`
+    pub fn require_section(&self, name: &str) -> Result<&IniSection, String> {
+        self.section(name)
+            .ok_or_else(|| format!("Missing [{}] section", name))
+    }
+}
+
+impl IniSection {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// All `(key, value)` pairs, in file order
+    pub fn entries(&self) -> &[(String, String)] {
+        &self.entries
+    }
+
+    /// Every value of a repeatable key such as `Address` or `AllowedIPs`
+    pub fn get_all(&self, key: &str) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+
+    /// First value of a key
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// First value of a key, failing with `Missing Key in [Name]`
+    pub fn require(&self, key: &str) -> Result<&str, String> {
+        self.get(key)
+            .ok_or_else(|| format!("Missing {} in [{}]", key, self.name))
+    }
+
+    /// Every value of a key that must appear at least once
+    pub fn require_all(&self, key: &str) -> Result<Vec<String>, String> {
+        let values = self.get_all(key);
+        if values.is_empty() {
+            return Err(format!("Missing {} in [{}]", key, self.name));
+        }
+        Ok(values)
+    }
+
+    /// First value of a key parsed as `T`, failing with `Invalid Key: ...`
+    pub fn parse<T>(&self, key: &str) -> Result<Option<T>, String>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.get(key)
+            .map(|value| {
+                value
+                    .parse::<T>()
+                    .map_err(|e| format!("Invalid {}: {}", key, e))
+            })
+            .transpose()
+    }
+
+    /// A boolean key, where `on` and `true` (any case) are set and anything else is not
+    pub fn flag(&self, key: &str) -> Option<bool> {
+        self.get(key).map(|value| {
+            let value = value.to_lowercase();
+            value == "on" || value == "true"
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MULTI_PEER: &str = r#"
+# Comment before any section
+Orphan = dropped
+
+[Interface]
+ListenPort = 31234
+Address = fe80::1/64
+PrivateKey = MA3Oj1xzJzoGfIkMJagCXOHmGIkLkK49XUFfqS1Xjmo=
+Address = fd00::1/128
+
+[Peer]
+PublicKey = uS1AYe7zTGAP48XeNn0vppNjg7q0hawyh8Y0bvvAWhk=
+AllowedIPs = fd00::/8
+
+[BGP]
+Neighbor = fe80::2
+Local = fe80::1
+
+[Peer]
+PublicKey = TrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=
+AllowedIPs = 172.20.0.0/14
+"#;
+
+    #[test]
+    fn test_preserves_section_and_key_order() {
+        let doc = IniDocument::parse(MULTI_PEER);
+
+        let names: Vec<&str> = doc.sections().iter().map(IniSection::name).collect();
+        assert_eq!(names, vec!["Interface", "Peer", "BGP", "Peer"]);
+
+        let interface = doc.require_section("Interface").unwrap();
+        let keys: Vec<&str> = interface
+            .entries()
+            .iter()
+            .map(|(key, _)| key.as_str())
+            .collect();
+        assert_eq!(keys, vec!["ListenPort", "Address", "PrivateKey", "Address"]);
+        assert_eq!(
+            interface.get_all("Address"),
+            vec!["fe80::1/64", "fd00::1/128"]
+        );
+        // Split at the first `=`, so base64 padding survives
+        assert_eq!(
+            interface.get("PrivateKey"),
+            Some("MA3Oj1xzJzoGfIkMJagCXOHmGIkLkK49XUFfqS1Xjmo=")
+        );
+
+        let bgp = doc.require_section("BGP").unwrap();
+        let bgp_keys: Vec<&str> = bgp.entries().iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(bgp_keys, vec!["Neighbor", "Local"]);
+    }
+
+    #[test]
+    fn test_repeated_sections() {
+        let doc = IniDocument::parse(MULTI_PEER);
+
+        let peers: Vec<Option<&str>> = doc
+            .sections_named("Peer")
+            .map(|peer| peer.get("AllowedIPs"))
+            .collect();
+        assert_eq!(peers, vec![Some("fd00::/8"), Some("172.20.0.0/14")]);
+        assert_eq!(
+            doc.section("Peer").unwrap().get("AllowedIPs"),
+            Some("172.20.0.0/14")
+        );
+    }
+
+    #[test]
+    fn test_typed_accessors() {
+        let doc =
+            IniDocument::parse("[BGP]\nMPBGP = On\nEnabled = no\nMaxPrefixes = lots\nPort = 179\n");
+        let bgp = doc.require_section("BGP").unwrap();
+
+        assert_eq!(bgp.flag("MPBGP"), Some(true));
+        assert_eq!(bgp.flag("Enabled"), Some(false));
+        assert_eq!(bgp.flag("Missing"), None);
+        assert_eq!(bgp.parse::<u16>("Port").unwrap(), Some(179));
+        assert_eq!(bgp.parse::<u16>("Missing").unwrap(), None);
+        assert!(bgp
+            .parse::<u32>("MaxPrefixes")
+            .unwrap_err()
+            .starts_with("Invalid MaxPrefixes"));
+        assert_eq!(bgp.require("Local").unwrap_err(), "Missing Local in [BGP]");
+        assert_eq!(
+            doc.require_section("Peer").unwrap_err(),
+            "Missing [Peer] section"
+        );
+    }
+}
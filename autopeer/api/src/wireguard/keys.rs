@@ -0,0 +1,241 @@
+//! Typed WireGuard keys, so a private key can't end up where a public one belongs
+
+use base64::Engine;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Base64 characters that may end a 32-byte key (the last two bits are padding)
+const FINAL_KEY_CHARS: &str = "AEIMQUYcgkosw048";
+
+/// Check that `key` is a base64-encoded 32-byte key (44 characters)
+fn validate_key(key: &str, kind: &str) -> Result<(), String> {
+    if key.len() != 44 {
+        return Err(format!("WireGuard {} key must be 44 characters", kind));
+    }
+
+    let (body, padding) = key.split_at(43);
+    let is_base64 = body
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/');
+    let last_ok = body
+        .chars()
+        .last()
+        .is_some_and(|c| FINAL_KEY_CHARS.contains(c));
+    if !is_base64 || !last_ok || padding != "=" {
+        return Err(format!("WireGuard {} key must be valid base64", kind));
+    }
+
+    Ok(())
+}
+
+/// WireGuard public key
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PublicKey(String);
+
+impl PublicKey {
+    /// Validate and wrap a base64 public key
+    pub fn new(key: impl Into<String>) -> Result<Self, String> {
+        let key = key.into();
+        validate_key(&key, "public")?;
+        Ok(PublicKey(key))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for PublicKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s.trim())
+    }
+}
+
+impl TryFrom<String> for PublicKey {
+    type Error = String;
+
+    fn try_from(key: String) -> Result<Self, Self::Error> {
+        Self::new(key)
+    }
+}
+
+impl From<PublicKey> for String {
+    fn from(key: PublicKey) -> Self {
+        key.0
+    }
+}
+
+impl fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Debug for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PublicKey({})", self.0)
+    }
+}
+
+/// WireGuard private key. `Debug` is redacted and there is no `Display`;
+/// use `expose` where the key really has to be written out.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PrivateKey(String);
+
+impl PrivateKey {
+    /// Validate and wrap a base64 private key
+    pub fn new(key: impl Into<String>) -> Result<Self, String> {
+        let key = key.into();
+        validate_key(&key, "private")?;
+        Ok(PrivateKey(key))
+    }
+
+    /// The raw key, for config files and `wg pubkey`
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for PrivateKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s.trim())
+    }
+}
+
+impl TryFrom<String> for PrivateKey {
+    type Error = String;
+
+    fn try_from(key: String) -> Result<Self, Self::Error> {
+        Self::new(key)
+    }
+}
+
+impl From<PrivateKey> for String {
+    fn from(key: PrivateKey) -> Self {
+        key.0
+    }
+}
+
+impl fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PrivateKey(<redacted>)")
+    }
+}
+
+/// WireGuard preshared key, shared by both sides of a tunnel. Secret like a
+/// private key: `Debug` is redacted and there is no `Display`.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PresharedKey(String);
+
+impl PresharedKey {
+    /// Validate and wrap a base64 preshared key
+    pub fn new(key: impl Into<String>) -> Result<Self, String> {
+        let key = key.into();
+        validate_key(&key, "preshared")?;
+        Ok(PresharedKey(key))
+    }
+
+    /// A fresh random key, as `wg genpsk` makes
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 32];
+        rand::rng().fill_bytes(&mut bytes);
+        PresharedKey(base64::engine::general_purpose::STANDARD.encode(bytes))
+    }
+
+    /// The raw key, for config files and the peer that has to install it
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for PresharedKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s.trim())
+    }
+}
+
+impl TryFrom<String> for PresharedKey {
+    type Error = String;
+
+    fn try_from(key: String) -> Result<Self, Self::Error> {
+        Self::new(key)
+    }
+}
+
+impl From<PresharedKey> for String {
+    fn from(key: PresharedKey) -> Self {
+        key.0
+    }
+}
+
+impl fmt::Debug for PresharedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PresharedKey(<redacted>)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=";
+
+    #[test]
+    fn test_key_validation() {
+        assert!(PublicKey::new(KEY).is_ok());
+        assert!(PrivateKey::new(KEY).is_ok());
+
+        assert!(PublicKey::new("tooshort").is_err());
+        // Right length, but not a 32-byte key
+        assert!(PublicKey::new("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQR").is_err());
+        assert!(PublicKey::new("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBml=").is_err());
+        assert!(PrivateKey::new("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fB!k=").is_err());
+    }
+
+    #[test]
+    fn test_private_key_debug_is_redacted() {
+        let key = PrivateKey::new(KEY).unwrap();
+
+        let debug = format!("{:?}", key);
+        assert!(!debug.contains(KEY));
+        assert!(debug.contains("redacted"));
+        assert_eq!(key.expose(), KEY);
+    }
+
+    #[test]
+    fn test_preshared_key() {
+        let key = PresharedKey::new(KEY).unwrap();
+        assert!(!format!("{:?}", key).contains(KEY));
+        assert!(PresharedKey::new("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBml=").is_err());
+        assert_eq!(
+            PresharedKey::new("tooshort").unwrap_err(),
+            "WireGuard preshared key must be 44 characters"
+        );
+
+        // Generated keys pass the same validation and don't repeat
+        let generated = PresharedKey::generate();
+        assert!(PresharedKey::new(generated.expose()).is_ok());
+        assert_ne!(generated, PresharedKey::generate());
+    }
+
+    #[test]
+    fn test_public_key_serde_roundtrip() {
+        let key = PublicKey::new(KEY).unwrap();
+
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(json, format!("\"{}\"", KEY));
+        assert_eq!(serde_json::from_str::<PublicKey>(&json).unwrap(), key);
+        assert!(serde_json::from_str::<PublicKey>("\"tooshort\"").is_err());
+    }
+}
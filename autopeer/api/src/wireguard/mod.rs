@@ -2,6 +2,13 @@
 
 mod config;
 pub mod deploy;
+mod ini;
+mod keys;
 
-pub use config::{BgpConfig, ChallengeConfig, InterfaceConfig, PeerConfig, WgConfig};
+pub use config::{
+    BgpConfig, ChallengeConfig, InterfaceConfig, MetaConfig, PeerConfig, RegistryAuthConfig,
+    WgConfig,
+};
 pub use deploy::{deploy_config, remove_config, WgKeypair};
+pub use ini::{IniDocument, IniSection};
+pub use keys::{PresharedKey, PrivateKey, PublicKey};